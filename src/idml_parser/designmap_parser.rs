@@ -19,6 +19,18 @@ enum DesignMapContent {
     SpreadSrc(SrcWrapper),
     #[serde(rename = "idPkg:Story")]
     StorySrc(SrcWrapper),
+    Section(Section),
+    TextVariable(TextVariable),
+    Hyperlink(Hyperlink),
+    #[serde(rename = "HyperlinkURLDestination")]
+    HyperlinkUrlDestination(HyperlinkUrlDestination),
+    HyperlinkPageDestination(HyperlinkPageDestination),
+    CrossReferenceFormat(CrossReferenceFormat),
+    Bookmark(Bookmark),
+    Index(Index),
+    Condition(Condition),
+    ConditionSet(ConditionSet),
+    ConditionalTextPreference(ConditionalTextPreference),
     #[serde(other)]
     NotImplemented,
 }
@@ -28,11 +40,486 @@ pub struct SrcWrapper {
     src: String,
 }
 
+#[derive(Deserialize, Debug, PartialEq, Clone, Getters)]
+#[serde(rename_all = "PascalCase")]
+pub struct Section {
+    #[serde(rename = "Self")]
+    id: String,
+    name: Option<String>,
+    length: Option<u32>,
+    continue_numbering: Option<bool>,
+    page_number_start: Option<u32>,
+    page_start: Option<String>,
+    include_section_prefix: Option<bool>,
+    section_prefix: Option<String>,
+    marker: Option<String>,
+    properties: Option<SectionProperties>,
+}
+
+#[derive(Deserialize, Debug, PartialEq, Clone, Getters)]
+#[serde(rename_all = "PascalCase")]
+pub struct SectionProperties {
+    page_number_style: Option<String>,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum PageNumberStyle {
+    Arabic,
+    UpperRoman,
+    LowerRoman,
+    UpperLetters,
+    LowerLetters,
+    FullWidthArabic,
+}
+
+impl PageNumberStyle {
+    /// Falls back to arabic numerals for styles we do not support yet
+    pub fn from_name(name: &str) -> PageNumberStyle {
+        match name {
+            "UpperRoman" => PageNumberStyle::UpperRoman,
+            "LowerRoman" => PageNumberStyle::LowerRoman,
+            "UpperLetters" => PageNumberStyle::UpperLetters,
+            "LowerLetters" => PageNumberStyle::LowerLetters,
+            "FullWidthArabic" => PageNumberStyle::FullWidthArabic,
+            _ => PageNumberStyle::Arabic,
+        }
+    }
+
+    /// Style of a numbering format like "I, II, III, IV...", as used by chapter
+    /// numbers and numbered lists. Falls back to arabic numerals as well.
+    pub fn from_format(format: &str) -> PageNumberStyle {
+        match format {
+            "I, II, III, IV..." => PageNumberStyle::UpperRoman,
+            "i, ii, iii, iv..." => PageNumberStyle::LowerRoman,
+            "A, B, C, D..." => PageNumberStyle::UpperLetters,
+            "a, b, c, d..." => PageNumberStyle::LowerLetters,
+            _ => PageNumberStyle::Arabic,
+        }
+    }
+}
+
+impl Section {
+    pub fn page_number_style(&self) -> PageNumberStyle {
+        self.properties
+            .as_ref()
+            .and_then(|properties| properties.page_number_style.as_deref())
+            .map(PageNumberStyle::from_name)
+            .unwrap_or(PageNumberStyle::Arabic)
+    }
+}
+
+#[derive(Deserialize, Debug, PartialEq, Clone, Getters)]
+#[serde(rename_all = "PascalCase")]
+pub struct TextVariable {
+    #[serde(rename = "Self")]
+    id: String,
+    name: Option<String>,
+    variable_type: Option<TextVariableType>,
+    chapter_number_variable_preference: Option<TextVariablePreference>,
+    date_variable_preference: Option<TextVariablePreference>,
+    file_name_variable_preference: Option<TextVariablePreference>,
+    page_number_variable_preference: Option<TextVariablePreference>,
+    match_paragraph_style_preference: Option<TextVariablePreference>,
+    match_character_style_preference: Option<TextVariablePreference>,
+    custom_text_variable_preference: Option<TextVariablePreference>,
+    caption_metadata_variable_preference: Option<TextVariablePreference>,
+}
+
+#[derive(Deserialize, Debug, PartialEq, Clone, Copy)]
+pub enum TextVariableType {
+    ChapterNumberType,
+    CreationDateType,
+    ModificationDateType,
+    OutputDateType,
+    FileNameType,
+    LastPageNumberType,
+    MatchParagraphStyleType,
+    MatchCharacterStyleType,
+    CustomTextType,
+    LiveCaptionType,
+    XrefChapterNumberType,
+    XrefPageNumberType,
+    #[serde(other)]
+    NotImplementedYet,
+}
+
+/// Settings of a text variable. Each variable type has its own preference
+/// element, which only holds the attributes relevant to the type.
+#[derive(Default, Deserialize, Debug, PartialEq, Clone, Getters)]
+#[serde(rename_all = "PascalCase")]
+pub struct TextVariablePreference {
+    text_before: Option<String>,
+    text_after: Option<String>,
+    format: Option<String>,
+    scope: Option<String>,
+    include_path: Option<bool>,
+    include_extension: Option<bool>,
+    applied_paragraph_style: Option<String>,
+    applied_character_style: Option<String>,
+    search_strategy: Option<String>,
+    change_case: Option<String>,
+    delete_end_punctuation: Option<bool>,
+    contents: Option<String>,
+}
+
+impl TextVariable {
+    pub fn preference(&self) -> Option<&TextVariablePreference> {
+        self.chapter_number_variable_preference
+            .as_ref()
+            .or(self.date_variable_preference.as_ref())
+            .or(self.file_name_variable_preference.as_ref())
+            .or(self.page_number_variable_preference.as_ref())
+            .or(self.match_paragraph_style_preference.as_ref())
+            .or(self.match_character_style_preference.as_ref())
+            .or(self.custom_text_variable_preference.as_ref())
+            .or(self.caption_metadata_variable_preference.as_ref())
+    }
+}
+
+/// Link from a text source, such as a `HyperlinkTextSource` or a `CrossReferenceSource`
+/// in a story, to a destination in the document or on the web
+#[derive(Deserialize, Debug, PartialEq, Clone, Getters)]
+#[serde(rename_all = "PascalCase")]
+pub struct Hyperlink {
+    #[serde(rename = "Self")]
+    id: String,
+    name: Option<String>,
+    source: Option<String>,
+    /// Whether the link has a visible border
+    visible: Option<bool>,
+    highlight: Option<HyperlinkHighlight>,
+    width: Option<HyperlinkWidth>,
+    properties: Option<HyperlinkProperties>,
+}
+
+#[derive(Deserialize, Debug, PartialEq, Clone, Getters)]
+#[serde(rename_all = "PascalCase")]
+pub struct HyperlinkProperties {
+    destination: Option<String>,
+}
+
+#[derive(Deserialize, Debug, PartialEq, Clone, Copy)]
+pub enum HyperlinkHighlight {
+    None,
+    Invert,
+    Outline,
+    Inset,
+}
+
+#[derive(Deserialize, Debug, PartialEq, Clone, Copy)]
+pub enum HyperlinkWidth {
+    Thin,
+    Medium,
+    Thick,
+}
+
+#[derive(Deserialize, Debug, PartialEq, Clone, Getters)]
+#[serde(rename_all = "PascalCase")]
+pub struct HyperlinkUrlDestination {
+    #[serde(rename = "Self")]
+    id: String,
+    name: Option<String>,
+    #[serde(rename = "DestinationURL")]
+    destination_url: Option<String>,
+}
+
+#[derive(Deserialize, Debug, PartialEq, Clone, Getters)]
+#[serde(rename_all = "PascalCase")]
+pub struct HyperlinkPageDestination {
+    #[serde(rename = "Self")]
+    id: String,
+    name: Option<String>,
+    destination_page: Option<String>,
+}
+
+/// Format cross-reference text is built from, block by block
+#[derive(Deserialize, Debug, PartialEq, Clone, Getters)]
+#[serde(rename_all = "PascalCase")]
+pub struct CrossReferenceFormat {
+    #[serde(rename = "Self")]
+    id: String,
+    name: Option<String>,
+    #[serde(rename = "BuildingBlock")]
+    building_blocks: Option<Vec<BuildingBlock>>,
+}
+
+#[derive(Deserialize, Debug, PartialEq, Clone, Getters)]
+#[serde(rename_all = "PascalCase")]
+pub struct BuildingBlock {
+    block_type: Option<BuildingBlockType>,
+    custom_text: Option<String>,
+    /// Character a partial paragraph ends at
+    applied_delimiter: Option<String>,
+    include_delimiter: Option<bool>,
+}
+
+#[derive(Deserialize, Debug, PartialEq, Clone, Copy)]
+pub enum BuildingBlockType {
+    CustomStringBuildingBlock,
+    FullParagraphBuildingBlock,
+    PartialParagraphBuildingBlock,
+    ParagraphNumberBuildingBlock,
+    ParagraphTextBuildingBlock,
+    PageNumberBuildingBlock,
+    TextAnchorNameBuildingBlock,
+    ChapterNumberBuildingBlock,
+    FileNameBuildingBlock,
+    #[serde(other)]
+    NotImplementedYet,
+}
+
+/// Entry of the bookmark tree, exported as a PDF outline item
+#[derive(Deserialize, Debug, PartialEq, Clone, Getters)]
+#[serde(rename_all = "PascalCase")]
+pub struct Bookmark {
+    #[serde(rename = "Self")]
+    id: String,
+    name: Option<String>,
+    /// Text destination, page destination or page the bookmark goes to
+    destination: Option<String>,
+    #[serde(rename = "Bookmark")]
+    bookmarks: Option<Vec<Bookmark>>,
+}
+
+/// Topics of the index of the document. Pages refer to
+/// topics through `PageReference`s in their stories.
+#[derive(Deserialize, Debug, PartialEq, Clone, Getters)]
+#[serde(rename_all = "PascalCase")]
+pub struct Index {
+    #[serde(rename = "Self")]
+    id: String,
+    #[serde(rename = "Topic")]
+    topics: Option<Vec<Topic>>,
+}
+
+#[derive(Deserialize, Debug, PartialEq, Clone, Getters)]
+#[serde(rename_all = "PascalCase")]
+pub struct Topic {
+    #[serde(rename = "Self")]
+    id: String,
+    name: Option<String>,
+    /// Text the topic is sorted by, instead of its name
+    sort_order: Option<String>,
+    /// Subtopics, one level further in
+    #[serde(rename = "Topic")]
+    topics: Option<Vec<Topic>>,
+}
+
+/// Condition that can be applied to text, which is left out of the layout when hidden
+#[derive(Deserialize, Debug, PartialEq, Clone, Getters)]
+#[serde(rename_all = "PascalCase")]
+pub struct Condition {
+    #[serde(rename = "Self")]
+    id: String,
+    name: Option<String>,
+    visible: Option<bool>,
+    indicator_method: Option<ConditionIndicatorMethod>,
+    underline_indicator_appearance: Option<ConditionUnderlineIndicatorAppearance>,
+    properties: Option<ConditionProperties>,
+}
+
+#[derive(Deserialize, Debug, PartialEq, Clone, Getters)]
+#[serde(rename_all = "PascalCase")]
+pub struct ConditionProperties {
+    indicator_color: Option<IndicatorColor>,
+}
+
+/// Color of a condition indicator, which is either one of InDesign's
+/// UI colors or a list of RGB values from 0 to 255
+#[derive(Deserialize, Debug, PartialEq, Clone, Getters)]
+pub struct IndicatorColor {
+    #[serde(rename = "$value")]
+    values: Option<Vec<IndicatorColorValue>>,
+}
+
+#[derive(Deserialize, Debug, PartialEq, Clone, Copy)]
+pub enum IndicatorColorValue {
+    ListItem(f64),
+    LightBlue,
+    Red,
+    Green,
+    Blue,
+    Yellow,
+    Magenta,
+    Cyan,
+    Gray,
+    Black,
+    Orange,
+    Violet,
+    Pink,
+    Gold,
+    #[serde(other)]
+    NotImplementedYet,
+}
+
+#[derive(Deserialize, Debug, PartialEq, Clone, Copy)]
+pub enum ConditionIndicatorMethod {
+    UseHighlight,
+    UseUnderline,
+}
+
+#[derive(Deserialize, Debug, PartialEq, Clone, Copy)]
+pub enum ConditionUnderlineIndicatorAppearance {
+    Solid,
+    Wavy,
+    Dashed,
+}
+
+/// Visibility of each condition, saved under a name so editions can be switched between
+#[derive(Deserialize, Debug, PartialEq, Clone, Getters)]
+#[serde(rename_all = "PascalCase")]
+pub struct ConditionSet {
+    #[serde(rename = "Self")]
+    id: String,
+    name: Option<String>,
+    properties: Option<ConditionSetProperties>,
+}
+
+#[derive(Deserialize, Debug, PartialEq, Clone, Getters)]
+#[serde(rename_all = "PascalCase")]
+pub struct ConditionSetProperties {
+    set_conditions: Option<SetConditions>,
+}
+
+/// Pairs of a condition id and whether the condition is visible
+#[derive(Deserialize, Debug, PartialEq, Clone, Getters)]
+pub struct SetConditions {
+    #[serde(rename = "ListItem")]
+    pairs: Option<Vec<SetCondition>>,
+}
+
+#[derive(Deserialize, Debug, PartialEq, Clone, Getters)]
+pub struct SetCondition {
+    #[serde(rename = "ListItem")]
+    values: Option<Vec<String>>,
+}
+
+impl ConditionSet {
+    /// Condition ids with whether the set shows them
+    pub fn visibilities(&self) -> Vec<(String, bool)> {
+        self.properties
+            .as_ref()
+            .and_then(|properties| properties.set_conditions.as_ref())
+            .and_then(|set_conditions| set_conditions.pairs.as_ref())
+            .into_iter()
+            .flatten()
+            .filter_map(|pair| match pair.values.as_deref() {
+                Some([id, visible]) => Some((id.clone(), visible == "true")),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+#[derive(Default, Deserialize, Debug, PartialEq, Clone, Getters)]
+#[serde(rename_all = "PascalCase")]
+pub struct ConditionalTextPreference {
+    show_condition_indicators: Option<ShowConditionIndicators>,
+    active_condition_set: Option<String>,
+}
+
+#[derive(Deserialize, Debug, PartialEq, Clone, Copy)]
+pub enum ShowConditionIndicators {
+    ShowIndicators,
+    HideIndicators,
+    /// Indicators are only printed, and exported, when set to this
+    ShowAndPrintIndicators,
+}
+
 #[derive(Default, Deserialize, Debug, PartialEq, Getters)]
 pub struct DesignMap {
     master_spread_ids: Vec<String>,
     spread_ids: Vec<String>,
     story_ids: Vec<String>,
+    sections: Vec<Section>,
+    text_variables: Vec<TextVariable>,
+    hyperlinks: Vec<Hyperlink>,
+    hyperlink_url_destinations: Vec<HyperlinkUrlDestination>,
+    hyperlink_page_destinations: Vec<HyperlinkPageDestination>,
+    cross_reference_formats: Vec<CrossReferenceFormat>,
+    bookmarks: Vec<Bookmark>,
+    index: Option<Index>,
+    conditions: Vec<Condition>,
+    condition_sets: Vec<ConditionSet>,
+    conditional_text_preference: ConditionalTextPreference,
+}
+
+impl DesignMap {
+    /// Shows and hides the conditions as the condition set named `name` does.
+    /// The error names the condition sets of the document to choose from instead.
+    pub fn apply_condition_set(&mut self, name: &str) -> Result<(), String> {
+        let condition_set = match self
+            .condition_sets
+            .iter()
+            .find(|condition_set| condition_set.name.as_deref() == Some(name))
+        {
+            Some(condition_set) => condition_set,
+            None => {
+                let names: Vec<String> = self
+                    .condition_sets
+                    .iter()
+                    .filter_map(|condition_set| condition_set.name.as_ref())
+                    .map(|name| format!("'{}'", name))
+                    .collect();
+                return Err(match names.is_empty() {
+                    true => format!(
+                        "No condition set named '{}', the document has no condition sets",
+                        name
+                    ),
+                    false => format!(
+                        "No condition set named '{}', the condition sets of the document are {}",
+                        name,
+                        names.join(", ")
+                    ),
+                });
+            }
+        };
+        let visibilities = condition_set.visibilities();
+        for condition in &mut self.conditions {
+            if let Some((_, visible)) = visibilities.iter().find(|(id, _)| id == &condition.id) {
+                condition.visible = Some(*visible);
+            }
+        }
+        self.conditional_text_preference.active_condition_set = Some(condition_set.id.clone());
+        Ok(())
+    }
+
+    pub fn with_condition_indicators(&mut self, show: ShowConditionIndicators) -> &mut Self {
+        self.conditional_text_preference.show_condition_indicators = Some(show);
+        self
+    }
+
+    /// Conditions in the applied conditions of a text range. Condition ids
+    /// may hold spaces, so they are matched against the whole list, longest
+    /// first, so an id is not taken for the start of a longer one.
+    pub fn applied_conditions(&self, applied_conditions: &str) -> Vec<&Condition> {
+        let mut applied = format!(" {} ", applied_conditions);
+        let mut conditions: Vec<&Condition> = self.conditions.iter().collect();
+        conditions.sort_by_key(|condition| std::cmp::Reverse(condition.id.len()));
+        conditions
+            .into_iter()
+            .filter(|condition| {
+                let id = format!(" {} ", condition.id);
+                match applied.find(&id) {
+                    Some(start) => {
+                        applied.replace_range(start..start + id.len(), " ");
+                        true
+                    }
+                    None => false,
+                }
+            })
+            .collect()
+    }
+
+    /// Whether text with the given conditions is hidden, which
+    /// it is when all of them are, as long as there are any
+    pub fn is_hidden(&self, applied_conditions: &str) -> bool {
+        let conditions = self.applied_conditions(applied_conditions);
+        !conditions.is_empty()
+            && conditions
+                .iter()
+                .all(|condition| condition.visible == Some(false))
+    }
 }
 
 pub fn parse_designmap_from_path(path: &Path) -> Result<DesignMap, DeError> {
@@ -72,11 +559,220 @@ pub fn parse_designmap_from_path(path: &Path) -> Result<DesignMap, DeError> {
         .map(|x| path_to_id(&x.src))
         .collect();
 
+    let sections = document
+        .designmap_content()
+        .iter()
+        .filter_map(|x| match x {
+            DesignMapContent::Section(section) => Some(section.clone()),
+            _ => None,
+        })
+        .collect();
+
+    let text_variables = document
+        .designmap_content()
+        .iter()
+        .filter_map(|x| match x {
+            DesignMapContent::TextVariable(variable) => Some(variable.clone()),
+            _ => None,
+        })
+        .collect();
+
+    let hyperlinks = document
+        .designmap_content()
+        .iter()
+        .filter_map(|x| match x {
+            DesignMapContent::Hyperlink(hyperlink) => Some(hyperlink.clone()),
+            _ => None,
+        })
+        .collect();
+
+    let hyperlink_url_destinations = document
+        .designmap_content()
+        .iter()
+        .filter_map(|x| match x {
+            DesignMapContent::HyperlinkUrlDestination(destination) => Some(destination.clone()),
+            _ => None,
+        })
+        .collect();
+
+    let hyperlink_page_destinations = document
+        .designmap_content()
+        .iter()
+        .filter_map(|x| match x {
+            DesignMapContent::HyperlinkPageDestination(destination) => Some(destination.clone()),
+            _ => None,
+        })
+        .collect();
+
+    let cross_reference_formats = document
+        .designmap_content()
+        .iter()
+        .filter_map(|x| match x {
+            DesignMapContent::CrossReferenceFormat(format) => Some(format.clone()),
+            _ => None,
+        })
+        .collect();
+
+    let bookmarks = document
+        .designmap_content()
+        .iter()
+        .filter_map(|x| match x {
+            DesignMapContent::Bookmark(bookmark) => Some(bookmark.clone()),
+            _ => None,
+        })
+        .collect();
+
+    let index = document
+        .designmap_content()
+        .iter()
+        .find_map(|x| match x {
+            DesignMapContent::Index(index) => Some(index.clone()),
+            _ => None,
+        });
+
+    let conditions = document
+        .designmap_content()
+        .iter()
+        .filter_map(|x| match x {
+            DesignMapContent::Condition(condition) => Some(condition.clone()),
+            _ => None,
+        })
+        .collect();
+
+    let condition_sets = document
+        .designmap_content()
+        .iter()
+        .filter_map(|x| match x {
+            DesignMapContent::ConditionSet(condition_set) => Some(condition_set.clone()),
+            _ => None,
+        })
+        .collect();
+
+    let conditional_text_preference = document
+        .designmap_content()
+        .iter()
+        .find_map(|x| match x {
+            DesignMapContent::ConditionalTextPreference(preference) => Some(preference.clone()),
+            _ => None,
+        })
+        .unwrap_or_default();
+
     let design_map = DesignMap {
         master_spread_ids,
         spread_ids,
         story_ids,
+        sections,
+        text_variables,
+        hyperlinks,
+        hyperlink_url_destinations,
+        hyperlink_page_destinations,
+        cross_reference_formats,
+        bookmarks,
+        index,
+        conditions,
+        condition_sets,
+        conditional_text_preference,
     };
 
     Ok(design_map)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn condition(id: &str, visible: bool) -> Condition {
+        Condition {
+            id: id.to_string(),
+            name: None,
+            visible: Some(visible),
+            indicator_method: None,
+            underline_indicator_appearance: None,
+            properties: None,
+        }
+    }
+
+    fn design_map(conditions: Vec<Condition>, condition_sets: Vec<ConditionSet>) -> DesignMap {
+        DesignMap {
+            conditions,
+            condition_sets,
+            ..DesignMap::default()
+        }
+    }
+
+    #[test]
+    fn text_without_conditions_is_shown() {
+        let design_map = design_map(vec![condition("Condition/Print", false)], vec![]);
+        assert!(!design_map.is_hidden(""));
+        assert!(!design_map.is_hidden("Condition/Unknown"));
+    }
+
+    #[test]
+    fn text_is_hidden_when_all_its_conditions_are() {
+        let design_map = design_map(
+            vec![
+                condition("Condition/Print", false),
+                condition("Condition/Web", false),
+                condition("Condition/Draft", true),
+            ],
+            vec![],
+        );
+        assert!(design_map.is_hidden("Condition/Print"));
+        assert!(design_map.is_hidden("Condition/Print Condition/Web"));
+        assert!(!design_map.is_hidden("Condition/Print Condition/Draft"));
+    }
+
+    #[test]
+    fn condition_ids_are_matched_whole() {
+        let design_map = design_map(
+            vec![
+                condition("Condition/Print", true),
+                condition("Condition/Print only", false),
+            ],
+            vec![],
+        );
+        assert!(design_map.is_hidden("Condition/Print only"));
+        assert_eq!(
+            design_map.applied_conditions("Condition/Print only").len(),
+            1
+        );
+    }
+
+    #[test]
+    fn condition_sets_set_the_visibility_of_conditions() {
+        let condition_set = ConditionSet {
+            id: "ConditionSet/Web".to_string(),
+            name: Some("Web".to_string()),
+            properties: Some(ConditionSetProperties {
+                set_conditions: Some(SetConditions {
+                    pairs: Some(vec![SetCondition {
+                        values: Some(vec!["Condition/Print".to_string(), "false".to_string()]),
+                    }]),
+                }),
+            }),
+        };
+        let mut design_map = design_map(
+            vec![condition("Condition/Print", true)],
+            vec![condition_set],
+        );
+        assert!(!design_map.is_hidden("Condition/Print"));
+        design_map.apply_condition_set("Web").unwrap();
+        assert!(design_map.is_hidden("Condition/Print"));
+        assert_eq!(
+            design_map
+                .conditional_text_preference()
+                .active_condition_set()
+                .as_deref(),
+            Some("ConditionSet/Web")
+        );
+    }
+
+    #[test]
+    fn unknown_condition_sets_are_reported() {
+        let mut design_map = design_map(vec![], vec![]);
+        assert_eq!(
+            design_map.apply_condition_set("Web"),
+            Err("No condition set named 'Web', the document has no condition sets".to_string())
+        );
+    }
+}
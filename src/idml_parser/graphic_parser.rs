@@ -11,8 +11,14 @@ pub struct IdPkgGraphic {
     dom_version: Option<f32>,
     #[serde(rename = "Color")]
     colors: Vec<Color>,
+    #[serde(rename = "Ink", default)]
+    inks: Vec<Ink>,
     #[serde(rename = "Swatch")]
     swatches: Vec<Swatch>,
+    #[serde(rename = "Gradient", default)]
+    gradients: Vec<Gradient>,
+    #[serde(rename = "Tint", default)]
+    tints: Vec<Tint>,
 }
 
 #[derive(Default, Deserialize, Debug, PartialEq, Getters)]
@@ -33,6 +39,18 @@ pub struct Color {
     spot_ink_alias_spot_color_reference: Option<String>,
 }
 
+/// Ink printed on a plate of its own, either one of the process inks or a spot ink
+#[derive(Default, Deserialize, Debug, PartialEq, Getters)]
+#[serde(rename_all = "PascalCase")]
+pub struct Ink {
+    #[serde(rename = "Self")]
+    id: String,
+    name: String,
+    is_process_ink: Option<bool>,
+    /// Name of the ink printed in place of this one, or `$ID/` if it is not aliased
+    alias_ink_name: Option<String>,
+}
+
 #[derive(Default, Deserialize, Debug, PartialEq, Getters)]
 #[serde(rename_all = "PascalCase")]
 pub struct Swatch {
@@ -45,6 +63,50 @@ pub struct Swatch {
     swatch_creator_id: Option<i32>,
 }
 
+/// Gradient swatch blending between the colors of its stops
+#[derive(Default, Deserialize, Debug, PartialEq, Getters)]
+#[serde(rename_all = "PascalCase")]
+pub struct Gradient {
+    #[serde(rename = "Self")]
+    id: String,
+    name: String,
+    #[serde(rename = "Type")]
+    gradient_type: Option<GradientType>,
+    #[serde(rename = "GradientStop")]
+    gradient_stops: Vec<GradientStop>,
+}
+
+/// Tint swatch, a percentage of another color swatch
+#[derive(Default, Deserialize, Debug, PartialEq, Getters)]
+#[serde(rename_all = "PascalCase")]
+pub struct Tint {
+    #[serde(rename = "Self")]
+    id: String,
+    name: String,
+    base_color: Option<String>,
+    /// Percentage of the base color, from 0 to 100
+    tint_value: Option<f64>,
+}
+
+#[derive(Default, Deserialize, Debug, PartialEq, Getters)]
+#[serde(rename_all = "PascalCase")]
+pub struct GradientStop {
+    #[serde(rename = "Self")]
+    id: String,
+    stop_color: Option<String>,
+    /// Where the stop is along the gradient, from 0 to 100
+    location: Option<f64>,
+    /// Where the colors of this stop and the one before are mixed evenly, from 0 to 100
+    /// along the way from that stop to this one
+    midpoint: Option<f64>,
+}
+
+#[derive(Deserialize, Debug, PartialEq, Clone, Copy)]
+pub enum GradientType {
+    Linear,
+    Radial,
+}
+
 #[derive(Deserialize, Debug, PartialEq)]
 pub enum ColorSpace {
     RGB,
@@ -64,13 +126,25 @@ pub enum ColorOverride {
     Mixedinkparent,
 }
 
-#[derive(Deserialize, Debug, PartialEq)]
+#[derive(Deserialize, Debug, PartialEq, Clone, Copy)]
 pub enum ColorModel {
     Spot,
     Process,
     Registration,
 }
 
+impl IdPkgGraphic {
+    /// Makes every spot color a process color made from its color values,
+    /// like "All Spots to Process" in the Ink Manager of InDesign
+    pub fn convert_spots_to_process(&mut self) {
+        for color in self.colors.iter_mut() {
+            if color.model == Some(ColorModel::Spot) {
+                color.model = Some(ColorModel::Process);
+            }
+        }
+    }
+}
+
 pub fn parse_graphic_from_path(path: &Path) -> Result<IdPkgGraphic, quick_xml::DeError> {
     let xml = std::fs::read_to_string(path).unwrap();
     quick_xml::de::from_str(xml.as_str())
@@ -0,0 +1,25 @@
+use derive_getters::Getters;
+use regex::Regex;
+use serde::Deserialize;
+use std::path::Path;
+
+/// XMP metadata of the document. Only the dates are read, as the
+/// RDF structure of XMP does not map well onto structs.
+#[derive(Default, Deserialize, Debug, PartialEq, Getters)]
+pub struct Metadata {
+    create_date: Option<String>,
+    modify_date: Option<String>,
+}
+
+pub fn parse_metadata_from_path(path: &Path) -> Result<Metadata, std::io::Error> {
+    let xml = std::fs::read_to_string(path)?;
+    let property = |name: &str| {
+        let re = Regex::new(&format!(r"<xmp:{0}>([^<]*)</xmp:{0}>", name)).unwrap();
+        re.captures(&xml).map(|caps| caps[1].trim().to_string())
+    };
+
+    Ok(Metadata {
+        create_date: property("CreateDate"),
+        modify_date: property("ModifyDate"),
+    })
+}
@@ -2,6 +2,8 @@ pub mod designmap_parser;
 pub mod fonts_parser;
 mod formats;
 pub mod graphic_parser;
+pub mod metadata_parser;
+pub mod preferences_parser;
 pub mod spread_parser;
 #[macro_use]
 pub mod styles;
@@ -18,7 +20,9 @@ use std::{fs, io};
 use designmap_parser::DesignMap;
 use fonts_parser::IdPkgFonts;
 use graphic_parser::IdPkgGraphic;
-use spread_parser::Spread;
+use metadata_parser::Metadata;
+use preferences_parser::IdPkgPreferences;
+use spread_parser::{Polygon, Spread};
 use story_parser::Story;
 use styles_parser::IdPkgStyles;
 
@@ -40,7 +44,25 @@ pub struct IDMLResources {
     fonts: IdPkgFonts,
     styles: IdPkgStyles,
     graphic: IdPkgGraphic,
-    preferences: Vec<String>,
+    preferences: IdPkgPreferences,
+}
+
+#[cfg(test)]
+impl IDMLResources {
+    /// Resources holding only the swatches and inks of the graphic
+    pub fn with_graphic(graphic: IdPkgGraphic) -> IDMLResources {
+        IDMLResources {
+            fonts: IdPkgFonts::default(),
+            styles: IdPkgStyles::default(),
+            graphic,
+            preferences: IdPkgPreferences::default(),
+        }
+    }
+
+    pub fn with_preferences(mut self, preferences: IdPkgPreferences) -> IDMLResources {
+        self.preferences = preferences;
+        self
+    }
 }
 
 #[derive(Deserialize, Debug)]
@@ -55,9 +77,10 @@ pub struct IdmlXml {
     tags: Vec<String>,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, Getters)]
 pub struct MetaInf {
     container: String,
+    metadata: Metadata,
 }
 
 impl IDMLPackage {
@@ -70,6 +93,7 @@ impl IDMLPackage {
         let master_spreads = parse_master_spreads(path).expect("Failed to parse master spreads");
         let spreads = parse_spreads(path).expect("Failed to parse spreads");
         let stories = parse_stories(path).expect("Failed to parse stories");
+        let metadata = parse_metadata(path).unwrap_or_default();
 
         // Combine everything
         let idml_package = IDMLPackage {
@@ -87,6 +111,7 @@ impl IDMLPackage {
             },
             meta_inf: MetaInf {
                 container: "Container dummy".to_string(),
+                metadata,
             },
         };
 
@@ -96,6 +121,44 @@ impl IDMLPackage {
     pub fn master_spread_with_id(&self, id: &str) -> Option<&Spread> {
         self.master_spreads.get(id)
     }
+
+    /// Removes the text hidden by the conditions applied to it from the stories,
+    /// so it takes up no room in the layout. Condition sets must be applied first.
+    pub fn remove_hidden_text(&mut self) {
+        let designmap = &self.designmap;
+        for story in self.stories.values_mut() {
+            story.without_hidden_text(&|conditions| designmap.is_hidden(conditions));
+        }
+    }
+
+    /// Prints the spot colors with process inks instead of on plates of their own
+    pub fn convert_spots_to_process(&mut self) {
+        self.resources.graphic.convert_spots_to_process();
+    }
+
+    pub fn designmap_mut(&mut self) -> &mut DesignMap {
+        &mut self.designmap
+    }
+
+    pub fn story_with_id_mut(&mut self, id: &str) -> Option<&mut Story> {
+        self.stories.get_mut(id)
+    }
+
+    /// The frame with the id on one of the spreads or master spreads
+    pub fn frame_with_id(&self, id: &str) -> Option<&Polygon> {
+        self.spreads
+            .values()
+            .chain(self.master_spreads.values())
+            .find_map(|spread| spread.polygon_with_id(id))
+    }
+
+    /// The frame with the id on one of the spreads or master spreads
+    pub fn frame_with_id_mut(&mut self, id: &str) -> Option<&mut Polygon> {
+        self.spreads
+            .values_mut()
+            .chain(self.master_spreads.values_mut())
+            .find_map(|spread| spread.polygon_with_id_mut(id))
+    }
 }
 
 fn parse_design_map(path: &Path) -> Result<DesignMap, quick_xml::DeError> {
@@ -104,6 +167,13 @@ fn parse_design_map(path: &Path) -> Result<DesignMap, quick_xml::DeError> {
     designmap_parser::parse_designmap_from_path(&xml_path)
 }
 
+fn parse_metadata(path: &Path) -> Result<Metadata, io::Error> {
+    let mut xml_path = PathBuf::from(path);
+    xml_path.push("META-INF");
+    xml_path.push("metadata.xml");
+    metadata_parser::parse_metadata_from_path(&xml_path)
+}
+
 fn parse_resources(path: &Path) -> Result<IDMLResources, io::Error> {
     let mut resource_dir = PathBuf::from(path);
     resource_dir.push("Resources");
@@ -126,9 +196,13 @@ fn parse_resources(path: &Path) -> Result<IDMLResources, io::Error> {
         .expect("Failed to parse Graphic.xml");
     resource_dir.pop();
 
-    // Preferences
+    // Preferences, which only change the defaults, so the document can do without them
     resource_dir.push("Preferences.xml");
-    let preferences = vec!["Preferences dummy".to_string()];
+    let preferences = preferences_parser::parse_preferences_from_path(&resource_dir)
+        .unwrap_or_else(|err| {
+            println!("Failed to parse Preferences.xml, using default preferences: {}", err);
+            IdPkgPreferences::default()
+        });
     resource_dir.pop();
 
     let resources = IDMLResources {
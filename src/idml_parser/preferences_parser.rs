@@ -0,0 +1,177 @@
+use derive_getters::Getters;
+use serde::Deserialize;
+use std::path::Path;
+
+#[derive(Default, Deserialize, Debug, PartialEq, Getters)]
+#[serde(rename = "idPkg:Preferences")]
+#[serde(rename_all = "PascalCase")]
+pub struct IdPkgPreferences {
+    #[serde(rename = "DOMVersion")]
+    dom_version: Option<f32>,
+    text_preference: Option<TextPreference>,
+    chapter_number_preference: Option<ChapterNumberPreference>,
+    footnote_option: Option<FootnoteOption>,
+    index_options: Option<IndexOptions>,
+}
+
+#[derive(Default, Deserialize, Debug, PartialEq, Getters, Clone)]
+#[serde(rename_all = "PascalCase")]
+pub struct TextPreference {
+    superscript_size: Option<f64>,
+    superscript_position: Option<f64>,
+    subscript_size: Option<f64>,
+    subscript_position: Option<f64>,
+    small_cap: Option<f64>,
+}
+
+#[derive(Default, Deserialize, Debug, PartialEq, Getters, Clone)]
+#[serde(rename_all = "PascalCase")]
+pub struct ChapterNumberPreference {
+    chapter_number: Option<u32>,
+    properties: Option<ChapterNumberProperties>,
+}
+
+#[derive(Default, Deserialize, Debug, PartialEq, Getters, Clone)]
+#[serde(rename_all = "PascalCase")]
+pub struct ChapterNumberProperties {
+    chapter_number_format: Option<String>,
+}
+
+/// Numbering and layout of the footnotes in the document
+#[derive(Default, Deserialize, Debug, PartialEq, Getters, Clone)]
+#[serde(rename_all = "PascalCase")]
+pub struct FootnoteOption {
+    start_at: Option<u32>,
+    prefix: Option<String>,
+    suffix: Option<String>,
+    footnote_marker_style: Option<String>,
+    /// Space between the last line of the text and the first footnote
+    spacer: Option<f64>,
+    space_between: Option<f64>,
+    rule_on: Option<bool>,
+    rule_line_weight: Option<f64>,
+    rule_left_indent: Option<f64>,
+    rule_width: Option<f64>,
+    /// Distance from the bottom of the rule to the top of the first footnote
+    rule_offset: Option<f64>,
+    /// Rule above footnotes that go on from the frame before
+    continuing_rule_on: Option<bool>,
+    continuing_rule_line_weight: Option<f64>,
+    continuing_rule_left_indent: Option<f64>,
+    continuing_rule_width: Option<f64>,
+    continuing_rule_offset: Option<f64>,
+    properties: Option<FootnoteOptionProperties>,
+}
+
+#[derive(Default, Deserialize, Debug, PartialEq, Getters, Clone)]
+#[serde(rename_all = "PascalCase")]
+pub struct FootnoteOptionProperties {
+    footnote_numbering_style: Option<String>,
+    restart_numbering: Option<String>,
+    show_prefix_suffix: Option<String>,
+    marker_positioning: Option<String>,
+    rule_type: Option<String>,
+    rule_color: Option<String>,
+    rule_gap_color: Option<String>,
+    continuing_rule_type: Option<String>,
+    continuing_rule_color: Option<String>,
+    continuing_rule_gap_color: Option<String>,
+}
+
+/// Styles and separators the index of the document is generated with
+#[derive(Default, Deserialize, Debug, PartialEq, Getters, Clone)]
+#[serde(rename_all = "PascalCase")]
+pub struct IndexOptions {
+    title: Option<String>,
+    title_style: Option<String>,
+    include_section_headings: Option<bool>,
+    level1_style: Option<String>,
+    level2_style: Option<String>,
+    level3_style: Option<String>,
+    level4_style: Option<String>,
+    section_heading_style: Option<String>,
+    /// Character style of the page numbers of the entries
+    page_number_style: Option<String>,
+    /// Text between a topic and its page numbers
+    following_topic_separator: Option<String>,
+    between_page_numbers_separator: Option<String>,
+    /// Text between the first and last page of a range of pages
+    page_range_separator: Option<String>,
+    /// Text at the end of each entry
+    entry_end_separator: Option<String>,
+}
+
+impl IndexOptions {
+    /// Paragraph style of the entries at a level of the index, starting at 1.
+    /// Levels past the fourth take the style of the fourth.
+    pub fn level_style(&self, level: u32) -> Option<&String> {
+        match level {
+            0 | 1 => self.level1_style.as_ref(),
+            2 => self.level2_style.as_ref(),
+            3 => self.level3_style.as_ref(),
+            _ => self.level4_style.as_ref(),
+        }
+    }
+
+    /// Paragraph styles of the entries and section headings of the index, which
+    /// tell a generated index apart from other stories. Entries in no paragraph
+    /// style cannot be told apart, so that style is left out.
+    pub fn index_styles(&self) -> Vec<&String> {
+        [
+            &self.level1_style,
+            &self.level2_style,
+            &self.level3_style,
+            &self.level4_style,
+            &self.section_heading_style,
+        ]
+        .iter()
+        .filter_map(|style| style.as_ref())
+        .filter(|style| !style.ends_with("[No paragraph style]"))
+        .collect()
+    }
+}
+
+pub fn parse_preferences_from_path(path: &Path) -> Result<IdPkgPreferences, quick_xml::DeError> {
+    let xml = std::fs::read_to_string(path)
+        .map_err(|err| quick_xml::DeError::Xml(quick_xml::Error::Io(err)))?;
+    quick_xml::de::from_str(xml.as_str())
+}
+
+impl IdPkgPreferences {
+    /// Superscript size and position as percentages of the font size
+    pub fn superscript(&self) -> (f64, f64) {
+        let text_preference = self.text_preference.clone().unwrap_or_default();
+        (
+            text_preference.superscript_size.unwrap_or(58.3),
+            text_preference.superscript_position.unwrap_or(33.3),
+        )
+    }
+
+    /// Subscript size and position as percentages of the font size
+    pub fn subscript(&self) -> (f64, f64) {
+        let text_preference = self.text_preference.clone().unwrap_or_default();
+        (
+            text_preference.subscript_size.unwrap_or(58.3),
+            text_preference.subscript_position.unwrap_or(33.3),
+        )
+    }
+
+    /// Size of synthesized small caps as a percentage of the font size
+    pub fn small_cap(&self) -> f64 {
+        self.text_preference
+            .as_ref()
+            .and_then(|p| p.small_cap)
+            .unwrap_or(70_f64)
+    }
+
+    /// Chapter number of the document and its format, such as "i, ii, iii, iv..."
+    pub fn chapter_number(&self) -> (u32, Option<String>) {
+        let preference = self.chapter_number_preference.clone().unwrap_or_default();
+        (
+            preference.chapter_number.unwrap_or(1),
+            preference
+                .properties
+                .and_then(|properties| properties.chapter_number_format),
+        )
+    }
+}
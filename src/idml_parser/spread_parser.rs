@@ -40,6 +40,16 @@ pub struct Spread {
 }
 
 impl Spread {
+    /// The frame with the id, which may be inside a group
+    pub fn polygon_with_id(&self, id: &str) -> Option<&Polygon> {
+        polygon_with_id(&self.contents, id)
+    }
+
+    /// The frame with the id, which may be inside a group
+    pub fn polygon_with_id_mut(&mut self, id: &str) -> Option<&mut Polygon> {
+        polygon_with_id_mut(&mut self.contents, id)
+    }
+
     pub fn pages(&self) -> Vec<Option<&Page>> {
         let pages: Vec<Option<&Page>> = self
             .contents()
@@ -54,7 +64,23 @@ impl Spread {
     }
 }
 
-#[derive(Deserialize, Debug, PartialEq)]
+fn polygon_with_id<'c>(contents: &'c [SpreadContent], id: &str) -> Option<&'c Polygon> {
+    contents.iter().find_map(|content| match content {
+        SpreadContent::Polygon(polygon) if polygon.id == id => Some(polygon),
+        SpreadContent::Group(group) => polygon_with_id(group.contents.as_deref()?, id),
+        _ => None,
+    })
+}
+
+fn polygon_with_id_mut<'c>(contents: &'c mut [SpreadContent], id: &str) -> Option<&'c mut Polygon> {
+    contents.iter_mut().find_map(|content| match content {
+        SpreadContent::Polygon(polygon) if polygon.id == id => Some(polygon),
+        SpreadContent::Group(group) => polygon_with_id_mut(group.contents.as_deref_mut()?, id),
+        _ => None,
+    })
+}
+
+#[derive(Deserialize, Debug, PartialEq, Clone)]
 pub enum SpreadContent {
     FlattenerPreference(FlattenerPreference),
     Page(Page),
@@ -66,11 +92,13 @@ pub enum SpreadContent {
     )]
     Polygon(Polygon),
     Group(Group),
+    /// Only found among the contents of groups, see `Group::anchored_object_setting`
+    AnchoredObjectSetting(AnchoredObjectSetting),
     #[serde(other)]
     NotImplementedYet,
 }
 
-#[derive(Default, Deserialize, Debug, PartialEq, Getters)]
+#[derive(Default, Deserialize, Debug, PartialEq, Getters, Clone)]
 #[serde(rename_all = "PascalCase")]
 pub struct Page {
     #[serde(rename = "Self")]
@@ -93,11 +121,11 @@ pub struct Page {
     snapshot_blending_mode: Option<String>,
     tab_order: Option<String>,
     use_master_grid: bool,
-    // margin_preference: MarginPreference,
+    margin_preference: Option<MarginPreference>,
     properties: Properties,
 }
 
-#[derive(Deserialize, Debug, PartialEq)]
+#[derive(Deserialize, Debug, PartialEq, Clone)]
 pub enum PageColorOptions {
     UseMasterColor,
     Color(Color),
@@ -105,28 +133,28 @@ pub enum PageColorOptions {
     Nothing,
 }
 
-#[derive(Default, Deserialize, Debug, PartialEq, Getters)]
+#[derive(Default, Deserialize, Debug, PartialEq, Getters, Clone)]
 pub struct Color {
     r: f64,
     g: f64,
     b: f64,
 }
 
-// #[derive(Default, Deserialize, Debug, PartialEq, Getters)]
-// #[serde(rename_all = "PascalCase")]
-// pub struct MarginPreference {
-//     column_count: i32, // One of these i32's causes an error when deserializing
-//     column_gutter: i32,
-//     top: i32,
-//     bottom: i32,
-//     left: i32,
-//     right: i32,
-//     column_direction: String,
-//     #[serde(default, deserialize_with = "deserialize_space_seperated_vec")]
-//     columns_positions: Vec<f64>,
-// }
+#[derive(Default, Deserialize, Debug, PartialEq, Getters, Clone)]
+#[serde(rename_all = "PascalCase")]
+pub struct MarginPreference {
+    column_count: Option<f64>,
+    column_gutter: Option<f64>,
+    top: Option<f64>,
+    bottom: Option<f64>,
+    left: Option<f64>,
+    right: Option<f64>,
+    column_direction: Option<String>,
+    #[serde(default, deserialize_with = "deserialize_space_seperated_vec")]
+    columns_positions: Vec<f64>,
+}
 
-#[derive(Default, Deserialize, Debug, PartialEq, Getters)]
+#[derive(Default, Deserialize, Debug, PartialEq, Getters, Clone)]
 #[serde(rename_all = "PascalCase")]
 pub struct FlattenerPreference {
     // #[serde(rename="Self")]
@@ -134,7 +162,7 @@ pub struct FlattenerPreference {
 // fill_color: Option<String>,
 }
 
-#[derive(Default, Deserialize, Debug, PartialEq, Getters)]
+#[derive(Default, Deserialize, Debug, PartialEq, Getters, Clone)]
 #[serde(rename_all = "PascalCase")]
 pub struct Polygon {
     #[serde(rename = "Self")]
@@ -151,24 +179,364 @@ pub struct Polygon {
     properties: Option<Properties>,
     stroke_color: Option<String>,
     stroke_weight: Option<f64>,
+    /// Percentage of the fill color, or -1 when not set
+    fill_tint: Option<f64>,
+    /// Percentage of the stroke color, or -1 when not set
+    stroke_tint: Option<f64>,
+    /// Where a gradient fill starts, in the coordinates of the item
+    #[serde(default, deserialize_with = "deserialize_space_seperated_opt_vec")]
+    gradient_fill_start: Option<Vec<f64>>,
+    gradient_fill_length: Option<f64>,
+    gradient_fill_angle: Option<f64>,
+    /// Where a gradient stroke starts, in the coordinates of the item
+    #[serde(default, deserialize_with = "deserialize_space_seperated_opt_vec")]
+    gradient_stroke_start: Option<Vec<f64>>,
+    gradient_stroke_length: Option<f64>,
+    gradient_stroke_angle: Option<f64>,
     applied_object_style: Option<String>,
+    /// Set on items anchored in a story
+    anchored_object_setting: Option<AnchoredObjectSetting>,
+    /// Raster image placed in the frame
+    image: Option<Image>,
+    /// PDF page or Illustrator artwork placed in the frame
+    #[serde(rename = "PDF")]
+    pdf: Option<Pdf>,
+    /// Encapsulated PostScript graphic placed in the frame
+    #[serde(rename = "EPS")]
+    eps: Option<Eps>,
+    /// SVG graphic placed in the frame
+    #[serde(rename = "SVG")]
+    svg: Option<Svg>,
+    /// How graphics placed in the frame are fitted to it
+    frame_fitting_option: Option<FrameFittingOption>,
+    /// Columns of the text in the frame
+    text_frame_preference: Option<TextFramePreference>,
+    /// Set when the graphic is replaced with `replace_content`, so it is fitted to the frame
+    #[serde(skip)]
+    content_replaced: bool,
 }
 
-#[derive(Default, Deserialize, Debug, PartialEq, Getters)]
+impl Polygon {
+    /// Replaces the graphic placed in the frame with the file at `uri`, like a data merge does.
+    /// The kind of graphic is told by the file extension. It is placed as in an empty frame,
+    /// so it is fitted to the frame as the frame fitting options say.
+    pub fn replace_content(&mut self, uri: &str) {
+        let link = Some(Link {
+            id: format!("{}Link", self.id),
+            link_resource_uri: Some(uri.to_string()),
+            ..Link::default()
+        });
+        let id = format!("{}Content", self.id);
+        let extension = Path::new(uri)
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(|extension| extension.to_lowercase());
+        self.image = None;
+        self.pdf = None;
+        self.eps = None;
+        self.svg = None;
+        match extension.as_deref() {
+            Some("pdf") | Some("ai") => self.pdf = Some(Pdf { id, link, ..Pdf::default() }),
+            Some("eps") => self.eps = Some(Eps { id, link, ..Eps::default() }),
+            Some("svg") => self.svg = Some(Svg { id, link, ..Svg::default() }),
+            _ => self.image = Some(Image { id, link, ..Image::default() }),
+        }
+        self.content_replaced = true;
+    }
+}
+
+/// How graphics placed in a frame are fitted to it
+#[derive(Default, Deserialize, Debug, PartialEq, Getters, Clone)]
+#[serde(rename_all = "PascalCase")]
+pub struct FrameFittingOption {
+    /// Fit the graphic again whenever the frame or the graphic changes
+    auto_fit: Option<bool>,
+    /// How far the graphic reaches beyond each side of the frame, or negative for space between them
+    left_crop: Option<f64>,
+    top_crop: Option<f64>,
+    right_crop: Option<f64>,
+    bottom_crop: Option<f64>,
+    fitting_on_empty_frame: Option<EmptyFrameFitting>,
+    /// Point of the frame the graphic is aligned to
+    fitting_alignment: Option<AnchorPoint>,
+}
+
+/// How a graphic is fitted to a frame when it is placed in it
+#[derive(Deserialize, Debug, PartialEq, Clone, Copy)]
+pub enum EmptyFrameFitting {
+    /// The graphic is placed at its own size
+    None,
+    /// The graphic is stretched to the size of the frame
+    ContentToFrame,
+    /// The graphic is scaled to fit inside the frame, keeping its proportions
+    Proportionally,
+    /// The graphic is scaled to cover the frame, keeping its proportions
+    FillProportionally,
+    /// Like `FillProportionally`, as we do not know what the graphic shows
+    ContentAwareFit,
+    /// The graphic is centered in the frame at its own size
+    CenterContent,
+}
+
+/// Raster image placed in a graphic frame. Its transform places it relative to the frame.
+#[derive(Default, Deserialize, Debug, PartialEq, Getters, Clone)]
+#[serde(rename_all = "PascalCase")]
+pub struct Image {
+    #[serde(rename = "Self")]
+    id: String,
+    #[serde(default, deserialize_with = "deserialize_space_seperated_opt_vec")]
+    item_transform: Option<Vec<f64>>,
+    #[serde(default, deserialize_with = "deserialize_space_seperated_opt_vec")]
+    actual_ppi: Option<Vec<f64>>,
+    #[serde(default, deserialize_with = "deserialize_space_seperated_opt_vec")]
+    effective_ppi: Option<Vec<f64>>,
+    image_type_name: Option<String>,
+    visible: Option<bool>,
+    /// Holds the bounds of the image and, if it is embedded, its base64 encoded data
+    properties: Option<Properties>,
+    /// Clipping path made from a path or alpha channel saved in the image, or from its edges
+    clipping_path_settings: Option<ClippingPathSettings>,
+    link: Option<Link>,
+}
+
+/// How an image is clipped, besides by its frame
+#[derive(Default, Deserialize, Debug, PartialEq, Getters, Clone)]
+#[serde(rename_all = "PascalCase")]
+pub struct ClippingPathSettings {
+    clipping_type: Option<ClippingPathType>,
+    /// Show the part of the image outside the clipping path instead
+    invert_path: Option<bool>,
+    /// Also hide light areas inside the image when detecting edges
+    include_inside_edges: Option<bool>,
+    restrict_to_frame: Option<bool>,
+    use_high_resolution_image: Option<bool>,
+    /// Pixels this close to white are hidden when detecting edges, from 0 to 255
+    threshold: Option<f64>,
+    tolerance: Option<f64>,
+    /// How far the clipping path is moved inwards, or outwards if negative
+    inset_frame: Option<f64>,
+    /// Name of the Photoshop path or alpha channel the image is clipped to
+    applied_path_name: Option<String>,
+    index: Option<i32>,
+    /// Path the image is clipped to when its clipping path has been edited,
+    /// in the coordinates of the image
+    clipping_path_geometry: Option<PathGeometry>,
+}
+
+#[derive(Deserialize, Debug, PartialEq, Clone, Copy)]
+pub enum ClippingPathType {
+    None,
+    DetectEdges,
+    AlphaChannel,
+    PhotoshopPath,
+    UserModifiedPath,
+}
+
+/// Page of a PDF or Illustrator file placed in a graphic frame, which is kept as vector
+/// content. Its transform places it relative to the frame, like for images.
+#[derive(Default, Deserialize, Debug, PartialEq, Getters, Clone)]
+#[serde(rename_all = "PascalCase")]
+pub struct Pdf {
+    #[serde(rename = "Self")]
+    id: String,
+    #[serde(default, deserialize_with = "deserialize_space_seperated_opt_vec")]
+    item_transform: Option<Vec<f64>>,
+    visible: Option<bool>,
+    /// Holds the bounds of the page and, if it is embedded, its base64 encoded data
+    properties: Option<Properties>,
+    #[serde(rename = "PDFAttribute")]
+    pdf_attribute: Option<PdfAttribute>,
+    link: Option<Link>,
+}
+
+/// Encapsulated PostScript graphic placed in a graphic frame. Its transform places it relative to the frame.
+#[derive(Default, Deserialize, Debug, PartialEq, Getters, Clone)]
+#[serde(rename_all = "PascalCase")]
+pub struct Eps {
+    #[serde(rename = "Self")]
+    id: String,
+    #[serde(default, deserialize_with = "deserialize_space_seperated_opt_vec")]
+    item_transform: Option<Vec<f64>>,
+    visible: Option<bool>,
+    /// Holds the bounding box of the graphic and, if it is embedded, its base64 encoded data
+    properties: Option<Properties>,
+    link: Option<Link>,
+}
+
+/// SVG graphic placed in a graphic frame. Its transform places it relative to the frame.
+#[derive(Default, Deserialize, Debug, PartialEq, Getters, Clone)]
+#[serde(rename_all = "PascalCase")]
+pub struct Svg {
+    #[serde(rename = "Self")]
+    id: String,
+    #[serde(default, deserialize_with = "deserialize_space_seperated_opt_vec")]
+    item_transform: Option<Vec<f64>>,
+    visible: Option<bool>,
+    /// Holds the bounds of the graphic and, if it is embedded, its base64 encoded data
+    properties: Option<Properties>,
+    link: Option<Link>,
+}
+
+/// Which page of a placed PDF is shown, and how it is cropped
+#[derive(Default, Deserialize, Debug, PartialEq, Getters, Clone)]
+#[serde(rename_all = "PascalCase")]
+pub struct PdfAttribute {
+    page_number: Option<u32>,
+    #[serde(rename = "PDFCrop")]
+    pdf_crop: Option<PdfCrop>,
+    transparent_background: Option<bool>,
+}
+
+/// Page box of a placed PDF shown in the frame
+#[derive(Deserialize, Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum PdfCrop {
+    /// Bounds of the artwork on the page
+    CropContent,
+    CropContentAllLayers,
+    CropContentVisibleLayers,
+    CropArt,
+    /// The crop box of the page
+    #[serde(rename = "CropPDF")]
+    CropPdf,
+    CropTrim,
+    CropBleed,
+    CropMedia,
+}
+
+/// Link to the file a placed graphic was imported from
+#[derive(Default, Deserialize, Debug, PartialEq, Getters, Clone)]
+#[serde(rename_all = "PascalCase")]
+pub struct Link {
+    #[serde(rename = "Self")]
+    id: String,
+    #[serde(rename = "LinkResourceURI")]
+    link_resource_uri: Option<String>,
+    link_resource_format: Option<String>,
+    /// `Embedded` when the file is saved in the document
+    stored_state: Option<String>,
+}
+
+#[derive(Default, Deserialize, Debug, PartialEq, Getters, Clone)]
 #[serde(rename_all = "PascalCase")]
 pub struct Group {
     #[serde(rename = "Self")]
     id: String,
+    #[serde(default, deserialize_with = "deserialize_space_seperated_opt_vec")]
+    item_transform: Option<Vec<f64>>,
     // fill_color: Option<String>,
-    // #[serde(rename="$value")]
-    // contents: Vec<PageContent>
+    #[serde(rename = "$value")]
+    contents: Option<Vec<SpreadContent>>,
 }
 
-#[derive(Default, Deserialize, Debug, PartialEq, Getters)]
+impl Group {
+    /// Anchored object setting of the group. This is deserialized as part
+    /// of the contents, as the group holds both the setting and `$value`s
+    pub fn anchored_object_setting(&self) -> Option<&AnchoredObjectSetting> {
+        self.contents
+            .iter()
+            .flatten()
+            .find_map(|content| match content {
+                SpreadContent::AnchoredObjectSetting(setting) => Some(setting),
+                _ => None,
+            })
+    }
+}
+
+/// Columns the text of a text frame is set in
+#[derive(Default, Deserialize, Debug, PartialEq, Getters, Clone)]
+#[serde(rename_all = "PascalCase")]
+pub struct TextFramePreference {
+    text_column_count: Option<usize>,
+    /// Room between the columns
+    text_column_gutter: Option<f64>,
+}
+
+/// Where an item anchored in a story is placed
+#[derive(Default, Deserialize, Debug, PartialEq, Getters, Clone)]
+#[serde(rename_all = "PascalCase")]
+pub struct AnchoredObjectSetting {
+    anchored_position: Option<AnchorPosition>,
+    /// Point on the item that is placed at the reference point
+    anchor_point: Option<AnchorPoint>,
+    horizontal_alignment: Option<HorizontalAlignment>,
+    horizontal_reference_point: Option<AnchoredRelativeTo>,
+    vertical_alignment: Option<VerticalAlignment>,
+    vertical_reference_point: Option<VerticallyRelativeTo>,
+    #[serde(rename = "AnchorXoffset")]
+    anchor_x_offset: Option<f64>,
+    #[serde(rename = "AnchorYoffset")]
+    anchor_y_offset: Option<f64>,
+    anchor_space_above: Option<f64>,
+}
+
+#[derive(Deserialize, Debug, PartialEq, Clone, Copy)]
+pub enum AnchorPosition {
+    InlinePosition,
+    AboveLine,
+    Anchored,
+}
+
+#[derive(Deserialize, Debug, PartialEq, Clone, Copy)]
+pub enum AnchorPoint {
+    TopLeftAnchor,
+    TopCenterAnchor,
+    TopRightAnchor,
+    LeftCenterAnchor,
+    CenterAnchor,
+    RightCenterAnchor,
+    BottomLeftAnchor,
+    BottomCenterAnchor,
+    BottomRightAnchor,
+}
+
+#[derive(Deserialize, Debug, PartialEq, Clone, Copy)]
+pub enum HorizontalAlignment {
+    LeftAlign,
+    CenterAlign,
+    RightAlign,
+    TextAlign,
+}
+
+#[derive(Deserialize, Debug, PartialEq, Clone, Copy)]
+pub enum VerticalAlignment {
+    TopAlign,
+    CenterAlign,
+    BottomAlign,
+}
+
+#[derive(Deserialize, Debug, PartialEq, Clone, Copy)]
+pub enum AnchoredRelativeTo {
+    AnchorLocation,
+    ColumnEdge,
+    TextFrame,
+    PageMargins,
+    PageEdge,
+}
+
+#[derive(Deserialize, Debug, PartialEq, Clone, Copy)]
+pub enum VerticallyRelativeTo {
+    LineBaseline,
+    LineXheight,
+    LineAscent,
+    CapHeight,
+    TopOfLeading,
+    EmboxTop,
+    EmboxMiddle,
+    EmboxBottom,
+    ColumnEdge,
+    TextFrame,
+    PageMargins,
+    PageEdge,
+}
+
+#[derive(Default, Deserialize, Debug, PartialEq, Getters, Clone)]
 #[serde(rename_all = "PascalCase")]
 pub struct Properties {
     applied_font: Option<String>,
     path_geometry: Option<PathGeometry>,
+    graphic_bounds: Option<GraphicBounds>,
+    /// Base64 encoded data of embedded graphics
+    contents: Option<String>,
     // descriptor: Option<Descriptor>,
 }
 
@@ -204,13 +572,23 @@ pub struct Properties {
 //     }
 // }
 
-#[derive(Default, Deserialize, Debug, PartialEq, Getters)]
+/// Bounds of a placed graphic in its own coordinates
+#[derive(Default, Deserialize, Debug, PartialEq, Getters, Clone)]
+#[serde(rename_all = "PascalCase")]
+pub struct GraphicBounds {
+    left: f64,
+    top: f64,
+    right: f64,
+    bottom: f64,
+}
+
+#[derive(Default, Deserialize, Debug, PartialEq, Getters, Clone)]
 #[serde(rename_all = "PascalCase")]
 pub struct PathGeometry {
     geometry_path_type: GeometryPathType,
 }
 
-#[derive(Default, Deserialize, Debug, PartialEq, Getters)]
+#[derive(Default, Deserialize, Debug, PartialEq, Getters, Clone)]
 #[serde(rename_all = "PascalCase")]
 pub struct GeometryPathType {
     path_open: bool,
@@ -218,14 +596,14 @@ pub struct GeometryPathType {
     path_point_arrays: Vec<PathPointArray>,
 }
 
-#[derive(Default, Deserialize, Debug, PartialEq, Getters)]
+#[derive(Default, Deserialize, Debug, PartialEq, Getters, Clone)]
 #[serde(rename_all = "PascalCase")]
 pub struct PathPointArray {
     #[serde(rename = "$value")]
     path_point_array: Vec<PathPointType>,
 }
 
-#[derive(Default, Deserialize, Debug, PartialEq, Getters)]
+#[derive(Default, Deserialize, Debug, PartialEq, Getters, Clone)]
 #[serde(rename_all = "PascalCase")]
 pub struct PathPointType {
     #[serde(default, deserialize_with = "deserialize_space_seperated_opt_vec")]
@@ -1,9 +1,13 @@
 use super::formats::*;
+use crate::idml_parser::spread_parser::{Group, Polygon};
+use crate::idml_parser::styles::cell_style::*;
+use crate::idml_parser::styles::commom_text_properties::*;
+use crate::idml_parser::styles::table_style::*;
 use derive_getters::Getters;
 use quick_xml::de::{from_str, DeError};
+use regex::{Captures, Regex};
 use serde::Deserialize;
 use std::path::Path;
-use crate::idml_parser::styles::commom_text_properties::*;
 
 #[derive(Default, Deserialize, Debug, Getters)]
 #[serde(rename = "idPkg:Story")]
@@ -19,12 +23,34 @@ pub struct StoryWrapper {
 pub struct Story {
     #[serde(rename = "Self")]
     id: String,
+    /// Table of contents style the story was generated with, if it is a
+    /// table of contents. Other stories have it set to "n".
+    #[serde(rename = "AppliedTOCStyle")]
+    applied_toc_style: Option<String>,
     user_text: Option<bool>,
     story_title: Option<String>,
+    /// Whether the story holds the text of the endnotes of the document
+    is_endnote_story: Option<bool>,
+    story_preference: Option<StoryPreference>,
     #[serde(rename = "ParagraphStyleRange")]
     paragraph_style_ranges: Option<Vec<ParagraphStyleRange>>,
 }
 
+#[derive(Default, Deserialize, Debug, Getters)]
+#[serde(rename_all = "PascalCase")]
+pub struct StoryPreference {
+    optical_margin_alignment: Option<bool>,
+    optical_margin_size: Option<f64>,
+    story_orientation: Option<StoryOrientation>,
+    story_direction: Option<ParagraphDirection>,
+}
+
+#[derive(Deserialize, Debug, PartialEq, Clone, Copy)]
+pub enum StoryOrientation {
+    Horizontal,
+    Vertical,
+}
+
 common_text_properties_struct! {
     ParagraphStyleRange {
         #[serde(rename = "CharacterStyleRange")]
@@ -34,6 +60,7 @@ common_text_properties_struct! {
 
 common_text_properties_struct! {
     CharacterStyleRange {
+        paragraph_break_type: Option<StartParagraph>,
         properties: Option<Properties>,
         #[serde(rename = "$value")]
         contents: Option<Vec<StoryContent>>,
@@ -44,6 +71,12 @@ common_text_properties_struct! {
 #[serde(rename_all = "PascalCase")]
 pub struct Properties {
     applied_font: Option<String>,
+    underline_color: Option<String>,
+    underline_gap_color: Option<String>,
+    underline_type: Option<String>,
+    strike_through_color: Option<String>,
+    strike_through_gap_color: Option<String>,
+    strike_through_type: Option<String>,
 }
 
 #[derive(Deserialize, Debug, Clone, PartialEq)]
@@ -51,10 +84,191 @@ pub enum StoryContent {
     Properties(Properties),
     Content(String),
     Br,
+    ForcedLineBreak,
+    AutoPageNumber,
+    SectionMarker,
+    FootnoteNumber,
+    IndentToHere,
+    RightIndentTab,
+    EmSpace,
+    EnSpace,
+    ThirdSpace,
+    QuarterSpace,
+    SixthSpace,
+    ThinSpace,
+    HairSpace,
+    FigureSpace,
+    PunctuationSpace,
+    FlushSpace,
+    NonBreakingSpace,
+    FixedWidthNonBreakingSpace,
+    NonBreakingHyphen,
+    TextVariableInstance(TextVariableInstance),
+    Footnote(Footnote),
+    Endnote(Endnote),
+    HyperlinkTextSource(HyperlinkTextSource),
+    CrossReferenceSource(CrossReferenceSource),
+    #[serde(alias = "ParagraphDestination")]
+    HyperlinkTextDestination(HyperlinkTextDestination),
+    PageReference(PageReference),
+    Table(Table),
+    #[serde(
+        alias = "Rectangle",
+        alias = "Oval",
+        alias = "TextFrame",
+        alias = "GraphicLine"
+    )]
+    Polygon(Polygon),
+    Group(Group),
     #[serde(other)]
     NotImplementedYet,
 }
 
+#[derive(Default, Deserialize, Debug, Clone, PartialEq, Getters)]
+#[serde(rename_all = "PascalCase")]
+pub struct TextVariableInstance {
+    #[serde(rename = "Self")]
+    id: String,
+    name: Option<String>,
+    /// Text of the variable when the document was last saved
+    result_text: Option<String>,
+    associated_text_variable: Option<String>,
+}
+
+/// Text that is the source of a hyperlink
+#[derive(Default, Deserialize, Debug, Clone, PartialEq, Getters)]
+#[serde(rename_all = "PascalCase")]
+pub struct HyperlinkTextSource {
+    #[serde(rename = "Self")]
+    id: String,
+    name: Option<String>,
+    applied_character_style: Option<String>,
+    #[serde(rename = "$value")]
+    contents: Option<Vec<StoryContent>>,
+}
+
+/// Text of a cross-reference, as it was when the document was last saved
+#[derive(Default, Deserialize, Debug, Clone, PartialEq, Getters)]
+#[serde(rename_all = "PascalCase")]
+pub struct CrossReferenceSource {
+    #[serde(rename = "Self")]
+    id: String,
+    name: Option<String>,
+    applied_format: Option<String>,
+    applied_character_style: Option<String>,
+    #[serde(rename = "$value")]
+    contents: Option<Vec<StoryContent>>,
+}
+
+/// Place in the text that hyperlinks, cross-references and bookmarks can go to.
+/// Paragraph destinations of cross-references are read as these too.
+#[derive(Default, Deserialize, Debug, Clone, PartialEq, Getters)]
+#[serde(rename_all = "PascalCase")]
+pub struct HyperlinkTextDestination {
+    #[serde(rename = "Self")]
+    id: String,
+    name: Option<String>,
+}
+
+/// Marks a page of the text as referring to a topic of the index
+#[derive(Default, Deserialize, Debug, Clone, PartialEq, Getters)]
+#[serde(rename_all = "PascalCase")]
+pub struct PageReference {
+    #[serde(rename = "Self")]
+    id: String,
+    page_reference_type: Option<String>,
+    /// Number of paragraphs or pages the reference spans, or the paragraph
+    /// style ending it, for the types of reference that take one
+    page_reference_limit: Option<String>,
+    referenced_topic: Option<String>,
+}
+
+/// Footnote referenced where it is in the text, holding the text of the footnote
+#[derive(Default, Deserialize, Debug, Clone, PartialEq, Getters)]
+#[serde(rename_all = "PascalCase")]
+pub struct Footnote {
+    #[serde(rename = "Self")]
+    id: Option<String>,
+    #[serde(rename = "ParagraphStyleRange")]
+    paragraph_style_ranges: Option<Vec<ParagraphStyleRange>>,
+}
+
+/// Endnote referenced where it is in the text. The text of
+/// the endnote is in the endnote story of the document.
+#[derive(Default, Deserialize, Debug, Clone, PartialEq, Getters)]
+#[serde(rename_all = "PascalCase")]
+pub struct Endnote {
+    #[serde(rename = "Self")]
+    id: Option<String>,
+}
+
+table_attributes_struct! {
+    Table {
+        #[serde(rename = "Self")]
+        id: String,
+        header_row_count: Option<usize>,
+        footer_row_count: Option<usize>,
+        body_row_count: Option<usize>,
+        column_count: Option<usize>,
+        /// Leaves the header rows out of the frame the table starts in
+        skip_first_header: Option<bool>,
+        /// Leaves the footer rows out of the frame the table ends in
+        skip_last_footer: Option<bool>,
+        applied_table_style: Option<String>,
+        #[serde(rename = "Row")]
+        rows: Option<Vec<Row>>,
+        #[serde(rename = "Column")]
+        columns: Option<Vec<Column>>,
+        #[serde(rename = "Cell")]
+        cells: Option<Vec<Cell>>,
+    }
+}
+
+#[derive(Default, Deserialize, Debug, Clone, PartialEq, Getters)]
+#[serde(rename_all = "PascalCase")]
+pub struct Row {
+    #[serde(rename = "Self")]
+    id: String,
+    /// Index of the row in the table
+    name: usize,
+    single_row_height: Option<f64>,
+    minimum_height: Option<f64>,
+    /// Whether the row grows to fit its contents
+    auto_grow: Option<bool>,
+}
+
+#[derive(Default, Deserialize, Debug, Clone, PartialEq, Getters)]
+#[serde(rename_all = "PascalCase")]
+pub struct Column {
+    #[serde(rename = "Self")]
+    id: String,
+    /// Index of the column in the table
+    name: usize,
+    single_column_width: Option<f64>,
+}
+
+cell_attributes_struct! {
+    Cell {
+        #[serde(rename = "Self")]
+        id: String,
+        /// Position of the cell as "column:row"
+        name: String,
+        row_span: Option<usize>,
+        column_span: Option<usize>,
+        applied_cell_style: Option<String>,
+        #[serde(rename = "ParagraphStyleRange")]
+        paragraph_style_ranges: Option<Vec<ParagraphStyleRange>>,
+    }
+}
+
+impl Cell {
+    /// Column and row index of the cell
+    pub fn position(&self) -> Option<(usize, usize)> {
+        let mut indices = self.name.split(':').map(|index| index.parse().ok());
+        Some((indices.next()??, indices.next()??))
+    }
+}
+
 #[derive(Default, Deserialize, Debug, Getters)]
 #[serde(rename_all = "PascalCase")]
 pub struct Content {
@@ -64,7 +278,237 @@ pub struct Content {
 
 pub fn parse_story_from_path(path: &Path) -> Result<StoryWrapper, DeError> {
     let xml = std::fs::read_to_string(path).unwrap();
-    from_str(xml.as_str())
+    from_str(split_special_characters(&xml).as_str())
+}
+
+/// Element name of the `StoryContent` a processing instruction
+/// such as `<?ACE 18?>` stands for
+fn special_instruction(code: &str) -> Option<&'static str> {
+    match code {
+        "4" => Some("FootnoteNumber"),
+        "7" => Some("IndentToHere"),
+        "8" => Some("RightIndentTab"),
+        "18" => Some("AutoPageNumber"),
+        "19" => Some("SectionMarker"),
+        _ => None,
+    }
+}
+
+/// Element name of the `StoryContent` a special character stands for
+fn special_character(c: char) -> Option<&'static str> {
+    match c {
+        '\u{2028}' => Some("ForcedLineBreak"),
+        '\u{2003}' => Some("EmSpace"),
+        '\u{2002}' => Some("EnSpace"),
+        '\u{2004}' => Some("ThirdSpace"),
+        '\u{2005}' => Some("QuarterSpace"),
+        '\u{2006}' => Some("SixthSpace"),
+        '\u{2009}' => Some("ThinSpace"),
+        '\u{200A}' => Some("HairSpace"),
+        '\u{2007}' => Some("FigureSpace"),
+        '\u{2008}' => Some("PunctuationSpace"),
+        '\u{2001}' => Some("FlushSpace"),
+        '\u{00A0}' => Some("NonBreakingSpace"),
+        '\u{202F}' => Some("FixedWidthNonBreakingSpace"),
+        '\u{2011}' => Some("NonBreakingHyphen"),
+        _ => None,
+    }
+}
+
+/// Splits `Content` elements around processing instructions and special
+/// characters, so they are deserialized as their own `StoryContent`.
+/// Processing instructions are otherwise dropped by the XML reader.
+fn split_special_characters(xml: &str) -> String {
+    let content_re = Regex::new(r"(?s)<Content>(.*?)</Content>").unwrap();
+    let instruction_re = Regex::new(r"<\?ACE (\d+)\?>").unwrap();
+
+    content_re
+        .replace_all(xml, |caps: &Captures| {
+            let mut elements = String::new();
+            let mut text = String::new();
+            // Ends the current text, if any, followed by the given element
+            let mut push_element = |text: &mut String, name: Option<&str>| {
+                if !text.is_empty() {
+                    // The XML reader trims text, so spaces at either end are escaped
+                    let start = text.len() - text.trim_start_matches(' ').len();
+                    let end = text.trim_end_matches(' ').len().max(start);
+                    elements.push_str(&format!(
+                        "<Content>{}{}{}</Content>",
+                        "&#32;".repeat(start),
+                        &text[start..end],
+                        "&#32;".repeat(text.len() - end)
+                    ));
+                    text.clear();
+                }
+                if let Some(name) = name {
+                    elements.push_str(&format!("<{}/>", name));
+                }
+            };
+
+            let mut last_end = 0;
+            let mut segments = vec![];
+            for instruction in instruction_re.captures_iter(&caps[1]) {
+                let whole = instruction.get(0).unwrap();
+                segments.push((
+                    &caps[1][last_end..whole.start()],
+                    special_instruction(&instruction[1]),
+                ));
+                last_end = whole.end();
+            }
+            segments.push((&caps[1][last_end..], None));
+
+            for (segment, instruction) in segments {
+                for c in segment.chars() {
+                    match special_character(c) {
+                        Some(name) => push_element(&mut text, Some(name)),
+                        None => text.push(c),
+                    }
+                }
+                if instruction.is_some() {
+                    push_element(&mut text, instruction);
+                }
+            }
+            push_element(&mut text, None);
+            elements
+        })
+        .to_string()
+}
+
+impl Story {
+    /// Replaces the text of the story, such as with a regenerated table of contents
+    pub fn with_paragraph_style_ranges(
+        &mut self,
+        paragraph_style_ranges: Vec<ParagraphStyleRange>,
+    ) -> &mut Self {
+        self.paragraph_style_ranges = Some(paragraph_style_ranges);
+        self
+    }
+}
+
+impl Story {
+    /// Removes the text that `is_hidden` says is hidden by its applied conditions,
+    /// along with the paragraphs left without any text
+    pub fn without_hidden_text(&mut self, is_hidden: &dyn Fn(&str) -> bool) -> &mut Self {
+        if let Some(p_styles) = &mut self.paragraph_style_ranges {
+            remove_hidden_text(p_styles, is_hidden);
+        }
+        self
+    }
+}
+
+fn remove_hidden_text(p_styles: &mut Vec<ParagraphStyleRange>, is_hidden: &dyn Fn(&str) -> bool) {
+    p_styles.retain(|p_style| {
+        p_style
+            .character_style_ranges
+            .as_ref()
+            .map_or(true, |c_styles| {
+                c_styles.is_empty()
+                    || c_styles
+                        .iter()
+                        .any(|c_style| match &c_style.applied_conditions {
+                            Some(conditions) => !is_hidden(conditions),
+                            None => true,
+                        })
+            })
+    });
+    for p_style in p_styles.iter_mut() {
+        let c_styles = match &mut p_style.character_style_ranges {
+            Some(c_styles) => c_styles,
+            None => continue,
+        };
+        c_styles.retain(|c_style| match &c_style.applied_conditions {
+            Some(conditions) => !is_hidden(conditions),
+            None => true,
+        });
+        // Footnotes and tables in the text have text of their own
+        for content in c_styles
+            .iter_mut()
+            .filter_map(|c_style| c_style.contents.as_mut())
+            .flatten()
+        {
+            match content {
+                StoryContent::Footnote(footnote) => {
+                    if let Some(p_styles) = &mut footnote.paragraph_style_ranges {
+                        remove_hidden_text(p_styles, is_hidden);
+                    }
+                }
+                StoryContent::Table(table) => {
+                    let p_styles = table
+                        .cells
+                        .iter_mut()
+                        .flatten()
+                        .filter_map(|cell| cell.paragraph_style_ranges.as_mut());
+                    for p_styles in p_styles {
+                        remove_hidden_text(p_styles, is_hidden);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+impl ParagraphStyleRange {
+    pub fn new(
+        applied_paragraph_style: Option<String>,
+        character_style_ranges: Vec<CharacterStyleRange>,
+    ) -> ParagraphStyleRange {
+        ParagraphStyleRange {
+            applied_paragraph_style,
+            character_style_ranges: Some(character_style_ranges),
+            ..ParagraphStyleRange::default()
+        }
+    }
+
+    /// Ends the last paragraph of the range with a break,
+    /// or leaves it open for the text after it to continue
+    pub fn with_end_break(&mut self, end_break: bool) -> &mut Self {
+        let c_styles = self.character_style_ranges.get_or_insert_with(Vec::new);
+        if c_styles.is_empty() {
+            c_styles.push(CharacterStyleRange::default());
+        }
+        let contents = c_styles
+            .last_mut()
+            .unwrap()
+            .contents
+            .get_or_insert_with(Vec::new);
+        match (end_break, contents.last() == Some(&StoryContent::Br)) {
+            (true, false) => contents.push(StoryContent::Br),
+            (false, true) => {
+                contents.pop();
+            }
+            _ => {}
+        }
+        self
+    }
+}
+
+impl CharacterStyleRange {
+    pub fn new(
+        applied_character_style: Option<String>,
+        contents: Vec<StoryContent>,
+    ) -> CharacterStyleRange {
+        CharacterStyleRange {
+            applied_character_style,
+            contents: Some(contents),
+            ..CharacterStyleRange::default()
+        }
+    }
+
+    /// Local properties of the range. These are deserialized as part of the
+    /// contents, as the range holds both a `Properties` element and `$value`s
+    pub fn local_properties(&self) -> Option<&Properties> {
+        if let Some(properties) = &self.properties {
+            return Some(properties);
+        }
+        self.contents
+            .iter()
+            .flatten()
+            .find_map(|content| match content {
+                StoryContent::Properties(properties) => Some(properties),
+                _ => None,
+            })
+    }
 }
 
 impl StoryWrapper {
@@ -72,3 +516,46 @@ impl StoryWrapper {
         self.story
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plain_text_is_left_as_it_is() {
+        let xml = "<Br/><Content>Plain text</Content>";
+        assert_eq!(split_special_characters(xml), xml);
+    }
+
+    #[test]
+    fn special_characters_become_elements() {
+        assert_eq!(
+            split_special_characters("<Content>a\u{2003}b\u{2028}</Content>"),
+            "<Content>a</Content><EmSpace/><Content>b</Content><ForcedLineBreak/>"
+        );
+    }
+
+    #[test]
+    fn processing_instructions_become_elements() {
+        assert_eq!(
+            split_special_characters("<Content>Page<?ACE 18?> of <?ACE 19?></Content>"),
+            "<Content>Page</Content><AutoPageNumber/><Content>&#32;of&#32;</Content><SectionMarker/>"
+        );
+    }
+
+    #[test]
+    fn unknown_processing_instructions_are_dropped() {
+        assert_eq!(
+            split_special_characters("<Content>a<?ACE 99?>b</Content>"),
+            "<Content>ab</Content>"
+        );
+    }
+
+    #[test]
+    fn spaces_at_the_ends_of_text_are_kept() {
+        assert_eq!(
+            split_special_characters("<Content>  a b </Content>"),
+            "<Content>&#32;&#32;a b&#32;</Content>"
+        );
+    }
+}
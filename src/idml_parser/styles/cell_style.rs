@@ -0,0 +1,145 @@
+use super::{Style, StyleGroup};
+use derive_getters::Getters;
+use serde::Deserialize;
+
+#[derive(Default, Deserialize, Debug, PartialEq, Getters)]
+#[serde(rename_all = "PascalCase")]
+pub struct RootCellStyleGroup {
+    #[serde(rename = "Self")]
+    id: Option<String>,
+    #[serde(rename = "CellStyle")]
+    cell_styles: Option<Vec<CellStyle>>,
+}
+
+impl StyleGroup<CellStyle> for RootCellStyleGroup {
+    fn get_styles(&self) -> &Option<Vec<CellStyle>> {
+        &self.cell_styles()
+    }
+}
+
+#[derive(Deserialize, Debug, PartialEq, Clone, Copy)]
+pub enum VerticalJustification {
+    TopAlign,
+    CenterAlign,
+    BottomAlign,
+    JustifyAlign,
+}
+
+/// Attributes that cells share with cell styles
+pub trait CellAttributes {
+    fn top_inset(&self) -> Option<f64>;
+    fn left_inset(&self) -> Option<f64>;
+    fn bottom_inset(&self) -> Option<f64>;
+    fn right_inset(&self) -> Option<f64>;
+    fn fill_color(&self) -> Option<String>;
+    fn vertical_justification(&self) -> Option<VerticalJustification>;
+    fn top_edge_stroke_weight(&self) -> Option<f64>;
+    fn top_edge_stroke_color(&self) -> Option<String>;
+    fn left_edge_stroke_weight(&self) -> Option<f64>;
+    fn left_edge_stroke_color(&self) -> Option<String>;
+    fn bottom_edge_stroke_weight(&self) -> Option<f64>;
+    fn bottom_edge_stroke_color(&self) -> Option<String>;
+    fn right_edge_stroke_weight(&self) -> Option<f64>;
+    fn right_edge_stroke_color(&self) -> Option<String>;
+}
+
+#[macro_export]
+macro_rules! cell_attributes_struct {
+    ($StructName:ident { $($manual_fields:tt)* }) => {
+        #[derive(Default, Deserialize, Debug, PartialEq, Getters, Clone)]
+        #[serde(rename_all = "PascalCase")]
+        pub struct $StructName {
+            $($manual_fields)*
+            top_inset: Option<f64>,
+            left_inset: Option<f64>,
+            bottom_inset: Option<f64>,
+            right_inset: Option<f64>,
+            fill_color: Option<String>,
+            vertical_justification: Option<VerticalJustification>,
+            top_edge_stroke_weight: Option<f64>,
+            top_edge_stroke_color: Option<String>,
+            left_edge_stroke_weight: Option<f64>,
+            left_edge_stroke_color: Option<String>,
+            bottom_edge_stroke_weight: Option<f64>,
+            bottom_edge_stroke_color: Option<String>,
+            right_edge_stroke_weight: Option<f64>,
+            right_edge_stroke_color: Option<String>,
+        }
+
+        impl CellAttributes for $StructName {
+            fn top_inset(&self) -> Option<f64> {self.top_inset.clone()}
+            fn left_inset(&self) -> Option<f64> {self.left_inset.clone()}
+            fn bottom_inset(&self) -> Option<f64> {self.bottom_inset.clone()}
+            fn right_inset(&self) -> Option<f64> {self.right_inset.clone()}
+            fn fill_color(&self) -> Option<String> {self.fill_color.clone()}
+            fn vertical_justification(&self) -> Option<VerticalJustification> {self.vertical_justification.clone()}
+            fn top_edge_stroke_weight(&self) -> Option<f64> {self.top_edge_stroke_weight.clone()}
+            fn top_edge_stroke_color(&self) -> Option<String> {self.top_edge_stroke_color.clone()}
+            fn left_edge_stroke_weight(&self) -> Option<f64> {self.left_edge_stroke_weight.clone()}
+            fn left_edge_stroke_color(&self) -> Option<String> {self.left_edge_stroke_color.clone()}
+            fn bottom_edge_stroke_weight(&self) -> Option<f64> {self.bottom_edge_stroke_weight.clone()}
+            fn bottom_edge_stroke_color(&self) -> Option<String> {self.bottom_edge_stroke_color.clone()}
+            fn right_edge_stroke_weight(&self) -> Option<f64> {self.right_edge_stroke_weight.clone()}
+            fn right_edge_stroke_color(&self) -> Option<String> {self.right_edge_stroke_color.clone()}
+        }
+    };
+}
+
+cell_attributes_struct! {
+    CellStyle {
+        #[serde(rename = "Self")]
+        id: Option<String>,
+        name: Option<String>,
+        applied_paragraph_style: Option<String>,
+        properties: Option<CellStyleProperties>,
+    }
+}
+
+#[derive(Default, Deserialize, Debug, PartialEq, Getters, Clone)]
+#[serde(rename_all = "PascalCase")]
+pub struct CellStyleProperties {
+    based_on: Option<String>,
+}
+
+impl Style for CellStyle {
+    fn get_id(&self) -> &Option<String> {
+        &self.id()
+    }
+
+    fn get_parent_id(&self) -> &Option<String> {
+        match &self.properties {
+            Some(properties) => &properties.based_on,
+            _ => &None,
+        }
+    }
+
+    fn combine_with_parent(&self, parent: &CellStyle) -> CellStyle {
+        choose_fields!(
+            self,
+            self,
+            parent,
+            CellStyle {
+                // Manually set fields
+                id: self.id.clone(),
+                properties: self.properties.clone()
+            },
+            // Fields that can be overwritten by child
+            name,
+            applied_paragraph_style,
+            top_inset,
+            left_inset,
+            bottom_inset,
+            right_inset,
+            fill_color,
+            vertical_justification,
+            top_edge_stroke_weight,
+            top_edge_stroke_color,
+            left_edge_stroke_weight,
+            left_edge_stroke_color,
+            bottom_edge_stroke_weight,
+            bottom_edge_stroke_color,
+            right_edge_stroke_weight,
+            right_edge_stroke_color,
+        )
+    }
+}
@@ -27,6 +27,12 @@ common_text_properties_struct! {
 pub struct CharacterProperties {
     based_on: Option<String>,
     applied_font: Option<String>,
+    underline_color: Option<String>,
+    underline_gap_color: Option<String>,
+    underline_type: Option<String>,
+    strike_through_color: Option<String>,
+    strike_through_gap_color: Option<String>,
+    strike_through_type: Option<String>,
 }
 
 impl Style for CharacterStyle {
@@ -56,6 +62,12 @@ impl Style for CharacterStyle {
                 },
                 // Fields that can be overwritten by child properties
                 applied_font,
+                underline_color,
+                underline_gap_color,
+                underline_type,
+                strike_through_color,
+                strike_through_gap_color,
+                strike_through_type,
             )),
             _ => None,
         };
@@ -265,6 +277,7 @@ impl Style for CharacterStyle {
             strike_through_gap_overprint,
             strike_through_gap_tint,
             strike_through_gap_offset,
+            strike_through_offset,
             strike_through_overprint,
             strike_through_tint,
             strike_through_weight,
@@ -284,6 +297,7 @@ impl Style for CharacterStyle {
             underline_gap_overprint,
             underline_gap_tint,
             underline_gap_offset,
+            underline_offset,
             underline_overprint,
             underline_tint,
             underline_weight,
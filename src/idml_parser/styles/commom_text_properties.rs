@@ -193,13 +193,14 @@ pub trait CommonTextPropertiesAttributes {
     fn strike_through_gap_overprint(&self) -> Option<bool> {None}
     fn strike_through_gap_tint(&self) -> Option<f64> {None}
     fn strike_through_gap_offset(&self) -> Option<f64> {None}
+    fn strike_through_offset(&self) -> Option<f64> {None}
     fn strike_through_overprint(&self) -> Option<bool> {None}
     fn strike_through_tint(&self) -> Option<f64> {None}
     fn strike_through_weight(&self) -> Option<f64> {None}
     fn strike_thru(&self) -> Option<bool> {None}
     fn stroke_alignment(&self) -> Option<TextStrokeAlign> {None}
     fn stroke_color(&self) -> Option<String> {None}
-    fn stroke_tint(&self) -> Option<String> {None}
+    fn stroke_tint(&self) -> Option<f64> {None}
     fn stroke_weight(&self) -> Option<String> {None}
     fn tatechuyoko(&self) -> Option<bool> {None}
     fn tatechuyoko_x_offset(&self) -> Option<f64> {None}
@@ -212,6 +213,7 @@ pub trait CommonTextPropertiesAttributes {
     fn underline_gap_overprint(&self) -> Option<bool> {None}
     fn underline_gap_tint(&self) -> Option<f64> {None}
     fn underline_gap_offset(&self) -> Option<bool> {None}
+    fn underline_offset(&self) -> Option<f64> {None}
     fn underline_overprint(&self) -> Option<bool> {None}
     fn underline_tint(&self) -> Option<f64> {None}
     fn underline_weight(&self) -> Option<f64> {None}
@@ -228,7 +230,7 @@ pub trait CommonTextPropertiesAttributes {
 }
 
 
-#[derive(Deserialize, Debug, PartialEq, Clone)]
+#[derive(Deserialize, Debug, PartialEq, Clone, Copy)]
 pub enum Capitalization {
     Normal,
     SmallCaps,
@@ -248,7 +250,7 @@ pub enum CharacterAlignment {
 }
 
 
-#[derive(Deserialize, Debug, PartialEq, Clone)]
+#[derive(Deserialize, Debug, PartialEq, Clone, Copy)]
 pub enum CharacterDirection {
     DefaultDirection,
     LeftToRightDirection,
@@ -256,7 +258,7 @@ pub enum CharacterDirection {
 }
 
 
-#[derive(Deserialize, Debug, PartialEq, Clone)]
+#[derive(Deserialize, Debug, PartialEq, Clone, Copy)]
 pub enum DiacriticPosition {
     DefaultPosition,
     LoosePosition,
@@ -267,7 +269,7 @@ pub enum DiacriticPosition {
 }
 
 
-#[derive(Deserialize, Debug, PartialEq, Clone)]
+#[derive(Deserialize, Debug, PartialEq, Clone, Copy)]
 pub enum DigitsType {
     DefaultDigits,
     ArabicDigits,
@@ -334,7 +336,7 @@ pub enum Justification {
 }
 
 
-#[derive(Deserialize, Debug, PartialEq, Clone)]
+#[derive(Deserialize, Debug, PartialEq, Clone, Copy)]
 pub enum Kashidas {
     DefaultKashidas,
     KashidasOff,
@@ -352,7 +354,7 @@ pub enum KentenAlignment {
 }
 
 
-#[derive(Deserialize, Debug, PartialEq, Clone)]
+#[derive(Deserialize, Debug, PartialEq, Clone, Copy)]
 pub enum KentenCharacter {
     None,
     KentenSesameDot,
@@ -376,14 +378,14 @@ pub enum AdornmentOverprint {
 }
 
 
-#[derive(Deserialize, Debug, PartialEq, Clone)]
+#[derive(Deserialize, Debug, PartialEq, Clone, Copy)]
 pub enum RubyKentenPosition {
     AboveRight,
     BelowLeft,
 }
 
 
-#[derive(Deserialize, Debug, PartialEq, Clone)]
+#[derive(Deserialize, Debug, PartialEq, Clone, Copy)]
 pub enum KinsokuHangTypes {
     None,
     KinsokuHangRegular,
@@ -418,12 +420,13 @@ pub enum ListAlignment {
 }
 
 
-#[derive(Deserialize, Debug, PartialEq, Clone)]
+#[derive(Deserialize, Debug, PartialEq, Clone, Copy)]
 pub enum OTFFigureStyle {
-    TabularOldStyle,
-    TablularLining,
-    ProportionalOldStyle,
-    ProportinalLining,
+    TabularOldstyle,
+    TabularLining,
+    ProportionalOldstyle,
+    ProportionalLining,
+    #[serde(other)]
     Default,
 }
 
@@ -436,7 +439,7 @@ pub enum PageNumberType {
 }
 
 
-#[derive(Deserialize, Debug, PartialEq, Clone)]
+#[derive(Deserialize, Debug, PartialEq, Clone, Copy)]
 pub enum ParagraphDirection {
     LeftToRightDirection,
     RightToLeftDirection,
@@ -451,7 +454,7 @@ pub enum ParagraphJustification {
 }
 
 
-#[derive(Deserialize, Debug, PartialEq, Clone)]
+#[derive(Deserialize, Debug, PartialEq, Clone, Copy)]
 pub enum Position {
     Normal,
     Superscript,
@@ -474,7 +477,7 @@ pub enum PositionalForms {
 }
 
 
-#[derive(Deserialize, Debug, PartialEq, Clone)]
+#[derive(Deserialize, Debug, PartialEq, Clone, Copy)]
 pub enum RubyAlignments {
     RubyLeft,
     RubyCenter,
@@ -507,7 +510,7 @@ pub enum RubyParentSpacing {
 }
 
 
-#[derive(Deserialize, Debug, PartialEq, Clone)]
+#[derive(Deserialize, Debug, PartialEq, Clone, Copy)]
 pub enum RubyTypes {
     GroupRuby,
     PerCharacterRuby,
@@ -556,7 +559,7 @@ pub enum TextStrokeAlign {
 }
 
 
-#[derive(Deserialize, Debug, PartialEq, Clone)]
+#[derive(Deserialize, Debug, PartialEq, Clone, Copy)]
 pub enum WarichuAlignment {
     Auto,
     LeftAlign,
@@ -587,6 +590,7 @@ macro_rules! common_text_properties_struct {
             bullets_and_numbering_list_type: Option<String>,
             bullets_text_after: Option<String>,
             bunri_kinshi: Option<bool>,
+            #[serde(rename = "Capitalization")]
             captilization: Option<Capitalization>,
             character_alignment: Option<CharacterAlignment>,
             character_direction: Option<CharacterDirection>,
@@ -677,24 +681,43 @@ macro_rules! common_text_properties_struct {
             numbering_expression: Option<String>,
             numbering_level: Option<i32>,
             numbering_start_at: Option<i32>,
+            #[serde(rename = "OTFContextualAlternate")]
             otf_contextual_alternate: Option<bool>,
+            #[serde(rename = "OTFDiscretionaryLigature")]
             otf_discretionary_ligature: Option<bool>,
+            #[serde(rename = "OTFFigureStyle")]
             otf_figure_style: Option<OTFFigureStyle>,
+            #[serde(rename = "OTFFraction")]
             otf_fraction: Option<bool>,
+            #[serde(rename = "OTFHVKana")]
             otf_hv_kana: Option<bool>,
+            #[serde(rename = "OTFHistorical")]
             otf_historical: Option<bool>,
+            #[serde(rename = "OTFJustificationAlternate")]
             otf_justification_alternate: Option<bool>,
+            #[serde(rename = "OTFLocale")]
             otf_locale: Option<bool>,
+            #[serde(rename = "OTFMark")]
             otf_mark: Option<bool>,
+            #[serde(rename = "OTFOrdinal")]
             otf_ordinal: Option<bool>,
+            #[serde(rename = "OTFOverlapSwash")]
             otf_overlap_swash: Option<bool>,
+            #[serde(rename = "OTFProportionalMetrics")]
             otf_proportional_metrics: Option<bool>,
+            #[serde(rename = "OTFRomanItalics")]
             otf_roman_italics: Option<bool>,
+            #[serde(rename = "OTFSlashedZero")]
             otf_slashed_zero: Option<bool>,
+            #[serde(rename = "OTFStretchedAlternate")]
             otf_stretched_alternate: Option<bool>,
+            #[serde(rename = "OTFStylisticAlternate")]
             otf_stylistic_alternate: Option<bool>,
+            #[serde(rename = "OTFStylisticSets")]
             otf_stylistic_sets: Option<i32>,
+            #[serde(rename = "OTFSwash")]
             otf_swash: Option<bool>,
+            #[serde(rename = "OTFTitling")]
             otf_titling: Option<bool>,
             otf_overprint_fill: Option<bool>,
             otf_overprint_stroke: Option<bool>,
@@ -755,6 +778,7 @@ macro_rules! common_text_properties_struct {
             scale_affects_line_height: Option<bool>,
             shatai_adjust_rotation: Option<bool>,
             shatai_adjust_tsume: Option<bool>,
+            #[serde(rename = "ShataiDegreeAngle")]
             shatai_adjust_angle: Option<f64>,
             shatai_magnification: Option<f64>,
             single_word_justification: Option<SingleWordJustification>,
@@ -769,13 +793,14 @@ macro_rules! common_text_properties_struct {
             strike_through_gap_overprint: Option<bool>,
             strike_through_gap_tint: Option<f64>,
             strike_through_gap_offset: Option<f64>,
+            strike_through_offset: Option<f64>,
             strike_through_overprint: Option<bool>,
             strike_through_tint: Option<f64>,
             strike_through_weight: Option<f64>,
             strike_thru: Option<bool>,
             stroke_alignment: Option<TextStrokeAlign>,
             stroke_color: Option<String>,
-            stroke_tint: Option<String>,
+            stroke_tint: Option<f64>,
             stroke_weight: Option<String>,
             tatechuyoko: Option<bool>,
             tatechuyoko_x_offset: Option<f64>,
@@ -788,6 +813,7 @@ macro_rules! common_text_properties_struct {
             underline_gap_overprint: Option<bool>,
             underline_gap_tint: Option<f64>,
             underline_gap_offset: Option<bool>,
+            underline_offset: Option<f64>,
             underline_overprint: Option<bool>,
             underline_tint: Option<f64>,
             underline_weight: Option<f64>,
@@ -996,13 +1022,14 @@ macro_rules! common_text_properties_struct {
             fn strike_through_gap_overprint(&self) -> Option<bool> {self.strike_through_gap_overprint.clone()}
             fn strike_through_gap_tint(&self) -> Option<f64> {self.strike_through_gap_tint.clone()}
             fn strike_through_gap_offset(&self) -> Option<f64> {self.strike_through_gap_offset.clone()}
+            fn strike_through_offset(&self) -> Option<f64> {self.strike_through_offset.clone()}
             fn strike_through_overprint(&self) -> Option<bool> {self.strike_through_overprint.clone()}
             fn strike_through_tint(&self) -> Option<f64> {self.strike_through_tint.clone()}
             fn strike_through_weight(&self) -> Option<f64> {self.strike_through_weight.clone()}
             fn strike_thru(&self) -> Option<bool> {self.strike_thru.clone()}
             fn stroke_alignment(&self) -> Option<TextStrokeAlign> {self.stroke_alignment.clone()}
             fn stroke_color(&self) -> Option<String> {self.stroke_color.clone()}
-            fn stroke_tint(&self) -> Option<String> {self.stroke_tint.clone()}
+            fn stroke_tint(&self) -> Option<f64> {self.stroke_tint.clone()}
             fn stroke_weight(&self) -> Option<String> {self.stroke_weight.clone()}
             fn tatechuyoko(&self) -> Option<bool> {self.tatechuyoko.clone()}
             fn tatechuyoko_x_offset(&self) -> Option<f64> {self.tatechuyoko_x_offset.clone()}
@@ -1015,6 +1042,7 @@ macro_rules! common_text_properties_struct {
             fn underline_gap_overprint(&self) -> Option<bool> {self.underline_gap_overprint.clone()}
             fn underline_gap_tint(&self) -> Option<f64> {self.underline_gap_tint.clone()}
             fn underline_gap_offset(&self) -> Option<bool> {self.underline_gap_offset.clone()}
+            fn underline_offset(&self) -> Option<f64> {self.underline_offset.clone()}
             fn underline_overprint(&self) -> Option<bool> {self.underline_overprint.clone()}
             fn underline_tint(&self) -> Option<f64> {self.underline_tint.clone()}
             fn underline_weight(&self) -> Option<f64> {self.underline_weight.clone()}
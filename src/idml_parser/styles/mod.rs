@@ -82,3 +82,7 @@ pub mod commom_text_properties;
 pub mod object_style;
 pub mod paragraph_style;
 pub mod character_style;
+#[macro_use]
+pub mod cell_style;
+#[macro_use]
+pub mod table_style;
@@ -18,8 +18,10 @@ pub struct ObjectStyle {
     id: Option<String>,
     name: Option<String>,
     fill_color: Option<String>,
+    fill_tint: Option<f64>,
     stroke_weight: Option<f64>,
     stroke_color: Option<String>,
+    stroke_tint: Option<f64>,
     properties: Option<ObjectProperties>,
 }
 
@@ -71,7 +73,9 @@ impl Style for ObjectStyle {
             // Fields that can be overwritten by child
             name,
             fill_color,
+            fill_tint,
             stroke_color,
+            stroke_tint,
             stroke_weight,
         )
     }
@@ -34,6 +34,13 @@ common_text_properties_struct! {
 pub struct ParagraphProperties {
     based_on: Option<String>,
     applied_font: Option<String>,
+    underline_color: Option<String>,
+    underline_gap_color: Option<String>,
+    underline_type: Option<String>,
+    strike_through_color: Option<String>,
+    strike_through_gap_color: Option<String>,
+    strike_through_type: Option<String>,
+    numbering_format: Option<String>,
 }
 
 impl Style for ParagraphStyle {
@@ -61,6 +68,13 @@ impl Style for ParagraphStyle {
                 },
                 // Fields that can be overwritten by child properties
                 applied_font,
+                underline_color,
+                underline_gap_color,
+                underline_type,
+                strike_through_color,
+                strike_through_gap_color,
+                strike_through_type,
+                numbering_format,
             )),
             _ => None,
         };
@@ -270,6 +284,7 @@ impl Style for ParagraphStyle {
             strike_through_gap_overprint,
             strike_through_gap_tint,
             strike_through_gap_offset,
+            strike_through_offset,
             strike_through_overprint,
             strike_through_tint,
             strike_through_weight,
@@ -289,6 +304,7 @@ impl Style for ParagraphStyle {
             underline_gap_overprint,
             underline_gap_tint,
             underline_gap_offset,
+            underline_offset,
             underline_overprint,
             underline_tint,
             underline_weight,
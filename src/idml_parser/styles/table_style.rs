@@ -0,0 +1,209 @@
+use super::{Style, StyleGroup};
+use derive_getters::Getters;
+use serde::Deserialize;
+
+#[derive(Default, Deserialize, Debug, PartialEq, Getters)]
+#[serde(rename_all = "PascalCase")]
+pub struct RootTableStyleGroup {
+    #[serde(rename = "Self")]
+    id: Option<String>,
+    #[serde(rename = "TableStyle")]
+    table_styles: Option<Vec<TableStyle>>,
+}
+
+impl StyleGroup<TableStyle> for RootTableStyleGroup {
+    fn get_styles(&self) -> &Option<Vec<TableStyle>> {
+        &self.table_styles()
+    }
+
+    /// Table styles are based on each other by their name, e.g. "$ID/[No table style]",
+    /// which lacks the "TableStyle/" prefix of their id
+    fn style_from_id(&self, id: &String) -> Option<TableStyle> {
+        let prefixed_id = format!("TableStyle/{}", id);
+        let style = self.table_styles().iter().flatten().find(|style| match style.get_id() {
+            Some(style_id) => style_id == id || style_id == &prefixed_id,
+            None => false,
+        })?;
+        Some(self.with_parent_properties(style).into_owned())
+    }
+}
+
+/// Attributes that tables share with table styles
+pub trait TableAttributes {
+    fn text_top_inset(&self) -> Option<f64>;
+    fn text_left_inset(&self) -> Option<f64>;
+    fn text_bottom_inset(&self) -> Option<f64>;
+    fn text_right_inset(&self) -> Option<f64>;
+    fn top_border_stroke_weight(&self) -> Option<f64>;
+    fn top_border_stroke_color(&self) -> Option<String>;
+    fn left_border_stroke_weight(&self) -> Option<f64>;
+    fn left_border_stroke_color(&self) -> Option<String>;
+    fn bottom_border_stroke_weight(&self) -> Option<f64>;
+    fn bottom_border_stroke_color(&self) -> Option<String>;
+    fn right_border_stroke_weight(&self) -> Option<f64>;
+    fn right_border_stroke_color(&self) -> Option<String>;
+    /// Rows take the start row stroke and then the end row stroke by turns,
+    /// for as many rows as their counts say
+    fn start_row_stroke_count(&self) -> Option<usize>;
+    fn start_row_stroke_weight(&self) -> Option<f64>;
+    fn start_row_stroke_color(&self) -> Option<String>;
+    fn end_row_stroke_count(&self) -> Option<usize>;
+    fn end_row_stroke_weight(&self) -> Option<f64>;
+    fn end_row_stroke_color(&self) -> Option<String>;
+    /// Columns alternate between their start and end strokes like rows do
+    fn start_column_stroke_count(&self) -> Option<usize>;
+    fn start_column_stroke_weight(&self) -> Option<f64>;
+    fn start_column_stroke_color(&self) -> Option<String>;
+    fn end_column_stroke_count(&self) -> Option<usize>;
+    fn end_column_stroke_weight(&self) -> Option<f64>;
+    fn end_column_stroke_color(&self) -> Option<String>;
+    fn space_before(&self) -> Option<f64>;
+    fn space_after(&self) -> Option<f64>;
+    fn header_region_cell_style(&self) -> Option<String>;
+    fn footer_region_cell_style(&self) -> Option<String>;
+    fn body_region_cell_style(&self) -> Option<String>;
+}
+
+#[macro_export]
+macro_rules! table_attributes_struct {
+    ($StructName:ident { $($manual_fields:tt)* }) => {
+        #[derive(Default, Deserialize, Debug, PartialEq, Getters, Clone)]
+        #[serde(rename_all = "PascalCase")]
+        pub struct $StructName {
+            $($manual_fields)*
+            text_top_inset: Option<f64>,
+            text_left_inset: Option<f64>,
+            text_bottom_inset: Option<f64>,
+            text_right_inset: Option<f64>,
+            top_border_stroke_weight: Option<f64>,
+            top_border_stroke_color: Option<String>,
+            left_border_stroke_weight: Option<f64>,
+            left_border_stroke_color: Option<String>,
+            bottom_border_stroke_weight: Option<f64>,
+            bottom_border_stroke_color: Option<String>,
+            right_border_stroke_weight: Option<f64>,
+            right_border_stroke_color: Option<String>,
+            start_row_stroke_count: Option<usize>,
+            start_row_stroke_weight: Option<f64>,
+            start_row_stroke_color: Option<String>,
+            end_row_stroke_count: Option<usize>,
+            end_row_stroke_weight: Option<f64>,
+            end_row_stroke_color: Option<String>,
+            start_column_stroke_count: Option<usize>,
+            start_column_stroke_weight: Option<f64>,
+            start_column_stroke_color: Option<String>,
+            end_column_stroke_count: Option<usize>,
+            end_column_stroke_weight: Option<f64>,
+            end_column_stroke_color: Option<String>,
+            space_before: Option<f64>,
+            space_after: Option<f64>,
+            header_region_cell_style: Option<String>,
+            footer_region_cell_style: Option<String>,
+            body_region_cell_style: Option<String>,
+        }
+
+        impl TableAttributes for $StructName {
+            fn text_top_inset(&self) -> Option<f64> {self.text_top_inset.clone()}
+            fn text_left_inset(&self) -> Option<f64> {self.text_left_inset.clone()}
+            fn text_bottom_inset(&self) -> Option<f64> {self.text_bottom_inset.clone()}
+            fn text_right_inset(&self) -> Option<f64> {self.text_right_inset.clone()}
+            fn top_border_stroke_weight(&self) -> Option<f64> {self.top_border_stroke_weight.clone()}
+            fn top_border_stroke_color(&self) -> Option<String> {self.top_border_stroke_color.clone()}
+            fn left_border_stroke_weight(&self) -> Option<f64> {self.left_border_stroke_weight.clone()}
+            fn left_border_stroke_color(&self) -> Option<String> {self.left_border_stroke_color.clone()}
+            fn bottom_border_stroke_weight(&self) -> Option<f64> {self.bottom_border_stroke_weight.clone()}
+            fn bottom_border_stroke_color(&self) -> Option<String> {self.bottom_border_stroke_color.clone()}
+            fn right_border_stroke_weight(&self) -> Option<f64> {self.right_border_stroke_weight.clone()}
+            fn right_border_stroke_color(&self) -> Option<String> {self.right_border_stroke_color.clone()}
+            fn start_row_stroke_count(&self) -> Option<usize> {self.start_row_stroke_count.clone()}
+            fn start_row_stroke_weight(&self) -> Option<f64> {self.start_row_stroke_weight.clone()}
+            fn start_row_stroke_color(&self) -> Option<String> {self.start_row_stroke_color.clone()}
+            fn end_row_stroke_count(&self) -> Option<usize> {self.end_row_stroke_count.clone()}
+            fn end_row_stroke_weight(&self) -> Option<f64> {self.end_row_stroke_weight.clone()}
+            fn end_row_stroke_color(&self) -> Option<String> {self.end_row_stroke_color.clone()}
+            fn start_column_stroke_count(&self) -> Option<usize> {self.start_column_stroke_count.clone()}
+            fn start_column_stroke_weight(&self) -> Option<f64> {self.start_column_stroke_weight.clone()}
+            fn start_column_stroke_color(&self) -> Option<String> {self.start_column_stroke_color.clone()}
+            fn end_column_stroke_count(&self) -> Option<usize> {self.end_column_stroke_count.clone()}
+            fn end_column_stroke_weight(&self) -> Option<f64> {self.end_column_stroke_weight.clone()}
+            fn end_column_stroke_color(&self) -> Option<String> {self.end_column_stroke_color.clone()}
+            fn space_before(&self) -> Option<f64> {self.space_before.clone()}
+            fn space_after(&self) -> Option<f64> {self.space_after.clone()}
+            fn header_region_cell_style(&self) -> Option<String> {self.header_region_cell_style.clone()}
+            fn footer_region_cell_style(&self) -> Option<String> {self.footer_region_cell_style.clone()}
+            fn body_region_cell_style(&self) -> Option<String> {self.body_region_cell_style.clone()}
+        }
+    };
+}
+
+table_attributes_struct! {
+    TableStyle {
+        #[serde(rename = "Self")]
+        id: Option<String>,
+        name: Option<String>,
+        properties: Option<TableStyleProperties>,
+    }
+}
+
+#[derive(Default, Deserialize, Debug, PartialEq, Getters, Clone)]
+#[serde(rename_all = "PascalCase")]
+pub struct TableStyleProperties {
+    based_on: Option<String>,
+}
+
+impl Style for TableStyle {
+    fn get_id(&self) -> &Option<String> {
+        &self.id()
+    }
+
+    fn get_parent_id(&self) -> &Option<String> {
+        match &self.properties {
+            Some(properties) => &properties.based_on,
+            _ => &None,
+        }
+    }
+
+    fn combine_with_parent(&self, parent: &TableStyle) -> TableStyle {
+        choose_fields!(
+            self,
+            self,
+            parent,
+            TableStyle {
+                // Manually set fields
+                id: self.id.clone(),
+                properties: self.properties.clone()
+            },
+            // Fields that can be overwritten by child
+            name,
+            text_top_inset,
+            text_left_inset,
+            text_bottom_inset,
+            text_right_inset,
+            top_border_stroke_weight,
+            top_border_stroke_color,
+            left_border_stroke_weight,
+            left_border_stroke_color,
+            bottom_border_stroke_weight,
+            bottom_border_stroke_color,
+            right_border_stroke_weight,
+            right_border_stroke_color,
+            start_row_stroke_count,
+            start_row_stroke_weight,
+            start_row_stroke_color,
+            end_row_stroke_count,
+            end_row_stroke_weight,
+            end_row_stroke_color,
+            start_column_stroke_count,
+            start_column_stroke_weight,
+            start_column_stroke_color,
+            end_column_stroke_count,
+            end_column_stroke_weight,
+            end_column_stroke_color,
+            space_before,
+            space_after,
+            header_region_cell_style,
+            footer_region_cell_style,
+            body_region_cell_style,
+        )
+    }
+}
@@ -1,6 +1,8 @@
+use super::styles::cell_style::*;
 use super::styles::character_style::*;
 use super::styles::object_style::*;
 use super::styles::paragraph_style::*;
+use super::styles::table_style::*;
 use super::styles::*;
 use derive_getters::Getters;
 use serde::Deserialize;
@@ -15,6 +17,47 @@ pub struct IdPkgStyles {
     root_object_style_group: Option<RootObjectStyleGroup>,
     root_paragraph_style_group: Option<RootParagraphStyleGroup>,
     root_character_style_group: Option<RootCharacterStyleGroup>,
+    root_cell_style_group: Option<RootCellStyleGroup>,
+    root_table_style_group: Option<RootTableStyleGroup>,
+    #[serde(rename = "TOCStyle")]
+    toc_styles: Option<Vec<TocStyle>>,
+}
+
+/// Settings a table of contents is generated with
+#[derive(Default, Deserialize, Debug, PartialEq, Getters, Clone)]
+#[serde(rename_all = "PascalCase")]
+pub struct TocStyle {
+    #[serde(rename = "Self")]
+    id: String,
+    name: Option<String>,
+    title: Option<String>,
+    title_style: Option<String>,
+    #[serde(rename = "TOCStyleEntry")]
+    entries: Option<Vec<TocStyleEntry>>,
+}
+
+/// Paragraph style whose paragraphs go into the table of contents, and how
+#[derive(Default, Deserialize, Debug, PartialEq, Getters, Clone)]
+#[serde(rename_all = "PascalCase")]
+pub struct TocStyleEntry {
+    /// Name of the paragraph style
+    name: Option<String>,
+    /// Paragraph style of the entries in the table of contents
+    format_style: Option<String>,
+    page_number_position: Option<PageNumberPosition>,
+    page_number_style: Option<String>,
+    /// Text between the entry and its page number, where "^t" is a tab
+    separator: Option<String>,
+    separator_style: Option<String>,
+    level: Option<u32>,
+}
+
+#[derive(Deserialize, Debug, PartialEq, Clone, Copy)]
+pub enum PageNumberPosition {
+    AfterEntry,
+    BeforeEntry,
+    #[serde(other)]
+    NoPageNumber,
 }
 
 impl IdPkgStyles {
@@ -38,6 +81,27 @@ impl IdPkgStyles {
             _ => None,
         }
     }
+
+    pub fn cell_style_from_id(&self, id: &String) -> Option<CellStyle> {
+        match &self.root_cell_style_group {
+            Some(root_style_group) => root_style_group.style_from_id(id),
+            _ => None,
+        }
+    }
+
+    pub fn toc_style_from_id(&self, id: &String) -> Option<&TocStyle> {
+        self.toc_styles
+            .iter()
+            .flatten()
+            .find(|style| style.id() == id)
+    }
+
+    pub fn table_style_from_id(&self, id: &String) -> Option<TableStyle> {
+        match &self.root_table_style_group {
+            Some(root_style_group) => root_style_group.style_from_id(id),
+            _ => None,
+        }
+    }
 }
 
 pub fn parse_styles_from_path(path: &Path) -> Result<IdPkgStyles, quick_xml::DeError> {
@@ -1,6 +1,7 @@
 use idml_to_pdf::decompressor;
+use idml_to_pdf::idml_parser::designmap_parser::ShowConditionIndicators;
 use idml_to_pdf::idml_parser::IDMLPackage;
-use idml_to_pdf::pdf_printer::PDFPrinter;
+use idml_to_pdf::pdf_printer::{self, ImageExportSettings, PDFPrinter};
 use std::path::PathBuf;
 use std::time::Instant;
 
@@ -8,9 +9,94 @@ fn main() {
     std::process::exit(real_main().unwrap());
 }
 
+/// Removes an option and the value following it from the arguments, returning the value
+fn take_option(args: &mut Vec<String>, name: &str) -> Option<String> {
+    let index = args.iter().position(|arg| arg == name)?;
+    args.remove(index);
+    match index < args.len() {
+        true => Some(args.remove(index)),
+        false => None,
+    }
+}
+
+/// Removes a flag from the arguments, returning whether it was given
+fn take_flag(args: &mut Vec<String>, name: &str) -> bool {
+    let index = args.iter().position(|arg| arg == name);
+    if let Some(index) = index {
+        args.remove(index);
+    }
+    index.is_some()
+}
+
 fn real_main() -> Result<i32, std::io::Error> {
+    let mut args: Vec<String> = std::env::args().collect();
+
+    // Option --condition-set <name>: Condition set to show the text of
+    let condition_set = take_option(&mut args, "--condition-set");
+
+    // Option --condition-indicators: Mark the text with conditions applied to it
+    let show_condition_indicators = take_flag(&mut args, "--condition-indicators");
+
+    // Option --link-dir <dir>: Directory to look for missing linked files in, may be repeated
+    let mut link_search_dirs = vec![];
+    while let Some(dir) = take_option(&mut args, "--link-dir") {
+        link_search_dirs.push(PathBuf::from(dir));
+    }
+
+    // Option --missing-link-placeholders: Draw a grey box with a cross for missing linked files
+    let missing_link_placeholders = take_flag(&mut args, "--missing-link-placeholders");
+
+    // Option --missing-links-report <path>: Write the report of missing linked files to a file
+    let missing_links_report_path = take_option(&mut args, "--missing-links-report");
+
+    // Option --image-preset <name>: Downsample and compress images like the InDesign preset,
+    // either smallest-file-size or high-quality-print
+    let image_settings = match take_option(&mut args, "--image-preset") {
+        Some(name) => ImageExportSettings::from_preset(&name),
+        None => Ok(ImageExportSettings::default()),
+    };
+
+    // Options --image-<setting> <value>: Change one setting of the preset, the settings being
+    // ppi, threshold, resampling, compression and quality of color images, the same settings
+    // prefixed with gray- for grayscale images, and mono-ppi and mono-threshold of monochrome images
+    let mut image_settings = image_settings;
+    for name in ImageExportSettings::SETTING_NAMES.iter() {
+        if let Some(value) = take_option(&mut args, &format!("--image-{}", name)) {
+            image_settings =
+                image_settings.and_then(|settings| settings.with_setting(name, &value));
+        }
+    }
+    let image_settings = match image_settings {
+        Ok(image_settings) => image_settings,
+        Err(err) => {
+            println!("{}", err);
+            return Ok(2);
+        }
+    };
+
+    // Option --spots-to-process: Print spot colors with process inks instead of on their own plates
+    let spots_to_process = take_flag(&mut args, "--spots-to-process");
+
+    // Option --place <frame id>=<file>: Place the file in the frame, fitted as the frame fitting
+    // options of the frame say, may be repeated
+    let mut placements = vec![];
+    while let Some(placement) = take_option(&mut args, "--place") {
+        match placement.split_once('=') {
+            Some((frame_id, path)) if !frame_id.is_empty() && !path.is_empty() => {
+                placements.push((frame_id.to_string(), path.to_string()))
+            }
+            _ => {
+                println!(
+                    "Invalid placement '{}', placements are given as --place <frame id>=<file>",
+                    placement
+                );
+                return Ok(2);
+            }
+        }
+    }
+
     // Argument 1: IDML file path
-    let file_path = std::env::args().nth(1).expect("No path to IDML file given");
+    let file_path = args.get(1).cloned().expect("No path to IDML file given");
 
     // If no output path specified, just make a PDF in the same
     // folder as the IDML file
@@ -18,41 +104,100 @@ fn real_main() -> Result<i32, std::io::Error> {
     default_pdf_path.set_extension("pdf");
 
     // Argument 2: IDML file path
-    let pdf_path = std::env::args().nth(2).unwrap_or(
+    let pdf_path = args.get(2).cloned().unwrap_or(
         default_pdf_path.to_str().unwrap().to_string(), // Default
     );
 
     // Argument 3: Font directory path
-    let font_dir = match std::env::args().nth(3) {
+    let font_dir = match args.get(3) {
         Some(path) => Some(PathBuf::from(&path)),
         None => None,
     };
 
     // Argument 4: If the temporary folder with the extracted IDML data, should be kept
     // If no argument is provided, the folder will be deleted
-    let preserve_idml_dir = std::env::args().nth(4);
+    let preserve_idml_dir = args.get(4);
 
     // Decrompress idml file into a directory
     let idml_dir = decompressor::decompress_idml(&file_path).unwrap();
 
     // Parse contents of a directory into an IDML Package
     let start = Instant::now();
-    let idml_package = IDMLPackage::from_dir(&idml_dir)?;
+    let mut idml_package = IDMLPackage::from_dir(&idml_dir)?;
     let duration = start.elapsed();
     println!("Parsing IDML took {:.2?}", duration);
 
+    // Leave out the text hidden by conditions, once the chosen condition set is applied
+    if let Some(name) = &condition_set {
+        if let Err(err) = idml_package.designmap_mut().apply_condition_set(name) {
+            println!("{}", err);
+            std::fs::remove_dir_all(idml_dir)?;
+            return Ok(2);
+        }
+    }
+    if show_condition_indicators {
+        idml_package
+            .designmap_mut()
+            .with_condition_indicators(ShowConditionIndicators::ShowAndPrintIndicators);
+    }
+    idml_package.remove_hidden_text();
+
+    if spots_to_process {
+        idml_package.convert_spots_to_process();
+    }
+
+    // Replace the graphics of frames, like a data merge does
+    for (frame_id, path) in &placements {
+        match idml_package.frame_with_id_mut(frame_id) {
+            Some(frame) => frame.replace_content(path),
+            None => println!("No frame '{}' to place {} in", frame_id, path),
+        }
+    }
+
+    // Bring the tables of contents and the index up to date with the text,
+    // with the pages it is set on once the stories are laid out
+    let story_layout = PDFPrinter::new(&idml_package, &font_dir)
+        .unwrap()
+        .lay_out_stories();
+    pdf_printer::regenerate_stories(&mut idml_package, &story_layout);
+
     // Make IDML package into a PDF document
     let start = Instant::now();
-    let pdf_printer = PDFPrinter::new(&idml_package, &font_dir).unwrap();
+    // Linked images missing from where they were placed from are looked for in the given
+    // directories, then in the Links folder next to the IDML file and next to the IDML file
+    let link_dir = PathBuf::from(&file_path)
+        .parent()
+        .map(|dir| dir.to_path_buf())
+        .unwrap_or_default();
+    link_search_dirs.push(link_dir.join("Links"));
+    link_search_dirs.push(link_dir);
+    let pdf_printer = PDFPrinter::new(&idml_package, &font_dir)
+        .unwrap()
+        .with_story_layout(&story_layout)
+        .with_link_search_dirs(link_search_dirs)
+        .with_missing_link_placeholders(missing_link_placeholders)
+        .with_image_settings(image_settings);
     pdf_printer.render_pdf().unwrap();
     let duration = start.elapsed();
     println!("Making the PDF took {:.2?}", duration);
 
+    // Report the linked files that were not found
+    let missing_links_report = pdf_printer.missing_links_report();
+    if let Some(report) = &missing_links_report {
+        print!("{}", report);
+    }
+    if let Some(path) = &missing_links_report_path {
+        std::fs::write(path, missing_links_report.unwrap_or_default())?;
+    }
+
     // Save the PDF document
     let start = Instant::now();
-    pdf_printer.save_pdf(&pdf_path).unwrap();
+    let saved = pdf_printer.save_pdf(&pdf_path);
     let duration = start.elapsed();
-    println!("Saving the PDF took {:.2?}", duration);
+    match &saved {
+        Ok(()) => println!("Saving the PDF took {:.2?}", duration),
+        Err(err) => println!("Failed to save the PDF: {}", err),
+    }
 
     // Remove idml directory
     if preserve_idml_dir.is_some() {
@@ -61,5 +206,8 @@ fn real_main() -> Result<i32, std::io::Error> {
         std::fs::remove_dir_all(idml_dir)?;
     }
 
-    return Ok(1);
+    match saved {
+        Ok(()) => Ok(0),
+        Err(_) => Ok(1),
+    }
 }
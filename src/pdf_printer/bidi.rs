@@ -0,0 +1,68 @@
+use crate::idml_parser::styles::commom_text_properties::CharacterDirection;
+use crate::pdf_printer::text_layout::GlyphTransforms;
+use std::collections::HashMap;
+use std::ops::Range;
+use unicode_bidi::{BidiInfo, Level};
+
+/// Bidi levels of the text of a story, resolved over whole paragraphs (UAX #9).
+/// The text of each paragraph is gathered before the story is set, as the level
+/// of a character can depend on text further on in its paragraph.
+#[derive(Debug, Default, Clone)]
+pub struct ParagraphLevels {
+    /// Text of the paragraph being gathered
+    text: String,
+    paragraph_level: u8,
+    /// Story position and range in `text` of each piece of text in the paragraph
+    pieces: Vec<(usize, Range<usize>)>,
+    /// Levels of the characters of each piece of text, by story position
+    levels: HashMap<usize, Vec<u8>>,
+}
+
+impl ParagraphLevels {
+    /// Adds the text set for the content at `position` to the current paragraph.
+    /// An explicit character direction is applied as an embedding around the text.
+    pub fn push_text(&mut self, position: usize, text: &str, transforms: &GlyphTransforms) {
+        if self.pieces.is_empty() {
+            self.paragraph_level = transforms.paragraph_level();
+        }
+        let (prefix, suffix) = match transforms.character_direction {
+            CharacterDirection::DefaultDirection => ("", ""),
+            CharacterDirection::LeftToRightDirection => ("\u{202A}", "\u{202C}"),
+            CharacterDirection::RightToLeftDirection => ("\u{202B}", "\u{202C}"),
+        };
+        self.text.push_str(prefix);
+        let start = self.text.len();
+        self.text.push_str(text);
+        self.pieces.push((position, start..self.text.len()));
+        self.text.push_str(suffix);
+    }
+
+    /// Resolves the levels of the text of the current paragraph
+    pub fn end_paragraph(&mut self) {
+        let text = std::mem::take(&mut self.text);
+        let pieces = std::mem::take(&mut self.pieces);
+        if pieces.is_empty() {
+            return;
+        }
+        let paragraph_level = Level::new(self.paragraph_level).unwrap_or(Level::ltr());
+        let bidi_info = BidiInfo::new(&text, Some(paragraph_level));
+        for (position, range) in pieces {
+            let levels = text[range.clone()]
+                .char_indices()
+                .map(|(i, _)| {
+                    bidi_info
+                        .levels
+                        .get(range.start + i)
+                        .map(Level::number)
+                        .unwrap_or(self.paragraph_level)
+                })
+                .collect::<Vec<u8>>();
+            self.levels.entry(position).or_default().extend(levels);
+        }
+    }
+
+    /// Levels of the characters of the text set for the content at `position`
+    pub fn levels(&self, position: usize) -> Option<&[u8]> {
+        self.levels.get(&position).map(Vec::as_slice)
+    }
+}
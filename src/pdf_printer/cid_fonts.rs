@@ -0,0 +1,415 @@
+use lopdf::{Dictionary, Document, Object, ObjectId, Stream};
+use std::collections::{BTreeSet, HashMap};
+use std::rc::Rc;
+
+/// Tag and data of a table in a font file
+type TableRecord<'a> = ([u8; 4], &'a [u8]);
+
+/// TrueType font whose glyphs are drawn by glyph id. libharu writes the text of Unicode
+/// encoded fonts as CIDs that are the Unicode values of its characters, and maps them to
+/// glyphs through the font's cmap, so glyphs the cmap has no character for cannot be drawn.
+/// Instead every glyph drawn is given a CID of its own, written as the character of that
+/// value, and `write_cid_fonts` maps the CIDs to their glyphs once the PDF is saved.
+#[derive(Debug)]
+pub struct CidFont {
+    /// Name of the font in the PDF, without its subset tag
+    base_font: String,
+    data: Rc<Vec<u8>>,
+    /// Index in `glyphs` of each glyph drawn, keyed by glyph id
+    cids: HashMap<u16, usize>,
+    /// Each CID given out, with its glyph and the characters the glyph was shaped from
+    glyphs: Vec<(u16, u16, String)>,
+}
+
+impl CidFont {
+    /// Fonts without TrueType outlines are left to libharu, as only TrueType
+    /// outlines are embedded as they are
+    pub fn new(base_font: &str, data: Rc<Vec<u8>>) -> Option<CidFont> {
+        let face = ttf_parser::Face::parse(&data, 0).ok()?;
+        face.tables().glyf?;
+        Some(CidFont {
+            base_font: base_font.to_string(),
+            data,
+            cids: HashMap::new(),
+            glyphs: vec![],
+        })
+    }
+
+    /// Text that draws `glyph` when passed to libharu, where `chars` are the characters it was
+    /// shaped from. Returns `None` once every CID libharu can write has been given out.
+    pub fn encode_glyph(&mut self, glyph: u16, chars: &str) -> Option<Vec<u8>> {
+        let cid = match self.cids.get(&glyph) {
+            Some(&index) => {
+                let entry = &mut self.glyphs[index];
+                if entry.2.is_empty() {
+                    entry.2 = chars.to_string();
+                }
+                entry.0
+            }
+            None => {
+                // CID 0 would end the text, and CIDs in the surrogate range
+                // cannot be written as characters
+                let cid = match self.glyphs.last() {
+                    None => 1,
+                    Some((0xD7FF, _, _)) => 0xE000,
+                    Some((0xFFFF, _, _)) => return None,
+                    Some((cid, _, _)) => cid + 1,
+                };
+                self.cids.insert(glyph, self.glyphs.len());
+                self.glyphs.push((cid, glyph, chars.to_string()));
+                cid
+            }
+        };
+        let c = std::char::from_u32(cid as u32)?;
+        Some(c.to_string().into_bytes())
+    }
+}
+
+/// Points the fonts in the saved PDF at `path` to the glyphs given to their CIDs: the CIDs
+/// are mapped to the glyphs and their widths, text extraction maps them to the characters
+/// they were shaped from, and the glyphs drawn are embedded in place of libharu's subset.
+pub fn write_cid_fonts(path: &str, fonts: &[&CidFont]) -> Result<(), String> {
+    let fonts: HashMap<&[u8], &CidFont> = fonts
+        .iter()
+        .filter(|font| !font.glyphs.is_empty())
+        .map(|font| (font.base_font.as_bytes(), *font))
+        .collect();
+    if fonts.is_empty() {
+        return Ok(());
+    }
+    let mut doc = Document::load(path).map_err(|err| err.to_string())?;
+
+    let type0_fonts: Vec<(ObjectId, &CidFont)> = doc
+        .objects
+        .iter()
+        .filter_map(|(&id, object)| {
+            let dict = object.as_dict().ok()?;
+            if dict.get(b"Subtype").and_then(Object::as_name).ok()? != b"Type0" {
+                return None;
+            }
+            // Embedded fonts are named with a subset tag like `HPDFAA+`
+            let base_font = dict.get(b"BaseFont").and_then(Object::as_name).ok()?;
+            let base_font = match base_font.iter().position(|&b| b == b'+') {
+                Some(6) => &base_font[7..],
+                _ => base_font,
+            };
+            fonts.get(base_font).map(|font| (id, *font))
+        })
+        .collect();
+
+    for (font_id, font) in type0_fonts {
+        write_cid_font(&mut doc, font_id, font)
+            .map_err(|err| format!("Glyphs of font {} not written: {}", font.base_font, err))?;
+    }
+
+    doc.save(path).map_err(|err| err.to_string())?;
+    Ok(())
+}
+
+fn write_cid_font(doc: &mut Document, font_id: ObjectId, font: &CidFont) -> Result<(), String> {
+    let face = ttf_parser::Face::parse(&font.data, 0).map_err(|err| err.to_string())?;
+    let font_file = subset_glyphs(&font.data, font.glyphs.iter().map(|(_, glyph, _)| *glyph))?;
+
+    let descendant_id = doc
+        .get_dictionary(font_id)
+        .and_then(|dict| dict.get(b"DescendantFonts"))
+        .and_then(Object::as_array)
+        .ok()
+        .and_then(|fonts| fonts.first())
+        .and_then(|descendant| descendant.as_reference().ok())
+        .ok_or("No descendant font")?;
+    let descriptor_id = doc
+        .get_dictionary(descendant_id)
+        .and_then(|dict| dict.get(b"FontDescriptor"))
+        .and_then(Object::as_reference)
+        .map_err(|err| err.to_string())?;
+
+    let to_unicode = doc.add_object(to_unicode_cmap(&font.glyphs));
+    let cid_to_gid_map = doc.add_object(cid_to_gid_map(&font.glyphs));
+    let mut font_file_dict = Dictionary::new();
+    font_file_dict.set("Length1", Object::Integer(font_file.len() as i64));
+    let mut font_file = Stream::new(font_file_dict, font_file);
+    // The font file is left uncompressed if compressing it fails
+    let _ = font_file.compress();
+    let font_file = doc.add_object(font_file);
+
+    let units_per_em = face.units_per_em() as f64;
+    let width = |glyph: u16| {
+        let advance = face
+            .glyph_hor_advance(ttf_parser::GlyphId(glyph))
+            .unwrap_or(0);
+        Object::Integer((advance as f64 * 1000_f64 / units_per_em).round() as i64)
+    };
+    // Widths are listed for each run of consecutive CIDs
+    let mut widths: Vec<Object> = vec![];
+    let mut run: Vec<Object> = vec![];
+    for (i, (cid, glyph, _)) in font.glyphs.iter().enumerate() {
+        if i > 0 && font.glyphs[i - 1].0 + 1 != *cid {
+            widths.push(Object::Array(std::mem::take(&mut run)));
+        }
+        if run.is_empty() {
+            widths.push(Object::Integer(*cid as i64));
+        }
+        run.push(width(*glyph));
+    }
+    widths.push(Object::Array(run));
+
+    doc.get_dictionary_mut(font_id)
+        .map_err(|err| err.to_string())?
+        .set("ToUnicode", Object::Reference(to_unicode));
+    let descendant = doc
+        .get_dictionary_mut(descendant_id)
+        .map_err(|err| err.to_string())?;
+    descendant.set("CIDToGIDMap", Object::Reference(cid_to_gid_map));
+    descendant.set("W", Object::Array(widths));
+    doc.get_dictionary_mut(descriptor_id)
+        .map_err(|err| err.to_string())?
+        .set("FontFile2", Object::Reference(font_file));
+    Ok(())
+}
+
+/// Maps each CID to its glyph, as the big-endian glyph id at the index of the CID
+fn cid_to_gid_map(glyphs: &[(u16, u16, String)]) -> Stream {
+    let length = glyphs.last().map_or(1, |(cid, _, _)| *cid as usize + 1);
+    let mut map = vec![0_u8; length * 2];
+    for (cid, glyph, _) in glyphs {
+        let index = *cid as usize * 2;
+        map[index..index + 2].copy_from_slice(&glyph.to_be_bytes());
+    }
+    let mut stream = Stream::new(Dictionary::new(), map);
+    let _ = stream.compress();
+    stream
+}
+
+/// Maps each CID to the characters its glyph was shaped from, so the text can be copied
+fn to_unicode_cmap(glyphs: &[(u16, u16, String)]) -> Stream {
+    let mut cmap = String::from(
+        "/CIDInit /ProcSet findresource begin\n\
+         12 dict begin\n\
+         begincmap\n\
+         /CIDSystemInfo << /Registry (Adobe) /Ordering (UCS) /Supplement 0 >> def\n\
+         /CMapName /Adobe-Identity-UCS def\n\
+         /CMapType 2 def\n\
+         1 begincodespacerange\n<0000> <FFFF>\nendcodespacerange\n",
+    );
+    let mapped: Vec<&(u16, u16, String)> = glyphs
+        .iter()
+        .filter(|(_, _, chars)| !chars.is_empty())
+        .collect();
+    // A bfchar block holds at most 100 mappings
+    for block in mapped.chunks(100) {
+        cmap.push_str(&format!("{} beginbfchar\n", block.len()));
+        for (cid, _, chars) in block {
+            let utf16: String = chars
+                .encode_utf16()
+                .map(|unit| format!("{:04X}", unit))
+                .collect();
+            cmap.push_str(&format!("<{:04X}> <{}>\n", cid, utf16));
+        }
+        cmap.push_str("endbfchar\n");
+    }
+    cmap.push_str(
+        "endcmap\n\
+         CMapName currentdict /CMap defineresource pop\n\
+         end\n\
+         end\n",
+    );
+    let mut stream = Stream::new(Dictionary::new(), cmap.into_bytes());
+    let _ = stream.compress();
+    stream
+}
+
+/// Tables a PDF viewer needs to draw the glyphs of a TrueType font. The CIDs select the
+/// glyphs, so the cmap is left out, and so are the layout tables, as the text is already
+/// laid out, and the signature of the font.
+const EMBEDDED_TABLES: [&[u8; 4]; 13] = [
+    b"cvt ", b"fpgm", b"gasp", b"glyf", b"head", b"hhea", b"hmtx", b"loca", b"maxp", b"name",
+    b"OS/2", b"post", b"prep",
+];
+
+/// The TrueType font `data` with only the outlines of `glyphs`, the glyphs they are
+/// made of and the `.notdef` glyph. Glyphs keep their ids, the others are left empty.
+fn subset_glyphs(data: &[u8], glyphs: impl Iterator<Item = u16>) -> Result<Vec<u8>, String> {
+    let tables = table_records(data)?;
+    let table = |tag: &[u8; 4]| -> Result<&[u8], String> {
+        tables
+            .iter()
+            .find(|(table_tag, _)| table_tag == tag)
+            .map(|(_, table)| *table)
+            .ok_or(format!("No {} table", String::from_utf8_lossy(tag)))
+    };
+    let head = table(b"head")?;
+    let glyf = table(b"glyf")?;
+    let loca = table(b"loca")?;
+    let glyph_count = read_u16(table(b"maxp")?, 4)? as usize;
+    let long_offsets = read_u16(head, 50)? == 1;
+    let glyph_data = |glyph: usize| -> Result<&[u8], String> {
+        let (start, end) = match long_offsets {
+            true => (read_u32(loca, glyph * 4)?, read_u32(loca, glyph * 4 + 4)?),
+            false => (
+                read_u16(loca, glyph * 2)? as u32 * 2,
+                read_u16(loca, glyph * 2 + 2)? as u32 * 2,
+            ),
+        };
+        glyf.get(start as usize..end as usize)
+            .ok_or(format!("Glyph {} is outside the glyf table", glyph))
+    };
+
+    // Composite glyphs are drawn from the glyphs they are made of
+    let mut kept: BTreeSet<usize> = glyphs.map(|glyph| glyph as usize).collect();
+    kept.insert(0);
+    let mut pending: Vec<usize> = kept.iter().copied().collect();
+    while let Some(glyph) = pending.pop() {
+        if glyph >= glyph_count {
+            continue;
+        }
+        for component in glyph_components(glyph_data(glyph)?)? {
+            if kept.insert(component) {
+                pending.push(component);
+            }
+        }
+    }
+
+    let mut new_glyf = vec![];
+    let mut new_loca = vec![];
+    for glyph in 0..glyph_count {
+        new_loca.extend(&(new_glyf.len() as u32).to_be_bytes());
+        if kept.contains(&glyph) {
+            new_glyf.extend(glyph_data(glyph)?);
+            new_glyf.resize((new_glyf.len() + 3) & !3, 0);
+        }
+    }
+    new_loca.extend(&(new_glyf.len() as u32).to_be_bytes());
+    let mut new_head = head.to_vec();
+    new_head[8..12].copy_from_slice(&[0; 4]);
+    new_head[50..52].copy_from_slice(&1_u16.to_be_bytes());
+    // Glyph names are left out by writing the post table as version 3
+    let mut new_post = table(b"post")?.get(0..32).ok_or("Truncated font")?.to_vec();
+    new_post[0..4].copy_from_slice(&0x0003_0000_u32.to_be_bytes());
+
+    let new_tables: Vec<([u8; 4], &[u8])> = tables
+        .iter()
+        .filter(|(tag, _)| EMBEDDED_TABLES.contains(&tag))
+        .map(|(tag, table)| match tag {
+            b"glyf" => (*tag, new_glyf.as_slice()),
+            b"loca" => (*tag, new_loca.as_slice()),
+            b"head" => (*tag, new_head.as_slice()),
+            b"post" => (*tag, new_post.as_slice()),
+            _ => (*tag, *table),
+        })
+        .collect();
+    let mut font = write_tables(&new_tables);
+
+    // The checksum adjustment makes the whole font sum up to a fixed value
+    let head_offset = new_tables
+        .iter()
+        .position(|(tag, _)| tag == b"head")
+        .map(|index| read_u32(&font, 12 + index * 16 + 8))
+        .ok_or("No head table")??;
+    let adjustment = 0xB1B0_AFBA_u32.wrapping_sub(checksum(&font));
+    let offset = head_offset as usize + 8;
+    font[offset..offset + 4].copy_from_slice(&adjustment.to_be_bytes());
+    Ok(font)
+}
+
+/// The tables of the font, or of the first font of a collection
+fn table_records(data: &[u8]) -> Result<Vec<TableRecord<'_>>, String> {
+    let font_offset = match data.get(0..4) {
+        Some(b"ttcf") => read_u32(data, 12)? as usize,
+        _ => 0,
+    };
+    let table_count = read_u16(data, font_offset + 4)? as usize;
+    (0..table_count)
+        .map(|i| {
+            let record = font_offset + 12 + i * 16;
+            let mut tag = [0_u8; 4];
+            tag.copy_from_slice(data.get(record..record + 4).ok_or("Truncated font")?);
+            let offset = read_u32(data, record + 8)? as usize;
+            let length = read_u32(data, record + 12)? as usize;
+            let table = data
+                .get(offset..offset + length)
+                .ok_or("Table outside of the font")?;
+            Ok((tag, table))
+        })
+        .collect()
+}
+
+/// Glyph ids of the components of a composite glyph
+fn glyph_components(glyph: &[u8]) -> Result<Vec<usize>, String> {
+    const ARGS_ARE_WORDS: u16 = 0x0001;
+    const HAS_SCALE: u16 = 0x0008;
+    const MORE_COMPONENTS: u16 = 0x0020;
+    const HAS_X_AND_Y_SCALE: u16 = 0x0040;
+    const HAS_TWO_BY_TWO: u16 = 0x0080;
+
+    let mut components = vec![];
+    if glyph.len() < 10 || (read_u16(glyph, 0)? as i16) >= 0 {
+        return Ok(components);
+    }
+    let mut offset = 10;
+    loop {
+        let flags = read_u16(glyph, offset)?;
+        components.push(read_u16(glyph, offset + 2)? as usize);
+        offset += 4;
+        offset += if flags & ARGS_ARE_WORDS != 0 { 4 } else { 2 };
+        if flags & HAS_SCALE != 0 {
+            offset += 2;
+        } else if flags & HAS_X_AND_Y_SCALE != 0 {
+            offset += 4;
+        } else if flags & HAS_TWO_BY_TWO != 0 {
+            offset += 8;
+        }
+        if flags & MORE_COMPONENTS == 0 {
+            return Ok(components);
+        }
+    }
+}
+
+/// A TrueType font file of the tables, in the order given
+fn write_tables(tables: &[([u8; 4], &[u8])]) -> Vec<u8> {
+    let count = tables.len() as u16;
+    let entry_selector = 15 - count.max(1).leading_zeros() as u16;
+    let search_range = (1_u16 << entry_selector) * 16;
+
+    let mut font = vec![];
+    font.extend(&0x0001_0000_u32.to_be_bytes());
+    font.extend(&count.to_be_bytes());
+    font.extend(&search_range.to_be_bytes());
+    font.extend(&entry_selector.to_be_bytes());
+    font.extend(&(count * 16 - search_range).to_be_bytes());
+
+    let mut offset = 12 + tables.len() * 16;
+    for (tag, table) in tables {
+        font.extend(tag);
+        font.extend(&checksum(table).to_be_bytes());
+        font.extend(&(offset as u32).to_be_bytes());
+        font.extend(&(table.len() as u32).to_be_bytes());
+        offset += (table.len() + 3) & !3;
+    }
+    for (_, table) in tables {
+        font.extend(*table);
+        font.resize((font.len() + 3) & !3, 0);
+    }
+    font
+}
+
+/// Sum of the data as big-endian 32-bit numbers, padded with zeros
+fn checksum(data: &[u8]) -> u32 {
+    data.chunks(4).fold(0_u32, |sum, chunk| {
+        let mut word = [0_u8; 4];
+        word[..chunk.len()].copy_from_slice(chunk);
+        sum.wrapping_add(u32::from_be_bytes(word))
+    })
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Result<u16, String> {
+    data.get(offset..offset + 2)
+        .map(|bytes| u16::from_be_bytes([bytes[0], bytes[1]]))
+        .ok_or("Truncated font".to_string())
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Result<u32, String> {
+    data.get(offset..offset + 4)
+        .map(|bytes| u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+        .ok_or("Truncated font".to_string())
+}
@@ -0,0 +1,677 @@
+use crate::idml_parser::styles::commom_text_properties::{
+    CommonTextPropertiesAttributes, KentenCharacter, KinsokuHangTypes, RubyAlignments,
+    RubyKentenPosition, RubyTypes, WarichuAlignment,
+};
+use crate::pdf_printer::text_layout::{
+    self, FontEncoding, GlyphTransforms, PositionedGlyph, EM_BOX_ASCENT,
+};
+use libharu_sys::*;
+
+/// Characters that may not start a line (hard kinsoku, JIS X 4051)
+const NOT_AT_LINE_START: &str = "!),.:;?]}¢°’”‰′″℃、。々〉》」』】〕〗〙〟ぁぃぅぇぉっゃゅょゎゕゖ゛゜ゝゞ゠ァィゥェォッャュョヮヵヶ・ーヽヾㇰㇱㇲㇳㇴㇵㇶㇷㇸㇹㇺㇻㇼㇽㇾㇿ！％），．：；？］｝｡｣､･ｧｨｩｪｫｬｭｮｯｰﾞﾟ";
+
+/// Characters that may not end a line (hard kinsoku, JIS X 4051)
+const NOT_AT_LINE_END: &str = "([{£¥‘“〈《「『【〔〖〘〝＄（［｛｢￡￥";
+
+/// Punctuation that may hang past the end of the line (burasage)
+const HANGING_PUNCTUATION: &str = "、。，．､｡";
+
+/// Full width opening brackets, which are blank in the first half of their em
+const OPENING_PUNCTUATION: &str = "‘“（〔［｛〈《「『【〘〖〝︵︷︹︻︽︿﹁﹃﹇︗";
+
+/// Full width closing brackets, commas and periods, which are blank in the
+/// second half of their em
+const CLOSING_PUNCTUATION: &str = "’”）〕］｝〉》」』】〙〗〟、。，．︶︸︺︼︾﹀﹂﹄﹈︘︐︑︒";
+
+/// Characters of Chinese, Japanese and Korean text. These are set upright in
+/// vertical text and lines may break between them.
+pub fn is_cjk(c: char) -> bool {
+    matches!(
+        c as u32,
+        0x1100..=0x11FF
+        | 0x2E80..=0x303F
+        | 0x3040..=0x31FF
+        | 0x3200..=0x4DBF
+        | 0x4E00..=0x9FFF
+        | 0xA960..=0xA97F
+        | 0xAC00..=0xD7FF
+        | 0xF900..=0xFAFF
+        | 0xFE10..=0xFE1F
+        | 0xFE30..=0xFE4F
+        | 0xFF00..=0xFFEF
+        | 0x20000..=0x3FFFF
+    )
+}
+
+/// Whether `c` is drawn upright in vertical text. Long vowel marks and waves
+/// have no vertical form in Unicode, so they are rotated like Latin text.
+pub fn is_upright(c: char, rotate_single_byte: bool) -> bool {
+    match c {
+        'ー' | '〜' | '～' => false,
+        _ if is_cjk(c) => true,
+        _ => rotate_single_byte && (c as u32) < 0x100 && !c.is_whitespace(),
+    }
+}
+
+/// Whether a line may break between `before` and `after`. Breaks are allowed
+/// around CJK characters unless kinsoku forbids them; other text only breaks
+/// at spaces. `after` is `None` at the end of a run.
+pub fn can_break_between(before: char, after: Option<char>) -> bool {
+    if NOT_AT_LINE_END.contains(before) {
+        return false;
+    }
+    match after {
+        Some(after) if NOT_AT_LINE_START.contains(after) => false,
+        Some(after) => is_cjk(before) || is_cjk(after),
+        None => is_cjk(before),
+    }
+}
+
+pub fn is_hanging_punctuation(c: char) -> bool {
+    HANGING_PUNCTUATION.contains(c)
+}
+
+/// Space in em taken out between `before` and `after` by basic mojikumi: where
+/// full width punctuation meets, the half em blanks between them are set as
+/// one, so a closing bracket before another bracket or an opening bracket
+/// after one loses its blank half.
+pub fn mojikumi_reduction(before: char, after: char) -> f64 {
+    let opens_after = OPENING_PUNCTUATION.contains(after);
+    let closes_after = CLOSING_PUNCTUATION.contains(after);
+    match (
+        OPENING_PUNCTUATION.contains(before),
+        CLOSING_PUNCTUATION.contains(before),
+    ) {
+        (_, true) if opens_after || closes_after => 0.5_f64,
+        (true, _) if opens_after => 0.5_f64,
+        _ => 0_f64,
+    }
+}
+
+/// The Unicode vertical presentation form of punctuation. Fonts usually only
+/// have these through the `vert` feature, which libharu cannot draw.
+pub fn vertical_form(c: char) -> Option<char> {
+    let form = match c {
+        '，' => '︐',
+        '、' => '︑',
+        '。' => '︒',
+        '：' => '︓',
+        '；' => '︔',
+        '！' => '︕',
+        '？' => '︖',
+        '〖' => '︗',
+        '〗' => '︘',
+        '…' => '︙',
+        '‥' => '︰',
+        '—' => '︱',
+        '–' => '︲',
+        '（' => '︵',
+        '）' => '︶',
+        '｛' => '︷',
+        '｝' => '︸',
+        '〔' => '︹',
+        '〕' => '︺',
+        '【' => '︻',
+        '】' => '︼',
+        '《' => '︽',
+        '》' => '︾',
+        '〈' => '︿',
+        '〉' => '﹀',
+        '「' => '﹁',
+        '」' => '﹂',
+        '『' => '﹃',
+        '』' => '﹄',
+        '［' => '﹇',
+        '］' => '﹈',
+        _ => return None,
+    };
+    Some(form)
+}
+
+/// Japanese typography settings as they cascade through paragraph,
+/// character and local formatting. Unset values are `None`.
+#[derive(Debug, Clone, Default)]
+pub struct CjkStyle {
+    ruby_flag: Option<i32>,
+    ruby_string: Option<String>,
+    ruby_type: Option<RubyTypes>,
+    ruby_font_size: Option<f64>,
+    ruby_position: Option<RubyKentenPosition>,
+    ruby_alignment: Option<RubyAlignments>,
+    ruby_x_offset: Option<f64>,
+    ruby_y_offset: Option<f64>,
+    ruby_x_scale: Option<f64>,
+    ruby_y_scale: Option<f64>,
+    kenten_kind: Option<KentenCharacter>,
+    kenten_custom_character: Option<String>,
+    kenten_font_size: Option<f64>,
+    kenten_position: Option<RubyKentenPosition>,
+    kenten_placement: Option<f64>,
+    kenten_x_scale: Option<f64>,
+    kenten_y_scale: Option<f64>,
+    warichu: Option<bool>,
+    warichu_lines: Option<i16>,
+    warichu_size: Option<f64>,
+    warichu_line_spacing: Option<f64>,
+    warichu_alignment: Option<WarichuAlignment>,
+    tatechuyoko: Option<bool>,
+    tatechuyoko_x_offset: Option<f64>,
+    tatechuyoko_y_offset: Option<f64>,
+    rotate_single_byte_character: Option<bool>,
+    kinsoku_hang_type: Option<KinsokuHangTypes>,
+    leading_aki: Option<f64>,
+    trailing_aki: Option<f64>,
+    tsume: Option<f64>,
+    jidori: Option<i16>,
+    shatai_magnification: Option<f64>,
+    shatai_angle: Option<f64>,
+}
+
+impl CjkStyle {
+    pub fn from_attributes(attributes: &impl CommonTextPropertiesAttributes) -> CjkStyle {
+        CjkStyle {
+            ruby_flag: attributes.ruby_flag(),
+            ruby_string: attributes.ruby_string(),
+            ruby_type: attributes.ruby_type(),
+            ruby_font_size: attributes.ruby_font_size(),
+            ruby_position: attributes.ruby_position(),
+            ruby_alignment: attributes.ruby_alignment(),
+            ruby_x_offset: attributes.ruby_x_offset(),
+            ruby_y_offset: attributes.ruby_y_offset(),
+            ruby_x_scale: attributes.ruby_x_scale(),
+            ruby_y_scale: attributes.ruby_y_scale(),
+            kenten_kind: attributes.kenten_kind(),
+            kenten_custom_character: attributes.kenten_custom_character(),
+            kenten_font_size: attributes.kenten_font_size(),
+            kenten_position: attributes.kenten_position(),
+            kenten_placement: attributes.kenten_placement(),
+            kenten_x_scale: attributes.kenten_x_scale(),
+            kenten_y_scale: attributes.kenten_y_scale(),
+            warichu: attributes.warichu(),
+            warichu_lines: attributes.warichu_lines(),
+            warichu_size: attributes.warichu_size(),
+            warichu_line_spacing: attributes.warichu_line_spacing(),
+            warichu_alignment: attributes.warichu_alignment(),
+            tatechuyoko: attributes.tatechuyoko(),
+            tatechuyoko_x_offset: attributes.tatechuyoko_x_offset(),
+            tatechuyoko_y_offset: attributes.tatechuyoko_y_offset(),
+            rotate_single_byte_character: attributes.rotate_single_byte_character(),
+            kinsoku_hang_type: attributes.kinsoku_hang_type(),
+            leading_aki: attributes.leading_aki(),
+            trailing_aki: attributes.trailing_aki(),
+            tsume: attributes.tsume(),
+            jidori: attributes.jidori(),
+            shatai_magnification: attributes.shatai_magnification(),
+            shatai_angle: attributes.shatai_adjust_angle(),
+        }
+    }
+
+    /// Overrides the current values with the ones set in `other`
+    pub fn merge(&mut self, other: CjkStyle) {
+        self.ruby_flag = other.ruby_flag.or(self.ruby_flag);
+        self.ruby_string = other.ruby_string.or(self.ruby_string.take());
+        self.ruby_type = other.ruby_type.or(self.ruby_type);
+        self.ruby_font_size = other.ruby_font_size.or(self.ruby_font_size);
+        self.ruby_position = other.ruby_position.or(self.ruby_position);
+        self.ruby_alignment = other.ruby_alignment.or(self.ruby_alignment);
+        self.ruby_x_offset = other.ruby_x_offset.or(self.ruby_x_offset);
+        self.ruby_y_offset = other.ruby_y_offset.or(self.ruby_y_offset);
+        self.ruby_x_scale = other.ruby_x_scale.or(self.ruby_x_scale);
+        self.ruby_y_scale = other.ruby_y_scale.or(self.ruby_y_scale);
+        self.kenten_kind = other.kenten_kind.or(self.kenten_kind);
+        self.kenten_custom_character = other
+            .kenten_custom_character
+            .or(self.kenten_custom_character.take());
+        self.kenten_font_size = other.kenten_font_size.or(self.kenten_font_size);
+        self.kenten_position = other.kenten_position.or(self.kenten_position);
+        self.kenten_placement = other.kenten_placement.or(self.kenten_placement);
+        self.kenten_x_scale = other.kenten_x_scale.or(self.kenten_x_scale);
+        self.kenten_y_scale = other.kenten_y_scale.or(self.kenten_y_scale);
+        self.warichu = other.warichu.or(self.warichu);
+        self.warichu_lines = other.warichu_lines.or(self.warichu_lines);
+        self.warichu_size = other.warichu_size.or(self.warichu_size);
+        self.warichu_line_spacing = other.warichu_line_spacing.or(self.warichu_line_spacing);
+        self.warichu_alignment = other.warichu_alignment.or(self.warichu_alignment);
+        self.tatechuyoko = other.tatechuyoko.or(self.tatechuyoko);
+        self.tatechuyoko_x_offset = other.tatechuyoko_x_offset.or(self.tatechuyoko_x_offset);
+        self.tatechuyoko_y_offset = other.tatechuyoko_y_offset.or(self.tatechuyoko_y_offset);
+        self.rotate_single_byte_character = other
+            .rotate_single_byte_character
+            .or(self.rotate_single_byte_character);
+        self.kinsoku_hang_type = other.kinsoku_hang_type.or(self.kinsoku_hang_type);
+        self.leading_aki = other.leading_aki.or(self.leading_aki);
+        self.trailing_aki = other.trailing_aki.or(self.trailing_aki);
+        self.tsume = other.tsume.or(self.tsume);
+        self.jidori = other.jidori.or(self.jidori);
+        self.shatai_magnification = other.shatai_magnification.or(self.shatai_magnification);
+        self.shatai_angle = other.shatai_angle.or(self.shatai_angle);
+    }
+
+    // TODO: Mojikumi spacing tables other than the basic one and layout grid
+    //  line taking (gyoudori)
+    /// Sets the character level settings on `transforms`
+    pub fn apply_to(&self, transforms: &mut GlyphTransforms) {
+        // Negative aki means the spacing is left to the mojikumi table
+        let aki = |value: Option<f64>| value.filter(|aki| *aki >= 0_f64).unwrap_or(0_f64);
+        transforms.rotate_single_byte = self.rotate_single_byte_character.unwrap_or(false);
+        transforms.kinsoku_hang = matches!(
+            self.kinsoku_hang_type,
+            Some(KinsokuHangTypes::KinsokuHangRegular) | Some(KinsokuHangTypes::KinsokuHangForce)
+        );
+        transforms.leading_aki = aki(self.leading_aki);
+        transforms.trailing_aki = aki(self.trailing_aki);
+        transforms.tsume = self.tsume.unwrap_or(0_f64);
+        transforms.jidori = self.jidori.unwrap_or(0).max(0) as f64;
+        transforms.shatai = (
+            self.shatai_magnification.unwrap_or(0_f64),
+            // The angle is stored in hundredths of a degree
+            self.shatai_angle.unwrap_or(4500_f64) / 100_f64,
+        );
+    }
+
+    pub fn is_warichu(&self) -> bool {
+        self.warichu.unwrap_or(false)
+    }
+
+    pub fn is_tate_chu_yoko(&self) -> bool {
+        self.tatechuyoko.unwrap_or(false)
+    }
+
+    pub fn ruby(&self) -> Option<Ruby> {
+        let text = self.ruby_string.clone().filter(|text| !text.is_empty())?;
+        if self.ruby_flag.unwrap_or(0) == 0 {
+            return None;
+        }
+        Some(Ruby {
+            text,
+            per_character: self.ruby_type == Some(RubyTypes::PerCharacterRuby),
+            font_size: self.ruby_font_size.filter(|size| *size > 0_f64),
+            above_right: self.ruby_position != Some(RubyKentenPosition::BelowLeft),
+            alignment: self.ruby_alignment.unwrap_or(RubyAlignments::RubyJIS),
+            offset: (
+                self.ruby_x_offset.unwrap_or(0_f64),
+                self.ruby_y_offset.unwrap_or(0_f64),
+            ),
+            scale: (
+                self.ruby_x_scale.unwrap_or(100_f64),
+                self.ruby_y_scale.unwrap_or(100_f64),
+            ),
+        })
+    }
+
+    pub fn kenten(&self) -> Option<Kenten> {
+        let mark = match self.kenten_kind? {
+            KentenCharacter::None => return None,
+            KentenCharacter::KentenSesameDot | KentenCharacter::SesameDot => '﹅',
+            KentenCharacter::KentenBlackCircle => '●',
+            KentenCharacter::KentenWhiteCircle => '○',
+            KentenCharacter::KentenBlackTriangle => '▲',
+            KentenCharacter::KentenWhiteTriangle => '△',
+            KentenCharacter::KentenBullseye => '◎',
+            KentenCharacter::KentenFishseye => '◉',
+            KentenCharacter::KentenSmallWhiteCircle => '◦',
+            KentenCharacter::Custom => self.kenten_custom_character.as_ref()?.chars().next()?,
+        };
+        Some(Kenten {
+            mark,
+            font_size: self.kenten_font_size.filter(|size| *size > 0_f64),
+            above_right: self.kenten_position != Some(RubyKentenPosition::BelowLeft),
+            placement: self.kenten_placement.unwrap_or(0_f64),
+            scale: (
+                self.kenten_x_scale.unwrap_or(100_f64),
+                self.kenten_y_scale.unwrap_or(100_f64),
+            ),
+        })
+    }
+
+    pub fn warichu(&self) -> Warichu {
+        Warichu {
+            lines: self.warichu_lines.unwrap_or(2).max(1) as usize,
+            size: self.warichu_size.unwrap_or(50_f64),
+            line_spacing: self
+                .warichu_line_spacing
+                .filter(|spacing| *spacing >= 0_f64)
+                .unwrap_or(0_f64),
+            alignment: self.warichu_alignment.unwrap_or(WarichuAlignment::Auto),
+        }
+    }
+
+    pub fn tate_chu_yoko_offset(&self) -> (f64, f64) {
+        (
+            self.tatechuyoko_x_offset.unwrap_or(0_f64),
+            self.tatechuyoko_y_offset.unwrap_or(0_f64),
+        )
+    }
+}
+
+/// Where glyphs were drawn on a line. `start` and `end` are positions along
+/// the line: x coordinates in horizontal text and y coordinates in vertical
+/// text. `line` is the baseline in horizontal text and the center of the
+/// column in vertical text.
+#[derive(Debug, Clone, Copy)]
+pub struct LineSpan {
+    pub start: f64,
+    pub end: f64,
+    pub line: f64,
+    pub vertical: bool,
+}
+
+impl LineSpan {
+    /// Position along the line of the center of every glyph
+    fn glyph_centers(&self, glyphs: &[PositionedGlyph]) -> Vec<f64> {
+        let direction = if self.vertical { -1_f64 } else { 1_f64 };
+        let mut pen = self.start;
+        glyphs
+            .iter()
+            .map(|glyph| {
+                let center = pen + direction * glyph.advance / 2_f64;
+                pen += direction * (glyph.advance + glyph.kerning);
+                center
+            })
+            .collect()
+    }
+}
+
+/// Everything needed to draw text next to the base text of a run
+pub struct Adornments<'a> {
+    pub page: HPDF_Page,
+    pub font: HPDF_Font,
+    pub encoding: &'a FontEncoding,
+    pub font_data: Option<&'a [u8]>,
+    pub transforms: &'a GlyphTransforms,
+}
+
+impl<'a> Adornments<'a> {
+    /// Transforms for text set at `font_size`, without the spacing of the base text
+    fn transforms(&self, font_size: f64, scale: (f64, f64)) -> GlyphTransforms {
+        let mut transforms = self.transforms.clone();
+        transforms.font_size = font_size;
+        transforms.tracking = 0_f64;
+        transforms.kerning_value = 0_f64;
+        transforms.baseline_shift = 0_f64;
+        transforms.leading_aki = 0_f64;
+        transforms.trailing_aki = 0_f64;
+        transforms.jidori = 0_f64;
+        transforms.horizontal_scale *= scale.0 / 100_f64;
+        transforms.vertical_scale *= scale.1 / 100_f64;
+        transforms
+    }
+
+    fn layout(&self, text: &str, transforms: &GlyphTransforms) -> Vec<PositionedGlyph> {
+        text_layout::layout_glyphs(
+            text,
+            self.font,
+            self.encoding,
+            self.font_data,
+            transforms,
+            None,
+        )
+    }
+
+    /// Draws glyphs laid out with `transforms`. In horizontal text `line` is
+    /// the baseline, in vertical text the center of the column.
+    fn show(
+        &self,
+        glyphs: &[PositionedGlyph],
+        transforms: &GlyphTransforms,
+        start: f64,
+        line: f64,
+    ) -> Result<(), String> {
+        unsafe {
+            HPDF_Page_SetFontAndSize(self.page, self.font, transforms.font_size as f32);
+            let shown = match transforms.vertical {
+                true => {
+                    text_layout::show_glyphs_vertical(self.page, glyphs, line, start, transforms)
+                }
+                false => text_layout::show_glyphs(self.page, glyphs, start, line, transforms),
+            };
+            HPDF_Page_SetFontAndSize(self.page, self.font, self.transforms.font_size as f32);
+            shown.map(|_| ())
+        }
+    }
+
+    /// Draws `text` centered on `center` along the line, `offset` away from
+    /// the line of the base text
+    fn show_centered(
+        &self,
+        text: &str,
+        transforms: &GlyphTransforms,
+        center: f64,
+        offset: f64,
+    ) -> Result<(), String> {
+        let glyphs = self.layout(text, transforms);
+        let length = text_layout::glyphs_width(&glyphs);
+        match transforms.vertical {
+            true => self.show(&glyphs, transforms, center + length / 2_f64, offset),
+            false => self.show(&glyphs, transforms, center - length / 2_f64, offset),
+        }
+    }
+
+    /// Distance from the line of the base text to the line of adornment text
+    /// of `size`, placed above (or right of) the base text or below (left of) it
+    fn adornment_offset(&self, size: f64, above_right: bool, gap: f64) -> f64 {
+        let base_size = self.transforms.font_size;
+        match (self.transforms.vertical, above_right) {
+            (true, true) => (base_size + size) / 2_f64 + gap,
+            (true, false) => -(base_size + size) / 2_f64 - gap,
+            (false, true) => base_size * EM_BOX_ASCENT + size * (1_f64 - EM_BOX_ASCENT) + gap,
+            (false, false) => -base_size * (1_f64 - EM_BOX_ASCENT) - size * EM_BOX_ASCENT - gap,
+        }
+    }
+
+    /// Draws the part of the ruby that belongs to `glyphs`, which are glyphs
+    /// `first..first + glyphs.len()` of a run of `run_length` glyphs. A run
+    /// broken over several lines gets a proportional share of the ruby on each.
+    pub fn show_ruby(
+        &self,
+        ruby: &Ruby,
+        glyphs: &[PositionedGlyph],
+        span: &LineSpan,
+        first: usize,
+        run_length: usize,
+    ) -> Result<(), String> {
+        let size = ruby.font_size.unwrap_or(self.transforms.font_size / 2_f64);
+        let transforms = self.transforms(size, ruby.scale);
+        let (along, across) = match span.vertical {
+            true => (-ruby.offset.1, ruby.offset.0),
+            false => (ruby.offset.0, ruby.offset.1),
+        };
+        let line = span.line + self.adornment_offset(size, ruby.above_right, across);
+
+        let parts: Vec<&str> = ruby.text.split_whitespace().collect();
+        let base_glyphs: Vec<usize> = (0..glyphs.len())
+            .filter(|&i| !glyphs[i].is_space && !glyphs[i].chars.is_empty())
+            .collect();
+        if ruby.per_character && parts.len() == run_length {
+            // Every base character has its own ruby
+            let centers = span.glyph_centers(glyphs);
+            for (i, part) in base_glyphs.iter().zip(parts.iter().skip(first)) {
+                self.show_centered(part, &transforms, centers[*i] + along, line)?;
+            }
+            return Ok(());
+        }
+
+        let chars: Vec<char> = ruby.text.chars().filter(|c| !c.is_whitespace()).collect();
+        let share = |glyph: usize| glyph * chars.len() / run_length.max(1);
+        let text: String = chars[share(first)..share(first + glyphs.len())]
+            .iter()
+            .collect();
+        let center = match ruby.alignment {
+            RubyAlignments::RubyLeft if !span.vertical => {
+                let glyphs = self.layout(&text, &transforms);
+                span.start + text_layout::glyphs_width(&glyphs) / 2_f64
+            }
+            RubyAlignments::RubyRight if !span.vertical => {
+                let glyphs = self.layout(&text, &transforms);
+                span.end - text_layout::glyphs_width(&glyphs) / 2_f64
+            }
+            _ => (span.start + span.end) / 2_f64,
+        };
+        self.show_centered(&text, &transforms, center + along, line)
+    }
+
+    /// Draws an emphasis mark next to every character of `glyphs`
+    pub fn show_kenten(
+        &self,
+        kenten: &Kenten,
+        glyphs: &[PositionedGlyph],
+        span: &LineSpan,
+    ) -> Result<(), String> {
+        let size = kenten
+            .font_size
+            .unwrap_or(self.transforms.font_size / 2_f64);
+        let transforms = self.transforms(size, kenten.scale);
+        let line = span.line + self.adornment_offset(size, kenten.above_right, kenten.placement);
+        // Fall back to a bullet when the font's encoding lacks the mark
+        let mark = match self.encoding.can_encode(kenten.mark) {
+            true => kenten.mark.to_string(),
+            false => "・".to_string(),
+        };
+        for (glyph, center) in glyphs.iter().zip(span.glyph_centers(glyphs)) {
+            if !glyph.is_space && !glyph.chars.is_empty() {
+                self.show_centered(&mark, &transforms, center, line)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Lays out `text` as an inline note of several small lines and returns
+    /// the lines with the length they take up in the base line
+    pub fn layout_warichu(
+        &self,
+        warichu: &Warichu,
+        text: &str,
+    ) -> (Vec<Vec<PositionedGlyph>>, f64) {
+        let transforms = self.transforms(
+            self.transforms.font_size * warichu.size / 100_f64,
+            (100_f64, 100_f64),
+        );
+        let glyphs = self.layout(text, &transforms);
+        let line_length = text_layout::glyphs_width(&glyphs) / warichu.lines as f64;
+
+        let mut lines: Vec<Vec<PositionedGlyph>> = vec![vec![]];
+        let mut length = 0_f64;
+        for glyph in glyphs {
+            if length >= line_length * lines.len() as f64 && lines.len() < warichu.lines {
+                lines.push(vec![]);
+            }
+            length += glyph.advance + glyph.kerning;
+            lines.last_mut().unwrap().push(glyph);
+        }
+        let longest = lines
+            .iter()
+            .map(|line| text_layout::glyphs_width(line))
+            .fold(0_f64, f64::max);
+        (lines, longest)
+    }
+
+    /// Draws warichu lines starting at `start` along the base line. The lines
+    /// are stacked and centered on the em box of the base text.
+    pub fn show_warichu(
+        &self,
+        warichu: &Warichu,
+        lines: &[Vec<PositionedGlyph>],
+        start: f64,
+        line: f64,
+    ) -> Result<(), String> {
+        let base_size = self.transforms.font_size;
+        let size = base_size * warichu.size / 100_f64;
+        let transforms = self.transforms(size, (100_f64, 100_f64));
+        let block = lines.len() as f64 * size + (lines.len() - 1) as f64 * warichu.line_spacing;
+        let longest = lines
+            .iter()
+            .map(|glyphs| text_layout::glyphs_width(glyphs))
+            .fold(0_f64, f64::max);
+
+        for (i, glyphs) in lines.iter().enumerate() {
+            let slack = longest - text_layout::glyphs_width(glyphs);
+            let indent = match warichu.alignment {
+                WarichuAlignment::CenterAlign | WarichuAlignment::CenterJustified => slack / 2_f64,
+                WarichuAlignment::RightAlign | WarichuAlignment::RightJustified => slack,
+                _ => 0_f64,
+            };
+            let step = i as f64 * (size + warichu.line_spacing);
+            match self.transforms.vertical {
+                true => {
+                    // Columns from right to left, centered on the base column
+                    let column = line + block / 2_f64 - size / 2_f64 - step;
+                    self.show(glyphs, &transforms, start - indent, column)?;
+                }
+                false => {
+                    let em_center = line + base_size * (EM_BOX_ASCENT - 0.5_f64);
+                    let top = em_center + block / 2_f64 - step;
+                    self.show(
+                        glyphs,
+                        &transforms,
+                        start + indent,
+                        top - size * EM_BOX_ASCENT,
+                    )?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Draws `text` horizontally within one em of a vertical line, starting
+    /// at `top`, and returns the length it takes up in the line
+    pub fn show_tate_chu_yoko(
+        &self,
+        text: &str,
+        offset: (f64, f64),
+        top: f64,
+        column: f64,
+    ) -> Result<f64, String> {
+        let size = self.transforms.font_size;
+        let mut transforms = self.transforms.clone();
+        transforms.vertical = false;
+        transforms.tracking = 0_f64;
+        let mut glyphs = self.layout(text, &transforms);
+        let width = text_layout::glyphs_width(&glyphs);
+        if width > size {
+            // Compress the text to fit the width of the column
+            transforms.horizontal_scale *= size / width;
+            glyphs = self.layout(text, &transforms);
+        }
+        let width = text_layout::glyphs_width(&glyphs);
+        let baseline = top - size * EM_BOX_ASCENT - offset.1;
+        text_layout::show_glyphs(
+            self.page,
+            &glyphs,
+            column - width / 2_f64 + offset.0,
+            baseline,
+            &transforms,
+        )?;
+        Ok(size)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Ruby {
+    text: String,
+    per_character: bool,
+    /// `None` when the size follows the base text
+    font_size: Option<f64>,
+    above_right: bool,
+    alignment: RubyAlignments,
+    offset: (f64, f64),
+    scale: (f64, f64),
+}
+
+#[derive(Debug, Clone)]
+pub struct Kenten {
+    mark: char,
+    /// `None` when the size follows the base text
+    font_size: Option<f64>,
+    above_right: bool,
+    placement: f64,
+    scale: (f64, f64),
+}
+
+#[derive(Debug, Clone)]
+pub struct Warichu {
+    lines: usize,
+    /// Percentage of the base font size
+    size: f64,
+    line_spacing: f64,
+    alignment: WarichuAlignment,
+}
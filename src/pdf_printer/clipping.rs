@@ -0,0 +1,651 @@
+use crate::pdf_printer::psd;
+use libharu_sys::*;
+use std::collections::VecDeque;
+use std::io::Cursor;
+use tiff::decoder::{Decoder, DecodingResult};
+use tiff::tags::Tag;
+
+/// Point of a path. Paths saved in images have their points as fractions
+/// of the width and height of the image from its top left corner.
+pub type PathPoint = (f64, f64);
+
+/// Anchor point of a path with the control points before and after it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Knot {
+    pub preceding: PathPoint,
+    pub anchor: PathPoint,
+    pub leaving: PathPoint,
+}
+
+/// A path saved in a Photoshop or TIFF image, such as the path it is clipped to
+#[derive(Debug, Clone, PartialEq)]
+pub struct PhotoshopPath {
+    pub name: String,
+    pub subpaths: Vec<Vec<Knot>>,
+}
+
+/// Paths and names of alpha channels saved in the image resources of an image
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PhotoshopResources {
+    pub paths: Vec<PhotoshopPath>,
+    /// Name of the path Photoshop clips the image to
+    pub clipping_path_name: Option<String>,
+    pub alpha_channel_names: Vec<String>,
+}
+
+impl PhotoshopResources {
+    /// The path with the name, or else the clipping path, or else the first path
+    pub fn path(&self, name: Option<&str>) -> Option<&PhotoshopPath> {
+        let find = |name: &str| self.paths.iter().find(|path| path.name == name);
+        match name {
+            Some(name) if !name.is_empty() => find(name),
+            _ => self
+                .clipping_path_name
+                .as_deref()
+                .and_then(find)
+                .or(self.paths.first()),
+        }
+    }
+}
+
+/// Reads the image resources of a PSD file, or of a TIFF file that Photoshop saved them in.
+/// Other images have none. See https://www.adobe.com/devnet-apps/photoshop/fileformatashtml/
+pub fn read_photoshop_resources(data: &[u8]) -> Result<PhotoshopResources, String> {
+    let resources = match data {
+        _ if data.starts_with(b"8BPS") => psd::read_image_resources(data)?.to_vec(),
+        _ if data.starts_with(b"II*\0") || data.starts_with(b"MM\0*") => {
+            let mut decoder = Decoder::new(Cursor::new(data)).map_err(|err| err.to_string())?;
+            match decoder.find_tag(Tag::Unknown(34377)) {
+                Ok(Some(value)) => value.into_u8_vec().map_err(|err| err.to_string())?,
+                _ => vec![],
+            }
+        }
+        _ => vec![],
+    };
+
+    let mut photoshop_resources = PhotoshopResources::default();
+    let mut unicode_alpha_names = None;
+    for (id, name, data) in resource_blocks(&resources) {
+        match id {
+            1006 => {
+                let mut pos = 0;
+                while let Some((name, length)) = pascal_string(&data[pos..]) {
+                    photoshop_resources.alpha_channel_names.push(name);
+                    pos += length;
+                }
+            }
+            1045 => unicode_alpha_names = Some(unicode_strings(data)),
+            2000..=2997 => photoshop_resources.paths.push(PhotoshopPath {
+                name,
+                subpaths: read_path(data),
+            }),
+            2999 => {
+                photoshop_resources.clipping_path_name = pascal_string(data).map(|(name, _)| name)
+            }
+            _ => {}
+        }
+    }
+    if let Some(names) = unicode_alpha_names {
+        photoshop_resources.alpha_channel_names = names;
+    }
+    Ok(photoshop_resources)
+}
+
+/// Id, name and data of each image resource block
+fn resource_blocks(data: &[u8]) -> Vec<(u16, String, &[u8])> {
+    let mut blocks = vec![];
+    let mut pos = 0;
+    while pos + 12 <= data.len() && &data[pos..pos + 4] == b"8BIM" {
+        let id = u16::from_be_bytes([data[pos + 4], data[pos + 5]]);
+        pos += 6;
+        let (name, name_length) = pascal_string(&data[pos..]).unwrap_or_default();
+        // The name is padded to an even length, with its length byte
+        pos += name_length.max(1) + name_length.max(1) % 2;
+        let size = match data.get(pos..pos + 4) {
+            Some(b) => u32::from_be_bytes([b[0], b[1], b[2], b[3]]) as usize,
+            None => break,
+        };
+        pos += 4;
+        match data.get(pos..pos + size) {
+            Some(block) => blocks.push((id, name, block)),
+            None => break,
+        }
+        pos += size + size % 2;
+    }
+    blocks
+}
+
+/// A string preceded by its length in a byte, with the number of bytes it takes up
+fn pascal_string(data: &[u8]) -> Option<(String, usize)> {
+    let length = *data.first()? as usize;
+    let text = data.get(1..1 + length)?;
+    Some((String::from_utf8_lossy(text).to_string(), 1 + length))
+}
+
+/// UTF-16 strings, each preceded by its length in characters
+fn unicode_strings(data: &[u8]) -> Vec<String> {
+    let mut strings = vec![];
+    let mut pos = 0;
+    while let Some(b) = data.get(pos..pos + 4) {
+        let length = u32::from_be_bytes([b[0], b[1], b[2], b[3]]) as usize;
+        let units: Vec<u16> = match data.get(pos + 4..pos + 4 + length * 2) {
+            Some(text) => text
+                .chunks(2)
+                .map(|unit| u16::from_be_bytes([unit[0], unit[1]]))
+                .collect(),
+            None => break,
+        };
+        strings.push(
+            String::from_utf16_lossy(&units)
+                .trim_end_matches('\0')
+                .to_string(),
+        );
+        pos += 4 + length * 2;
+    }
+    strings
+}
+
+/// Reads the subpaths of a path resource, which is made of 26 byte records. Records
+/// saying how many knots a subpath has are followed by those knots, while other records
+/// hold settings of the path we have no use for.
+fn read_path(data: &[u8]) -> Vec<Vec<Knot>> {
+    let mut subpaths: Vec<Vec<Knot>> = vec![];
+    for record in data.chunks_exact(26) {
+        // Coordinates are fixed point numbers with 24 bits after the point, vertical first
+        let point = |at: usize| -> PathPoint {
+            let number = |at: usize| {
+                i32::from_be_bytes([record[at], record[at + 1], record[at + 2], record[at + 3]])
+                    as f64
+                    / (1 << 24) as f64
+            };
+            (number(at + 4), number(at))
+        };
+        match u16::from_be_bytes([record[0], record[1]]) {
+            0 | 3 => subpaths.push(vec![]),
+            1 | 2 | 4 | 5 => {
+                if let Some(subpath) = subpaths.last_mut() {
+                    subpath.push(Knot {
+                        preceding: point(2),
+                        anchor: point(10),
+                        leaving: point(18),
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+    subpaths.retain(|subpath| !subpath.is_empty());
+    subpaths
+}
+
+/// 8 bit samples of the channels of a TIFF image after its color channels, which are its alpha
+/// channels. Photoshop may save alpha channels there, which the image decoder leaves out.
+pub fn read_tiff_extra_channels(data: &[u8]) -> Result<Vec<Vec<u8>>, String> {
+    let mut decoder = Decoder::new(Cursor::new(data)).map_err(|err| err.to_string())?;
+    let color_channel_count = match decoder.find_tag_unsigned::<u16>(Tag::PhotometricInterpretation)
+    {
+        Ok(Some(2)) | Ok(Some(6)) => 3,
+        Ok(Some(5)) => 4,
+        _ => 1,
+    };
+    // The decoder only reads all the samples of an image as grayscale with extra samples
+    let data = as_grayscale_tiff(data).ok_or("Failed to read TIFF directory")?;
+    let mut decoder = Decoder::new(Cursor::new(data)).map_err(|err| err.to_string())?;
+    let (width, height) = decoder.dimensions().map_err(|err| err.to_string())?;
+    let samples = match decoder.read_image().map_err(|err| err.to_string())? {
+        DecodingResult::U8(samples) => samples,
+        _ => return Err("Only alpha channels of 8 bit TIFF images are read".to_string()),
+    };
+    let pixel_count = width as usize * height as usize;
+    let channel_count = match pixel_count {
+        0 => 0,
+        _ => samples.len() / pixel_count,
+    };
+    Ok((color_channel_count..channel_count)
+        .map(|channel| {
+            samples
+                .iter()
+                .skip(channel)
+                .step_by(channel_count)
+                .copied()
+                .collect()
+        })
+        .collect())
+}
+
+/// A copy of the TIFF file with the photometric interpretation of its first image set to grayscale
+fn as_grayscale_tiff(data: &[u8]) -> Option<Vec<u8>> {
+    let big_endian = data.starts_with(b"MM");
+    let u16_at = |at: usize| {
+        let bytes = [*data.get(at)?, *data.get(at + 1)?];
+        Some(match big_endian {
+            true => u16::from_be_bytes(bytes),
+            false => u16::from_le_bytes(bytes),
+        })
+    };
+    let u32_at = |at: usize| {
+        let bytes = [
+            *data.get(at)?,
+            *data.get(at + 1)?,
+            *data.get(at + 2)?,
+            *data.get(at + 3)?,
+        ];
+        Some(match big_endian {
+            true => u32::from_be_bytes(bytes),
+            false => u32::from_le_bytes(bytes),
+        })
+    };
+    let directory = u32_at(4)? as usize;
+    let entry = (0..u16_at(directory)? as usize)
+        .map(|i| directory + 2 + i * 12)
+        .find(|&entry| u16_at(entry) == Some(262))?;
+    let mut data = data.to_vec();
+    let value: [u8; 2] = match big_endian {
+        true => 1_u16.to_be_bytes(),
+        false => 1_u16.to_le_bytes(),
+    };
+    data[entry + 8..entry + 10].copy_from_slice(&value);
+    Some(data)
+}
+
+/// Mask hiding the light background of an image, for clipping it to its detected edges.
+/// Pixels whose lightness is within `threshold` of white are background, but unless
+/// `inside_edges` is set, only those connected to the sides of the image are hidden.
+pub fn edge_mask(
+    lightness: &[u8],
+    (width, height): (u32, u32),
+    threshold: u8,
+    inside_edges: bool,
+) -> Vec<u8> {
+    let is_background = |index: usize| lightness[index] >= 255 - threshold;
+    if inside_edges {
+        return (0..lightness.len())
+            .map(|index| match is_background(index) {
+                true => 0,
+                false => 255,
+            })
+            .collect();
+    }
+
+    let (width, height) = (width as usize, height as usize);
+    let mut mask = vec![255_u8; width * height];
+    let mut queue: VecDeque<usize> = (0..width)
+        .flat_map(|x| vec![x, (height - 1) * width + x])
+        .chain((0..height).flat_map(|y| vec![y * width, y * width + width - 1]))
+        .filter(|&index| index < mask.len())
+        .collect();
+    while let Some(index) = queue.pop_front() {
+        if mask[index] == 0 || !is_background(index) {
+            continue;
+        }
+        mask[index] = 0;
+        let (x, y) = (index % width, index / width);
+        if x > 0 {
+            queue.push_back(index - 1);
+        }
+        if x + 1 < width {
+            queue.push_back(index + 1);
+        }
+        if y > 0 {
+            queue.push_back(index - width);
+        }
+        if y + 1 < height {
+            queue.push_back(index + width);
+        }
+    }
+    mask
+}
+
+/// Moves the edges of a mask inwards by `inset` pixels, or outwards if it is negative
+pub fn inset_mask(mask: &[u8], (width, height): (u32, u32), inset: i32) -> Vec<u8> {
+    let (width, height) = (width as usize, height as usize);
+    let radius = inset.unsigned_abs() as usize;
+    if radius == 0 || mask.is_empty() {
+        return mask.to_vec();
+    }
+    // Insetting takes the least opaque pixel around each pixel, and outsetting the most
+    let pick = |values: &mut dyn Iterator<Item = u8>| match inset > 0 {
+        true => values.min().unwrap_or(0),
+        false => values.max().unwrap_or(0),
+    };
+    let rows: Vec<u8> = (0..width * height)
+        .map(|index| {
+            let (x, y) = (index % width, index / width);
+            let from = x.saturating_sub(radius);
+            let to = (x + radius).min(width - 1);
+            pick(&mut (from..=to).map(|x| mask[y * width + x]))
+        })
+        .collect();
+    (0..width * height)
+        .map(|index| {
+            let (x, y) = (index % width, index / width);
+            let from = y.saturating_sub(radius);
+            let to = (y + radius).min(height - 1);
+            pick(&mut (from..=to).map(|y| rows[y * width + x]))
+        })
+        .collect()
+}
+
+/// Moves each knot of a closed subpath inwards by `inset`, or outwards if it is negative
+pub fn inset_subpath(subpath: &[Knot], inset: f64) -> Vec<Knot> {
+    let count = subpath.len();
+    if count < 3 || inset == 0_f64 {
+        return subpath.to_vec();
+    }
+    // Which side of the path is inside depends on which way it runs
+    let area: f64 = (0..count)
+        .map(|i| {
+            let (a, b) = (subpath[i].anchor, subpath[(i + 1) % count].anchor);
+            a.0 * b.1 - b.0 * a.1
+        })
+        .sum();
+    let side = match area > 0_f64 {
+        true => 1_f64,
+        false => -1_f64,
+    };
+    offset_subpath(subpath, inset * side, true)
+}
+
+/// The outline of a stroke of `width` along the subpath, as subpaths to fill by the even-odd
+/// rule. Closed subpaths are outlined by a subpath on either side, while open subpaths are
+/// outlined by a single subpath going out along one side and back along the other.
+pub fn stroke_outline(subpath: &[Knot], width: f64, closed: bool) -> Vec<Vec<Knot>> {
+    let left = offset_subpath(subpath, width / 2_f64, closed);
+    let right = offset_subpath(subpath, -width / 2_f64, closed);
+    if closed {
+        return vec![left, right];
+    }
+    // The ends are cut straight across, from one side to the other
+    let straight_ends = |knots: &mut Vec<Knot>| {
+        if let Some(first) = knots.first_mut() {
+            first.preceding = first.anchor;
+        }
+        if let Some(last) = knots.last_mut() {
+            last.leaving = last.anchor;
+        }
+    };
+    let mut outline = left;
+    straight_ends(&mut outline);
+    let mut back: Vec<Knot> = right
+        .iter()
+        .rev()
+        .map(|knot| Knot {
+            preceding: knot.leaving,
+            anchor: knot.anchor,
+            leaving: knot.preceding,
+        })
+        .collect();
+    straight_ends(&mut back);
+    outline.append(&mut back);
+    vec![outline]
+}
+
+/// Moves each knot of the subpath by `distance` to the left of the way it runs, along the
+/// direction halfway between the sides meeting at its anchor point
+fn offset_subpath(subpath: &[Knot], distance: f64, closed: bool) -> Vec<Knot> {
+    let count = subpath.len();
+    if count < 2 || distance == 0_f64 {
+        return subpath.to_vec();
+    }
+    let normal = |from: PathPoint, to: PathPoint| -> PathPoint {
+        let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+        let length = dx.hypot(dy);
+        match length > 0_f64 {
+            true => (-dy / length, dx / length),
+            false => (0_f64, 0_f64),
+        }
+    };
+    (0..count)
+        .map(|i| {
+            let knot = subpath[i];
+            let previous = subpath[(i + count - 1) % count].anchor;
+            let next = subpath[(i + 1) % count].anchor;
+            // The ends of open subpaths only have the side after or before them
+            let (n1, n2) = match (closed, i) {
+                (false, 0) => (normal(knot.anchor, next), normal(knot.anchor, next)),
+                (false, i) if i == count - 1 => {
+                    (normal(previous, knot.anchor), normal(previous, knot.anchor))
+                }
+                _ => (normal(previous, knot.anchor), normal(knot.anchor, next)),
+            };
+            let (nx, ny) = (n1.0 + n2.0, n1.1 + n2.1);
+            let length = nx.hypot(ny);
+            let (dx, dy) = match length > 0_f64 {
+                // Corners are moved further, so the sides move by the distance
+                true => {
+                    let cosine = (nx * n1.0 + ny * n1.1) / length;
+                    let distance = distance / cosine.max(0.2_f64);
+                    (nx / length * distance, ny / length * distance)
+                }
+                false => (0_f64, 0_f64),
+            };
+            let moved = |point: PathPoint| (point.0 + dx, point.1 + dy);
+            Knot {
+                preceding: moved(knot.preceding),
+                anchor: moved(knot.anchor),
+                leaving: moved(knot.leaving),
+            }
+        })
+        .collect()
+}
+
+/// Adds the closed subpath to the page, with its points in page coordinates
+pub unsafe fn add_subpath(current_page: HPDF_Page, subpath: &[Knot]) {
+    let first = match subpath.first() {
+        Some(first) => first,
+        None => return,
+    };
+    HPDF_Page_MoveTo(current_page, first.anchor.0 as f32, first.anchor.1 as f32);
+    for (from, to) in subpath.iter().zip(subpath.iter().cycle().skip(1)) {
+        HPDF_Page_CurveTo(
+            current_page,
+            from.leaving.0 as f32,
+            from.leaving.1 as f32,
+            to.preceding.0 as f32,
+            to.preceding.1 as f32,
+            to.anchor.0 as f32,
+            to.anchor.1 as f32,
+        );
+    }
+    HPDF_Page_ClosePath(current_page);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Image resource block with the id, name and data
+    fn resource_block(id: u16, name: &str, data: &[u8]) -> Vec<u8> {
+        let mut block = b"8BIM".to_vec();
+        block.extend_from_slice(&id.to_be_bytes());
+        block.push(name.len() as u8);
+        block.extend_from_slice(name.as_bytes());
+        if name.len() % 2 == 0 {
+            block.push(0);
+        }
+        block.extend_from_slice(&(data.len() as u32).to_be_bytes());
+        block.extend_from_slice(data);
+        if data.len() % 2 == 1 {
+            block.push(0);
+        }
+        block
+    }
+
+    /// Path resource with a closed subpath through each list of anchor points
+    fn path_resource(subpaths: &[&[PathPoint]]) -> Vec<u8> {
+        let fixed = |number: f64| ((number * (1 << 24) as f64) as i32).to_be_bytes();
+        let mut data = vec![];
+        for subpath in subpaths {
+            let mut record = vec![0_u8; 26];
+            record[2..4].copy_from_slice(&(subpath.len() as u16).to_be_bytes());
+            data.extend(record);
+            for &(x, y) in subpath.iter() {
+                let mut record = 1_u16.to_be_bytes().to_vec();
+                for _ in 0..3 {
+                    record.extend_from_slice(&fixed(y));
+                    record.extend_from_slice(&fixed(x));
+                }
+                data.extend(record);
+            }
+        }
+        data
+    }
+
+    /// A PSD file with no pixels and the image resource blocks
+    fn psd(blocks: &[Vec<u8>]) -> Vec<u8> {
+        let resources = blocks.concat();
+        let mut data = b"8BPS".to_vec();
+        data.extend_from_slice(&1_u16.to_be_bytes());
+        data.extend_from_slice(&[0_u8; 6]);
+        data.extend_from_slice(&3_u16.to_be_bytes());
+        data.extend_from_slice(&0_u32.to_be_bytes());
+        data.extend_from_slice(&0_u32.to_be_bytes());
+        data.extend_from_slice(&8_u16.to_be_bytes());
+        data.extend_from_slice(&3_u16.to_be_bytes());
+        data.extend_from_slice(&0_u32.to_be_bytes());
+        data.extend_from_slice(&(resources.len() as u32).to_be_bytes());
+        data.extend(resources);
+        data
+    }
+
+    fn anchors(subpath: &[Knot]) -> Vec<PathPoint> {
+        let round = |number: f64| (number * 1e6_f64).round() / 1e6_f64;
+        subpath
+            .iter()
+            .map(|knot| (round(knot.anchor.0), round(knot.anchor.1)))
+            .collect()
+    }
+
+    fn square(side: f64) -> Vec<Knot> {
+        [(0_f64, 0_f64), (side, 0_f64), (side, side), (0_f64, side)]
+            .iter()
+            .map(|&anchor| Knot {
+                preceding: anchor,
+                anchor,
+                leaving: anchor,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn photoshop_resources_hold_the_paths_and_alpha_channel_names() {
+        let triangle: &[PathPoint] = &[(0_f64, 0_f64), (1_f64, 0_f64), (0.5_f64, 0.75_f64)];
+        let corner: &[PathPoint] = &[(0_f64, 0_f64), (0.25_f64, 0_f64), (0_f64, 0.25_f64)];
+        let mut clipping_path = vec![4_u8];
+        clipping_path.extend_from_slice(b"Clip");
+        clipping_path.extend_from_slice(&[0_u8, 1_u8]);
+        let mut alpha_names = vec![7_u8];
+        alpha_names.extend_from_slice(b"Alpha 1");
+        let resources = read_photoshop_resources(&psd(&[
+            resource_block(2000, "Outline", &path_resource(&[triangle])),
+            resource_block(2001, "Clip", &path_resource(&[corner, triangle])),
+            resource_block(2999, "", &clipping_path),
+            resource_block(1006, "", &alpha_names),
+        ]))
+        .unwrap();
+
+        let names: Vec<&str> = resources
+            .paths
+            .iter()
+            .map(|path| path.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["Outline", "Clip"]);
+        assert_eq!(anchors(&resources.paths[0].subpaths[0]), triangle.to_vec());
+        assert_eq!(resources.paths[1].subpaths.len(), 2);
+        assert_eq!(resources.clipping_path_name.as_deref(), Some("Clip"));
+        assert_eq!(resources.alpha_channel_names, vec!["Alpha 1".to_string()]);
+
+        // Without a name, the clipping path is used
+        assert_eq!(resources.path(None).unwrap().name, "Clip");
+        assert_eq!(resources.path(Some("")).unwrap().name, "Clip");
+        assert_eq!(resources.path(Some("Outline")).unwrap().name, "Outline");
+        assert!(resources.path(Some("Missing")).is_none());
+    }
+
+    #[test]
+    fn images_other_than_photoshop_and_tiff_have_no_resources() {
+        assert_eq!(
+            read_photoshop_resources(b"\x89PNG\r\n\x1a\n").unwrap(),
+            PhotoshopResources::default()
+        );
+    }
+
+    #[test]
+    fn detected_edges_hide_the_background_around_the_image() {
+        // A dark ring with a light center, on a light background
+        let lightness: Vec<u8> = (0..25)
+            .map(|index| match (index % 5, index / 5) {
+                (2, 2) => 250,
+                (1..=3, 1..=3) => 0,
+                _ => 255,
+            })
+            .collect();
+        let mask = edge_mask(&lightness, (5, 5), 10, false);
+        assert_eq!(&mask[5..10], &[0, 255, 255, 255, 0]);
+        assert_eq!(mask[12], 255);
+        assert_eq!(mask.iter().filter(|&&value| value == 0).count(), 16);
+
+        let mask = edge_mask(&lightness, (5, 5), 10, true);
+        assert_eq!(mask[12], 0);
+        assert_eq!(mask.iter().filter(|&&value| value == 0).count(), 17);
+    }
+
+    #[test]
+    fn masks_are_inset_and_outset_by_whole_pixels() {
+        let mask: Vec<u8> = (0..25)
+            .map(|index| match (index % 5, index / 5) {
+                (1..=3, 1..=3) => 255,
+                _ => 0,
+            })
+            .collect();
+        let inset = inset_mask(&mask, (5, 5), 1);
+        assert_eq!(inset.iter().position(|&value| value == 255), Some(12));
+        assert_eq!(inset.iter().filter(|&&value| value == 255).count(), 1);
+        assert!(inset_mask(&mask, (5, 5), -1)
+            .iter()
+            .all(|&value| value == 255));
+        assert_eq!(inset_mask(&mask, (5, 5), 0), mask);
+    }
+
+    #[test]
+    fn subpaths_are_inset_whichever_way_they_run() {
+        let inset = vec![
+            (1_f64, 1_f64),
+            (9_f64, 1_f64),
+            (9_f64, 9_f64),
+            (1_f64, 9_f64),
+        ];
+        assert_eq!(anchors(&inset_subpath(&square(10_f64), 1_f64)), inset);
+
+        let mut reversed = square(10_f64);
+        reversed.reverse();
+        let mut expected = inset.clone();
+        expected.reverse();
+        assert_eq!(anchors(&inset_subpath(&reversed, 1_f64)), expected);
+
+        let outset = anchors(&inset_subpath(&square(10_f64), -1_f64));
+        assert_eq!(outset[0], (-1_f64, -1_f64));
+        assert_eq!(outset[2], (11_f64, 11_f64));
+    }
+
+    #[test]
+    fn strokes_are_outlined_on_either_side_of_the_path() {
+        let line = &square(10_f64)[0..2];
+        let outline = stroke_outline(line, 2_f64, false);
+        assert_eq!(outline.len(), 1);
+        assert_eq!(
+            anchors(&outline[0]),
+            vec![
+                (0_f64, 1_f64),
+                (10_f64, 1_f64),
+                (10_f64, -1_f64),
+                (0_f64, -1_f64)
+            ]
+        );
+
+        let outline = stroke_outline(&square(10_f64), 2_f64, true);
+        assert_eq!(outline.len(), 2);
+        assert_eq!(anchors(&outline[0])[0], (1_f64, 1_f64));
+        assert_eq!(anchors(&outline[1])[0], (-1_f64, -1_f64));
+    }
+}
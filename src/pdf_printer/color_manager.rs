@@ -1,4 +1,7 @@
-use crate::idml_parser::graphic_parser::{Color as IdmlColor, ColorSpace, Swatch};
+use crate::idml_parser::graphic_parser::{
+    Color as IdmlColor, ColorModel, ColorSpace, Gradient as IdmlGradient, GradientType, Swatch,
+    Tint,
+};
 use crate::idml_parser::IDMLResources;
 use derive_getters::Getters;
 
@@ -17,13 +20,186 @@ pub struct Rgb {
     b: f32,
 }
 
+/// CIE L*a*b* color relative to the D50 white point
+#[derive(Debug, Clone, Copy, Getters)]
+pub struct Lab {
+    l: f32,
+    a: f32,
+    b: f32,
+}
+
+impl Lab {
+    /// The color in sRGB, out of gamut colors clipped
+    pub fn to_rgb(&self) -> Rgb {
+        let fy = (self.l + 16_f32) / 116_f32;
+        let fx = fy + self.a / 500_f32;
+        let fz = fy - self.b / 200_f32;
+        let inverse = |f: f32| match f > 6_f32 / 29_f32 {
+            true => f.powi(3),
+            false => 3_f32 * (6_f32 / 29_f32).powi(2) * (f - 4_f32 / 29_f32),
+        };
+        let (x, y, z) = (
+            0.96422_f32 * inverse(fx),
+            inverse(fy),
+            0.82521_f32 * inverse(fz),
+        );
+        // XYZ to linear sRGB, adapted to D50
+        let gamma = |linear: f32| {
+            let linear = linear.max(0_f32).min(1_f32);
+            match linear <= 0.0031308_f32 {
+                true => 12.92_f32 * linear,
+                false => 1.055_f32 * linear.powf(1_f32 / 2.4_f32) - 0.055_f32,
+            }
+        };
+        Rgb {
+            r: gamma(3.1338561_f32 * x - 1.6168667_f32 * y - 0.4906146_f32 * z),
+            g: gamma(-0.9787684_f32 * x + 1.9161415_f32 * y + 0.0334540_f32 * z),
+            b: gamma(0.0719453_f32 * x - 0.2289914_f32 * y + 1.4052427_f32 * z),
+        }
+    }
+}
+
+/// Color a spot ink is shown as where it is not printed on a plate of its own
 #[derive(Debug, Clone, Copy)]
+pub enum SpotAlternate {
+    Cmyk(Cmyk),
+    Rgb(Rgb),
+    Lab(Lab),
+}
+
+/// Tint of a spot ink, which is printed on a plate of its own
+#[derive(Debug, Clone, Getters)]
+pub struct Spot {
+    /// Position of the spot color among the colors of the document
+    index: usize,
+    /// Name of the ink, which names the plate
+    ink: String,
+    /// From 0 for no ink to 1 for solid ink
+    tint: f32,
+    /// Color of the solid ink
+    alternate: SpotAlternate,
+}
+
+impl Spot {
+    /// The tint of the alternate color, for where the ink cannot be printed
+    pub fn process_color(&self) -> Color {
+        let solid = match self.alternate {
+            SpotAlternate::Cmyk(color) => Color::Cmyk(color),
+            SpotAlternate::Rgb(color) => Color::Rgb(color),
+            SpotAlternate::Lab(color) => Color::Rgb(color.to_rgb()),
+        };
+        solid.with_tint(self.tint as f64 * 100_f64)
+    }
+}
+
+#[cfg(test)]
+impl Spot {
+    /// Solid spot color of an ink shown as the CMYK color
+    pub fn new(index: usize, ink: &str, (c, m, y, k): (f32, f32, f32, f32)) -> Spot {
+        Spot {
+            index,
+            ink: ink.to_string(),
+            tint: 1_f32,
+            alternate: SpotAlternate::Cmyk(Cmyk { c, m, y, k }),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GradientKind {
+    Linear,
+    Radial,
+}
+
+/// Color of a gradient at its location, from 0 at the start to 1 at the end
+#[derive(Debug, Clone, Getters)]
+pub struct GradientStop {
+    location: f32,
+    /// Where the colors of this stop and the one before are mixed evenly,
+    /// as a fraction of the way from that stop to this one
+    midpoint: f32,
+    color: Color,
+}
+
+#[derive(Debug, Clone, Getters)]
+pub struct Gradient {
+    kind: GradientKind,
+    stops: Vec<GradientStop>,
+}
+
+#[derive(Debug, Clone)]
 pub enum Color {
     Cmyk(Cmyk),
     Rgb(Rgb),
+    Spot(Spot),
+    Gradient(Gradient),
     None,
 }
 
+impl Color {
+    /// Returns the color with a tint applied, where a tint of 100 yields the color
+    /// itself and a tint of 0 yields white. IDML uses a tint of -1 to mean "not set".
+    pub fn with_tint(self, tint: f64) -> Color {
+        if tint < 0_f64 {
+            return self;
+        }
+        let t = (tint.min(100_f64) / 100_f64) as f32;
+        match self {
+            Color::Cmyk(color) => Color::Cmyk(Cmyk {
+                c: color.c * t,
+                m: color.m * t,
+                y: color.y * t,
+                k: color.k * t,
+            }),
+            Color::Rgb(color) => Color::Rgb(Rgb {
+                r: 1_f32 - (1_f32 - color.r) * t,
+                g: 1_f32 - (1_f32 - color.g) * t,
+                b: 1_f32 - (1_f32 - color.b) * t,
+            }),
+            Color::Spot(spot) => Color::Spot(Spot {
+                tint: spot.tint * t,
+                ..spot
+            }),
+            Color::Gradient(gradient) => Color::Gradient(Gradient {
+                kind: gradient.kind,
+                stops: gradient
+                    .stops
+                    .into_iter()
+                    .map(|stop| GradientStop {
+                        color: stop.color.with_tint(tint),
+                        ..stop
+                    })
+                    .collect(),
+            }),
+            Color::None => Color::None,
+        }
+    }
+
+    /// The color as CMYK, with RGB colors converted without color management. Spot colors
+    /// are given as their alternate color and gradients as the color of their first stop.
+    pub fn to_cmyk(&self) -> Option<Cmyk> {
+        match self {
+            Color::Cmyk(color) => Some(*color),
+            Color::Rgb(color) => {
+                let k = 1_f32 - color.r.max(color.g).max(color.b);
+                let ink = |value: f32| match k < 1_f32 {
+                    true => (1_f32 - value - k) / (1_f32 - k),
+                    false => 0_f32,
+                };
+                Some(Cmyk {
+                    c: ink(color.r),
+                    m: ink(color.g),
+                    y: ink(color.b),
+                    k,
+                })
+            }
+            Color::Spot(spot) => spot.process_color().to_cmyk(),
+            Color::Gradient(gradient) => gradient.stops.first()?.color.to_cmyk(),
+            Color::None => None,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum ColorError {
     ColorNotImplemented,
@@ -50,6 +226,31 @@ pub fn color_from_id(idml_resources: &IDMLResources, id: &String) -> Result<Colo
 
 impl IDMLResources {
     pub fn color_from_id(&self, id: &String) -> Result<Color, ColorError> {
+        if let Some(gradient) = self
+            .graphic()
+            .gradients()
+            .iter()
+            .find(|gradient| gradient.id() == id)
+        {
+            return self.gradient(gradient);
+        }
+        if let Some(tint) = self.tint_from_id(id) {
+            return self.tinted_color_from_id(
+                tint.base_color().as_ref().ok_or(ColorError::NoColorMatch)?,
+                *tint.tint_value(),
+            );
+        }
+
+        if let Some((index, color)) = self
+            .graphic()
+            .colors()
+            .iter()
+            .enumerate()
+            .find(|(_, color)| color.id() == id && *color.model() == Some(ColorModel::Spot))
+        {
+            return self.spot(index, color);
+        }
+
         // List to search
         let mut matches = vec![];
 
@@ -84,6 +285,172 @@ impl IDMLResources {
     }
 }
 
+impl IDMLResources {
+    /// The color of a swatch with a tint applied. As in InDesign, the tint of a tint swatch
+    /// is a percentage of its base color rather than of the tint swatch itself.
+    pub fn tinted_color_from_id(
+        &self,
+        id: &String,
+        tint: Option<f64>,
+    ) -> Result<Color, ColorError> {
+        match tint.filter(|tint| *tint >= 0_f64) {
+            Some(tint) => {
+                let base_id = self
+                    .tint_from_id(id)
+                    .and_then(|tint| tint.base_color().as_ref())
+                    .unwrap_or(id);
+                Ok(self.color_from_id(base_id)?.with_tint(tint))
+            }
+            None => self.color_from_id(id),
+        }
+    }
+
+    fn tint_from_id(&self, id: &String) -> Option<&Tint> {
+        self.graphic().tints().iter().find(|tint| tint.id() == id)
+    }
+
+    /// The spot colors of the document as solid ink
+    pub fn spot_colors(&self) -> Vec<Spot> {
+        self.graphic()
+            .colors()
+            .iter()
+            .enumerate()
+            .filter(|(_, color)| *color.model() == Some(ColorModel::Spot))
+            .filter_map(|(index, color)| match self.spot(index, color) {
+                Ok(Color::Spot(spot)) => Some(spot),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// The spot color printed on the plate of the ink it is aliased to, and shown like the spot
+    /// color of that ink. Spot colors aliased to a process ink are printed as that process color.
+    fn spot(&self, index: usize, color: &IdmlColor) -> Result<Color, ColorError> {
+        let (index, color) = self.spot_color_alias(index, color);
+        let ink = self.ink_alias(color.name());
+        let process = match ink.as_str() {
+            "Cyan" => Some((1_f32, 0_f32, 0_f32, 0_f32)),
+            "Magenta" => Some((0_f32, 1_f32, 0_f32, 0_f32)),
+            "Yellow" => Some((0_f32, 0_f32, 1_f32, 0_f32)),
+            "Black" => Some((0_f32, 0_f32, 0_f32, 1_f32)),
+            _ => None,
+        };
+        if let Some((c, m, y, k)) = process {
+            return Ok(Color::Cmyk(Cmyk { c, m, y, k }));
+        }
+
+        let (index, color) = self
+            .graphic()
+            .colors()
+            .iter()
+            .enumerate()
+            .find(|(_, other)| {
+                *other.model() == Some(ColorModel::Spot) && other.name().as_str() == ink
+            })
+            .unwrap_or((index, color));
+        let alternate = match (color.space(), color.color_value()) {
+            (Some(ColorSpace::LAB), Some(value)) => SpotAlternate::Lab(Lab {
+                l: value[0] as f32,
+                a: value[1] as f32,
+                b: value[2] as f32,
+            }),
+            _ => match color.to_pdf_color()? {
+                Color::Cmyk(color) => SpotAlternate::Cmyk(color),
+                Color::Rgb(color) => SpotAlternate::Rgb(color),
+                _ => return Err(ColorError::ColorNotImplemented),
+            },
+        };
+        Ok(Color::Spot(Spot {
+            index,
+            ink,
+            tint: 1_f32,
+            alternate,
+        }))
+    }
+
+    /// The spot color the spot color is aliased to, following aliases of aliases
+    fn spot_color_alias<'a>(
+        &'a self,
+        mut index: usize,
+        mut color: &'a IdmlColor,
+    ) -> (usize, &'a IdmlColor) {
+        let colors = self.graphic().colors();
+        for _ in 0..colors.len() {
+            let alias = match color.spot_ink_alias_spot_color_reference() {
+                Some(id) if id != "n" => id,
+                _ => break,
+            };
+            match colors
+                .iter()
+                .enumerate()
+                .find(|(_, other)| other.id() == alias)
+            {
+                Some((alias_index, alias_color)) => {
+                    index = alias_index;
+                    color = alias_color;
+                }
+                None => break,
+            }
+        }
+        (index, color)
+    }
+
+    /// Name of the ink printed in place of the ink with the name, following aliases of aliases.
+    /// Process inks are named like the colorants of DeviceCMYK.
+    fn ink_alias(&self, name: &str) -> String {
+        let inks = self.graphic().inks();
+        let mut name = name.to_string();
+        for _ in 0..inks.len() {
+            let alias = inks
+                .iter()
+                .find(|ink| *ink.name() == name)
+                .and_then(|ink| ink.alias_ink_name().as_ref())
+                .filter(|alias| !alias.is_empty() && alias.as_str() != "$ID/");
+            match alias {
+                Some(alias) => name = alias.clone(),
+                None => break,
+            }
+        }
+        let colorant = match name.trim_start_matches("$ID/") {
+            "Process Cyan" => Some("Cyan"),
+            "Process Magenta" => Some("Magenta"),
+            "Process Yellow" => Some("Yellow"),
+            "Process Black" => Some("Black"),
+            _ => None,
+        };
+        colorant.map(String::from).unwrap_or(name)
+    }
+
+    /// The gradient with the colors of its stops, which are swatches themselves
+    fn gradient(&self, gradient: &IdmlGradient) -> Result<Color, ColorError> {
+        let stops = gradient
+            .gradient_stops()
+            .iter()
+            .map(|stop| {
+                let color = match stop.stop_color() {
+                    Some(id) => self.color_from_id(id)?,
+                    None => return Err(ColorError::NoColorMatch),
+                };
+                Ok(GradientStop {
+                    location: (stop.location().unwrap_or(0_f64) / 100_f64) as f32,
+                    midpoint: (stop.midpoint().unwrap_or(50_f64) / 100_f64) as f32,
+                    color,
+                })
+            })
+            .collect::<Result<Vec<GradientStop>, ColorError>>()?;
+        if stops.is_empty() {
+            return Err(ColorError::ColorNotImplemented);
+        }
+        Ok(Color::Gradient(Gradient {
+            kind: match gradient.gradient_type() {
+                Some(GradientType::Radial) => GradientKind::Radial,
+                _ => GradientKind::Linear,
+            },
+            stops,
+        }))
+    }
+}
+
 pub trait ToPDFColor {
     fn to_pdf_color(&self) -> Result<Color, ColorError>;
 }
@@ -112,6 +479,14 @@ impl ToPDFColor for IdmlColor {
                     b: value[2] as f32,
                 }))
             }
+            (Some(ColorSpace::LAB), Some(value)) => Ok(Color::Rgb(
+                Lab {
+                    l: value[0] as f32,
+                    a: value[1] as f32,
+                    b: value[2] as f32,
+                }
+                .to_rgb(),
+            )),
             _ => Err(ColorError::ColorNotImplemented),
         }
     }
@@ -123,3 +498,255 @@ impl ToPDFColor for Swatch {
         Err(ColorError::ColorNotImplemented)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::idml_parser::graphic_parser::IdPkgGraphic;
+
+    fn cmyk(color: &Color) -> (f32, f32, f32, f32) {
+        let color = color.to_cmyk().unwrap();
+        (color.c, color.m, color.y, color.k)
+    }
+
+    fn assert_close(actual: (f32, f32, f32, f32), expected: (f32, f32, f32, f32)) {
+        let pairs = [
+            (actual.0, expected.0),
+            (actual.1, expected.1),
+            (actual.2, expected.2),
+            (actual.3, expected.3),
+        ];
+        assert!(
+            pairs.iter().all(|(a, e)| (a - e).abs() < 1e-4),
+            "{:?} != {:?}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn tints_scale_process_inks() {
+        let red = Color::Cmyk(Cmyk {
+            c: 0_f32,
+            m: 1_f32,
+            y: 0.8_f32,
+            k: 0.2_f32,
+        });
+        assert_close(
+            cmyk(&red.with_tint(50_f64)),
+            (0_f32, 0.5_f32, 0.4_f32, 0.1_f32),
+        );
+    }
+
+    #[test]
+    fn tints_mix_rgb_with_white() {
+        let tinted = Color::Rgb(Rgb {
+            r: 1_f32,
+            g: 0_f32,
+            b: 0.5_f32,
+        })
+        .with_tint(25_f64);
+        match tinted {
+            Color::Rgb(color) => {
+                assert!((color.r - 1_f32).abs() < 1e-6);
+                assert!((color.g - 0.75_f32).abs() < 1e-6);
+                assert!((color.b - 0.875_f32).abs() < 1e-6);
+            }
+            other => panic!("expected an RGB color, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn unset_tint_keeps_color_and_tints_above_full_are_clamped() {
+        let color = Color::Cmyk(Cmyk {
+            c: 0.6_f32,
+            m: 0_f32,
+            y: 0_f32,
+            k: 0_f32,
+        });
+        assert_close(
+            cmyk(&color.clone().with_tint(-1_f64)),
+            (0.6_f32, 0_f32, 0_f32, 0_f32),
+        );
+        assert_close(
+            cmyk(&color.with_tint(250_f64)),
+            (0.6_f32, 0_f32, 0_f32, 0_f32),
+        );
+    }
+
+    #[test]
+    fn tints_of_tints_multiply() {
+        let color = Color::Cmyk(Cmyk {
+            c: 1_f32,
+            m: 0_f32,
+            y: 0_f32,
+            k: 0_f32,
+        });
+        assert_close(
+            cmyk(&color.with_tint(50_f64).with_tint(50_f64)),
+            (0.25_f32, 0_f32, 0_f32, 0_f32),
+        );
+    }
+
+    #[test]
+    fn spot_tints_keep_the_ink_and_tint_the_alternate() {
+        let spot = Color::Spot(Spot::new(
+            3,
+            "PANTONE 300 C",
+            (1_f32, 0.4_f32, 0_f32, 0_f32),
+        ))
+        .with_tint(40_f64);
+        match &spot {
+            Color::Spot(spot) => {
+                assert_eq!(spot.index, 3);
+                assert_eq!(spot.ink, "PANTONE 300 C");
+                assert!((spot.tint - 0.4_f32).abs() < 1e-6);
+            }
+            other => panic!("expected a spot color, got {:?}", other),
+        }
+        assert_close(cmyk(&spot), (0.4_f32, 0.16_f32, 0_f32, 0_f32));
+    }
+
+    #[test]
+    fn gradient_stops_are_tinted() {
+        let gradient = Color::Gradient(Gradient {
+            kind: GradientKind::Linear,
+            stops: vec![GradientStop {
+                location: 0_f32,
+                midpoint: 0.5_f32,
+                color: Color::Cmyk(Cmyk {
+                    c: 0_f32,
+                    m: 0_f32,
+                    y: 0_f32,
+                    k: 1_f32,
+                }),
+            }],
+        });
+        assert_close(
+            cmyk(&gradient.with_tint(30_f64)),
+            (0_f32, 0_f32, 0_f32, 0.3_f32),
+        );
+    }
+
+    #[test]
+    fn rgb_converts_to_cmyk_through_black() {
+        let orange = Color::Rgb(Rgb {
+            r: 1_f32,
+            g: 0.5_f32,
+            b: 0_f32,
+        });
+        assert_close(cmyk(&orange), (0_f32, 0.5_f32, 1_f32, 0_f32));
+        let black = Color::Rgb(Rgb {
+            r: 0_f32,
+            g: 0_f32,
+            b: 0_f32,
+        });
+        assert_close(cmyk(&black), (0_f32, 0_f32, 0_f32, 1_f32));
+    }
+
+    #[test]
+    fn lab_white_is_rgb_white() {
+        let white = Lab {
+            l: 100_f32,
+            a: 0_f32,
+            b: 0_f32,
+        }
+        .to_rgb();
+        assert!((white.r - 1_f32).abs() < 1e-3);
+        assert!((white.g - 1_f32).abs() < 1e-3);
+        assert!((white.b - 1_f32).abs() < 1e-3);
+    }
+
+    /// Resources with the colors and then the inks, in the order of Graphic.xml
+    fn resources(colors: &str, inks: &str) -> IDMLResources {
+        let xml = format!(
+            r#"<idPkg:Graphic xmlns:idPkg="http://ns.adobe.com/AdobeInDesign/idml/1.0/packaging" DOMVersion="16.0">
+                <Color Self="Color/Black" Model="Process" Space="CMYK" ColorValue="0 0 0 100" Name="Black"/>
+                {}
+                {}
+                <Swatch Self="Swatch/None" Name="None"/>
+            </idPkg:Graphic>"#,
+            colors, inks
+        );
+        IDMLResources::with_graphic(quick_xml::de::from_str(&xml).unwrap())
+    }
+
+    fn spot(color: Color) -> Spot {
+        match color {
+            Color::Spot(spot) => spot,
+            other => panic!("expected a spot color, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn spot_colors_are_printed_on_the_plate_of_their_ink() {
+        let resources = resources(
+            r#"<Color Self="Color/Gold" Model="Spot" Space="CMYK" ColorValue="0 20 60 20" Name="Gold"/>
+            <Color Self="Color/Blue" Model="Spot" Space="LAB" ColorValue="30 10 -60" Name="Blue"/>"#,
+            "",
+        );
+        let spots = resources.spot_colors();
+        assert_eq!(spots.len(), 2);
+        assert_eq!((spots[0].index, spots[0].ink.as_str()), (1, "Gold"));
+        assert_eq!((spots[1].index, spots[1].ink.as_str()), (2, "Blue"));
+        assert!(matches!(spots[1].alternate, SpotAlternate::Lab(lab) if lab.b == -60_f32));
+
+        let gold = resources
+            .tinted_color_from_id(&"Color/Gold".to_string(), Some(25_f64))
+            .unwrap();
+        assert_close(cmyk(&gold), (0_f32, 0.05_f32, 0.15_f32, 0.05_f32));
+        assert!((spot(gold).tint - 0.25_f32).abs() < 1e-6);
+    }
+
+    #[test]
+    fn aliased_spot_colors_share_the_plate_of_the_ink_they_are_aliased_to() {
+        let resources = resources(
+            r#"<Color Self="Color/Gold" Model="Spot" Space="CMYK" ColorValue="0 20 60 20" Name="Gold"/>
+            <Color Self="Color/Yellowish" Model="Spot" Space="CMYK" ColorValue="0 0 80 0" Name="Yellowish"/>
+            <Color Self="Color/Old gold" Model="Spot" Space="CMYK" ColorValue="0 30 70 30" Name="Old gold" SpotInkAliasSpotColorReference="Color/Gold"/>"#,
+            r#"<Ink Self="Ink/$ID/Gold" Name="Gold" AliasInkName="$ID/"/>
+            <Ink Self="Ink/$ID/Yellowish" Name="Yellowish" AliasInkName="Golden"/>
+            <Ink Self="Ink/$ID/Golden" Name="Golden" AliasInkName="Gold"/>"#,
+        );
+        for id in &["Color/Yellowish", "Color/Old gold"] {
+            let aliased = spot(resources.color_from_id(&id.to_string()).unwrap());
+            assert_eq!((aliased.index, aliased.ink.as_str()), (1, "Gold"), "{}", id);
+            assert_close(
+                cmyk(&Color::Spot(aliased)),
+                (0_f32, 0.2_f32, 0.6_f32, 0.2_f32),
+            );
+        }
+    }
+
+    #[test]
+    fn spot_colors_aliased_to_process_inks_are_process_colors() {
+        let resources = resources(
+            r#"<Color Self="Color/Rubine" Model="Spot" Space="CMYK" ColorValue="0 100 20 5" Name="Rubine"/>"#,
+            r#"<Ink Self="Ink/$ID/Process Magenta" Name="$ID/Process Magenta" IsProcessInk="true" AliasInkName="$ID/"/>
+            <Ink Self="Ink/$ID/Rubine" Name="Rubine" AliasInkName="$ID/Process Magenta"/>"#,
+        );
+        let rubine = resources
+            .tinted_color_from_id(&"Color/Rubine".to_string(), Some(40_f64))
+            .unwrap();
+        assert!(matches!(rubine, Color::Cmyk(_)));
+        assert_close(cmyk(&rubine), (0_f32, 0.4_f32, 0_f32, 0_f32));
+        assert!(resources.spot_colors().is_empty());
+    }
+
+    #[test]
+    fn spots_converted_to_process_are_process_colors() {
+        let mut graphic: IdPkgGraphic = quick_xml::de::from_str(
+            r#"<idPkg:Graphic DOMVersion="16.0">
+                <Color Self="Color/Gold" Model="Spot" Space="CMYK" ColorValue="0 20 60 20" Name="Gold"/>
+                <Swatch Self="Swatch/None" Name="None"/>
+            </idPkg:Graphic>"#,
+        )
+        .unwrap();
+        graphic.convert_spots_to_process();
+        let resources = IDMLResources::with_graphic(graphic);
+        assert!(resources.spot_colors().is_empty());
+        let gold = resources.color_from_id(&"Color/Gold".to_string()).unwrap();
+        assert!(matches!(gold, Color::Cmyk(_)));
+        assert_close(cmyk(&gold), (0_f32, 0.2_f32, 0.6_f32, 0.2_f32));
+    }
+}
@@ -0,0 +1,132 @@
+use crate::idml_parser::designmap_parser::{
+    Condition, ConditionIndicatorMethod, ConditionUnderlineIndicatorAppearance,
+    IndicatorColorValue, ShowConditionIndicators,
+};
+use crate::idml_parser::IDMLPackage;
+use crate::pdf_printer::pdf_utils::BoundingBox;
+use libharu_sys::*;
+use std::ffi::CString;
+use std::ptr;
+
+/// Area of the page covered by text with conditions applied to it
+#[derive(Debug, Clone)]
+pub struct ConditionArea {
+    /// Applied conditions of the text, as a list of condition ids
+    pub conditions: String,
+    pub area: BoundingBox,
+}
+
+/// Marks the text with conditions applied to it with a highlight or underline
+/// annotation for each condition, in its indicator color. As in InDesign,
+/// indicators are only exported when they are set to be shown and printed.
+pub fn render_condition_indicators(
+    idml_package: &IDMLPackage,
+    current_page: HPDF_Page,
+    condition_areas: &[ConditionArea],
+) {
+    let designmap = idml_package.designmap();
+    if *designmap
+        .conditional_text_preference()
+        .show_condition_indicators()
+        != Some(ShowConditionIndicators::ShowAndPrintIndicators)
+    {
+        return;
+    }
+    for condition_area in condition_areas {
+        for condition in designmap.applied_conditions(&condition_area.conditions) {
+            render_indicator(condition, &condition_area.area, current_page);
+        }
+    }
+}
+
+fn render_indicator(condition: &Condition, area: &BoundingBox, current_page: HPDF_Page) {
+    let rect = HPDF_Rect {
+        left: area.left as f32,
+        bottom: area.bottom as f32,
+        right: area.right as f32,
+        top: area.top as f32,
+    };
+    let name = CString::new(condition.name().clone().unwrap_or_default()).unwrap_or_default();
+    let (r, g, b) = indicator_color(condition);
+    unsafe {
+        let annotation = match (
+            condition.indicator_method(),
+            condition.underline_indicator_appearance(),
+        ) {
+            (Some(ConditionIndicatorMethod::UseUnderline), Some(appearance)) => match appearance {
+                ConditionUnderlineIndicatorAppearance::Wavy => HPDF_Page_CreateSquigglyAnnot(
+                    current_page,
+                    rect,
+                    name.as_ptr(),
+                    ptr::null_mut(),
+                ),
+                _ => HPDF_Page_CreateUnderlineAnnot(
+                    current_page,
+                    rect,
+                    name.as_ptr(),
+                    ptr::null_mut(),
+                ),
+            },
+            _ => HPDF_Page_CreateHighlightAnnot(current_page, rect, name.as_ptr(), ptr::null_mut()),
+        };
+        if annotation.is_null() {
+            return;
+        }
+        HPDF_TextMarkupAnnot_SetQuadPoints(
+            annotation,
+            HPDF_Point {
+                x: rect.left,
+                y: rect.bottom,
+            },
+            HPDF_Point {
+                x: rect.right,
+                y: rect.bottom,
+            },
+            HPDF_Point {
+                x: rect.right,
+                y: rect.top,
+            },
+            HPDF_Point {
+                x: rect.left,
+                y: rect.top,
+            },
+        );
+        HPDF_Annot_SetRGBColor(
+            annotation,
+            HPDF_RGBColor {
+                r: r / 255_f32,
+                g: g / 255_f32,
+                b: b / 255_f32,
+            },
+        );
+        HPDF_MarkupAnnot_SetTitle(annotation, name.as_ptr());
+    }
+}
+
+/// Indicator color of a condition as RGB values from 0 to 255
+fn indicator_color(condition: &Condition) -> (f32, f32, f32) {
+    use IndicatorColorValue::*;
+    let values = condition
+        .properties()
+        .as_ref()
+        .and_then(|properties| properties.indicator_color().as_ref())
+        .and_then(|color| color.values().as_deref())
+        .unwrap_or_default();
+    match values {
+        [ListItem(r), ListItem(g), ListItem(b)] => (*r as f32, *g as f32, *b as f32),
+        [Red] => (255_f32, 0_f32, 0_f32),
+        [Green] => (79_f32, 255_f32, 79_f32),
+        [Blue] => (0_f32, 0_f32, 255_f32),
+        [Yellow] => (255_f32, 255_f32, 79_f32),
+        [Magenta] => (255_f32, 79_f32, 255_f32),
+        [Cyan] => (0_f32, 255_f32, 255_f32),
+        [Gray] => (128_f32, 128_f32, 128_f32),
+        [Black] => (0_f32, 0_f32, 0_f32),
+        [Orange] => (255_f32, 102_f32, 0_f32),
+        [Violet] => (153_f32, 51_f32, 255_f32),
+        [Pink] => (255_f32, 153_f32, 204_f32),
+        [Gold] => (255_f32, 153_f32, 0_f32),
+        // Light blue is the color new conditions get
+        _ => (79_f32, 153_f32, 255_f32),
+    }
+}
@@ -0,0 +1,419 @@
+use crate::idml_parser::designmap_parser::{
+    BuildingBlock, BuildingBlockType, CrossReferenceFormat, PageNumberStyle, TextVariablePreference,
+};
+use crate::idml_parser::spread_parser::SpreadContent;
+use crate::idml_parser::story_parser::{PageReference, ParagraphStyleRange, StoryContent};
+use crate::idml_parser::IDMLPackage;
+use crate::pdf_printer::note_numbering;
+use crate::pdf_printer::page_numbering::{self, PageNumber};
+use crate::pdf_printer::story_layout::StoryLayout;
+use crate::pdf_printer::text_variables;
+use std::collections::{HashMap, HashSet};
+
+/// Paragraph a cross-reference goes to, with the page its text destination is on
+#[derive(Debug, Default, Clone)]
+struct Destination {
+    page_id: Option<String>,
+    paragraph: String,
+    number: Option<String>,
+    name: Option<String>,
+}
+
+/// Regenerates the text of each cross-reference from its format, keyed by
+/// cross-reference source id. Cross-references whose destination cannot be
+/// found, or whose format uses blocks we cannot fill in, are left out so the
+/// text saved with the cross-reference is used instead.
+pub fn cross_reference_texts(
+    idml_package: &IDMLPackage,
+    page_numbers: &HashMap<String, PageNumber>,
+    layout: &StoryLayout,
+) -> HashMap<String, String> {
+    let mut destinations = HashMap::new();
+    let mut source_formats = HashMap::new();
+    for (page_id, paragraph) in document_paragraphs(idml_package, layout) {
+        for (id, name) in paragraph.destinations {
+            let page_id = layout.marker_page(&id).cloned().or(page_id.clone());
+            destinations.entry(id).or_insert(Destination {
+                page_id,
+                paragraph: paragraph.text.clone(),
+                number: paragraph.number.clone(),
+                name,
+            });
+        }
+        source_formats.extend(paragraph.cross_reference_sources);
+    }
+
+    let designmap = idml_package.designmap();
+    designmap
+        .hyperlinks()
+        .iter()
+        .filter_map(|hyperlink| {
+            let source = hyperlink.source().as_ref()?;
+            let format_id: &String = source_formats.get(source)?;
+            let format = designmap
+                .cross_reference_formats()
+                .iter()
+                .find(|format| format.id() == format_id)?;
+            let destination = hyperlink
+                .properties()
+                .as_ref()
+                .and_then(|properties| properties.destination().as_ref())
+                .and_then(|destination_id| destinations.get(destination_id))?;
+            let text = format_cross_reference(idml_package, format, destination, page_numbers)?;
+            Some((source.clone(), text))
+        })
+        .collect()
+}
+
+fn format_cross_reference(
+    idml_package: &IDMLPackage,
+    format: &CrossReferenceFormat,
+    destination: &Destination,
+    page_numbers: &HashMap<String, PageNumber>,
+) -> Option<String> {
+    let mut text = String::new();
+    for block in format.building_blocks().iter().flatten() {
+        match (*block.block_type())? {
+            BuildingBlockType::CustomStringBuildingBlock => {
+                text.push_str(block.custom_text().as_deref().unwrap_or_default())
+            }
+            BuildingBlockType::FullParagraphBuildingBlock
+            | BuildingBlockType::ParagraphTextBuildingBlock => {
+                text.push_str(&destination.paragraph)
+            }
+            BuildingBlockType::PartialParagraphBuildingBlock => {
+                text.push_str(&partial_paragraph(&destination.paragraph, block))
+            }
+            BuildingBlockType::ParagraphNumberBuildingBlock => {
+                text.push_str(destination.number.as_deref().unwrap_or_default())
+            }
+            BuildingBlockType::PageNumberBuildingBlock => {
+                let page_id = destination.page_id.as_ref()?;
+                text.push_str(&page_numbers.get(page_id)?.number)
+            }
+            BuildingBlockType::TextAnchorNameBuildingBlock => {
+                text.push_str(destination.name.as_ref()?)
+            }
+            BuildingBlockType::ChapterNumberBuildingBlock => text.push_str(
+                &text_variables::chapter_number(idml_package, &TextVariablePreference::default()),
+            ),
+            BuildingBlockType::FileNameBuildingBlock | BuildingBlockType::NotImplementedYet => {
+                return None
+            }
+        }
+    }
+    Some(text)
+}
+
+/// The start of the paragraph, up to the first delimiter character
+fn partial_paragraph(paragraph: &str, block: &BuildingBlock) -> String {
+    let delimiter = match block.applied_delimiter().as_deref() {
+        Some("$ID/Tab") => '\t',
+        Some(delimiter) if delimiter.chars().count() == 1 => delimiter.chars().next().unwrap(),
+        _ => return paragraph.to_string(),
+    };
+    match paragraph.find(delimiter) {
+        Some(end) if *block.include_delimiter() == Some(true) => {
+            paragraph[..end + delimiter.len_utf8()].to_string()
+        }
+        Some(end) => paragraph[..end].to_string(),
+        None => paragraph.to_string(),
+    }
+}
+
+/// Text of a paragraph, with what is marked in it
+#[derive(Debug, Default)]
+pub struct Paragraph {
+    /// Id of the story the paragraph is in
+    pub story: String,
+    /// Number of paragraphs before it in its story. The paragraphs of table cells
+    /// count as the paragraph the table is in, as a table is set as a whole.
+    pub index: usize,
+    pub text: String,
+    /// Id of the paragraph style of the paragraph
+    pub style: Option<String>,
+    /// Number the paragraph is given as part of a numbered list, like "2.1."
+    pub number: Option<String>,
+    /// How the paragraph is numbered, if it is in a numbered list
+    list: Option<ListNumbering>,
+    /// Text destinations in the paragraph by id and name
+    pub destinations: Vec<(String, Option<String>)>,
+    /// Cross-reference sources in the paragraph, with the id of their format
+    pub cross_reference_sources: Vec<(String, String)>,
+    /// Index topics the paragraph is referenced from
+    pub page_references: Vec<PageReference>,
+}
+
+/// Paragraphs of the stories on the pages of the document, in the order their
+/// stories first appear, each with the id of the page its first line is set on
+/// in `layout`. Paragraphs that were not laid out, such as overset paragraphs,
+/// are on the page of the first frame of their story.
+pub fn document_paragraphs(
+    idml_package: &IDMLPackage,
+    layout: &StoryLayout,
+) -> Vec<(Option<String>, Paragraph)> {
+    let mut document_paragraphs = vec![];
+    let mut stories_seen = HashSet::new();
+
+    let spreads = idml_package
+        .designmap()
+        .spread_ids()
+        .iter()
+        .filter_map(|spread_id| idml_package.spreads().get(spread_id));
+    let mut page_id = None;
+    for spread in spreads {
+        for content in spread.contents() {
+            if let SpreadContent::Page(page) = content {
+                page_id = Some(page.id().clone());
+                continue;
+            }
+            for story_id in note_numbering::story_ids(content) {
+                if !stories_seen.insert(story_id) {
+                    continue;
+                }
+                let story = match idml_package.stories().get(story_id) {
+                    Some(story) => story,
+                    None => continue,
+                };
+                let mut paragraphs = vec![Paragraph::default()];
+                collect_paragraphs(
+                    idml_package,
+                    story.paragraph_style_ranges(),
+                    &mut paragraphs,
+                );
+                number_paragraphs(&mut paragraphs);
+                document_paragraphs.extend(paragraphs.into_iter().map(|mut paragraph| {
+                    paragraph.story = story_id.clone();
+                    let paragraph_page = layout
+                        .paragraph_page(story_id, paragraph.index)
+                        .cloned()
+                        .or(page_id.clone());
+                    (paragraph_page, paragraph)
+                }));
+            }
+        }
+    }
+    document_paragraphs
+}
+
+/// Numbered list settings of a paragraph, from its paragraph style and local formatting
+#[derive(Debug, Clone)]
+struct ListNumbering {
+    /// Level of the paragraph in the list, starting at 1
+    level: usize,
+    /// Number the list starts over at from this paragraph
+    restart_at: Option<u32>,
+    /// Whether paragraphs at lower levels start over after this one
+    restart_lower_levels: bool,
+    /// Number with its surrounding text, like "^#.^t"
+    expression: String,
+    style: PageNumberStyle,
+}
+
+impl ListNumbering {
+    /// Settings of the paragraphs in a paragraph style range,
+    /// if they are in a numbered list
+    fn from_range(idml_package: &IDMLPackage, p_style: &ParagraphStyleRange) -> Option<Self> {
+        let style = p_style
+            .applied_paragraph_style()
+            .as_ref()
+            .and_then(|style_id| {
+                idml_package
+                    .resources()
+                    .styles()
+                    .paragraph_style_from_id(style_id)
+            })
+            .unwrap_or_default();
+        let list_type = p_style
+            .bullets_and_numbering_list_type()
+            .as_ref()
+            .or_else(|| style.bullets_and_numbering_list_type().as_ref())?;
+        if list_type != "NumberedList" {
+            return None;
+        }
+
+        let level = p_style.numbering_level().or(*style.numbering_level());
+        let continues = p_style.numbering_continue().or(*style.numbering_continue());
+        let start_at = p_style.numbering_start_at().or(*style.numbering_start_at());
+        let restart_policy = p_style
+            .numbering_apply_restart_policy()
+            .or(*style.numbering_apply_restart_policy());
+        let expression = p_style
+            .numbering_expression()
+            .clone()
+            .or_else(|| style.numbering_expression().clone());
+        let format = style
+            .properties()
+            .as_ref()
+            .and_then(|properties| properties.numbering_format().as_deref())
+            .map(PageNumberStyle::from_format);
+
+        Some(ListNumbering {
+            level: level.unwrap_or(1).max(1) as usize,
+            restart_at: match continues {
+                Some(false) => Some(start_at.unwrap_or(1).max(0) as u32),
+                _ => None,
+            },
+            restart_lower_levels: restart_policy != Some(false),
+            expression: expression.unwrap_or_else(|| "^#.^t".to_string()),
+            style: format.unwrap_or(PageNumberStyle::Arabic),
+        })
+    }
+}
+
+/// Numbers the paragraphs of a story that are in a numbered list. The list
+/// carries on past paragraphs that are not in it, as all numbered paragraphs
+/// of a story are in the same list.
+fn number_paragraphs(paragraphs: &mut [Paragraph]) {
+    // Number each level of the list is at, with the styles its numbers are in
+    let mut counters: Vec<(u32, PageNumberStyle)> = vec![];
+    for paragraph in paragraphs {
+        let list = match &paragraph.list {
+            Some(list) => list,
+            None => continue,
+        };
+        if counters.len() < list.level {
+            counters.resize(list.level, (0, list.style));
+        }
+        let counter = &mut counters[list.level - 1];
+        counter.0 = match list.restart_at {
+            Some(start_at) => start_at,
+            None => counter.0 + 1,
+        };
+        counter.1 = list.style;
+        if list.restart_lower_levels {
+            counters.truncate(list.level);
+        }
+        paragraph.number = Some(list_number(&list.expression, list.level, &counters));
+    }
+}
+
+/// Fills in the numbers of a numbering expression, where `^#` is the number of
+/// the paragraph and `^1` to `^9` the numbers of the levels above it. The tab
+/// that separates the number from the text of the paragraph is left out.
+fn list_number(expression: &str, level: usize, counters: &[(u32, PageNumberStyle)]) -> String {
+    let level_number = |level: usize| match counters.get(level.wrapping_sub(1)) {
+        Some((value, style)) => page_numbering::format_page_number((*value).max(1), *style),
+        None => "1".to_string(),
+    };
+
+    let mut number = String::new();
+    let mut chars = expression.chars();
+    while let Some(c) = chars.next() {
+        if c != '^' {
+            number.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('#') => number.push_str(&level_number(level)),
+            Some(digit @ '1'..='9') => {
+                number.push_str(&level_number(digit.to_digit(10).unwrap() as usize))
+            }
+            Some('t') => number.push('\t'),
+            Some(c) => number.push(c),
+            None => {}
+        }
+    }
+    number.trim_end().to_string()
+}
+
+/// Splits text into paragraphs, adding them to the paragraph last started
+fn collect_paragraphs(
+    idml_package: &IDMLPackage,
+    paragraph_style_ranges: &Option<Vec<ParagraphStyleRange>>,
+    paragraphs: &mut Vec<Paragraph>,
+) {
+    for p_style in paragraph_style_ranges.iter().flatten() {
+        // Paragraphs end with the range they started in, so a range starts a paragraph
+        let style = p_style.applied_paragraph_style();
+        let list = ListNumbering::from_range(idml_package, p_style);
+        let paragraph = paragraphs.last_mut().unwrap();
+        paragraph.style = style.clone();
+        paragraph.list = list.clone();
+        let contents = p_style
+            .character_style_ranges()
+            .iter()
+            .flatten()
+            .filter_map(|c_style| c_style.contents().as_ref())
+            .flatten();
+        for content in contents {
+            collect_content(idml_package, content, style, &list, paragraphs);
+        }
+    }
+}
+
+fn collect_content(
+    idml_package: &IDMLPackage,
+    content: &StoryContent,
+    style: &Option<String>,
+    list: &Option<ListNumbering>,
+    paragraphs: &mut Vec<Paragraph>,
+) {
+    let paragraph = paragraphs.last_mut().unwrap();
+    match content {
+        StoryContent::Content(text) => paragraph.text.push_str(text),
+        StoryContent::Br => {
+            let index = paragraph.index + 1;
+            paragraphs.push(Paragraph {
+                index,
+                style: style.clone(),
+                list: list.clone(),
+                ..Paragraph::default()
+            })
+        }
+        StoryContent::NonBreakingHyphen => paragraph.text.push('-'),
+        StoryContent::ForcedLineBreak
+        | StoryContent::EmSpace
+        | StoryContent::EnSpace
+        | StoryContent::ThirdSpace
+        | StoryContent::QuarterSpace
+        | StoryContent::SixthSpace
+        | StoryContent::ThinSpace
+        | StoryContent::HairSpace
+        | StoryContent::FigureSpace
+        | StoryContent::PunctuationSpace
+        | StoryContent::FlushSpace
+        | StoryContent::NonBreakingSpace
+        | StoryContent::FixedWidthNonBreakingSpace => paragraph.text.push(' '),
+        StoryContent::HyperlinkTextDestination(destination) => paragraph
+            .destinations
+            .push((destination.id().clone(), destination.name().clone())),
+        StoryContent::PageReference(page_reference) => {
+            paragraph.page_references.push(page_reference.clone())
+        }
+        StoryContent::HyperlinkTextSource(source) => {
+            for content in source.contents().iter().flatten() {
+                collect_content(idml_package, content, style, list, paragraphs);
+            }
+        }
+        StoryContent::CrossReferenceSource(source) => {
+            if let Some(format_id) = source.applied_format() {
+                paragraph
+                    .cross_reference_sources
+                    .push((source.id().clone(), format_id.clone()));
+            }
+            for content in source.contents().iter().flatten() {
+                collect_content(idml_package, content, style, list, paragraphs);
+            }
+        }
+        // Each paragraph of a cell is a paragraph of its own
+        StoryContent::Table(table) => {
+            let index = paragraph.index;
+            let first_cell_paragraph = paragraphs.len();
+            for cell in table.cells().iter().flatten() {
+                paragraphs.push(Paragraph::default());
+                collect_paragraphs(idml_package, cell.paragraph_style_ranges(), paragraphs);
+            }
+            for cell_paragraph in &mut paragraphs[first_cell_paragraph..] {
+                cell_paragraph.index = index;
+            }
+            paragraphs.push(Paragraph {
+                index,
+                style: style.clone(),
+                list: list.clone(),
+                ..Paragraph::default()
+            });
+        }
+        _ => {}
+    }
+}
@@ -0,0 +1,196 @@
+/// Preview saved in an EPS file, which is what we draw in place of the PostScript
+#[derive(Debug)]
+pub enum EpsPreview<'d> {
+    /// TIFF image in the binary header of a DOS EPS file
+    Tiff(&'d [u8]),
+    /// Samples of an EPSI preview, scaled to 8 bit gray
+    Gray {
+        width: u32,
+        height: u32,
+        pixels: Vec<u8>,
+    },
+}
+
+/// Reads the preview of an EPS file. DOS EPS files may have a TIFF preview,
+/// while other EPS files may have an EPSI preview in their comments.
+/// See https://www.adobe.com/content/dam/acom/en/devnet/actionscript/articles/5002.EPSF_Spec.pdf
+pub fn read_preview(data: &[u8]) -> Result<EpsPreview<'_>, String> {
+    if let Some(tiff) = dos_section(data, 4)? {
+        return Ok(EpsPreview::Tiff(tiff));
+    }
+    read_epsi_preview(postscript(data)?)
+}
+
+/// Width and height of the `%%BoundingBox` of the graphic in points
+pub fn bounding_box_size(data: &[u8]) -> Result<(f64, f64), String> {
+    let text = String::from_utf8_lossy(postscript(data)?);
+    let bounds = text
+        .lines()
+        .filter_map(|line| line.strip_prefix("%%BoundingBox:"))
+        .map(|bounds| {
+            bounds
+                .split_whitespace()
+                .filter_map(|value| value.parse().ok())
+                .collect::<Vec<f64>>()
+        })
+        // The bounding box may be given at the end instead, with `(atend)`
+        .find(|bounds| bounds.len() == 4)
+        .ok_or("EPS has no bounding box")?;
+    Ok((bounds[2] - bounds[0], bounds[3] - bounds[1]))
+}
+
+/// The PostScript of the file, which is a section of DOS EPS files
+fn postscript(data: &[u8]) -> Result<&[u8], String> {
+    Ok(dos_section(data, 0)?.unwrap_or(data))
+}
+
+/// The section of a DOS EPS file whose offset is the `index`th field of the header and whose
+/// length is the next field, or None if the file is not a DOS EPS file or the section is empty
+fn dos_section(data: &[u8], index: usize) -> Result<Option<&[u8]>, String> {
+    if !data.starts_with(&[0xC5, 0xD0, 0xD3, 0xC6]) {
+        return Ok(None);
+    }
+    if data.len() < 30 {
+        return Err("Unexpected end of EPS header".to_string());
+    }
+    let header = |index: usize| -> usize {
+        let b = &data[4 + index * 4..8 + index * 4];
+        u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as usize
+    };
+    let (offset, length) = (header(index), header(index + 1));
+    match length {
+        0 => Ok(None),
+        _ => data
+            .get(offset..offset + length)
+            .map(Some)
+            .ok_or("Section is outside the EPS file".to_string()),
+    }
+}
+
+/// Reads the preview between `%%BeginPreview: width height depth lines` and `%%EndPreview`,
+/// which is given as hexadecimal samples in comments, with 0 as white
+fn read_epsi_preview(postscript: &[u8]) -> Result<EpsPreview<'_>, String> {
+    let text = String::from_utf8_lossy(postscript);
+    let mut lines = text.lines();
+    let parameters: Vec<u32> = lines
+        .find_map(|line| line.strip_prefix("%%BeginPreview:"))
+        .ok_or("EPS has no TIFF or EPSI preview")?
+        .split_whitespace()
+        .filter_map(|value| value.parse().ok())
+        .collect();
+    let (width, height, depth) = match parameters.as_slice() {
+        &[width, height, depth, ..] if [1, 2, 4, 8].contains(&depth) => (width, height, depth),
+        _ => return Err("EPSI preview has an unknown size or depth".to_string()),
+    };
+
+    let hex: Vec<u8> = lines
+        .take_while(|line| !line.starts_with("%%EndPreview"))
+        .flat_map(|line| line.trim_start_matches('%').bytes())
+        .filter(|byte| byte.is_ascii_hexdigit())
+        .collect();
+    let samples: Vec<u8> = hex
+        .chunks(2)
+        .filter_map(|pair| u8::from_str_radix(std::str::from_utf8(pair).ok()?, 16).ok())
+        .collect();
+
+    let row_length = (width * depth + 7) as usize / 8;
+    if samples.len() < row_length * height as usize {
+        return Err("EPSI preview is cut short".to_string());
+    }
+    let max = (1_u32 << depth) - 1;
+    let pixels = (0..height as usize)
+        .flat_map(|row| (0..width as usize).map(move |column| (row, column)))
+        .map(|(row, column)| {
+            let bit = column * depth as usize;
+            let byte = samples[row * row_length + bit / 8];
+            let sample = (byte >> (8 - depth as usize - bit % 8)) as u32 & max;
+            (255 - sample * 255 / max) as u8
+        })
+        .collect();
+    Ok(EpsPreview::Gray {
+        width,
+        height,
+        pixels,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gray_pixels(postscript: &str) -> (u32, u32, Vec<u8>) {
+        match read_preview(postscript.as_bytes()) {
+            Ok(EpsPreview::Gray {
+                width,
+                height,
+                pixels,
+            }) => (width, height, pixels),
+            other => panic!("Expected a gray preview, got {:?}", other),
+        }
+    }
+
+    /// DOS EPS file holding `postscript` and `tiff`
+    fn dos_eps(postscript: &[u8], tiff: &[u8]) -> Vec<u8> {
+        let ps_offset = 30_u32;
+        let tiff_offset = ps_offset + postscript.len() as u32;
+        let mut data = vec![0xC5, 0xD0, 0xD3, 0xC6];
+        for field in &[
+            ps_offset,
+            postscript.len() as u32,
+            0,
+            0,
+            tiff_offset,
+            tiff.len() as u32,
+        ] {
+            data.extend_from_slice(&field.to_le_bytes());
+        }
+        data.extend_from_slice(&[0xFF, 0xFF]);
+        data.extend_from_slice(postscript);
+        data.extend_from_slice(tiff);
+        data
+    }
+
+    #[test]
+    fn one_bit_previews_are_read_with_zero_as_white() {
+        let postscript = "%!PS-Adobe-3.0 EPSF-3.0\n\
+                          %%BeginPreview: 4 2 1 2\n\
+                          % A0\n\
+                          % 50\n\
+                          %%EndPreview\n";
+        assert_eq!(
+            gray_pixels(postscript),
+            (4, 2, vec![0, 255, 0, 255, 255, 0, 255, 0])
+        );
+    }
+
+    #[test]
+    fn eight_bit_previews_are_inverted() {
+        let postscript = "%%BeginPreview: 3 1 8 1\n% 00FF80\n%%EndPreview\n";
+        assert_eq!(gray_pixels(postscript), (3, 1, vec![255, 0, 127]));
+    }
+
+    #[test]
+    fn tiff_previews_are_taken_from_dos_eps_files() {
+        let data = dos_eps(b"%!PS-Adobe-3.0 EPSF-3.0\n", b"II*\0tiff");
+        match read_preview(&data) {
+            Ok(EpsPreview::Tiff(tiff)) => assert_eq!(tiff, b"II*\0tiff"),
+            other => panic!("Expected a TIFF preview, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn files_without_a_preview_are_reported() {
+        assert!(read_preview(b"%!PS-Adobe-3.0 EPSF-3.0\nshowpage\n").is_err());
+        assert!(read_preview(b"%%BeginPreview: 4 2 1 2\n% A0\n%%EndPreview\n").is_err());
+    }
+
+    #[test]
+    fn the_bounding_box_may_be_given_at_the_end() {
+        let postscript = b"%%BoundingBox: (atend)\n%%BoundingBox: 10 20 110 70\n";
+        assert_eq!(bounding_box_size(postscript), Ok((100_f64, 50_f64)));
+        assert_eq!(
+            bounding_box_size(&dos_eps(postscript, b"")),
+            Ok((100_f64, 50_f64))
+        );
+    }
+}
@@ -1,15 +1,22 @@
 use crate::idml_parser::fonts_parser::{Font, FontType};
 use crate::idml_parser::IDMLResources;
+use crate::pdf_printer::cid_fonts::{self, CidFont};
+use crate::pdf_printer::text_layout::FontEncoding;
 use dirs;
 use libharu_sys::*;
-use std::cell::Cell;
+use std::cell::{Cell, RefCell};
+use std::collections::hash_map::Entry;
 use std::collections::HashMap;
-use std::ffi::CString;
+use std::ffi::{CStr, CString};
 use std::fs;
 use std::iter::FromIterator;
 use std::marker::Copy;
 use std::path::PathBuf;
 use std::ptr;
+use std::rc::Rc;
+
+/// libharu's name for its UTF-8 encoder
+const UNICODE_ENCODING: &str = "UTF-8";
 
 #[derive(Debug, PartialEq, Hash, Eq, Copy, Clone)]
 pub struct FontId<'a> {
@@ -20,14 +27,59 @@ pub struct FontId<'a> {
 #[derive(Debug, Copy, Clone)]
 pub enum FontStatus<'a> {
     Uninitialized(&'a Font),
-    Initialized(HPDF_Font),
+    Initialized(HPDF_Font, Option<FontMetrics>),
+}
+
+/// Metrics read from the font file, in units of 1/1000 em like the metrics reported by libharu
+#[derive(Debug, Copy, Clone)]
+pub struct FontMetrics {
+    pub underline_position: f64,
+    pub underline_thickness: f64,
+    pub strikeout_position: f64,
+    pub strikeout_thickness: f64,
+}
+
+impl FontMetrics {
+    /// Approximates the metrics from what libharu knows about the font,
+    /// used for fonts where we do not have access to the font file
+    fn from_pdf_font(pdf_font: HPDF_Font) -> FontMetrics {
+        let x_height = unsafe { HPDF_Font_GetXHeight(pdf_font) } as f64;
+        let x_height = if x_height > 0_f64 { x_height } else { 500_f64 };
+        FontMetrics {
+            underline_position: -100_f64,
+            underline_thickness: 50_f64,
+            strikeout_position: x_height / 2_f64 + 25_f64,
+            strikeout_thickness: 50_f64,
+        }
+    }
+
+    fn from_data(data: &[u8]) -> Option<FontMetrics> {
+        let face = ttf_parser::Face::parse(data, 0).ok()?;
+        let scale = 1000_f64 / face.units_per_em() as f64;
+        let underline = face.underline_metrics()?;
+        let strikeout = face.strikeout_metrics().unwrap_or(ttf_parser::LineMetrics {
+            position: face.x_height().unwrap_or(500) / 2 + underline.thickness,
+            thickness: underline.thickness,
+        });
+        Some(FontMetrics {
+            underline_position: underline.position as f64 * scale,
+            underline_thickness: underline.thickness as f64 * scale,
+            strikeout_position: strikeout.position as f64 * scale,
+            strikeout_thickness: strikeout.thickness as f64 * scale,
+        })
+    }
 }
 
 #[derive(Debug)]
 pub struct FontLibrary<'a> {
+    idml_resources: &'a IDMLResources,
     resource_dir: &'a Option<PathBuf>,
     pdf_doc: HPDF_Doc,
     fonts: HashMap<FontId<'a>, Cell<FontStatus<'a>>>,
+    font_data: RefCell<HashMap<(String, String), Rc<Vec<u8>>>>,
+    encodings: RefCell<HashMap<String, Rc<FontEncoding>>>,
+    /// TrueType fonts drawn by glyph id, keyed by their name in the PDF
+    cid_fonts: RefCell<HashMap<String, Rc<RefCell<CidFont>>>>,
 }
 
 impl<'a> FontLibrary<'a> {
@@ -38,7 +90,7 @@ impl<'a> FontLibrary<'a> {
         };
         match self.fonts.get(&id) {
             Some(cell) => match cell.get() {
-                FontStatus::Initialized(pdf_font) => Ok(pdf_font),
+                FontStatus::Initialized(pdf_font, _) => Ok(pdf_font),
                 FontStatus::Uninitialized(idml_font) => {
                     // Try finding a font file matching one of these names
                     // Load the first found font file matching
@@ -49,22 +101,36 @@ impl<'a> FontLibrary<'a> {
                         idml_font.name(),
                     ];
                     for id in possible_ids.iter() {
-                        if let Ok(pdf_font) = self.load_font_from_id(id, idml_font.font_type()) {
-                            cell.set(FontStatus::Initialized(pdf_font));
+                        if let Ok((pdf_font, font_data)) =
+                            self.load_font_from_id(id, idml_font.font_type())
+                        {
+                            let metrics =
+                                font_data.as_ref().and_then(|d| FontMetrics::from_data(d));
+                            if let Some(data) = font_data {
+                                let data = Rc::new(data);
+                                if self.is_unicode(pdf_font) {
+                                    let name = pdf_font_name(pdf_font);
+                                    let mut cid_fonts = self.cid_fonts.borrow_mut();
+                                    if let Entry::Vacant(entry) = cid_fonts.entry(name) {
+                                        if let Some(cid_font) =
+                                            CidFont::new(entry.key(), data.clone())
+                                        {
+                                            entry.insert(Rc::new(RefCell::new(cid_font)));
+                                        }
+                                    }
+                                }
+                                self.font_data
+                                    .borrow_mut()
+                                    .insert((font_name.to_string(), font_style.to_string()), data);
+                            }
+                            cell.set(FontStatus::Initialized(pdf_font, metrics));
                             return Ok(pdf_font);
                         }
                     }
 
                     // We were not able to find a font - set a default instead
                     println!("No font matched: {:?}", id);
-                    unsafe {
-                        let font = HPDF_GetFont(
-                            self.pdf_doc,
-                            CString::new("Helvetica").unwrap().as_ptr(),
-                            ptr::null_mut(),
-                        );
-                        Ok(font)
-                    }
+                    Ok(self.fallback_font(idml_font))
                 }
             },
             None => Err(format!(
@@ -75,6 +141,97 @@ impl<'a> FontLibrary<'a> {
         }
     }
 
+    /// Returns the metrics of a font, falling back to approximate metrics
+    /// if the font file could not be read
+    pub fn get_font_metrics(
+        &self,
+        font_name: &str,
+        font_style: &str,
+    ) -> Result<FontMetrics, String> {
+        let pdf_font = self.get_font(font_name, font_style)?;
+        let id = FontId {
+            font_name,
+            font_style,
+        };
+        match self.fonts.get(&id).map(|cell| cell.get()) {
+            Some(FontStatus::Initialized(_, Some(metrics))) => Ok(metrics),
+            _ => Ok(FontMetrics::from_pdf_font(pdf_font)),
+        }
+    }
+
+    /// Returns the raw font file of a font, if it was loaded from a TrueType or OpenType file
+    pub fn get_font_data(&self, font_name: &str, font_style: &str) -> Option<Rc<Vec<u8>>> {
+        self.get_font(font_name, font_style).ok()?;
+        self.font_data
+            .borrow()
+            .get(&(font_name.to_string(), font_style.to_string()))
+            .cloned()
+    }
+
+    /// Returns the encoding text drawn with `pdf_font` has to be written in.
+    /// Fonts drawn by glyph id each have an encoding of their own.
+    pub fn get_encoding(&self, pdf_font: HPDF_Font) -> Rc<FontEncoding> {
+        if self.is_unicode(pdf_font) {
+            let font_name = pdf_font_name(pdf_font);
+            if let Some(cid_font) = self.cid_fonts.borrow().get(&font_name) {
+                let mut encodings = self.encodings.borrow_mut();
+                return encodings
+                    .entry(font_name)
+                    .or_insert_with(|| Rc::new(FontEncoding::glyph_ids(cid_font.clone())))
+                    .clone();
+            }
+        }
+        unsafe {
+            let encoding_name = HPDF_Font_GetEncodingName(pdf_font);
+            let name = CStr::from_ptr(encoding_name).to_string_lossy().to_string();
+            if let Some(encoding) = self.encodings.borrow().get(&name) {
+                return encoding.clone();
+            }
+            let encoding = Rc::new(match name.as_str() {
+                UNICODE_ENCODING => FontEncoding::unicode(),
+                _ => FontEncoding::from_encoder(HPDF_GetEncoder(self.pdf_doc, encoding_name)),
+            });
+            self.encodings.borrow_mut().insert(name, encoding.clone());
+            encoding
+        }
+    }
+
+    fn is_unicode(&self, pdf_font: HPDF_Font) -> bool {
+        let encoding_name = unsafe { CStr::from_ptr(HPDF_Font_GetEncodingName(pdf_font)) };
+        encoding_name.to_bytes() == UNICODE_ENCODING.as_bytes()
+    }
+
+    /// Writes the glyphs drawn by glyph id into the fonts of the saved PDF at `path`
+    pub fn write_cid_fonts(&self, path: &str) -> Result<(), String> {
+        let cid_fonts = self.cid_fonts.borrow();
+        let cid_fonts: Vec<_> = cid_fonts.values().map(|font| font.borrow()).collect();
+        let cid_fonts: Vec<&CidFont> = cid_fonts.iter().map(|font| &**font).collect();
+        cid_fonts::write_cid_fonts(path, &cid_fonts)
+    }
+
+    /// One of libharu's built-in fonts, chosen by the writing script of the
+    /// font. CJK text falls back to a CID font so it can still be drawn.
+    fn fallback_font(&self, idml_font: &Font) -> HPDF_Font {
+        let (font_name, encoding) = match idml_font.writing_script() {
+            1 => ("MS-Mincho", Some("90ms-RKSJ-H")),
+            2 => ("MingLiU", Some("ETen-B5-H")),
+            3 => ("Batang", Some("KSCms-UHC-H")),
+            25 => ("SimSun", Some("GBK-EUC-H")),
+            _ => ("Helvetica", None),
+        };
+        let font_name = CString::new(font_name).unwrap();
+        let encoding = encoding.map(|encoding| CString::new(encoding).unwrap());
+        unsafe {
+            HPDF_GetFont(
+                self.pdf_doc,
+                font_name.as_ptr(),
+                encoding
+                    .as_ref()
+                    .map_or(ptr::null(), |encoding| encoding.as_ptr()),
+            )
+        }
+    }
+
     pub fn new(
         idml_resources: &'a IDMLResources,
         pdf_doc: HPDF_Doc,
@@ -97,16 +254,89 @@ impl<'a> FontLibrary<'a> {
                 })
             });
 
+        // TrueType fonts are embedded with a Unicode encoding,
+        // so text is not limited to a single byte encoding
+        unsafe {
+            HPDF_UseUTFEncodings(pdf_doc);
+
+            // The built-in CJK fonts are used for CJK text when no font file is found
+            HPDF_UseJPFonts(pdf_doc);
+            HPDF_UseJPEncodings(pdf_doc);
+            HPDF_UseKRFonts(pdf_doc);
+            HPDF_UseKREncodings(pdf_doc);
+            HPDF_UseCNSFonts(pdf_doc);
+            HPDF_UseCNSEncodings(pdf_doc);
+            HPDF_UseCNTFonts(pdf_doc);
+            HPDF_UseCNTEncodings(pdf_doc);
+        }
+
         let font_lib = FontLibrary {
+            idml_resources,
             resource_dir: resource_dir,
             pdf_doc,
             fonts: HashMap::from_iter(font_refs),
+            font_data: RefCell::default(),
+            encodings: RefCell::default(),
+            cid_fonts: RefCell::default(),
         };
 
         Ok(font_lib)
     }
 
-    fn load_font_from_id(&self, id: &str, font_type: &FontType) -> Result<HPDF_Font, String> {
+    /// Returns the font files of the TrueType and OpenType fonts declared in the IDML resources
+    /// that can be found, with the family and style of each. The fonts are not loaded into the PDF.
+    pub fn font_files(&self) -> Vec<(String, String, PathBuf)> {
+        let mut font_files: Vec<(String, String, PathBuf)> = self
+            .fonts
+            .keys()
+            .filter_map(|id| {
+                let idml_font = self.find_idml_font(id)?;
+                if let FontType::Type1 = idml_font.font_type() {
+                    return None;
+                }
+                let font_path = [
+                    idml_font.post_script_name(),
+                    idml_font.full_name_native(),
+                    idml_font.full_name(),
+                    idml_font.name(),
+                ]
+                .iter()
+                .find_map(|id| self.find_font_file(id))?;
+                Some((
+                    id.font_name.to_string(),
+                    id.font_style.to_string(),
+                    font_path,
+                ))
+            })
+            .collect();
+        // The order of the map is random, and the first font is used when no other matches
+        font_files.sort();
+        font_files
+    }
+
+    fn find_idml_font(&self, id: &FontId) -> Option<&'a Font> {
+        self.idml_resources
+            .fonts()
+            .font_families()
+            .into_iter()
+            .flat_map(|family| family.fonts().iter())
+            .find(|font| {
+                font.font_family() == id.font_name && font.font_style_name() == id.font_style
+            })
+    }
+
+    fn load_font_from_id(
+        &self,
+        id: &str,
+        font_type: &FontType,
+    ) -> Result<(HPDF_Font, Option<Vec<u8>>), String> {
+        match self.find_font_file(id) {
+            Some(font_path) => self.load_font_from_path(&font_path, font_type),
+            None => Err(format!("No font matched: {}", id)),
+        }
+    }
+
+    fn find_font_file(&self, id: &str) -> Option<PathBuf> {
         let mut font_lookup = vec![];
 
         // Search in provided font directory
@@ -119,29 +349,25 @@ impl<'a> FontLibrary<'a> {
             font_lookup.append(&mut find_font_in_dir(id, &font_dir));
         }
 
-        match &font_lookup[..] {
-            [] => Err(format!("No font matched: {}", id)),
-            [font_path] => self.load_font_from_path(font_path, font_type),
-            [font_path, ..] => self.load_font_from_path(font_path, font_type),
-        }
+        font_lookup.into_iter().next()
     }
 
     fn load_font_from_path(
         &self,
         font_path: &PathBuf,
         font_type: &FontType,
-    ) -> Result<HPDF_Font, String> {
+    ) -> Result<(HPDF_Font, Option<Vec<u8>>), String> {
         unsafe {
             let font_name = match font_type {
                 FontType::TrueType => HPDF_LoadTTFontFromFile(
                     self.pdf_doc,
                     CString::new(font_path.to_str().unwrap()).unwrap().as_ptr(),
-                    HPDF_FALSE,
+                    HPDF_TRUE,
                 ),
                 FontType::OpenTypeTT => HPDF_LoadTTFontFromFile(
                     self.pdf_doc,
                     CString::new(font_path.to_str().unwrap()).unwrap().as_ptr(),
-                    HPDF_FALSE,
+                    HPDF_TRUE,
                 ),
                 FontType::OpenTypeCFF => HPDF_LoadTTFontFromFile(
                     self.pdf_doc,
@@ -166,12 +392,29 @@ impl<'a> FontLibrary<'a> {
                     .to_string())
                 }
             };
-            let font = HPDF_GetFont(self.pdf_doc, font_name, ptr::null_mut());
-            Ok(font)
+            let font = match font_type {
+                FontType::TrueType | FontType::OpenTypeTT => {
+                    let encoding = CString::new(UNICODE_ENCODING).unwrap();
+                    HPDF_GetFont(self.pdf_doc, font_name, encoding.as_ptr())
+                }
+                _ => HPDF_GetFont(self.pdf_doc, font_name, ptr::null_mut()),
+            };
+            let font_data = match font_type {
+                FontType::Type1 => None,
+                _ => fs::read(font_path).ok(),
+            };
+            Ok((font, font_data))
         }
     }
 }
 
+/// Name of the font in the PDF. libharu loads each font once by its name.
+fn pdf_font_name(pdf_font: HPDF_Font) -> String {
+    unsafe { CStr::from_ptr(HPDF_Font_GetFontName(pdf_font)) }
+        .to_string_lossy()
+        .to_string()
+}
+
 /// Get a list of paths to every file matching font_name in a given directory
 fn find_font_in_dir(font_name: &str, dir: &PathBuf) -> Vec<PathBuf> {
     fs::read_dir(dir)
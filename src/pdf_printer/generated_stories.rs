@@ -0,0 +1,808 @@
+use crate::idml_parser::designmap_parser::Topic;
+use crate::idml_parser::preferences_parser::IndexOptions;
+use crate::idml_parser::story_parser::{
+    CharacterStyleRange, PageReference, ParagraphStyleRange, Story, StoryContent,
+};
+use crate::idml_parser::styles_parser::{IdPkgStyles, PageNumberPosition, TocStyle, TocStyleEntry};
+use crate::idml_parser::IDMLPackage;
+use crate::pdf_printer::cross_references::{self, Paragraph};
+use crate::pdf_printer::page_numbering::{self, PageNumber};
+use crate::pdf_printer::story_layout::StoryLayout;
+use std::collections::HashMap;
+
+/// Regenerates the tables of contents and the index of the document, which are
+/// saved as plain stories that go stale once the text they are made from is edited.
+/// Entries show the pages their text was set on in `layout`. Must be called before
+/// the document is printed, as it replaces their text.
+pub fn regenerate_stories(idml_package: &mut IDMLPackage, layout: &StoryLayout) {
+    let page_numbers = page_numbering::page_numbers(idml_package);
+    let paragraphs = cross_references::document_paragraphs(idml_package, layout);
+
+    let index_options = idml_package
+        .resources()
+        .preferences()
+        .index_options()
+        .clone()
+        .unwrap_or_default();
+
+    let mut regenerated = vec![];
+    for story in idml_package.stories().values() {
+        let toc_style = story
+            .applied_toc_style()
+            .as_ref()
+            .and_then(|id| idml_package.resources().styles().toc_style_from_id(id));
+        if let Some(toc_style) = toc_style {
+            let styles = idml_package.resources().styles();
+            let text = table_of_contents(styles, toc_style, &paragraphs, &page_numbers);
+            regenerated.push((story.id().clone(), text));
+        } else if is_index_story(idml_package, story, &index_options) {
+            let text = index(
+                idml_package,
+                story,
+                &index_options,
+                &paragraphs,
+                &page_numbers,
+                layout,
+            );
+            regenerated.push((story.id().clone(), text));
+        }
+    }
+
+    for (story_id, text) in regenerated {
+        if let Some(story) = idml_package.story_with_id_mut(&story_id) {
+            story.with_paragraph_style_ranges(text);
+        }
+    }
+}
+
+/// Entries for the paragraphs whose styles are in the table of contents
+/// style, in the order they are in the document, below its title
+fn table_of_contents(
+    styles: &IdPkgStyles,
+    toc_style: &TocStyle,
+    paragraphs: &[(Option<String>, Paragraph)],
+    page_numbers: &HashMap<String, PageNumber>,
+) -> Vec<ParagraphStyleRange> {
+    let mut text = vec![];
+    if let Some(title) = toc_style.title().as_ref().filter(|title| !title.is_empty()) {
+        text.push(ParagraphStyleRange::new(
+            toc_style.title_style().clone(),
+            vec![CharacterStyleRange::new(
+                None,
+                vec![StoryContent::Content(title.clone())],
+            )],
+        ));
+    }
+
+    // Entries are looked up by the name of the paragraph style, so each style is looked up once
+    let mut style_entries: HashMap<&String, Option<&TocStyleEntry>> = HashMap::new();
+    for (page_id, paragraph) in paragraphs {
+        let style_id = match &paragraph.style {
+            Some(style_id) => style_id,
+            None => continue,
+        };
+        let entry = *style_entries.entry(style_id).or_insert_with(|| {
+            let style_name = styles
+                .paragraph_style_from_id(style_id)
+                .and_then(|style| style.name().clone());
+            toc_style.entries().iter().flatten().find(|entry| {
+                let entry_name = entry.name().clone().unwrap_or_default();
+                style_name.as_ref() == Some(&entry_name)
+                    || style_id == &format!("ParagraphStyle/{}", entry_name)
+            })
+        });
+        let entry = match entry {
+            Some(entry) if !paragraph.text.trim().is_empty() => entry,
+            _ => continue,
+        };
+
+        let entry_text = CharacterStyleRange::new(
+            None,
+            vec![StoryContent::Content(paragraph.text.trim().to_string())],
+        );
+        let page_number = page_id
+            .as_ref()
+            .and_then(|page_id| page_numbers.get(page_id))
+            .map(|page_number| {
+                vec![
+                    CharacterStyleRange::new(
+                        entry.separator_style().clone(),
+                        separator_contents(entry.separator().as_deref().unwrap_or("^t")),
+                    ),
+                    CharacterStyleRange::new(
+                        entry.page_number_style().clone(),
+                        vec![StoryContent::Content(page_number.number.clone())],
+                    ),
+                ]
+            })
+            .unwrap_or_default();
+        let character_style_ranges = match entry.page_number_position() {
+            Some(PageNumberPosition::AfterEntry) | None => [vec![entry_text], page_number].concat(),
+            Some(PageNumberPosition::BeforeEntry) => {
+                let mut page_number = page_number;
+                page_number.reverse();
+                [page_number, vec![entry_text]].concat()
+            }
+            Some(PageNumberPosition::NoPageNumber) => vec![entry_text],
+        };
+        text.push(ParagraphStyleRange::new(
+            entry.format_style().clone(),
+            character_style_ranges,
+        ));
+    }
+    end_paragraphs(text)
+}
+
+/// The text between an entry and its page number, where
+/// InDesign's metacharacters stand for special characters
+fn separator_contents(separator: &str) -> Vec<StoryContent> {
+    let mut contents = vec![];
+    let mut text = String::new();
+    let mut chars = separator.chars();
+    while let Some(c) = chars.next() {
+        let special = match c {
+            '^' => match chars.next() {
+                Some('t') => Some(StoryContent::Content("\t".to_string())),
+                Some('y') => Some(StoryContent::RightIndentTab),
+                Some('m') => Some(StoryContent::EmSpace),
+                Some('>') => Some(StoryContent::EnSpace),
+                Some('s') => Some(StoryContent::NonBreakingSpace),
+                Some('<') => Some(StoryContent::ThinSpace),
+                Some('|') => Some(StoryContent::HairSpace),
+                Some('=') => Some(StoryContent::Content("\u{2013}".to_string())),
+                Some('_') => Some(StoryContent::Content("\u{2014}".to_string())),
+                Some(other) => {
+                    text.push('^');
+                    text.push(other);
+                    None
+                }
+                None => {
+                    text.push('^');
+                    None
+                }
+            },
+            _ => {
+                text.push(c);
+                None
+            }
+        };
+        if let Some(special) = special {
+            if !text.is_empty() {
+                contents.push(StoryContent::Content(std::mem::take(&mut text)));
+            }
+            contents.push(special);
+        }
+    }
+    if !text.is_empty() {
+        contents.push(StoryContent::Content(text));
+    }
+    contents
+}
+
+fn is_index_paragraph(p_style: &ParagraphStyleRange, index_options: &IndexOptions) -> bool {
+    match p_style.applied_paragraph_style() {
+        Some(style_id) => index_options.index_styles().contains(&style_id),
+        None => false,
+    }
+}
+
+/// Whether the story is the generated index of the document. Indexes are not marked as
+/// such in IDML, so they are known by the paragraph styles the index options set them in.
+fn is_index_story(idml_package: &IDMLPackage, story: &Story, index_options: &IndexOptions) -> bool {
+    idml_package.designmap().index().is_some()
+        && story
+            .paragraph_style_ranges()
+            .iter()
+            .flatten()
+            .any(|p_style| is_index_paragraph(p_style, index_options))
+}
+
+/// Topics of the index with the pages referring to them, sorted and grouped
+/// under section heads by their first letter. The paragraphs before the
+/// first entry of the index, such as its title, are kept as they are.
+fn index(
+    idml_package: &IDMLPackage,
+    story: &Story,
+    index_options: &IndexOptions,
+    paragraphs: &[(Option<String>, Paragraph)],
+    page_numbers: &HashMap<String, PageNumber>,
+    layout: &StoryLayout,
+) -> Vec<ParagraphStyleRange> {
+    let title: Vec<ParagraphStyleRange> = story
+        .paragraph_style_ranges()
+        .iter()
+        .flatten()
+        .take_while(|p_style| !is_index_paragraph(p_style, index_options))
+        .cloned()
+        .collect();
+
+    // Pages and ranges of pages referring to each topic, in page order without repeats
+    let pages = document_pages(idml_package);
+    let mut topic_ranges: HashMap<&String, Vec<(usize, usize)>> = HashMap::new();
+    for (i, (page_id, paragraph)) in paragraphs.iter().enumerate() {
+        for page_reference in &paragraph.page_references {
+            let topic_id = match page_reference.referenced_topic() {
+                Some(topic_id) => topic_id,
+                None => continue,
+            };
+            let ranges = topic_ranges.entry(topic_id).or_default();
+            if page_reference.page_reference_type().as_deref() == Some("SuppressPageNumbers") {
+                continue;
+            }
+            let page_id = layout.marker_page(page_reference.id()).or(page_id.as_ref());
+            let first = match page_id.and_then(|page_id| pages.iter().position(|p| p == page_id)) {
+                Some(first) => first,
+                None => continue,
+            };
+            let last = range_end(
+                page_reference,
+                first,
+                &paragraphs[i..],
+                &pages,
+                page_numbers,
+            );
+            ranges.push((first, last.max(first)));
+        }
+    }
+    let topic_pages: HashMap<&String, Vec<PageRange>> = topic_ranges
+        .into_iter()
+        .map(|(topic_id, mut ranges)| {
+            ranges.sort();
+            let mut merged: Vec<(usize, usize)> = vec![];
+            for (first, last) in ranges {
+                match merged.last_mut() {
+                    Some((_, merged_last)) if first <= *merged_last => {
+                        *merged_last = (*merged_last).max(last)
+                    }
+                    _ => merged.push((first, last)),
+                }
+            }
+            let number = |page: usize| {
+                page_numbers
+                    .get(&pages[page])
+                    .map(|page_number| page_number.number.clone())
+                    .unwrap_or_default()
+            };
+            let ranges = merged
+                .into_iter()
+                .map(|(first, last)| PageRange {
+                    first: number(first),
+                    last: Some(number(last)).filter(|_| first != last),
+                })
+                .collect();
+            (topic_id, ranges)
+        })
+        .collect();
+
+    let topics = idml_package
+        .designmap()
+        .index()
+        .as_ref()
+        .and_then(|index| index.topics().as_ref());
+    index_text(title, topics, &topic_pages, index_options)
+}
+
+/// Page numbers of a page, or a range of pages, referring to a topic
+#[derive(Debug, Clone, PartialEq)]
+struct PageRange {
+    first: String,
+    /// Number of the last page of a range of pages
+    last: Option<String>,
+}
+
+/// Paragraphs of the index: the title, followed by the entries of the topics
+/// referred to, under the heads of their sections if the index has them
+fn index_text(
+    mut text: Vec<ParagraphStyleRange>,
+    topics: Option<&Vec<Topic>>,
+    topic_pages: &HashMap<&String, Vec<PageRange>>,
+    index_options: &IndexOptions,
+) -> Vec<ParagraphStyleRange> {
+    let mut section_head = None;
+    for topic in sorted_topics(topics, topic_pages) {
+        // Topics not starting with a letter go first, without a section head
+        let first = sort_key(topic).chars().next().filter(|c| c.is_alphabetic());
+        let head = first.map(|c| c.to_uppercase().to_string());
+        if *index_options.include_section_headings() != Some(false)
+            && head.is_some()
+            && head != section_head
+        {
+            text.push(ParagraphStyleRange::new(
+                index_options.section_heading_style().clone(),
+                vec![CharacterStyleRange::new(
+                    None,
+                    vec![StoryContent::Content(head.clone().unwrap())],
+                )],
+            ));
+            section_head = head;
+        }
+        index_entries(topic, 1, topic_pages, index_options, &mut text);
+    }
+    end_paragraphs(text)
+}
+
+/// Ids of the pages of the document, in order
+fn document_pages(idml_package: &IDMLPackage) -> Vec<String> {
+    idml_package
+        .designmap()
+        .spread_ids()
+        .iter()
+        .filter_map(|spread_id| idml_package.spreads().get(spread_id))
+        .flat_map(|spread| spread.pages().into_iter().flatten())
+        .map(|page| page.id().clone())
+        .collect()
+}
+
+/// Index of the last page a page reference spans, of the reference starting on page
+/// `first` in the first of `paragraphs`, which are followed by the rest of the document
+fn range_end(
+    page_reference: &PageReference,
+    first: usize,
+    paragraphs: &[(Option<String>, Paragraph)],
+    pages: &[String],
+    page_numbers: &HashMap<String, PageNumber>,
+) -> usize {
+    let limit = page_reference.page_reference_limit().as_deref();
+    let count = limit
+        .and_then(|limit| limit.parse::<usize>().ok())
+        .unwrap_or(1);
+    let page_index = |page_id: &Option<String>| {
+        page_id
+            .as_ref()
+            .and_then(|page_id| pages.iter().position(|page| page == page_id))
+    };
+    // Paragraphs of the story of the reference, from the one it is in
+    let (_, paragraph) = &paragraphs[0];
+    let story_paragraphs = paragraphs
+        .iter()
+        .take_while(|(_, other)| other.story == paragraph.story);
+    let last_of = |paragraphs: Vec<&(Option<String>, Paragraph)>| {
+        paragraphs
+            .iter()
+            .filter_map(|(page_id, _)| page_index(page_id))
+            .max()
+            .unwrap_or(first)
+    };
+
+    match page_reference.page_reference_type().as_deref() {
+        Some("ToEndOfStory") => last_of(story_paragraphs.collect()),
+        Some("ToNextStyleChange") => last_of(
+            story_paragraphs
+                .take_while(|(_, other)| other.style == paragraph.style)
+                .collect(),
+        ),
+        Some("ToNextUseOfStyle") => last_of(
+            story_paragraphs
+                .enumerate()
+                .take_while(|(i, (_, other))| *i == 0 || other.style.as_deref() != limit)
+                .map(|(_, paragraph)| paragraph)
+                .collect(),
+        ),
+        // Paragraphs of table cells count as the paragraph the table is in
+        Some("ForNextNParagraphs") => last_of(
+            story_paragraphs
+                .take_while(|(_, other)| other.index < paragraph.index + count)
+                .collect(),
+        ),
+        Some("ForNextNPages") => (first + count.max(1) - 1).min(pages.len() - 1),
+        Some("ToEndOfSection") => {
+            let section = page_numbers
+                .get(&pages[first])
+                .and_then(|page_number| page_number.section.as_ref());
+            (first..pages.len())
+                .take_while(|page| {
+                    page_numbers
+                        .get(&pages[*page])
+                        .and_then(|page_number| page_number.section.as_ref())
+                        == section
+                })
+                .last()
+                .unwrap_or(first)
+        }
+        Some("ToEndOfDocument") => pages.len() - 1,
+        _ => first,
+    }
+}
+
+/// Adds the entry of a topic at `level`, followed by its subtopics
+fn index_entries(
+    topic: &Topic,
+    level: u32,
+    topic_pages: &HashMap<&String, Vec<PageRange>>,
+    index_options: &IndexOptions,
+    text: &mut Vec<ParagraphStyleRange>,
+) {
+    let separator = |separator: &Option<String>, default: &str| {
+        CharacterStyleRange::new(
+            None,
+            separator_contents(separator.as_deref().unwrap_or(default)),
+        )
+    };
+    let mut entry = vec![CharacterStyleRange::new(
+        None,
+        vec![StoryContent::Content(
+            topic.name().clone().unwrap_or_default(),
+        )],
+    )];
+    if let Some(pages) = topic_pages
+        .get(topic.id())
+        .filter(|pages| !pages.is_empty())
+    {
+        entry.push(separator(index_options.following_topic_separator(), "  "));
+        for (i, range) in pages.iter().enumerate() {
+            if i > 0 {
+                entry.push(separator(
+                    index_options.between_page_numbers_separator(),
+                    ", ",
+                ));
+            }
+            let mut contents = vec![StoryContent::Content(range.first.clone())];
+            if let Some(last) = &range.last {
+                let range_separator = index_options.page_range_separator();
+                contents.extend(separator_contents(
+                    range_separator.as_deref().unwrap_or("^="),
+                ));
+                contents.push(StoryContent::Content(last.clone()));
+            }
+            entry.push(CharacterStyleRange::new(
+                index_options.page_number_style().clone(),
+                contents,
+            ));
+        }
+    }
+    if let Some(entry_end) = index_options
+        .entry_end_separator()
+        .as_ref()
+        .filter(|entry_end| !entry_end.is_empty())
+    {
+        entry.push(CharacterStyleRange::new(
+            None,
+            separator_contents(entry_end),
+        ));
+    }
+    text.push(ParagraphStyleRange::new(
+        index_options.level_style(level).cloned(),
+        entry,
+    ));
+    for subtopic in sorted_topics(topic.topics().as_ref(), topic_pages) {
+        index_entries(subtopic, level + 1, topic_pages, index_options, text);
+    }
+}
+
+/// Topics referred to from the text, themselves or through their subtopics, in
+/// the order they go in the index. Topics not starting with a letter go first.
+fn sorted_topics<'t>(
+    topics: Option<&'t Vec<Topic>>,
+    topic_pages: &HashMap<&String, Vec<PageRange>>,
+) -> Vec<&'t Topic> {
+    let mut topics: Vec<&Topic> = topics
+        .into_iter()
+        .flatten()
+        .filter(|topic| is_referenced(topic, topic_pages))
+        .collect();
+    topics.sort_by_key(|topic| {
+        let key = sort_key(topic).to_lowercase();
+        let starts_with_letter = key.chars().next().map_or(false, char::is_alphabetic);
+        (starts_with_letter, key)
+    });
+    topics
+}
+
+fn sort_key(topic: &Topic) -> String {
+    topic
+        .sort_order()
+        .clone()
+        .filter(|sort_order| !sort_order.is_empty())
+        .or(topic.name().clone())
+        .unwrap_or_default()
+}
+
+fn is_referenced(topic: &Topic, topic_pages: &HashMap<&String, Vec<PageRange>>) -> bool {
+    topic_pages.contains_key(topic.id())
+        || topic
+            .topics()
+            .iter()
+            .flatten()
+            .any(|subtopic| is_referenced(subtopic, topic_pages))
+}
+
+/// Ends each paragraph but the last with a break, as they are in stories
+fn end_paragraphs(mut paragraphs: Vec<ParagraphStyleRange>) -> Vec<ParagraphStyleRange> {
+    let count = paragraphs.len();
+    for (i, p_style) in paragraphs.iter_mut().enumerate() {
+        p_style.with_end_break(i + 1 < count);
+    }
+    paragraphs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use quick_xml::de::from_str;
+
+    /// Style and text of each paragraph, with the text of special characters
+    fn paragraph_texts(text: &[ParagraphStyleRange]) -> Vec<(Option<String>, String)> {
+        text.iter()
+            .map(|p_style| {
+                let text = p_style
+                    .character_style_ranges()
+                    .iter()
+                    .flatten()
+                    .flat_map(|c_style| c_style.contents().iter().flatten())
+                    .filter_map(|content| match content {
+                        StoryContent::Content(text) => Some(text.as_str()),
+                        StoryContent::RightIndentTab => Some("^y"),
+                        StoryContent::EmSpace => Some("^m"),
+                        _ => None,
+                    })
+                    .collect();
+                (p_style.applied_paragraph_style().clone(), text)
+            })
+            .collect()
+    }
+
+    fn style(id: &str) -> Option<String> {
+        Some(id.to_string())
+    }
+
+    fn paragraph(text: &str, style: &str) -> Paragraph {
+        let mut paragraph = Paragraph::default();
+        paragraph.text = text.to_string();
+        paragraph.style = Some(style.to_string());
+        paragraph
+    }
+
+    fn page_numbers(pages: &[(&str, &str)]) -> HashMap<String, PageNumber> {
+        pages
+            .iter()
+            .map(|(page_id, number)| {
+                let page_number = PageNumber {
+                    number: number.to_string(),
+                    section_marker: String::new(),
+                    label: None,
+                    section: None,
+                };
+                (page_id.to_string(), page_number)
+            })
+            .collect()
+    }
+
+    fn toc_style(position: &str) -> TocStyle {
+        from_str(&format!(
+            r#"<TOCStyle Self="TOCStyle/Contents" Title="Contents" TitleStyle="ParagraphStyle/TOC Title">
+                <TOCStyleEntry Name="Chapter" FormatStyle="ParagraphStyle/TOC 1" PageNumberPosition="{0}" Separator="^y" SeparatorStyle="CharacterStyle/Leader"/>
+                <TOCStyleEntry Name="Section" FormatStyle="ParagraphStyle/TOC 2" PageNumberPosition="{0}" Separator="^m"/>
+            </TOCStyle>"#,
+            position
+        ))
+        .unwrap()
+    }
+
+    fn document() -> Vec<(Option<String>, Paragraph)> {
+        vec![
+            (
+                style("p1"),
+                paragraph("Beginnings ", "ParagraphStyle/Chapter"),
+            ),
+            (style("p1"), paragraph("Body text", "ParagraphStyle/Body")),
+            (
+                style("p2"),
+                paragraph("First steps", "ParagraphStyle/Section"),
+            ),
+            (style("p2"), paragraph("  ", "ParagraphStyle/Chapter")),
+            (style("p3"), paragraph("Endings", "ParagraphStyle/Chapter")),
+        ]
+    }
+
+    fn topic(xml: &str) -> Topic {
+        from_str(xml).unwrap()
+    }
+
+    fn index_options(xml_attributes: &str) -> IndexOptions {
+        from_str(&format!(
+            r#"<IndexOptions Level1Style="ParagraphStyle/Register 1" Level2Style="ParagraphStyle/Register 2" SectionHeadingStyle="ParagraphStyle/Register Head" PageNumberStyle="CharacterStyle/Page" {}/>"#,
+            xml_attributes
+        ))
+        .unwrap()
+    }
+
+    fn range(first: &str, last: Option<&str>) -> PageRange {
+        PageRange {
+            first: first.to_string(),
+            last: last.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn tables_of_contents_list_the_paragraphs_in_their_styles_in_order() {
+        let text = table_of_contents(
+            &IdPkgStyles::default(),
+            &toc_style("AfterEntry"),
+            &document(),
+            &page_numbers(&[("p1", "1"), ("p2", "2"), ("p3", "iii")]),
+        );
+        assert_eq!(
+            paragraph_texts(&text),
+            vec![
+                (style("ParagraphStyle/TOC Title"), "Contents".to_string()),
+                (style("ParagraphStyle/TOC 1"), "Beginnings^y1".to_string()),
+                (style("ParagraphStyle/TOC 2"), "First steps^m2".to_string()),
+                (style("ParagraphStyle/TOC 1"), "Endings^yiii".to_string()),
+            ]
+        );
+        // Separators and page numbers are in the styles of their entry
+        let entry = text[1].character_style_ranges().as_ref().unwrap();
+        assert_eq!(
+            entry[1].applied_character_style(),
+            &style("CharacterStyle/Leader")
+        );
+    }
+
+    #[test]
+    fn table_of_contents_page_numbers_can_go_before_the_entry_or_be_left_out() {
+        let page_numbers = page_numbers(&[("p1", "1"), ("p2", "2"), ("p3", "3")]);
+        let styles = IdPkgStyles::default();
+        let before = table_of_contents(
+            &styles,
+            &toc_style("BeforeEntry"),
+            &document(),
+            &page_numbers,
+        );
+        assert_eq!(paragraph_texts(&before)[1].1, "1^yBeginnings");
+        let left_out = table_of_contents(
+            &styles,
+            &toc_style("NoPageNumber"),
+            &document(),
+            &page_numbers,
+        );
+        assert_eq!(paragraph_texts(&left_out)[1].1, "Beginnings");
+    }
+
+    #[test]
+    fn paragraphs_end_with_a_break_but_the_last() {
+        let text = table_of_contents(
+            &IdPkgStyles::default(),
+            &toc_style("AfterEntry"),
+            &document(),
+            &page_numbers(&[]),
+        );
+        let ends_with_break = |p_style: &ParagraphStyleRange| {
+            let c_styles = p_style.character_style_ranges().as_ref().unwrap();
+            c_styles.last().unwrap().contents().as_ref().unwrap().last() == Some(&StoryContent::Br)
+        };
+        assert!(text[..text.len() - 1].iter().all(ends_with_break));
+        assert!(!ends_with_break(text.last().unwrap()));
+    }
+
+    #[test]
+    fn separators_turn_metacharacters_into_special_characters() {
+        assert_eq!(
+            separator_contents("^t. ^y^="),
+            vec![
+                StoryContent::Content("\t".to_string()),
+                StoryContent::Content(". ".to_string()),
+                StoryContent::RightIndentTab,
+                StoryContent::Content("\u{2013}".to_string()),
+            ]
+        );
+        assert_eq!(
+            separator_contents("^q"),
+            vec![StoryContent::Content("^q".to_string())]
+        );
+    }
+
+    #[test]
+    fn index_paragraphs_are_known_by_the_styles_of_the_index_options() {
+        let options = index_options("");
+        let paragraph = |style: &str| ParagraphStyleRange::new(Some(style.to_string()), vec![]);
+        assert!(is_index_paragraph(
+            &paragraph("ParagraphStyle/Register 2"),
+            &options
+        ));
+        assert!(is_index_paragraph(
+            &paragraph("ParagraphStyle/Register Head"),
+            &options
+        ));
+        assert!(!is_index_paragraph(
+            &paragraph("ParagraphStyle/Index Level 1"),
+            &options
+        ));
+
+        // Entries in no paragraph style cannot be told apart from other text
+        let unstyled: IndexOptions =
+            from_str(r#"<IndexOptions Level1Style="ParagraphStyle/$ID/[No paragraph style]"/>"#)
+                .unwrap();
+        assert!(!is_index_paragraph(
+            &paragraph("ParagraphStyle/$ID/[No paragraph style]"),
+            &unstyled
+        ));
+    }
+
+    #[test]
+    fn indexes_are_sorted_under_section_heads_in_the_styles_of_the_options() {
+        let topics = vec![
+            topic(r#"<Topic Self="t1" Name="Kerning"><Topic Self="t11" Name="pairs"/></Topic>"#),
+            topic(r#"<Topic Self="t2" Name="Bidi" SortOrder="bidirectional"/>"#),
+            topic(r#"<Topic Self="t3" Name="1-up"/>"#),
+            topic(r#"<Topic Self="t4" Name="Unused"/>"#),
+            topic(r#"<Topic Self="t5" Name="Baseline"/>"#),
+        ];
+        let ids: Vec<String> = ["t11", "t2", "t3", "t5"]
+            .iter()
+            .map(|id| id.to_string())
+            .collect();
+        let mut topic_pages = HashMap::new();
+        topic_pages.insert(&ids[0], vec![range("4", None), range("7", Some("9"))]);
+        topic_pages.insert(&ids[1], vec![range("2", None)]);
+        topic_pages.insert(&ids[2], vec![range("1", None)]);
+        topic_pages.insert(&ids[3], vec![]);
+
+        let title = vec![ParagraphStyleRange::new(
+            style("ParagraphStyle/Register Title"),
+            vec![CharacterStyleRange::new(
+                None,
+                vec![StoryContent::Content("Index".to_string())],
+            )],
+        )];
+        let options = index_options(
+            r#"FollowingTopicSeparator=", " BetweenPageNumbersSeparator="; " PageRangeSeparator="^_" EntryEndSeparator=".""#,
+        );
+        let text = index_text(title, Some(&topics), &topic_pages, &options);
+        assert_eq!(
+            paragraph_texts(&text),
+            vec![
+                (style("ParagraphStyle/Register Title"), "Index".to_string()),
+                (style("ParagraphStyle/Register 1"), "1-up, 1.".to_string()),
+                (style("ParagraphStyle/Register Head"), "B".to_string()),
+                (style("ParagraphStyle/Register 1"), "Baseline.".to_string()),
+                (style("ParagraphStyle/Register 1"), "Bidi, 2.".to_string()),
+                (style("ParagraphStyle/Register Head"), "K".to_string()),
+                (style("ParagraphStyle/Register 1"), "Kerning.".to_string()),
+                (
+                    style("ParagraphStyle/Register 2"),
+                    "pairs, 4; 7\u{2014}9.".to_string()
+                ),
+            ]
+        );
+        // Page numbers are in the page number style of the options
+        let pairs = text[7].character_style_ranges().as_ref().unwrap();
+        assert_eq!(
+            pairs[2].applied_character_style(),
+            &style("CharacterStyle/Page")
+        );
+    }
+
+    #[test]
+    fn indexes_separate_topics_from_their_pages_like_indesign_by_default() {
+        let topics = vec![topic(r#"<Topic Self="t1" Name="Tracking"/>"#)];
+        let id = "t1".to_string();
+        let mut topic_pages = HashMap::new();
+        topic_pages.insert(&id, vec![range("3", None), range("5", Some("6"))]);
+        let options = IndexOptions::default();
+        let text = index_text(vec![], Some(&topics), &topic_pages, &options);
+        assert_eq!(
+            paragraph_texts(&text),
+            vec![
+                (None, "T".to_string()),
+                (None, "Tracking  3, 5\u{2013}6".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn indexes_can_leave_out_section_heads() {
+        let topics = vec![topic(r#"<Topic Self="t1" Name="Tracking"/>"#)];
+        let id = "t1".to_string();
+        let mut topic_pages = HashMap::new();
+        topic_pages.insert(&id, vec![range("3", None)]);
+        let options = index_options(r#"IncludeSectionHeadings="false""#);
+        let text = index_text(vec![], Some(&topics), &topic_pages, &options);
+        assert_eq!(
+            paragraph_texts(&text),
+            vec![(
+                style("ParagraphStyle/Register 1"),
+                "Tracking  3".to_string()
+            )]
+        );
+    }
+}
@@ -0,0 +1,403 @@
+use crate::pdf_printer::color_manager::{Color, Gradient, GradientKind, Spot};
+use crate::pdf_printer::image_manager::ImageLibrary;
+use crate::pdf_printer::shading::{ColorStop, Shading, ShadingColorSpace, ShadingGeometry};
+use crate::pdf_printer::transforms::Transform;
+use libharu_sys::*;
+
+/// Where a gradient goes on the item it paints, in the coordinates of the item.
+/// Linear gradients run `length` from `start` at `angle` degrees counterclockwise
+/// from the x axis, while radial gradients run from `start` out to `length` away.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GradientPlacement {
+    pub start: (f64, f64),
+    pub length: f64,
+    pub angle: f64,
+}
+
+impl GradientPlacement {
+    /// The placement from the gradient start, length and angle of an item, or else placed across
+    /// `bounds` like InDesign does by default, where linear gradients run through the center
+    /// from edge to edge and radial gradients from the center out to the corners.
+    /// InDesign writes a length of zero or less for gradients it places by default.
+    pub fn from_attributes(
+        start: Option<&[f64]>,
+        length: Option<f64>,
+        angle: Option<f64>,
+        kind: GradientKind,
+        (left, top, right, bottom): (f64, f64, f64, f64),
+    ) -> GradientPlacement {
+        let angle = angle.unwrap_or(0_f64);
+        match (start, length) {
+            (Some(&[x, y]), Some(length)) if length > 0_f64 => {
+                return GradientPlacement {
+                    start: (x, y),
+                    length,
+                    angle,
+                };
+            }
+            _ => {}
+        }
+
+        let center = ((left + right) / 2_f64, (top + bottom) / 2_f64);
+        let (width, height) = ((right - left).abs(), (bottom - top).abs());
+        match kind {
+            GradientKind::Linear => {
+                let (sin, cos) = angle.to_radians().sin_cos();
+                let length = width * cos.abs() + height * sin.abs();
+                GradientPlacement {
+                    start: (
+                        center.0 - length / 2_f64 * cos,
+                        center.1 + length / 2_f64 * sin,
+                    ),
+                    length,
+                    angle,
+                }
+            }
+            GradientKind::Radial => GradientPlacement {
+                start: center,
+                length: width.hypot(height) / 2_f64,
+                angle,
+            },
+        }
+    }
+
+    fn geometry(&self, kind: GradientKind) -> ShadingGeometry {
+        let start = (self.start.0 as f32, self.start.1 as f32);
+        match kind {
+            // Item coordinates run down, while the angle goes up from the x axis
+            GradientKind::Linear => {
+                let angle = self.angle.to_radians();
+                ShadingGeometry::Linear {
+                    start,
+                    end: (
+                        (self.start.0 + self.length * angle.cos()) as f32,
+                        (self.start.1 - self.length * angle.sin()) as f32,
+                    ),
+                }
+            }
+            GradientKind::Radial => ShadingGeometry::Radial {
+                start,
+                start_radius: 0_f32,
+                end: start,
+                end_radius: self.length as f32,
+            },
+        }
+    }
+}
+
+/// Paints the gradient over `bounds` of an item placed on the page by `transform`.
+/// The page must already be clipped to what the gradient fills.
+pub unsafe fn paint_gradient(
+    current_page: HPDF_Page,
+    image_lib: &ImageLibrary,
+    gradient: &Gradient,
+    placement: &GradientPlacement,
+    transform: &Transform,
+    (left, top, right, bottom): (f64, f64, f64, f64),
+) -> Result<(), String> {
+    let all_rgb = gradient
+        .stops()
+        .iter()
+        .all(|stop| matches!(stop.color(), Color::Rgb(_)));
+    let color_space = match (spot_ink(gradient), all_rgb) {
+        (Some(spot), _) => ShadingColorSpace::Separation(spot),
+        (None, true) => ShadingColorSpace::Rgb,
+        (None, false) => ShadingColorSpace::Cmyk,
+    };
+    let shading = Shading {
+        geometry: placement.geometry(*gradient.kind()),
+        stops: color_stops(gradient, &color_space),
+        color_space,
+        bbox: [
+            left.min(right) as f32,
+            top.min(bottom) as f32,
+            left.max(right) as f32,
+            top.max(bottom) as f32,
+        ],
+    };
+    let form = image_lib.shading(shading, current_page)?;
+
+    // The shading is painted in the coordinates of the item
+    let origin = transform.apply_to_point(&0_f64, &0_f64);
+    let x_axis = transform.apply_to_point(&1_f64, &0_f64);
+    let y_axis = transform.apply_to_point(&0_f64, &1_f64);
+    HPDF_Page_GSave(current_page);
+    HPDF_Page_Concat(
+        current_page,
+        (x_axis[0] - origin[0]) as f32,
+        (x_axis[1] - origin[1]) as f32,
+        (y_axis[0] - origin[0]) as f32,
+        (y_axis[1] - origin[1]) as f32,
+        origin[0] as f32,
+        origin[1] as f32,
+    );
+    HPDF_Page_DrawImage(current_page, form, 0_f32, 0_f32, 1_f32, 1_f32);
+    HPDF_Page_GRestore(current_page);
+    Ok(())
+}
+
+/// The stops of the gradient in the color space. A stop whose midpoint is not halfway
+/// to the stop before it gets a stop in between at its midpoint, mixing their colors evenly.
+fn color_stops(gradient: &Gradient, color_space: &ShadingColorSpace) -> Vec<ColorStop> {
+    let components = |color: &Color| -> Vec<f32> {
+        match (color_space, color) {
+            (ShadingColorSpace::Rgb, Color::Rgb(rgb)) => vec![*rgb.r(), *rgb.g(), *rgb.b()],
+            (ShadingColorSpace::Separation(_), Color::Spot(spot)) => vec![*spot.tint()],
+            (ShadingColorSpace::Separation(_), _) => vec![0_f32],
+            _ => {
+                let cmyk = color.to_cmyk();
+                cmyk.map(|cmyk| vec![*cmyk.c(), *cmyk.m(), *cmyk.y(), *cmyk.k()])
+                    .unwrap_or(vec![0_f32; 4])
+            }
+        }
+    };
+    let mut stops: Vec<ColorStop> = vec![];
+    for stop in gradient.stops() {
+        let color = components(stop.color());
+        if let Some(previous) = stops.last() {
+            let midpoint = *stop.midpoint();
+            if (midpoint - 0.5_f32).abs() > f32::EPSILON {
+                let offset = previous.offset + (stop.location() - previous.offset) * midpoint;
+                let mixed = previous
+                    .color
+                    .iter()
+                    .zip(&color)
+                    .map(|(a, b)| (a + b) / 2_f32)
+                    .collect();
+                stops.push(ColorStop {
+                    offset,
+                    color: mixed,
+                });
+            }
+        }
+        stops.push(ColorStop {
+            offset: *stop.location(),
+            color,
+        });
+    }
+    stops
+}
+
+/// The spot color of a gradient between tints of one spot ink and paper, which is shaded
+/// in the Separation color space of the ink so the ink is printed on its own plate
+fn spot_ink(gradient: &Gradient) -> Option<Spot> {
+    let mut ink: Option<&Spot> = None;
+    for stop in gradient.stops() {
+        match stop.color() {
+            Color::Spot(spot) if ink.is_none_or(|ink| ink.ink() == spot.ink()) => ink = Some(spot),
+            Color::Cmyk(cmyk)
+                if [cmyk.c(), cmyk.m(), cmyk.y(), cmyk.k()]
+                    .iter()
+                    .all(|&&value| value == 0_f32) => {}
+            _ => return None,
+        }
+    }
+    ink.cloned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::idml_parser::IDMLResources;
+    use quick_xml::de::from_str;
+
+    const BOUNDS: (f64, f64, f64, f64) = (0_f64, 0_f64, 100_f64, 50_f64);
+
+    /// A gradient swatch with the stops, each a color id with its location and midpoint
+    fn gradient(kind: &str, stops: &[(&str, f64, f64)]) -> Gradient {
+        let stops: String = stops
+            .iter()
+            .enumerate()
+            .map(|(index, (color, location, midpoint))| {
+                format!(
+                    r#"<GradientStop Self="Gradient/G{}" StopColor="{}" Location="{}" Midpoint="{}"/>"#,
+                    index, color, location, midpoint
+                )
+            })
+            .collect();
+        let resources = IDMLResources::with_graphic(
+            from_str(&format!(
+                r#"<idPkg:Graphic>
+                    <Color Self="Color/Black" Model="Process" Space="CMYK" ColorValue="0 0 0 100" Name="Black"/>
+                    <Color Self="Color/Paper" Model="Process" Space="CMYK" ColorValue="0 0 0 0" Name="Paper"/>
+                    <Color Self="Color/Red" Model="Process" Space="RGB" ColorValue="255 0 0" Name="Red"/>
+                    <Color Self="Color/Blue" Model="Process" Space="RGB" ColorValue="0 0 255" Name="Blue"/>
+                    <Color Self="Color/Gold" Model="Spot" Space="CMYK" ColorValue="0 20 60 20" Name="Gold"/>
+                    <Color Self="Color/Teal" Model="Spot" Space="CMYK" ColorValue="80 0 40 0" Name="Teal"/>
+                    <Swatch Self="Swatch/None" Name="None"/>
+                    <Gradient Self="Gradient/G" Name="G" Type="{}">{}</Gradient>
+                </idPkg:Graphic>"#,
+                kind, stops
+            ))
+            .unwrap(),
+        );
+        match resources.color_from_id(&"Gradient/G".to_string()) {
+            Ok(Color::Gradient(gradient)) => gradient,
+            other => panic!("expected a gradient, got {:?}", other),
+        }
+    }
+
+    fn rounded((x, y): (f32, f32)) -> (f32, f32) {
+        (
+            (x * 1e4_f32).round() / 1e4_f32,
+            (y * 1e4_f32).round() / 1e4_f32,
+        )
+    }
+
+    #[test]
+    fn gradients_placed_on_the_item_keep_their_placement() {
+        let placement = GradientPlacement::from_attributes(
+            Some(&[10_f64, 20_f64]),
+            Some(30_f64),
+            Some(45_f64),
+            GradientKind::Linear,
+            BOUNDS,
+        );
+        assert_eq!(
+            placement,
+            GradientPlacement {
+                start: (10_f64, 20_f64),
+                length: 30_f64,
+                angle: 45_f64,
+            }
+        );
+    }
+
+    #[test]
+    fn gradients_placed_by_default_run_across_the_bounds() {
+        // A length of zero is written for gradients placed by default
+        let placement = GradientPlacement::from_attributes(
+            Some(&[10_f64, 20_f64]),
+            Some(0_f64),
+            None,
+            GradientKind::Linear,
+            BOUNDS,
+        );
+        assert_eq!(
+            (placement.start, placement.length),
+            ((0_f64, 25_f64), 100_f64)
+        );
+
+        let placement = GradientPlacement::from_attributes(
+            None,
+            None,
+            Some(90_f64),
+            GradientKind::Linear,
+            BOUNDS,
+        );
+        assert!((placement.length - 50_f64).abs() < 1e-9);
+        match placement.geometry(GradientKind::Linear) {
+            ShadingGeometry::Linear { start, end } => {
+                assert_eq!(rounded(start), (50_f32, 50_f32));
+                assert_eq!(rounded(end), (50_f32, 0_f32));
+            }
+            other => panic!("expected a linear shading, got {:?}", other),
+        }
+
+        let placement =
+            GradientPlacement::from_attributes(None, None, None, GradientKind::Radial, BOUNDS);
+        assert_eq!(placement.start, (50_f64, 25_f64));
+        assert_eq!(
+            placement.geometry(GradientKind::Radial),
+            ShadingGeometry::Radial {
+                start: (50_f32, 25_f32),
+                start_radius: 0_f32,
+                end: (50_f32, 25_f32),
+                end_radius: (100_f64.hypot(50_f64) / 2_f64) as f32,
+            }
+        );
+    }
+
+    #[test]
+    fn midpoints_off_center_add_a_stop_mixing_the_colors() {
+        let gradient = gradient(
+            "Linear",
+            &[
+                ("Color/Red", 0_f64, 50_f64),
+                ("Color/Blue", 100_f64, 25_f64),
+            ],
+        );
+        assert_eq!(
+            color_stops(&gradient, &ShadingColorSpace::Rgb),
+            vec![
+                ColorStop {
+                    offset: 0_f32,
+                    color: vec![1_f32, 0_f32, 0_f32],
+                },
+                ColorStop {
+                    offset: 0.25_f32,
+                    color: vec![0.5_f32, 0_f32, 0.5_f32],
+                },
+                ColorStop {
+                    offset: 1_f32,
+                    color: vec![0_f32, 0_f32, 1_f32],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn stops_are_converted_to_cmyk_unless_all_are_rgb() {
+        let gradient = gradient(
+            "Linear",
+            &[
+                ("Color/Red", 0_f64, 50_f64),
+                ("Color/Black", 100_f64, 50_f64),
+            ],
+        );
+        let stops = color_stops(&gradient, &ShadingColorSpace::Cmyk);
+        let colors: Vec<Vec<f32>> = stops.into_iter().map(|stop| stop.color).collect();
+        assert_eq!(
+            colors,
+            vec![
+                vec![0_f32, 1_f32, 1_f32, 0_f32],
+                vec![0_f32, 0_f32, 0_f32, 1_f32]
+            ]
+        );
+    }
+
+    #[test]
+    fn gradients_between_one_spot_ink_and_paper_are_shaded_in_its_separation() {
+        let spot = spot_ink(&gradient(
+            "Radial",
+            &[
+                ("Color/Paper", 0_f64, 50_f64),
+                ("Color/Gold", 100_f64, 50_f64),
+            ],
+        ));
+        assert_eq!(
+            spot.map(|spot| spot.ink().clone()),
+            Some("Gold".to_string())
+        );
+
+        let gold_to_paper = gradient(
+            "Linear",
+            &[
+                ("Color/Gold", 0_f64, 50_f64),
+                ("Color/Paper", 100_f64, 50_f64),
+            ],
+        );
+        let spot = spot_ink(&gold_to_paper).unwrap();
+        let stops = color_stops(&gold_to_paper, &ShadingColorSpace::Separation(spot));
+        let tints: Vec<Vec<f32>> = stops.into_iter().map(|stop| stop.color).collect();
+        assert_eq!(tints, vec![vec![1_f32], vec![0_f32]]);
+
+        // Other inks and process colors are shaded in a process color space
+        assert!(spot_ink(&gradient(
+            "Linear",
+            &[
+                ("Color/Gold", 0_f64, 50_f64),
+                ("Color/Teal", 100_f64, 50_f64)
+            ],
+        ))
+        .is_none());
+        assert!(spot_ink(&gradient(
+            "Linear",
+            &[
+                ("Color/Gold", 0_f64, 50_f64),
+                ("Color/Black", 100_f64, 50_f64)
+            ],
+        ))
+        .is_none());
+    }
+}
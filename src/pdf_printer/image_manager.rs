@@ -0,0 +1,879 @@
+use crate::idml_parser::spread_parser::{
+    ClippingPathType, Eps, Image, Link, Pdf, PdfCrop, Properties, Svg,
+};
+use crate::pdf_printer::clipping::{self, PhotoshopPath};
+use crate::pdf_printer::eps::{self, EpsPreview};
+use crate::pdf_printer::font_manager::FontLibrary;
+use crate::pdf_printer::image_settings::{ImageCompression, ImageExportSettings, Resampling};
+use crate::pdf_printer::link_resolver::LinkResolver;
+use crate::pdf_printer::pdf_import::{self, PlacedPage, Placeholder};
+use crate::pdf_printer::psd::{self, PsdColorMode};
+use crate::pdf_printer::resample;
+use crate::pdf_printer::shading::Shading;
+use crate::pdf_printer::svg::{self, SvgFonts};
+use image::codecs::jpeg::JpegEncoder;
+use image::ExtendedColorType;
+use libharu_sys::*;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fs;
+use std::io::Cursor;
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::sync::Arc;
+
+// Not exposed by libharu-sys
+extern "C" {
+    fn HPDF_Page_CreateXObjectAsWhiteRect(
+        pdf: HPDF_Doc,
+        page: HPDF_Page,
+        rect: HPDF_Rect,
+    ) -> HPDF_XObject;
+}
+
+/// Images, PDF pages and SVG graphics loaded into the PDF, so a graphic placed more than once is only embedded once
+#[derive(Debug)]
+pub struct ImageLibrary {
+    pdf_doc: HPDF_Doc,
+    link_resolver: LinkResolver,
+    /// Whether graphics whose linked file is missing are drawn as a box with a cross
+    missing_link_placeholders: bool,
+    images: RefCell<HashMap<String, Result<HPDF_Image, String>>>,
+    /// Data of the placed files, for images that are placed at several sizes and PDF files
+    graphic_files: RefCell<HashMap<String, Rc<Vec<u8>>>>,
+    /// Decoded placed images, when they are downsampled, recompressed or masked by a clipping path
+    decoded_images: RefCell<HashMap<String, Rc<Samples>>>,
+    image_settings: ImageExportSettings,
+    /// Placeholder forms of the placed PDF pages, keyed by file, page number and crop
+    pdf_pages: RefCell<HashMap<(String, u32, PdfCrop, bool), HPDF_XObject>>,
+    /// Sizes of the placed PDF pages, for fitting them to their frames
+    pdf_page_sizes: RefCell<HashMap<(String, u32, PdfCrop), (f64, f64)>>,
+    placeholders: RefCell<Vec<Placeholder>>,
+    svgs: RefCell<HashMap<String, Result<Rc<usvg::Tree>, String>>>,
+    /// Fonts to lay out SVG text in, which are only read if an SVG graphic has text
+    svg_fonts: RefCell<Option<Rc<SvgFonts>>>,
+    /// Graphic states setting the opacity, keyed by fill and stroke opacity in thousandths
+    alpha_states: RefCell<HashMap<(u16, u16), HPDF_ExtGState>>,
+}
+
+impl ImageLibrary {
+    pub fn new(pdf_doc: HPDF_Doc) -> ImageLibrary {
+        ImageLibrary {
+            pdf_doc,
+            link_resolver: LinkResolver::new(),
+            missing_link_placeholders: false,
+            images: RefCell::default(),
+            graphic_files: RefCell::default(),
+            decoded_images: RefCell::default(),
+            image_settings: ImageExportSettings::default(),
+            pdf_pages: RefCell::default(),
+            pdf_page_sizes: RefCell::default(),
+            placeholders: RefCell::default(),
+            svgs: RefCell::default(),
+            svg_fonts: RefCell::default(),
+            alpha_states: RefCell::default(),
+        }
+    }
+
+    pub fn with_search_dirs(mut self, search_dirs: Vec<PathBuf>) -> ImageLibrary {
+        self.link_resolver = self.link_resolver.with_search_dirs(search_dirs);
+        self
+    }
+
+    pub fn with_missing_link_placeholders(
+        mut self,
+        missing_link_placeholders: bool,
+    ) -> ImageLibrary {
+        self.missing_link_placeholders = missing_link_placeholders;
+        self
+    }
+
+    pub fn with_image_settings(mut self, image_settings: ImageExportSettings) -> ImageLibrary {
+        self.image_settings = image_settings;
+        self
+    }
+
+    /// Whether a placeholder should be drawn for the graphic, as its linked file is missing
+    pub fn needs_placeholder(&self, link: &Option<Link>) -> bool {
+        self.missing_link_placeholders
+            && link
+                .as_ref()
+                .map(|link| self.link_resolver.is_missing(link))
+                .unwrap_or(false)
+    }
+
+    pub fn link_resolver(&self) -> &LinkResolver {
+        &self.link_resolver
+    }
+
+    /// The image in the PDF, loaded from the embedded data or the linked file the first time it is used.
+    /// Images are downsampled for their `placed_size` on the page in points, if known,
+    /// and compressed as the image settings say, so an image placed at several sizes
+    /// may be in the PDF once for each size.
+    pub fn pdf_image(
+        &self,
+        image: &Image,
+        placed_size: Option<(f64, f64)>,
+    ) -> Result<HPDF_Image, String> {
+        let key = graphic_key(image.id(), image.link());
+        // Clipping to an alpha channel or the edges of the image masks its samples
+        let masked = image
+            .clipping_path_settings()
+            .as_ref()
+            .and_then(|settings| *settings.clipping_type())
+            .map_or(false, |clipping_type| {
+                matches!(
+                    clipping_type,
+                    ClippingPathType::AlphaChannel | ClippingPathType::DetectEdges
+                )
+            });
+        if self.image_settings.keeps_images() && !masked {
+            if let Some(pdf_image) = self.images.borrow().get(&key) {
+                return pdf_image.clone();
+            }
+            let pdf_image = self
+                .graphic_data(image.id(), image.properties(), image.link())
+                .and_then(|data| load_image(self.pdf_doc, &data));
+            self.images.borrow_mut().insert(key, pdf_image.clone());
+            return pdf_image;
+        }
+
+        let data = self.graphic_file(image.id(), image.properties(), image.link(), &key)?;
+        let samples = self.decoded_image(&key, || decode_image(&data))?;
+        let (samples, key) = match masked {
+            true => {
+                // The mask is made for the place of the image, as it is inset by a distance on the page
+                let points_per_pixel = placed_size
+                    .map(|(placed_width, _)| placed_width / samples.width as f64)
+                    .unwrap_or(1_f64);
+                let inset = image
+                    .clipping_path_settings()
+                    .as_ref()
+                    .and_then(|settings| *settings.inset_frame())
+                    .map_or(0, |inset| (inset / points_per_pixel).round() as i32);
+                let clipped_key = format!("{} clipped as {} inset {}", key, image.id(), inset);
+                let samples = self.decoded_image(&clipped_key, || {
+                    let mask = clipping_mask(image, &data, &samples, inset)?;
+                    Ok(Samples {
+                        pixels: samples.pixels.clone(),
+                        width: samples.width,
+                        height: samples.height,
+                        colors: samples.colors,
+                        alpha: Some(mask),
+                    })
+                })?;
+                (samples, clipped_key)
+            }
+            false => (samples, key),
+        };
+
+        let mono = samples.is_mono();
+        let settings = match (mono, samples.colors) {
+            (true, _) => self.image_settings.mono,
+            (false, SampleColors::Gray) => self.image_settings.gray,
+            _ => self.image_settings.color,
+        };
+        let size = (samples.width, samples.height);
+        let new_size = settings
+            .downsampling
+            .zip(placed_size)
+            .and_then(|(downsampling, placed_size)| downsampling.target_size(size, placed_size));
+        let (width, height) = new_size.unwrap_or(size);
+        let sized_key = format!("{} {}x{}", key, width, height);
+        if let Some(pdf_image) = self.images.borrow().get(&sized_key) {
+            return pdf_image.clone();
+        }
+
+        let pdf_image = match (new_size, settings.compression) {
+            (None, ImageCompression::Automatic) if !mono && is_jpeg(&data) => {
+                load_image(self.pdf_doc, &data).and_then(|pdf_image| match &samples.alpha {
+                    Some(alpha) => add_smask(self.pdf_doc, pdf_image, alpha, width, height),
+                    None => Ok(pdf_image),
+                })
+            }
+            (None, compression) => load_compressed(self.pdf_doc, &samples, compression, mono),
+            (Some(new_size), compression) => {
+                let resampling = settings.downsampling.unwrap().resampling;
+                load_compressed(
+                    self.pdf_doc,
+                    &samples.resample(new_size, resampling),
+                    compression,
+                    mono,
+                )
+            }
+        };
+        self.images
+            .borrow_mut()
+            .insert(sized_key, pdf_image.clone());
+        pdf_image
+    }
+
+    /// The decoded image cached under the key, decoded the first time it is used
+    fn decoded_image(
+        &self,
+        key: &str,
+        decode: impl FnOnce() -> Result<Samples, String>,
+    ) -> Result<Rc<Samples>, String> {
+        if let Some(samples) = self.decoded_images.borrow().get(key) {
+            return Ok(samples.clone());
+        }
+        let samples = Rc::new(decode()?);
+        self.decoded_images
+            .borrow_mut()
+            .insert(key.to_string(), samples.clone());
+        Ok(samples)
+    }
+
+    /// The path saved in the image that its clipping path settings clip it to, which is
+    /// the path they name, or else the path Photoshop clips the image to, or else the first path
+    pub fn photoshop_path(&self, image: &Image) -> Result<Option<PhotoshopPath>, String> {
+        let key = graphic_key(image.id(), image.link());
+        let data = self.graphic_file(image.id(), image.properties(), image.link(), &key)?;
+        let resources = clipping::read_photoshop_resources(&data)?;
+        let name = image
+            .clipping_path_settings()
+            .as_ref()
+            .and_then(|settings| settings.applied_path_name().clone());
+        Ok(resources.path(name.as_deref()).cloned())
+    }
+
+    /// Width and height of the image in pixels, which is read without decoding the image
+    pub fn image_pixel_size(&self, image: &Image) -> Result<(u32, u32), String> {
+        let key = graphic_key(image.id(), image.link());
+        let data = self.graphic_file(image.id(), image.properties(), image.link(), &key)?;
+        if data.starts_with(b"8BPS") && data.len() >= 22 {
+            let number = |at: usize| {
+                u32::from_be_bytes([data[at], data[at + 1], data[at + 2], data[at + 3]])
+            };
+            return Ok((number(18), number(14)));
+        }
+        image::ImageReader::new(Cursor::new(data.as_slice()))
+            .with_guessed_format()
+            .map_err(|err| err.to_string())?
+            .into_dimensions()
+            .map_err(|err| err.to_string())
+    }
+
+    /// Width and height of the placed PDF page in points, once cropped
+    pub fn pdf_page_size(&self, pdf: &Pdf) -> Result<(f64, f64), String> {
+        let file_key = graphic_key(pdf.id(), pdf.link());
+        let attribute = pdf.pdf_attribute().clone().unwrap_or_default();
+        let page_number = attribute.page_number().unwrap_or(1);
+        let crop = attribute.pdf_crop().unwrap_or(PdfCrop::CropPdf);
+        let size_key = (file_key.clone(), page_number, crop);
+        if let Some(&size) = self.pdf_page_sizes.borrow().get(&size_key) {
+            return Ok(size);
+        }
+        let data = self.graphic_file(pdf.id(), pdf.properties(), pdf.link(), &file_key)?;
+        let (width, height) = pdf_import::page_size(&data, page_number, crop)?;
+        let size = (width as f64, height as f64);
+        self.pdf_page_sizes.borrow_mut().insert(size_key, size);
+        Ok(size)
+    }
+
+    /// A form standing in for the placed PDF page in the PDF, until it is saved
+    /// and `replace_placeholders` replaces it with the page
+    pub fn pdf_page(&self, pdf: &Pdf, current_page: HPDF_Page) -> Result<HPDF_XObject, String> {
+        let file_key = graphic_key(pdf.id(), pdf.link());
+        let data = self.graphic_file(pdf.id(), pdf.properties(), pdf.link(), &file_key)?;
+
+        let attribute = pdf.pdf_attribute().clone().unwrap_or_default();
+        let placed_page = PlacedPage {
+            data,
+            page_number: attribute.page_number().unwrap_or(1),
+            crop: attribute.pdf_crop().unwrap_or(PdfCrop::CropPdf),
+            transparent_background: attribute.transparent_background().unwrap_or(true),
+        };
+        let page_key = (
+            file_key,
+            placed_page.page_number,
+            placed_page.crop,
+            placed_page.transparent_background,
+        );
+        if let Some(&placeholder) = self.pdf_pages.borrow().get(&page_key) {
+            return Ok(placeholder);
+        }
+
+        let placeholder = self.placeholder(Placeholder::Page(placed_page), current_page)?;
+        self.pdf_pages.borrow_mut().insert(page_key, placeholder);
+        Ok(placeholder)
+    }
+
+    /// A form standing in for the shading in the PDF, until it is saved and
+    /// `replace_placeholders` replaces it with a form painting the shading
+    pub fn shading(
+        &self,
+        shading: Shading,
+        current_page: HPDF_Page,
+    ) -> Result<HPDF_XObject, String> {
+        self.placeholder(Placeholder::Shading(shading), current_page)
+    }
+
+    fn placeholder(
+        &self,
+        placeholder: Placeholder,
+        current_page: HPDF_Page,
+    ) -> Result<HPDF_XObject, String> {
+        let mut placeholders = self.placeholders.borrow_mut();
+        let [left, bottom, right, top] = pdf_import::placeholder_bbox(placeholders.len());
+        let rect = HPDF_Rect {
+            left,
+            bottom,
+            right,
+            top,
+        };
+        let form = unsafe { HPDF_Page_CreateXObjectAsWhiteRect(self.pdf_doc, current_page, rect) };
+        if form.is_null() {
+            return Err("Failed to make a placeholder form".to_string());
+        }
+        placeholders.push(placeholder);
+        Ok(form)
+    }
+
+    /// Replaces the placeholders of placed PDF pages and shadings in the saved PDF at `path`
+    pub fn replace_placeholders(&self, path: &str) -> Result<(), String> {
+        let placeholders = self.placeholders.borrow();
+        match placeholders.is_empty() {
+            true => Ok(()),
+            false => pdf_import::replace_placeholders(path, &placeholders),
+        }
+    }
+
+    /// The preview of the EPS graphic as an image, as PostScript cannot be drawn in a PDF
+    pub fn eps_preview(&self, eps: &Eps) -> Result<HPDF_Image, String> {
+        let key = graphic_key(eps.id(), eps.link());
+        if let Some(pdf_image) = self.images.borrow().get(&key) {
+            return pdf_image.clone();
+        }
+
+        let pdf_image = self
+            .graphic_data(eps.id(), eps.properties(), eps.link())
+            .and_then(|data| match eps::read_preview(&data)? {
+                EpsPreview::Tiff(tiff) => load_image(self.pdf_doc, tiff),
+                EpsPreview::Gray {
+                    width,
+                    height,
+                    pixels,
+                } => load_samples(
+                    self.pdf_doc,
+                    &pixels,
+                    width,
+                    height,
+                    HPDF_ColorSpace::HPDF_CS_DEVICE_GRAY,
+                ),
+            });
+        // PostScript is not interpreted, so the graphic is listed in the missing links report
+        if pdf_image.is_ok() {
+            self.link_resolver.add_previewed(eps.id(), eps.link());
+        }
+        self.images.borrow_mut().insert(key, pdf_image.clone());
+        pdf_image
+    }
+
+    /// Width and height of the bounding box of the EPS graphic in points
+    pub fn eps_size(&self, eps: &Eps) -> Result<(f64, f64), String> {
+        let key = graphic_key(eps.id(), eps.link());
+        let data = self.graphic_file(eps.id(), eps.properties(), eps.link(), &key)?;
+        eps::bounding_box_size(&data)
+    }
+
+    /// The SVG graphic parsed, with the fonts its text is laid out in if it has text
+    pub fn svg_tree(
+        &self,
+        svg: &Svg,
+        font_lib: &FontLibrary,
+    ) -> Result<(Rc<usvg::Tree>, Option<Rc<SvgFonts>>), String> {
+        let key = graphic_key(svg.id(), svg.link());
+        let tree = self.svgs.borrow().get(&key).cloned();
+        let tree = match tree {
+            Some(tree) => tree,
+            None => {
+                let tree = self
+                    .graphic_data(svg.id(), svg.properties(), svg.link())
+                    .and_then(|data| {
+                        let fonts = match svg::has_text(&data) {
+                            true => Some(self.svg_fonts(font_lib)),
+                            false => None,
+                        };
+                        svg::parse_svg(&data, fonts.as_deref())
+                    })
+                    .map(Rc::new);
+                self.svgs.borrow_mut().insert(key, tree.clone());
+                tree
+            }
+        };
+        let tree = tree?;
+        let fonts = match tree.has_text_nodes() {
+            true => Some(self.svg_fonts(font_lib)),
+            false => None,
+        };
+        Ok((tree, fonts))
+    }
+
+    fn svg_fonts(&self, font_lib: &FontLibrary) -> Rc<SvgFonts> {
+        self.svg_fonts
+            .borrow_mut()
+            .get_or_insert_with(|| Rc::new(SvgFonts::new(font_lib)))
+            .clone()
+    }
+
+    /// An image embedded in an SVG graphic, which is loaded once for as long as the graphic is
+    pub fn svg_image(&self, data: &Arc<Vec<u8>>) -> Result<HPDF_Image, String> {
+        let key = format!("{:p}", Arc::as_ptr(data));
+        if let Some(pdf_image) = self.images.borrow().get(&key) {
+            return pdf_image.clone();
+        }
+        let pdf_image = load_image(self.pdf_doc, data);
+        self.images.borrow_mut().insert(key, pdf_image.clone());
+        pdf_image
+    }
+
+    /// A graphic state setting the opacity of fills and strokes, from 0 to 1
+    pub fn alpha_state(&self, fill_alpha: f32, stroke_alpha: f32) -> HPDF_ExtGState {
+        let key = (
+            (fill_alpha.clamp(0_f32, 1_f32) * 1000_f32).round() as u16,
+            (stroke_alpha.clamp(0_f32, 1_f32) * 1000_f32).round() as u16,
+        );
+        *self
+            .alpha_states
+            .borrow_mut()
+            .entry(key)
+            .or_insert_with(|| unsafe {
+                let state = HPDF_CreateExtGState(self.pdf_doc);
+                HPDF_ExtGState_SetAlphaFill(state, key.0 as f32 / 1000_f32);
+                HPDF_ExtGState_SetAlphaStroke(state, key.1 as f32 / 1000_f32);
+                state
+            })
+    }
+
+    /// Data of a placed graphic, which is read once for all the places it is used
+    fn graphic_file(
+        &self,
+        id: &str,
+        properties: &Option<Properties>,
+        link: &Option<Link>,
+        key: &str,
+    ) -> Result<Rc<Vec<u8>>, String> {
+        if let Some(data) = self.graphic_files.borrow().get(key) {
+            return Ok(data.clone());
+        }
+        let data = Rc::new(self.graphic_data(id, properties, link)?);
+        self.graphic_files
+            .borrow_mut()
+            .insert(key.to_string(), data.clone());
+        Ok(data)
+    }
+
+    /// Data of a placed graphic, either embedded as base64 or from the linked file
+    fn graphic_data(
+        &self,
+        id: &str,
+        properties: &Option<Properties>,
+        link: &Option<Link>,
+    ) -> Result<Vec<u8>, String> {
+        let contents = properties
+            .as_ref()
+            .and_then(|properties| properties.contents().as_ref());
+        if let Some(contents) = contents {
+            let contents: String = contents.split_whitespace().collect();
+            return base64::decode(&contents)
+                .map_err(|err| format!("Failed to decode embedded graphic '{}': {}", id, err));
+        }
+
+        let link = link
+            .as_ref()
+            .ok_or(format!("Graphic '{}' is neither embedded nor linked", id))?;
+        let path = self.link_resolver.resolve(id, link).ok_or(format!(
+            "No file found for link {:?}",
+            link.link_resource_uri()
+        ))?;
+        fs::read(&path).map_err(|err| format!("Failed to read {:?}: {}", path, err))
+    }
+}
+
+/// Embedded graphics are only used once, while linked files can be placed several times
+fn graphic_key(id: &str, link: &Option<Link>) -> String {
+    link.as_ref()
+        .filter(|link| !is_embedded(link))
+        .and_then(|link| link.link_resource_uri().clone())
+        .unwrap_or(id.to_string())
+}
+
+fn is_embedded(link: &Link) -> bool {
+    link.stored_state().as_deref() == Some("Embedded")
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum SampleColors {
+    Gray,
+    Rgb,
+    Cmyk,
+}
+
+impl SampleColors {
+    fn pdf_color_space(self) -> HPDF_ColorSpace {
+        match self {
+            SampleColors::Gray => HPDF_ColorSpace::HPDF_CS_DEVICE_GRAY,
+            SampleColors::Rgb => HPDF_ColorSpace::HPDF_CS_DEVICE_RGB,
+            SampleColors::Cmyk => HPDF_ColorSpace::HPDF_CS_DEVICE_CMYK,
+        }
+    }
+}
+
+/// Pixels of a decoded image, with any transparency as separate alpha samples
+#[derive(Debug)]
+struct Samples {
+    pixels: Vec<u8>,
+    width: u32,
+    height: u32,
+    colors: SampleColors,
+    alpha: Option<Vec<u8>>,
+}
+
+impl Samples {
+    fn channels(&self) -> usize {
+        match self.colors {
+            SampleColors::Gray => 1,
+            SampleColors::Rgb => 3,
+            SampleColors::Cmyk => 4,
+        }
+    }
+
+    /// Opaque grayscale images with only black and white pixels
+    fn is_mono(&self) -> bool {
+        self.colors == SampleColors::Gray
+            && self.alpha.is_none()
+            && self.pixels.iter().all(|&pixel| pixel == 0 || pixel == 255)
+    }
+
+    /// How light each pixel is, from 0 for black to 255 for white
+    fn lightness(&self) -> Vec<u8> {
+        match self.colors {
+            SampleColors::Gray => self.pixels.clone(),
+            SampleColors::Rgb => self
+                .pixels
+                .chunks_exact(3)
+                .map(|rgb| {
+                    (0.3_f32 * rgb[0] as f32 + 0.59_f32 * rgb[1] as f32 + 0.11_f32 * rgb[2] as f32)
+                        .round() as u8
+                })
+                .collect(),
+            // CMYK samples are amounts of ink
+            SampleColors::Cmyk => self
+                .pixels
+                .chunks_exact(4)
+                .map(|cmyk| {
+                    let ink = 0.3_f32 * cmyk[0] as f32
+                        + 0.59_f32 * cmyk[1] as f32
+                        + 0.11_f32 * cmyk[2] as f32
+                        + cmyk[3] as f32;
+                    255_u8 - ink.round().min(255_f32) as u8
+                })
+                .collect(),
+        }
+    }
+
+    fn resample(&self, (width, height): (u32, u32), resampling: Resampling) -> Samples {
+        let size = (self.width, self.height);
+        Samples {
+            pixels: resample::resample(
+                &self.pixels,
+                self.channels(),
+                size,
+                (width, height),
+                resampling,
+            ),
+            width,
+            height,
+            colors: self.colors,
+            alpha: self
+                .alpha
+                .as_ref()
+                .map(|alpha| resample::resample(alpha, 1, size, (width, height), resampling)),
+        }
+    }
+}
+
+fn is_jpeg(data: &[u8]) -> bool {
+    data.starts_with(&[0xFF, 0xD8])
+}
+
+/// Loads image data into the PDF. JPEG images are embedded as they are, while
+/// other formats are decoded and embedded as samples, with any transparency as a soft mask.
+fn load_image(pdf_doc: HPDF_Doc, data: &[u8]) -> Result<HPDF_Image, String> {
+    if is_jpeg(data) {
+        let pdf_image =
+            unsafe { HPDF_LoadJpegImageFromMem(pdf_doc, data.as_ptr(), data.len() as u32) };
+        return match pdf_image.is_null() {
+            true => Err("Failed to load JPEG image".to_string()),
+            false => Ok(pdf_image),
+        };
+    }
+    load_compressed(
+        pdf_doc,
+        &decode_image(data)?,
+        ImageCompression::Flate,
+        false,
+    )
+}
+
+fn decode_image(data: &[u8]) -> Result<Samples, String> {
+    if data.starts_with(b"8BPS") {
+        let psd = psd::read_psd(data)?;
+        let colors = match psd.color_mode {
+            PsdColorMode::Gray => SampleColors::Gray,
+            PsdColorMode::Rgb => SampleColors::Rgb,
+            PsdColorMode::Cmyk => SampleColors::Cmyk,
+        };
+        return Ok(Samples {
+            pixels: psd.pixels,
+            width: psd.width,
+            height: psd.height,
+            colors,
+            alpha: None,
+        });
+    }
+
+    let decoded = image::load_from_memory(data).map_err(|err| err.to_string())?;
+    let color = decoded.color();
+    let (pixels, colors) = match color.channel_count() {
+        1 | 2 => (decoded.to_luma8().into_raw(), SampleColors::Gray),
+        _ => (decoded.to_rgb8().into_raw(), SampleColors::Rgb),
+    };
+    let alpha = match color.has_alpha() {
+        true => Some(decoded.to_rgba8().pixels().map(|pixel| pixel[3]).collect()),
+        false => None,
+    };
+    Ok(Samples {
+        pixels,
+        width: decoded.width(),
+        height: decoded.height(),
+        colors,
+        alpha,
+    })
+}
+
+/// Loads the samples into the PDF, compressed as JPEG if asked and possible
+/// and else with Flate, and monochrome images with one bit per pixel and CCITT compression
+fn load_compressed(
+    pdf_doc: HPDF_Doc,
+    samples: &Samples,
+    compression: ImageCompression,
+    mono: bool,
+) -> Result<HPDF_Image, String> {
+    let (width, height) = (samples.width, samples.height);
+    let color_type = match samples.colors {
+        SampleColors::Gray => Some(ExtendedColorType::L8),
+        SampleColors::Rgb => Some(ExtendedColorType::Rgb8),
+        SampleColors::Cmyk => None,
+    };
+    let pdf_image = match (compression, color_type) {
+        _ if mono => {
+            let row_length = (width as usize + 7) / 8;
+            let mut bits = vec![0_u8; row_length * height as usize];
+            for (index, &pixel) in samples.pixels.iter().enumerate() {
+                let (row, column) = (index / width as usize, index % width as usize);
+                // libharu writes the bits with CCITT compression, where 1 is black
+                if pixel < 128 {
+                    bits[row * row_length + column / 8] |= 0x80 >> (column % 8);
+                }
+            }
+            let pdf_image = unsafe {
+                HPDF_LoadRawImageFromMem(
+                    pdf_doc,
+                    bits.as_ptr(),
+                    width,
+                    height,
+                    HPDF_ColorSpace::HPDF_CS_DEVICE_GRAY,
+                    1,
+                )
+            };
+            match pdf_image.is_null() {
+                true => Err(format!("Failed to load {}x{} image", width, height)),
+                false => Ok(pdf_image),
+            }
+        }
+        (ImageCompression::Jpeg { quality }, Some(color_type)) => {
+            let mut jpeg = vec![];
+            JpegEncoder::new_with_quality(&mut jpeg, quality.clamp(1, 100))
+                .encode(&samples.pixels, width, height, color_type)
+                .map_err(|err| format!("Failed to compress image as JPEG: {}", err))?;
+            let pdf_image =
+                unsafe { HPDF_LoadJpegImageFromMem(pdf_doc, jpeg.as_ptr(), jpeg.len() as u32) };
+            match pdf_image.is_null() {
+                true => Err("Failed to load JPEG image".to_string()),
+                false => Ok(pdf_image),
+            }
+        }
+        _ => load_samples(
+            pdf_doc,
+            &samples.pixels,
+            width,
+            height,
+            samples.colors.pdf_color_space(),
+        ),
+    }?;
+    match &samples.alpha {
+        Some(alpha) => add_smask(pdf_doc, pdf_image, alpha, width, height),
+        None => Ok(pdf_image),
+    }
+}
+
+/// Makes the image transparent where the alpha samples are below 255
+fn add_smask(
+    pdf_doc: HPDF_Doc,
+    pdf_image: HPDF_Image,
+    alpha: &[u8],
+    width: u32,
+    height: u32,
+) -> Result<HPDF_Image, String> {
+    let mask = load_samples(
+        pdf_doc,
+        alpha,
+        width,
+        height,
+        HPDF_ColorSpace::HPDF_CS_DEVICE_GRAY,
+    )?;
+    unsafe {
+        HPDF_Image_AddSMask(pdf_image, mask);
+    }
+    Ok(pdf_image)
+}
+
+/// Mask clipping the image to an alpha channel saved in it or to its detected edges,
+/// as its clipping path settings say, combined with any transparency the image has.
+/// `inset` is how many pixels the edges of the mask are moved inwards.
+fn clipping_mask(
+    image: &Image,
+    data: &[u8],
+    samples: &Samples,
+    inset: i32,
+) -> Result<Vec<u8>, String> {
+    let settings = image.clipping_path_settings().clone().unwrap_or_default();
+    let size = (samples.width, samples.height);
+    let pixel_count = samples.width as usize * samples.height as usize;
+    let mask = match settings.clipping_type() {
+        Some(ClippingPathType::AlphaChannel) => {
+            let channels = match data {
+                _ if data.starts_with(b"8BPS") => psd::read_psd(data)?.extra_channels,
+                _ if data.starts_with(b"II*\0") || data.starts_with(b"MM\0*") => {
+                    clipping::read_tiff_extra_channels(data)?
+                }
+                _ => vec![],
+            };
+            // The channel is picked by its name, or else by its index
+            let names = clipping::read_photoshop_resources(data)?.alpha_channel_names;
+            let index = settings
+                .applied_path_name()
+                .as_ref()
+                .and_then(|name| names.iter().position(|channel_name| channel_name == name))
+                .or_else(|| {
+                    settings
+                        .index()
+                        .filter(|&index| index >= 0)
+                        .map(|index| index as usize)
+                })
+                .unwrap_or(0);
+            // Other images only have their transparency as an alpha channel
+            match channels.get(index).or_else(|| channels.first()) {
+                Some(channel) if channel.len() == pixel_count => channel.clone(),
+                _ => samples.alpha.clone().ok_or(format!(
+                    "Image '{}' has no alpha channel to clip to",
+                    image.id()
+                ))?,
+            }
+        }
+        _ => clipping::edge_mask(
+            &samples.lightness(),
+            size,
+            settings.threshold().unwrap_or(25_f64).clamp(0_f64, 255_f64) as u8,
+            settings.include_inside_edges().unwrap_or(false),
+        ),
+    };
+
+    let mask = clipping::inset_mask(&mask, size, inset);
+    let mask = match settings.invert_path().unwrap_or(false) {
+        true => mask.iter().map(|&value| 255 - value).collect(),
+        false => mask,
+    };
+    Ok(match &samples.alpha {
+        Some(alpha) => mask
+            .iter()
+            .zip(alpha)
+            .map(|(&value, &alpha)| value.min(alpha))
+            .collect(),
+        None => mask,
+    })
+}
+
+fn load_samples(
+    pdf_doc: HPDF_Doc,
+    samples: &[u8],
+    width: u32,
+    height: u32,
+    color_space: HPDF_ColorSpace,
+) -> Result<HPDF_Image, String> {
+    let pdf_image = unsafe {
+        HPDF_LoadRawImageFromMem(pdf_doc, samples.as_ptr(), width, height, color_space, 8)
+    };
+    match pdf_image.is_null() {
+        true => Err(format!("Failed to load {}x{} image", width, height)),
+        false => Ok(pdf_image),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pdf_printer::PreviewedLink;
+    use quick_xml::de::from_str;
+
+    extern "C" fn ignore_errors(_: HPDF_STATUS, _: HPDF_STATUS, _: HPDF_HANDLE) {}
+
+    #[test]
+    fn eps_graphics_drawn_from_their_preview_are_reported() {
+        let dir = std::env::temp_dir().join(format!("idml_to_pdf-eps-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("logo.eps");
+        fs::write(
+            &path,
+            "%!PS-Adobe-3.0 EPSF-3.0\n\
+             %%BoundingBox: 0 0 4 2\n\
+             %%BeginPreview: 4 2 1 2\n\
+             % A0\n\
+             % 50\n\
+             %%EndPreview\n\
+             showpage\n",
+        )
+        .unwrap();
+        let uri = format!("file:{}", path.display());
+        let eps = from_str::<Eps>(&format!(
+            r#"<EPS Self="e1"><Link Self="l1" LinkResourceURI="{}"/></EPS>"#,
+            uri
+        ))
+        .unwrap();
+
+        unsafe {
+            let pdf_doc = HPDF_New(ignore_errors, std::ptr::null_mut());
+            let image_lib = ImageLibrary::new(pdf_doc);
+            assert!(image_lib.eps_preview(&eps).is_ok());
+            assert_eq!(
+                image_lib.link_resolver().previewed_links(),
+                vec![PreviewedLink {
+                    uri: Some(uri.clone()),
+                    graphic_id: "e1".to_string(),
+                }]
+            );
+            assert!(image_lib.link_resolver().missing_links().is_empty());
+            assert_eq!(
+                image_lib.link_resolver().missing_links_report(),
+                Some(format!(
+                    "Drawn from their low resolution preview (1):\n  {} (placed as 'e1')\n",
+                    uri
+                ))
+            );
+            HPDF_Free(pdf_doc);
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,410 @@
+/// How the pixels of a downsampled image are made from the pixels of the original
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Resampling {
+    /// Each pixel is the average of the pixels it covers
+    Average,
+    /// Each pixel is interpolated from the pixels around it with a bicubic filter,
+    /// which is slower but keeps edges sharper
+    Bicubic,
+}
+
+/// Lowering the resolution of images placed at a higher resolution than needed
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Downsampling {
+    pub resampling: Resampling,
+    /// Resolution the images are downsampled to, in pixels per inch as placed on the page
+    pub target_ppi: f64,
+    /// Only images placed at a higher resolution than this are downsampled
+    pub threshold_ppi: f64,
+}
+
+impl Downsampling {
+    /// The size in pixels an image of `pixels` placed at `placed_size` in points is downsampled to,
+    /// or None if it is not above the threshold resolution
+    pub fn target_size(
+        &self,
+        (width, height): (u32, u32),
+        (placed_width, placed_height): (f64, f64),
+    ) -> Option<(u32, u32)> {
+        if placed_width <= 0_f64 || placed_height <= 0_f64 {
+            return None;
+        }
+        let effective_ppi = f64::max(
+            width as f64 / (placed_width / 72_f64),
+            height as f64 / (placed_height / 72_f64),
+        );
+        if effective_ppi <= self.threshold_ppi {
+            return None;
+        }
+        let target = |placed: f64, pixels: u32| {
+            ((placed / 72_f64 * self.target_ppi).round() as u32).clamp(1, pixels)
+        };
+        let size = (target(placed_width, width), target(placed_height, height));
+        match size == (width, height) {
+            true => None,
+            false => Some(size),
+        }
+    }
+}
+
+/// How the samples of images are compressed in the PDF
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ImageCompression {
+    /// JPEG images are kept as they are, while other images are compressed with Flate
+    Automatic,
+    /// Images are compressed as JPEG with a quality from 0 to 100, except CMYK images which cannot be
+    Jpeg { quality: u8 },
+    /// Images are compressed without loss with Flate
+    Flate,
+}
+
+/// Settings for one kind of image
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ImageSettings {
+    /// None leaves the images at their resolution
+    pub downsampling: Option<Downsampling>,
+    pub compression: ImageCompression,
+}
+
+impl Default for ImageSettings {
+    fn default() -> Self {
+        ImageSettings {
+            downsampling: None,
+            compression: ImageCompression::Automatic,
+        }
+    }
+}
+
+/// How placed images are written into the PDF, with settings for color, grayscale
+/// and monochrome images like the compression settings of an InDesign PDF export.
+/// Monochrome images are always written with one bit per pixel and CCITT Group 4 compression.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct ImageExportSettings {
+    pub color: ImageSettings,
+    pub gray: ImageSettings,
+    pub mono: ImageSettings,
+}
+
+impl ImageExportSettings {
+    /// Like the "Smallest File Size" preset, for PDFs to read on screen
+    pub fn smallest_file_size() -> ImageExportSettings {
+        let color = ImageSettings {
+            downsampling: Some(Downsampling {
+                resampling: Resampling::Bicubic,
+                target_ppi: 100_f64,
+                threshold_ppi: 150_f64,
+            }),
+            compression: ImageCompression::Jpeg { quality: 30 },
+        };
+        ImageExportSettings {
+            color,
+            gray: color,
+            mono: ImageSettings {
+                downsampling: Some(Downsampling {
+                    resampling: Resampling::Bicubic,
+                    target_ppi: 300_f64,
+                    threshold_ppi: 450_f64,
+                }),
+                compression: ImageCompression::Automatic,
+            },
+        }
+    }
+
+    /// Like the "High Quality Print" preset, for PDFs to print on desktop printers
+    pub fn high_quality_print() -> ImageExportSettings {
+        let color = ImageSettings {
+            downsampling: Some(Downsampling {
+                resampling: Resampling::Bicubic,
+                target_ppi: 300_f64,
+                threshold_ppi: 450_f64,
+            }),
+            compression: ImageCompression::Jpeg { quality: 90 },
+        };
+        ImageExportSettings {
+            color,
+            gray: color,
+            mono: ImageSettings {
+                downsampling: Some(Downsampling {
+                    resampling: Resampling::Bicubic,
+                    target_ppi: 1200_f64,
+                    threshold_ppi: 1800_f64,
+                }),
+                compression: ImageCompression::Automatic,
+            },
+        }
+    }
+
+    /// The settings of a preset by its name, `smallest-file-size` or `high-quality-print`
+    pub fn from_preset(name: &str) -> Result<ImageExportSettings, String> {
+        match name {
+            "smallest-file-size" => Ok(ImageExportSettings::smallest_file_size()),
+            "high-quality-print" => Ok(ImageExportSettings::high_quality_print()),
+            _ => Err(format!("Unknown image export preset '{}'", name)),
+        }
+    }
+
+    /// Names of the settings `with_setting` takes, in the order they should be applied
+    pub const SETTING_NAMES: [&'static str; 12] = [
+        "ppi",
+        "threshold",
+        "resampling",
+        "compression",
+        "quality",
+        "gray-ppi",
+        "gray-threshold",
+        "gray-resampling",
+        "gray-compression",
+        "gray-quality",
+        "mono-ppi",
+        "mono-threshold",
+    ];
+
+    /// Changes one setting by its name and value, like one option of the compression settings of
+    /// an InDesign PDF export. Settings without a prefix apply to color images:
+    /// - `ppi`: downsample to the resolution, above one and a half times it
+    /// - `threshold`: only downsample images above the resolution
+    /// - `resampling`: `average`, `bicubic`, or `none` to not downsample
+    /// - `compression`: `automatic`, `jpeg` or `flate`
+    /// - `quality`: compress as JPEG with the quality from 0 to 100
+    /// - `gray-` followed by any of the above: the same for grayscale images
+    /// - `mono-ppi` and `mono-threshold`: like `ppi` and `threshold` for monochrome images
+    pub fn with_setting(mut self, name: &str, value: &str) -> Result<ImageExportSettings, String> {
+        let number = |value: &str| -> Result<f64, String> {
+            match value.parse::<f64>() {
+                Ok(number) if number > 0_f64 => Ok(number),
+                _ => Err(format!(
+                    "Image {} must be a positive number, not '{}'",
+                    name, value
+                )),
+            }
+        };
+        let (settings, setting) = self.settings_mut(name)?;
+        match setting {
+            "ppi" => {
+                let target_ppi = number(value)?;
+                settings.downsampling = Some(Downsampling {
+                    resampling: settings
+                        .downsampling
+                        .map(|downsampling| downsampling.resampling)
+                        .unwrap_or(Resampling::Bicubic),
+                    target_ppi,
+                    threshold_ppi: target_ppi * 1.5_f64,
+                });
+            }
+            "threshold" => {
+                let threshold_ppi = number(value)?;
+                match &mut settings.downsampling {
+                    Some(downsampling) if threshold_ppi >= downsampling.target_ppi => {
+                        downsampling.threshold_ppi = threshold_ppi;
+                    }
+                    Some(downsampling) => {
+                        return Err(format!(
+                            "Image {} of {} ppi is below the {} ppi images are downsampled to",
+                            name, threshold_ppi, downsampling.target_ppi
+                        ))
+                    }
+                    None => {
+                        return Err(format!(
+                            "Image {} needs images to be downsampled, with a ppi or a preset",
+                            name
+                        ))
+                    }
+                }
+            }
+            "resampling" => {
+                let resampling = match value {
+                    "average" => Some(Resampling::Average),
+                    "bicubic" => Some(Resampling::Bicubic),
+                    "none" => None,
+                    _ => return Err(format!("Unknown image resampling '{}'", value)),
+                };
+                match (resampling, &mut settings.downsampling) {
+                    (Some(resampling), Some(downsampling)) => downsampling.resampling = resampling,
+                    (Some(_), None) => {
+                        return Err(format!(
+                            "Image {} needs images to be downsampled, with a ppi or a preset",
+                            name
+                        ))
+                    }
+                    (None, _) => settings.downsampling = None,
+                }
+            }
+            "compression" => {
+                settings.compression = match (value, settings.compression) {
+                    ("automatic", _) => ImageCompression::Automatic,
+                    ("jpeg", ImageCompression::Jpeg { quality }) => {
+                        ImageCompression::Jpeg { quality }
+                    }
+                    ("jpeg", _) => ImageCompression::Jpeg { quality: 90 },
+                    ("flate", _) => ImageCompression::Flate,
+                    _ => return Err(format!("Unknown image compression '{}'", value)),
+                };
+            }
+            "quality" => {
+                let quality = match value.parse::<u8>() {
+                    Ok(quality) if quality <= 100 => quality,
+                    _ => {
+                        return Err(format!(
+                            "Image quality must be a number from 0 to 100, not '{}'",
+                            value
+                        ))
+                    }
+                };
+                settings.compression = ImageCompression::Jpeg { quality };
+            }
+            _ => return Err(format!("Unknown image setting '{}'", name)),
+        }
+        Ok(self)
+    }
+
+    /// The settings of the one kind of image a setting applies to, by the prefix of its name,
+    /// with the name of the setting without the prefix. Monochrome images are always compressed
+    /// the same way, so only their resolution can be set.
+    fn settings_mut<'b>(&mut self, name: &'b str) -> Result<(&mut ImageSettings, &'b str), String> {
+        if let Some(setting) = name.strip_prefix("gray-") {
+            return Ok((&mut self.gray, setting));
+        }
+        match name.strip_prefix("mono-") {
+            Some(setting @ ("ppi" | "threshold")) => Ok((&mut self.mono, setting)),
+            Some(_) => Err(format!("Unknown image setting '{}'", name)),
+            None => Ok((&mut self.color, name)),
+        }
+    }
+
+    /// Whether images are written as they are, without downsampling or recompressing them
+    pub fn keeps_images(&self) -> bool {
+        *self == ImageExportSettings::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn presets_are_found_by_name() {
+        assert_eq!(
+            ImageExportSettings::from_preset("smallest-file-size"),
+            Ok(ImageExportSettings::smallest_file_size())
+        );
+        assert_eq!(
+            ImageExportSettings::from_preset("high-quality-print"),
+            Ok(ImageExportSettings::high_quality_print())
+        );
+        assert_eq!(
+            ImageExportSettings::from_preset("press-quality"),
+            Err("Unknown image export preset 'press-quality'".to_string())
+        );
+    }
+
+    #[test]
+    fn presets_downsample_and_recompress_images() {
+        let settings = ImageExportSettings::from_preset("smallest-file-size").unwrap();
+        assert!(!settings.keeps_images());
+        assert_eq!(
+            settings.color.compression,
+            ImageCompression::Jpeg { quality: 30 }
+        );
+        assert_eq!(settings.color, settings.gray);
+        assert_eq!(
+            settings.mono.downsampling.map(|d| d.target_ppi),
+            Some(300_f64)
+        );
+        assert!(ImageExportSettings::default().keeps_images());
+    }
+
+    #[test]
+    fn settings_change_a_preset() {
+        let settings = ImageExportSettings::from_preset("high-quality-print")
+            .and_then(|settings| settings.with_setting("gray-ppi", "200"))
+            .and_then(|settings| settings.with_setting("quality", "60"))
+            .unwrap();
+        let downsampling = settings.gray.downsampling.unwrap();
+        assert_eq!(downsampling.target_ppi, 200_f64);
+        assert_eq!(downsampling.threshold_ppi, 300_f64);
+        assert_eq!(downsampling.resampling, Resampling::Bicubic);
+        assert_eq!(
+            settings.color.compression,
+            ImageCompression::Jpeg { quality: 60 }
+        );
+        assert_eq!(
+            settings.mono,
+            ImageExportSettings::high_quality_print().mono
+        );
+    }
+
+    #[test]
+    fn settings_apply_to_one_kind_of_image() {
+        let preset = ImageExportSettings::high_quality_print();
+        let settings = preset
+            .with_setting("ppi", "150")
+            .and_then(|settings| settings.with_setting("compression", "flate"))
+            .unwrap();
+        assert_eq!(
+            settings.color.downsampling.map(|d| d.target_ppi),
+            Some(150_f64)
+        );
+        assert_eq!(settings.color.compression, ImageCompression::Flate);
+        assert_eq!(settings.gray, preset.gray);
+        assert_eq!(settings.mono, preset.mono);
+
+        let settings = preset
+            .with_setting("gray-resampling", "none")
+            .and_then(|settings| settings.with_setting("gray-quality", "40"))
+            .unwrap();
+        assert_eq!(settings.gray.downsampling, None);
+        assert_eq!(
+            settings.gray.compression,
+            ImageCompression::Jpeg { quality: 40 }
+        );
+        assert_eq!(settings.color, preset.color);
+
+        let settings = preset.with_setting("mono-ppi", "600").unwrap();
+        assert_eq!(
+            settings.mono.downsampling.map(|d| d.target_ppi),
+            Some(600_f64)
+        );
+        assert_eq!(settings.color, preset.color);
+        assert_eq!(settings.gray, preset.gray);
+    }
+
+    #[test]
+    fn monochrome_images_only_take_a_resolution() {
+        let settings = ImageExportSettings::high_quality_print();
+        assert_eq!(
+            settings.with_setting("mono-quality", "50"),
+            Err("Unknown image setting 'mono-quality'".to_string())
+        );
+        assert!(settings.with_setting("gray-sharpness", "1").is_err());
+    }
+
+    #[test]
+    fn thresholds_below_the_target_resolution_are_refused() {
+        let settings = ImageExportSettings::high_quality_print();
+        assert!(settings.with_setting("threshold", "200").is_err());
+        assert!(settings.with_setting("threshold", "600").is_ok());
+        assert!(ImageExportSettings::default()
+            .with_setting("threshold", "600")
+            .is_err());
+    }
+
+    #[test]
+    fn images_are_downsampled_above_the_threshold() {
+        let downsampling = Downsampling {
+            resampling: Resampling::Average,
+            target_ppi: 150_f64,
+            threshold_ppi: 225_f64,
+        };
+        // 2000 pixels across 2 inches is 1000 ppi
+        assert_eq!(
+            downsampling.target_size((2000, 1000), (144_f64, 72_f64)),
+            Some((300, 150))
+        );
+        // 400 pixels across 2 inches is 200 ppi
+        assert_eq!(
+            downsampling.target_size((400, 200), (144_f64, 72_f64)),
+            None
+        );
+        assert_eq!(downsampling.target_size((400, 200), (0_f64, 72_f64)), None);
+    }
+}
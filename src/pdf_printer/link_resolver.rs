@@ -0,0 +1,308 @@
+use crate::idml_parser::spread_parser::Link;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Link that no file was found for, with the first graphic placing it
+#[derive(Debug, Clone, PartialEq)]
+pub struct MissingLink {
+    pub uri: String,
+    pub graphic_id: String,
+}
+
+/// Graphic drawn from the low resolution preview in its file, as its own contents
+/// cannot be drawn, such as the PostScript of an EPS file
+#[derive(Debug, Clone, PartialEq)]
+pub struct PreviewedLink {
+    /// URI of the linked file, or None if the graphic is embedded
+    pub uri: Option<String>,
+    pub graphic_id: String,
+}
+
+/// Finds the files placed graphics are linked to. Documents are often made on another
+/// machine than the one they are printed on, so when a linked file is not where its URI says,
+/// it is looked for by its name in the search directories, ignoring the case of the name.
+#[derive(Debug, Default)]
+pub struct LinkResolver {
+    search_dirs: Vec<PathBuf>,
+    /// Files found for the link URIs, or None if no file was found
+    resolved: RefCell<HashMap<String, Option<PathBuf>>>,
+    missing_links: RefCell<Vec<MissingLink>>,
+    previewed_links: RefCell<Vec<PreviewedLink>>,
+}
+
+impl LinkResolver {
+    pub fn new() -> LinkResolver {
+        LinkResolver::default()
+    }
+
+    /// Directories to look for linked files in, in the order they are searched
+    pub fn with_search_dirs(mut self, search_dirs: Vec<PathBuf>) -> LinkResolver {
+        self.search_dirs = search_dirs;
+        self
+    }
+
+    /// The linked file where the link says it is, or else by its name in the directory
+    /// the link says it is in or one of the search directories
+    pub fn resolve(&self, graphic_id: &str, link: &Link) -> Option<PathBuf> {
+        let uri = link.link_resource_uri().as_ref()?;
+        if let Some(resolved) = self.resolved.borrow().get(uri) {
+            return resolved.clone();
+        }
+
+        let path = path_from_uri(uri);
+        let resolved = match path.is_file() {
+            true => Some(path),
+            false => path.file_name().and_then(|file_name| {
+                path.parent()
+                    .into_iter()
+                    .chain(self.search_dirs.iter().map(|dir| dir.as_path()))
+                    .find_map(|dir| find_in_dir(dir, Path::new(file_name)))
+            }),
+        };
+        if resolved.is_none() {
+            self.missing_links.borrow_mut().push(MissingLink {
+                uri: uri.clone(),
+                graphic_id: graphic_id.to_string(),
+            });
+        }
+        self.resolved
+            .borrow_mut()
+            .insert(uri.clone(), resolved.clone());
+        resolved
+    }
+
+    /// Whether the link has been resolved without finding a file
+    pub fn is_missing(&self, link: &Link) -> bool {
+        match link.link_resource_uri() {
+            Some(uri) => matches!(self.resolved.borrow().get(uri), Some(None)),
+            None => false,
+        }
+    }
+
+    /// The links no file has been found for, in the order they were resolved
+    pub fn missing_links(&self) -> Vec<MissingLink> {
+        self.missing_links.borrow().clone()
+    }
+
+    /// Notes that the graphic is drawn from the preview in its file instead of its contents
+    pub fn add_previewed(&self, graphic_id: &str, link: &Option<Link>) {
+        self.previewed_links.borrow_mut().push(PreviewedLink {
+            uri: link
+                .as_ref()
+                .and_then(|link| link.link_resource_uri().clone()),
+            graphic_id: graphic_id.to_string(),
+        });
+    }
+
+    /// The graphics drawn from their preview, in the order they were drawn
+    pub fn previewed_links(&self) -> Vec<PreviewedLink> {
+        self.previewed_links.borrow().clone()
+    }
+
+    /// A report listing the missing links and the graphics drawn from their preview,
+    /// or None if every linked file was found and drawn
+    pub fn missing_links_report(&self) -> Option<String> {
+        let missing_links = self.missing_links.borrow();
+        let previewed_links = self.previewed_links.borrow();
+        if missing_links.is_empty() && previewed_links.is_empty() {
+            return None;
+        }
+        let mut report = String::new();
+        if !missing_links.is_empty() {
+            report.push_str(&format!("Missing links ({}):\n", missing_links.len()));
+            for missing_link in missing_links.iter() {
+                report.push_str(&format!(
+                    "  {} (placed as '{}')\n",
+                    missing_link.uri, missing_link.graphic_id
+                ));
+            }
+            report.push_str("Searched in:\n");
+            for dir in &self.search_dirs {
+                report.push_str(&format!("  {}\n", dir.display()));
+            }
+        }
+        if !previewed_links.is_empty() {
+            report.push_str(&format!(
+                "Drawn from their low resolution preview ({}):\n",
+                previewed_links.len()
+            ));
+            for previewed_link in previewed_links.iter() {
+                report.push_str(&format!(
+                    "  {} (placed as '{}')\n",
+                    previewed_link.uri.as_deref().unwrap_or("embedded"),
+                    previewed_link.graphic_id
+                ));
+            }
+        }
+        Some(report)
+    }
+}
+
+/// The file in `dir` with the name, or else one whose name only differs in case,
+/// as links made on macOS and Windows do not care about the case of file names
+fn find_in_dir(dir: &Path, file_name: &Path) -> Option<PathBuf> {
+    let path = dir.join(file_name);
+    if path.is_file() {
+        return Some(path);
+    }
+    let file_name = file_name.to_str()?.to_lowercase();
+    fs::read_dir(dir)
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file())
+        .find(|path| {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .map(|name| name.to_lowercase() == file_name)
+                .unwrap_or(false)
+        })
+}
+
+/// Path of a file URI such as `file:/Users/designer/Links/photo%201.jpg`
+/// or `file:///C:/Links/photo.jpg`, with any escaped characters decoded
+fn path_from_uri(uri: &str) -> PathBuf {
+    let path = uri.strip_prefix("file:").unwrap_or(uri);
+    let path = match path.strip_prefix("//") {
+        Some(path) => path,
+        None => path,
+    };
+    // Windows paths start with a drive letter
+    let path = match path.get(2..3) {
+        Some(":") if path.starts_with('/') => &path[1..],
+        _ => path,
+    };
+
+    let mut bytes = vec![];
+    let mut rest = path.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        let escaped = tail
+            .get(..2)
+            .filter(|_| byte == b'%')
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(escaped) => {
+                bytes.push(escaped);
+                rest = &tail[2..];
+            }
+            None => {
+                bytes.push(byte);
+                rest = tail;
+            }
+        }
+    }
+    PathBuf::from(String::from_utf8_lossy(&bytes).replace('\\', "/"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An empty directory of its own for each test
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("idml_to_pdf-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn unix_file_uris_are_paths() {
+        assert_eq!(
+            path_from_uri("file:/Users/designer/Links/photo.jpg"),
+            PathBuf::from("/Users/designer/Links/photo.jpg")
+        );
+        assert_eq!(
+            path_from_uri("file:///Users/designer/Links/photo.jpg"),
+            PathBuf::from("/Users/designer/Links/photo.jpg")
+        );
+    }
+
+    #[test]
+    fn windows_file_uris_keep_their_drive_letter() {
+        assert_eq!(
+            path_from_uri("file:///C:/Links/photo.jpg"),
+            PathBuf::from("C:/Links/photo.jpg")
+        );
+        assert_eq!(
+            path_from_uri("file:C:\\Links\\photo.jpg"),
+            PathBuf::from("C:/Links/photo.jpg")
+        );
+    }
+
+    #[test]
+    fn escaped_characters_are_decoded() {
+        assert_eq!(
+            path_from_uri("file:/Links/photo%201%C3%A9.jpg"),
+            PathBuf::from("/Links/photo 1é.jpg")
+        );
+        // A percent sign that does not start an escape is kept
+        assert_eq!(
+            path_from_uri("file:/Links/100%.jpg"),
+            PathBuf::from("/Links/100%.jpg")
+        );
+    }
+
+    #[test]
+    fn files_are_found_ignoring_the_case_of_their_name() {
+        let dir = scratch_dir("find_in_dir");
+        fs::write(dir.join("Photo.JPG"), b"").unwrap();
+        fs::create_dir(dir.join("logo.eps")).unwrap();
+
+        assert_eq!(
+            find_in_dir(&dir, Path::new("Photo.JPG")),
+            Some(dir.join("Photo.JPG"))
+        );
+        assert_eq!(
+            find_in_dir(&dir, Path::new("photo.jpg")),
+            Some(dir.join("Photo.JPG"))
+        );
+        // Directories are not linked files
+        assert_eq!(find_in_dir(&dir, Path::new("logo.eps")), None);
+        assert_eq!(find_in_dir(&dir, Path::new("missing.jpg")), None);
+        assert_eq!(
+            find_in_dir(&dir.join("missing"), Path::new("photo.jpg")),
+            None
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn graphics_drawn_from_their_preview_are_reported() {
+        let link_resolver = LinkResolver::new();
+        assert_eq!(link_resolver.missing_links_report(), None);
+
+        let link = quick_xml::de::from_str::<Link>(
+            r#"<Link Self="l1" LinkResourceURI="file:/Links/logo.eps"/>"#,
+        )
+        .unwrap();
+        link_resolver.add_previewed("e1", &Some(link));
+        link_resolver.add_previewed("e2", &None);
+
+        assert_eq!(
+            link_resolver.previewed_links(),
+            vec![
+                PreviewedLink {
+                    uri: Some("file:/Links/logo.eps".to_string()),
+                    graphic_id: "e1".to_string(),
+                },
+                PreviewedLink {
+                    uri: None,
+                    graphic_id: "e2".to_string(),
+                },
+            ]
+        );
+        assert_eq!(
+            link_resolver.missing_links_report(),
+            Some(
+                "Drawn from their low resolution preview (2):\n  \
+                 file:/Links/logo.eps (placed as 'e1')\n  \
+                 embedded (placed as 'e2')\n"
+                    .to_string()
+            )
+        );
+    }
+}
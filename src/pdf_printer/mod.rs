@@ -1,17 +1,53 @@
+mod bidi;
+mod cid_fonts;
+mod cjk_layout;
+mod clipping;
 pub mod color_manager;
+mod condition_indicators;
+mod cross_references;
+mod eps;
 mod font_manager;
+mod generated_stories;
+mod gradients;
+mod image_manager;
+mod image_settings;
+mod link_resolver;
+mod navigation;
+mod note_numbering;
 mod page_items;
+mod page_numbering;
+mod pdf_import;
 mod pdf_utils;
+mod psd;
+mod resample;
+mod separations;
+mod shading;
+mod story_layout;
+mod svg;
+mod text_decorations;
+mod text_layout;
+mod text_variables;
 mod transforms;
 
+use crate::idml_parser::designmap_parser::PageNumberStyle;
 use crate::idml_parser::spread_parser::*;
 use crate::idml_parser::IDMLPackage;
 use font_manager::FontLibrary;
+pub use generated_stories::regenerate_stories;
+use image_manager::ImageLibrary;
+pub use image_settings::{
+    Downsampling, ImageCompression, ImageExportSettings, ImageSettings, Resampling,
+};
 use libharu_sys::*;
+pub use link_resolver::{MissingLink, PreviewedLink};
+use navigation::Navigation;
+use page_numbering::{PageLabel, PageNumber};
 use std::cell::RefCell;
+use std::collections::HashMap;
 use std::ffi::CString;
 use std::path::PathBuf;
 use std::ptr;
+pub use story_layout::StoryLayout;
 use transforms::Transform;
 
 extern "C" fn error_handler(error_no: HPDF_STATUS, detail_no: HPDF_STATUS, user_data: HPDF_HANDLE) {
@@ -27,11 +63,27 @@ macro_rules! cstring {
     };
 }
 
+/// How many masters deep the masters that masters are based on are drawn
+const MAX_MASTER_DEPTH: usize = 8;
+
 pub struct PDFPrinter<'a> {
     idml_package: &'a IDMLPackage,
     font_lib: FontLibrary<'a>,
+    image_lib: ImageLibrary,
     pdf_doc: HPDF_Doc,
     current_page: RefCell<Option<HPDF_Page>>,
+    current_page_number: RefCell<PageNumber>,
+    page_numbers: HashMap<String, PageNumber>,
+    /// Text variables of each page, keyed by page id and variable id
+    text_variables: HashMap<String, HashMap<String, String>>,
+    current_text_variables: RefCell<HashMap<String, String>>,
+    /// Numbers of the footnotes and endnotes, keyed by note id
+    note_numbers: HashMap<String, String>,
+    navigation: RefCell<Navigation>,
+    story_layout: RefCell<StoryLayout>,
+    /// Number of pages added to the PDF and the label of the last one
+    page_count: RefCell<u32>,
+    last_page_label: RefCell<Option<PageLabel>>,
 }
 
 impl<'a> PDFPrinter<'a> {
@@ -46,31 +98,125 @@ impl<'a> PDFPrinter<'a> {
             }
             let font_lib =
                 FontLibrary::new(&idml_package.resources(), pdf_doc, resource_dir).unwrap();
+            // Images are stored as samples unless they are JPEG images, so they are compressed
+            HPDF_SetCompressionMode(pdf_doc, HPDF_COMP_IMAGE);
+            let image_lib = ImageLibrary::new(pdf_doc);
             let current_page = RefCell::default();
+            let current_page_number = RefCell::default();
+            let page_numbers = page_numbering::page_numbers(idml_package);
+            let text_variables = text_variables::resolve_text_variables(
+                idml_package,
+                &page_numbers,
+                &StoryLayout::default(),
+            );
+            let note_numbers = note_numbering::note_numbers(
+                idml_package,
+                &page_numbers,
+                &StoryLayout::default(),
+            );
+            let cross_references = cross_references::cross_reference_texts(
+                idml_package,
+                &page_numbers,
+                &StoryLayout::default(),
+            );
             let printer = PDFPrinter {
                 idml_package,
                 font_lib,
+                image_lib,
                 pdf_doc,
                 current_page,
+                current_page_number,
+                page_numbers,
+                text_variables,
+                current_text_variables: RefCell::default(),
+                note_numbers,
+                navigation: RefCell::new(Navigation::new(cross_references)),
+                story_layout: RefCell::default(),
+                page_count: RefCell::default(),
+                last_page_label: RefCell::default(),
             };
             Ok(printer)
         }
     }
 
+    /// Directories to look for linked images in, when they are not where their links say
+    pub fn with_link_search_dirs(mut self, search_dirs: Vec<PathBuf>) -> PDFPrinter<'a> {
+        self.image_lib = self.image_lib.with_search_dirs(search_dirs);
+        self
+    }
+
+    /// How placed images are downsampled and compressed in the PDF
+    pub fn with_image_settings(mut self, image_settings: ImageExportSettings) -> PDFPrinter<'a> {
+        self.image_lib = self.image_lib.with_image_settings(image_settings);
+        self
+    }
+
+    /// Draw a grey box with a cross in the frames of graphics whose linked file is missing
+    pub fn with_missing_link_placeholders(
+        mut self,
+        missing_link_placeholders: bool,
+    ) -> PDFPrinter<'a> {
+        self.image_lib = self
+            .image_lib
+            .with_missing_link_placeholders(missing_link_placeholders);
+        self
+    }
+
+    /// Where the text of the stories went when they were laid out before, which
+    /// cross-references, running headers and note numbers take their pages from
+    pub fn with_story_layout(mut self, layout: &StoryLayout) -> PDFPrinter<'a> {
+        self.note_numbers =
+            note_numbering::note_numbers(self.idml_package, &self.page_numbers, layout);
+        self.text_variables =
+            text_variables::resolve_text_variables(self.idml_package, &self.page_numbers, layout);
+        self.navigation.get_mut().cross_references =
+            cross_references::cross_reference_texts(self.idml_package, &self.page_numbers, layout);
+        self
+    }
+
+    /// Lays out the stories of each spread without drawing anything, to find out
+    /// which pages their text goes on
+    pub fn lay_out_stories(self) -> StoryLayout {
+        self.story_layout.replace(StoryLayout::measuring());
+        for spread_id in self.idml_package.designmap().spread_ids() {
+            let spread = self.idml_package.spreads().get(spread_id).unwrap();
+            self.render_spread(spread)
+                .expect(format!("Failed to lay out spread {:?}", spread).as_str());
+        }
+        unsafe {
+            HPDF_Free(self.pdf_doc);
+        }
+        self.story_layout.into_inner()
+    }
+
+    /// The links of the graphics rendered so far that no file was found for
+    pub fn missing_links(&self) -> Vec<MissingLink> {
+        self.image_lib.link_resolver().missing_links()
+    }
+
+    /// The graphics rendered so far that are drawn from the low resolution preview in their file
+    pub fn previewed_links(&self) -> Vec<PreviewedLink> {
+        self.image_lib.link_resolver().previewed_links()
+    }
+
+    /// A report of the missing links and the graphics drawn from their preview,
+    /// or None if every linked file was found and drawn
+    pub fn missing_links_report(&self) -> Option<String> {
+        self.image_lib.link_resolver().missing_links_report()
+    }
+
     /// Render each spread in the IDML Package
     pub fn render_pdf(&self) -> Result<(), String> {
         for spread_id in self.idml_package.designmap().spread_ids() {
             let spread = self.idml_package.spreads().get(spread_id).unwrap();
-            // Render applied master spread first
-            if let Some(master_id) = spread.applied_master() {
-                if let Some(master_spread) = self.idml_package.master_spread_with_id(master_id) {
-                    self.render_spread(&master_spread)
-                        .expect(format!("Failed to render master spread {:?}", master_id).as_str());
-                }
-            }
             self.render_spread(spread)
                 .expect(format!("Failed to render spread {:?}", spread).as_str());
         }
+
+        // Links and bookmarks can go to any page, so they are added once all pages are in
+        let navigation = self.navigation.borrow();
+        navigation.render_links(self.idml_package);
+        navigation.render_outlines(self.idml_package, self.pdf_doc);
         Ok(())
     }
 
@@ -87,8 +233,81 @@ impl<'a> PDFPrinter<'a> {
 
         for content in spread.contents() {
             self.render_spread_content(content, &spread_transform, &mut page_transform)?;
+
+            // Master items go beneath the items of the page
+            if let SpreadContent::Page(page) = content {
+                if spread.show_master_items().unwrap_or(true) {
+                    self.render_master_items(page, &page_transform, MAX_MASTER_DEPTH)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Draws the items of the master applied to the page, as they are on the master page on
+    /// the same side of the spine as the page. They are drawn while the page is the current
+    /// page, so page numbers and text variables on the master show those of the page.
+    /// Items of masters that masters are based on are drawn first, up to `depth` masters deep.
+    fn render_master_items(
+        &self,
+        page: &Page,
+        page_transform: &Transform,
+        depth: usize,
+    ) -> Result<(), String> {
+        let master = match page
+            .applied_master()
+            .as_deref()
+            .and_then(|id| self.idml_package.master_spread_with_id(id))
+        {
+            Some(master) => master,
+            None => return Ok(()),
+        };
+        let center = |page: &Page| match page.geometric_bounds().as_slice() {
+            [top, left, bottom, right] => transforms::from_vec(page.item_transform())
+                .apply_to_point(&((left + right) / 2_f64), &((top + bottom) / 2_f64))[0],
+            _ => 0_f64,
+        };
+        let master_page = master.pages().into_iter().flatten().min_by(|a, b| {
+            let (a, b) = (
+                (center(a) - center(page)).abs(),
+                (center(b) - center(page)).abs(),
+            );
+            a.partial_cmp(&b).unwrap_or(std::cmp::Ordering::Equal)
+        });
+        let master_page = match master_page {
+            Some(master_page) => master_page,
+            None => return Ok(()),
+        };
+
+        // From the master spread to the spread of the page, through the master page
+        let mut master_transform = transforms::from_vec(master_page.item_transform())
+            .reverse()
+            .combine_with(&transforms::from_vec(page.master_page_transform()))
+            .combine_with(&transforms::from_vec(page.item_transform()))
+            .combine_with(page_transform);
+        if depth > 0 {
+            self.render_master_items(master_page, &master_transform, depth - 1)?;
         }
 
+        // Master items overridden on the page are drawn with the items of the page instead
+        let overridden: Vec<&str> = page
+            .override_list()
+            .as_deref()
+            .map(|list| list.split_whitespace().collect())
+            .unwrap_or_default();
+        for content in master.contents() {
+            let id = match content {
+                SpreadContent::Page(_) => continue,
+                SpreadContent::Polygon(polygon) => Some(polygon.id()),
+                SpreadContent::Group(group) => Some(group.id()),
+                _ => None,
+            };
+            if id.map_or(false, |id| overridden.contains(&id.as_str())) {
+                continue;
+            }
+            self.render_spread_content(content, &transforms::identity(), &mut master_transform)?;
+        }
         Ok(())
     }
 
@@ -112,26 +331,67 @@ impl<'a> PDFPrinter<'a> {
 
                 // Update the current page reference
                 self.current_page.replace(Some(page));
+                self.navigation.borrow_mut().add_page(p.id(), page);
+                self.story_layout.borrow_mut().start_page(p);
+
+                // Pages outside of the document sections are numbered by their name
+                let page_number = match self.page_numbers.get(p.id()) {
+                    Some(page_number) => page_number.clone(),
+                    None => PageNumber {
+                        number: p.name().clone().unwrap_or_default(),
+                        ..PageNumber::default()
+                    },
+                };
+                self.add_page_label(&page_number);
+                self.current_page_number.replace(page_number);
+                self.current_text_variables
+                    .replace(self.text_variables.get(p.id()).cloned().unwrap_or_default());
             }
             SpreadContent::Polygon(polygon) => {
-                polygon
-                    .render(
-                        page_transform,
-                        &self.idml_package.resources(),
-                        self.current_page.borrow().expect("No page found"),
-                    )
-                    .expect(format!("Failed to render textframe '{}'", polygon.id()).as_str());
+                if !self.story_layout.borrow().is_measuring() {
+                    polygon
+                        .render(
+                            page_transform,
+                            &self.idml_package.resources(),
+                            &self.font_lib,
+                            &self.image_lib,
+                            self.current_page.borrow().expect("No page found"),
+                        )
+                        .expect(format!("Failed to render textframe '{}'", polygon.id()).as_str());
+                }
                 polygon
                     .render_story(
                         &self.idml_package,
                         page_transform,
                         &self.font_lib,
+                        &self.image_lib,
                         self.current_page.borrow().expect("No page found"),
+                        &self.current_page_number.borrow(),
+                        &self.current_text_variables.borrow(),
+                        &self.note_numbers,
+                        &mut self.navigation.borrow_mut(),
+                        &mut self.story_layout.borrow_mut(),
                     )
                     .expect(
                         format!("Failed to render story of textframe '{}'", polygon.id()).as_str(),
                     );
             }
+            SpreadContent::Group(group) => {
+                group
+                    .render(
+                        page_transform,
+                        &self.idml_package,
+                        &self.font_lib,
+                        &self.image_lib,
+                        self.current_page.borrow().expect("No page found"),
+                        &self.current_page_number.borrow(),
+                        &self.current_text_variables.borrow(),
+                        &self.note_numbers,
+                        &mut self.navigation.borrow_mut(),
+                        &mut self.story_layout.borrow_mut(),
+                    )
+                    .expect(format!("Failed to render group '{}'", group.id()).as_str());
+            }
             _ => {}
         }
 
@@ -167,12 +427,58 @@ impl<'a> PDFPrinter<'a> {
         }
     }
 
+    /// Labels the page just added to the PDF, unless it continues
+    /// the numbering of the previous page
+    fn add_page_label(&self, page_number: &PageNumber) {
+        let label = page_number.label.clone().unwrap_or(PageLabel {
+            style: PageNumberStyle::Arabic,
+            prefix: page_number.number.clone(),
+            value: 1,
+        });
+        let page_index = self.page_count.replace_with(|count| *count + 1);
+        let continues_numbering = match &*self.last_page_label.borrow() {
+            Some(last_label) => last_label.next() == label,
+            None => false,
+        };
+
+        if !continues_numbering {
+            let style = match label.style {
+                PageNumberStyle::Arabic | PageNumberStyle::FullWidthArabic => {
+                    HPDF_PageNumStyle::HPDF_PAGE_NUM_STYLE_DECIMAL
+                }
+                PageNumberStyle::UpperRoman => HPDF_PageNumStyle::HPDF_PAGE_NUM_STYLE_UPPER_ROMAN,
+                PageNumberStyle::LowerRoman => HPDF_PageNumStyle::HPDF_PAGE_NUM_STYLE_LOWER_ROMAN,
+                PageNumberStyle::UpperLetters => {
+                    HPDF_PageNumStyle::HPDF_PAGE_NUM_STYLE_UPPER_LETTERS
+                }
+                PageNumberStyle::LowerLetters => {
+                    HPDF_PageNumStyle::HPDF_PAGE_NUM_STYLE_LOWER_LETTERS
+                }
+            };
+            let prefix = cstring!(label.prefix.as_str());
+            unsafe {
+                HPDF_AddPageLabel(
+                    self.pdf_doc,
+                    page_index,
+                    style,
+                    label.value,
+                    prefix.as_ptr(),
+                );
+            }
+        }
+        self.last_page_label.replace(Some(label));
+    }
+
     pub fn save_pdf(self, path: &str) -> Result<(), String> {
         unsafe {
             let fname = cstring!(path);
             HPDF_SaveToFile(self.pdf_doc, fname.as_ptr());
             HPDF_Free(self.pdf_doc);
         }
-        Ok(())
+        // Placed PDF pages, shadings, spot colors and glyphs drawn by glyph id
+        // can only be written into the PDF once it is saved
+        self.image_lib.replace_placeholders(path)?;
+        separations::write_separations(path, &self.idml_package.resources().spot_colors())?;
+        self.font_lib.write_cid_fonts(path)
     }
 }
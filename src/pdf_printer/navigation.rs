@@ -0,0 +1,193 @@
+use crate::idml_parser::designmap_parser::{Bookmark, HyperlinkHighlight, HyperlinkWidth};
+use crate::idml_parser::IDMLPackage;
+use crate::pdf_printer::pdf_utils::BoundingBox;
+use libharu_sys::*;
+use std::collections::HashMap;
+use std::ffi::CString;
+use std::ptr;
+
+/// Area of the page covered by text that is the source of a hyperlink
+#[derive(Debug, Clone)]
+pub struct LinkArea {
+    pub source: String,
+    pub area: BoundingBox,
+}
+
+/// Where a text destination ended up on the page
+#[derive(Debug, Clone)]
+pub struct Anchor {
+    pub destination: String,
+    pub top: f64,
+}
+
+/// Hyperlinks, cross-references and bookmarks of the document. The areas
+/// of link sources and the places of text destinations are collected while
+/// the pages are rendered, and turned into link annotations and outlines once
+/// all pages are in the PDF, as links may go to any page.
+#[derive(Debug, Default)]
+pub struct Navigation {
+    /// Text of cross-references, regenerated from their formats, keyed by source id
+    pub cross_references: HashMap<String, String>,
+    /// Pages in the PDF, keyed by page id
+    pages: HashMap<String, HPDF_Page>,
+    link_areas: Vec<(HPDF_Page, LinkArea)>,
+    /// Text destinations by id, with the page and height they are at
+    anchors: HashMap<String, (HPDF_Page, f64)>,
+}
+
+impl Navigation {
+    pub fn new(cross_references: HashMap<String, String>) -> Navigation {
+        Navigation {
+            cross_references,
+            ..Navigation::default()
+        }
+    }
+
+    pub fn add_page(&mut self, page_id: &str, page: HPDF_Page) {
+        self.pages.insert(page_id.to_string(), page);
+    }
+
+    /// Adds the link areas and text destinations of a story set on `page`
+    pub fn add_story(&mut self, page: HPDF_Page, link_areas: Vec<LinkArea>, anchors: Vec<Anchor>) {
+        self.link_areas
+            .extend(link_areas.into_iter().map(|link_area| (page, link_area)));
+        for anchor in anchors {
+            // Stories in more than one frame go to the first of them
+            self.anchors
+                .entry(anchor.destination)
+                .or_insert((page, anchor.top));
+        }
+    }
+
+    /// A PDF destination for a text destination, page destination or page
+    fn destination(&self, idml_package: &IDMLPackage, id: &str) -> Option<HPDF_Destination> {
+        if let Some((page, top)) = self.anchors.get(id) {
+            return unsafe {
+                let destination = HPDF_Page_CreateDestination(*page);
+                HPDF_Destination_SetFitH(destination, *top as f32);
+                Some(destination)
+            };
+        }
+        let page_id = idml_package
+            .designmap()
+            .hyperlink_page_destinations()
+            .iter()
+            .find(|destination| destination.id() == id)
+            .and_then(|destination| destination.destination_page().as_deref())
+            .unwrap_or(id);
+        let page = self.pages.get(page_id)?;
+        unsafe { Some(HPDF_Page_CreateDestination(*page)) }
+    }
+
+    /// Adds a link annotation over each link area, going to the destination
+    /// of its hyperlink. Must be called once all pages are in the PDF.
+    pub fn render_links(&self, idml_package: &IDMLPackage) {
+        let designmap = idml_package.designmap();
+        for (page, link_area) in &self.link_areas {
+            let hyperlink = match designmap
+                .hyperlinks()
+                .iter()
+                .find(|hyperlink| hyperlink.source().as_ref() == Some(&link_area.source))
+            {
+                Some(hyperlink) => hyperlink,
+                None => continue,
+            };
+            let destination_id = match hyperlink
+                .properties()
+                .as_ref()
+                .and_then(|properties| properties.destination().as_ref())
+            {
+                Some(destination_id) => destination_id,
+                None => continue,
+            };
+            let url = designmap
+                .hyperlink_url_destinations()
+                .iter()
+                .find(|destination| destination.id() == destination_id)
+                .and_then(|destination| destination.destination_url().clone());
+
+            let rect = HPDF_Rect {
+                left: link_area.area.left as f32,
+                bottom: link_area.area.bottom as f32,
+                right: link_area.area.right as f32,
+                top: link_area.area.top as f32,
+            };
+            unsafe {
+                let annotation = match url {
+                    Some(url) => {
+                        let uri = CString::new(url).unwrap();
+                        HPDF_Page_CreateURILinkAnnot(*page, rect, uri.as_ptr())
+                    }
+                    None => match self.destination(idml_package, destination_id) {
+                        Some(destination) => HPDF_Page_CreateLinkAnnot(*page, rect, destination),
+                        None => continue,
+                    },
+                };
+                let border_width = match (hyperlink.visible(), hyperlink.width()) {
+                    (Some(true), Some(HyperlinkWidth::Medium)) => 2_f32,
+                    (Some(true), Some(HyperlinkWidth::Thick)) => 3_f32,
+                    (Some(true), _) => 1_f32,
+                    _ => 0_f32,
+                };
+                HPDF_LinkAnnot_SetBorderStyle(annotation, border_width, 0, 0);
+                let highlight = match hyperlink.highlight() {
+                    Some(HyperlinkHighlight::Invert) => {
+                        HPDF_AnnotHighlightMode::HPDF_ANNOT_INVERT_BOX
+                    }
+                    Some(HyperlinkHighlight::Outline) => {
+                        HPDF_AnnotHighlightMode::HPDF_ANNOT_INVERT_BORDER
+                    }
+                    Some(HyperlinkHighlight::Inset) => {
+                        HPDF_AnnotHighlightMode::HPDF_ANNOT_DOWN_APPEARANCE
+                    }
+                    _ => HPDF_AnnotHighlightMode::HPDF_ANNOT_NO_HIGHTLIGHT,
+                };
+                HPDF_LinkAnnot_SetHighlightMode(annotation, highlight);
+            }
+        }
+    }
+
+    /// Adds the bookmark tree of the document as the outline of the PDF.
+    /// Must be called once all pages are in the PDF.
+    pub fn render_outlines(&self, idml_package: &IDMLPackage, pdf_doc: HPDF_Doc) {
+        let encoding = CString::new("UTF-8").unwrap();
+        let encoder = unsafe { HPDF_GetEncoder(pdf_doc, encoding.as_ptr()) };
+        self.render_bookmarks(
+            idml_package.designmap().bookmarks(),
+            idml_package,
+            pdf_doc,
+            ptr::null_mut(),
+            encoder,
+        );
+    }
+
+    fn render_bookmarks(
+        &self,
+        bookmarks: &[Bookmark],
+        idml_package: &IDMLPackage,
+        pdf_doc: HPDF_Doc,
+        parent: HPDF_Outline,
+        encoder: HPDF_Encoder,
+    ) {
+        for bookmark in bookmarks {
+            let title =
+                CString::new(bookmark.name().clone().unwrap_or_default()).unwrap_or_default();
+            let outline = unsafe { HPDF_CreateOutline(pdf_doc, parent, title.as_ptr(), encoder) };
+            if outline.is_null() {
+                continue;
+            }
+            let destination = bookmark
+                .destination()
+                .as_ref()
+                .and_then(|id| self.destination(idml_package, id));
+            if let Some(destination) = destination {
+                unsafe {
+                    HPDF_Outline_SetDestination(outline, destination);
+                }
+            }
+            if let Some(children) = bookmark.bookmarks() {
+                self.render_bookmarks(children, idml_package, pdf_doc, outline, encoder);
+            }
+        }
+    }
+}
@@ -0,0 +1,399 @@
+use crate::idml_parser::designmap_parser::PageNumberStyle;
+use crate::idml_parser::spread_parser::SpreadContent;
+use crate::idml_parser::story_parser::{ParagraphStyleRange, StoryContent};
+use crate::idml_parser::IDMLPackage;
+use crate::pdf_printer::page_numbering::{self, PageNumber};
+use crate::pdf_printer::StoryLayout;
+use std::collections::{HashMap, HashSet};
+
+/// Numbers the footnotes and endnotes of the document in the order of the pages
+/// their references are set on in `layout`, keyed by note id. References that were
+/// not laid out are on the page of the first frame of their story. Footnotes can
+/// restart their numbering on each page, spread or section, while endnotes are
+/// numbered through the document.
+pub fn note_numbers(
+    idml_package: &IDMLPackage,
+    page_numbers: &HashMap<String, PageNumber>,
+    layout: &StoryLayout,
+) -> HashMap<String, String> {
+    let option = idml_package
+        .resources()
+        .preferences()
+        .footnote_option()
+        .clone()
+        .unwrap_or_default();
+    let properties = option.properties().clone().unwrap_or_default();
+    let numbering = NoteNumbering {
+        start_at: option.start_at().unwrap_or(1),
+        restart: properties.restart_numbering().clone().unwrap_or_default(),
+        style: properties
+            .footnote_numbering_style()
+            .clone()
+            .unwrap_or_default(),
+    };
+
+    let mut pages = vec![];
+    let mut notes_by_page = HashMap::new();
+    let mut stories_seen = HashSet::new();
+
+    let spreads = idml_package
+        .designmap()
+        .spread_ids()
+        .iter()
+        .filter_map(|spread_id| idml_package.spreads().get(spread_id));
+    for (spread_index, spread) in spreads.enumerate() {
+        for content in spread.contents() {
+            // Page items are on the page before them, as when they are rendered
+            if let SpreadContent::Page(page) = content {
+                let section = page_numbers
+                    .get(page.id())
+                    .and_then(|page_number| page_number.section.clone());
+                pages.push(NotePage {
+                    id: page.id().clone(),
+                    spread: spread_index,
+                    section,
+                });
+                continue;
+            }
+
+            let first_page = pages.last().map(|page| &page.id);
+            for story_id in story_ids(content) {
+                if !stories_seen.insert(story_id) {
+                    continue;
+                }
+                if let Some(story) = idml_package.stories().get(story_id) {
+                    let mut notes = vec![];
+                    collect_notes(idml_package, story.paragraph_style_ranges(), &mut notes);
+                    place_notes(notes, first_page, layout, &mut notes_by_page);
+                }
+            }
+        }
+    }
+
+    numbering.number_notes(&pages, notes_by_page)
+}
+
+/// Page of the document, with what footnote numbering can restart on
+struct NotePage {
+    id: String,
+    /// Index of the spread of the page in the document
+    spread: usize,
+    section: Option<String>,
+}
+
+/// Footnote numbering options of the document
+struct NoteNumbering {
+    start_at: u32,
+    /// When footnote numbering starts over, like "PageRestart"
+    restart: String,
+    /// Numbering style of footnotes, like "Arabic" or "Asterisks"
+    style: String,
+}
+
+impl NoteNumbering {
+    /// Numbers the notes on each page in the order of the pages. Notes that
+    /// are on no page come before the notes of the first page.
+    fn number_notes(
+        &self,
+        pages: &[NotePage],
+        mut notes_by_page: HashMap<Option<String>, Vec<Note>>,
+    ) -> HashMap<String, String> {
+        let mut numbers = HashMap::new();
+        let mut footnote_count = 0;
+        let mut endnote_count = 0;
+        let mut previous_page: Option<&NotePage> = None;
+
+        let notes = notes_by_page.remove(&None).unwrap_or_default();
+        let page_notes = pages.iter().map(|page| {
+            let notes = notes_by_page.remove(&Some(page.id.clone()));
+            (Some(page), notes.unwrap_or_default())
+        });
+        for (page, notes) in std::iter::once((None, notes)).chain(page_notes) {
+            if let Some(page) = page {
+                let restarts = match self.restart.as_str() {
+                    "PageRestart" => true,
+                    "SpreadRestart" => {
+                        previous_page.map(|previous| previous.spread) != Some(page.spread)
+                    }
+                    "SectionRestart" => {
+                        previous_page.map(|previous| &previous.section) != Some(&page.section)
+                    }
+                    _ => false,
+                };
+                if restarts {
+                    footnote_count = 0;
+                }
+                previous_page = Some(page);
+            }
+
+            for note in notes {
+                match note {
+                    Note::Footnote(id) => {
+                        footnote_count += 1;
+                        numbers.insert(
+                            id,
+                            format_footnote_number(self.start_at + footnote_count - 1, &self.style),
+                        );
+                    }
+                    Note::Endnote(id) => {
+                        endnote_count += 1;
+                        numbers.insert(id, endnote_count.to_string());
+                    }
+                }
+            }
+        }
+        numbers
+    }
+}
+
+/// Adds the notes of a story to the page their reference is set on, or to the
+/// page of the first frame of the story if the reference was not laid out
+fn place_notes(
+    notes: Vec<Note>,
+    first_page: Option<&String>,
+    layout: &StoryLayout,
+    notes_by_page: &mut HashMap<Option<String>, Vec<Note>>,
+) {
+    for note in notes {
+        let page_id = layout.marker_page(note.id()).or(first_page).cloned();
+        notes_by_page.entry(page_id).or_default().push(note);
+    }
+}
+
+/// Formats the number of a footnote in the numbering style of the document
+fn format_footnote_number(value: u32, style: &str) -> String {
+    match style {
+        "Asterisks" => "*".repeat(value as usize),
+        _ => page_numbering::format_page_number(value, PageNumberStyle::from_name(style)),
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Note {
+    Footnote(String),
+    Endnote(String),
+}
+
+impl Note {
+    fn id(&self) -> &str {
+        match self {
+            Note::Footnote(id) | Note::Endnote(id) => id,
+        }
+    }
+}
+
+/// Stories of the text frames in a page item, which may be a group of them
+pub fn story_ids(content: &SpreadContent) -> Vec<&String> {
+    match content {
+        SpreadContent::Polygon(polygon) => polygon.parent_story().iter().collect(),
+        SpreadContent::Group(group) => group
+            .contents()
+            .iter()
+            .flatten()
+            .flat_map(story_ids)
+            .collect(),
+        _ => vec![],
+    }
+}
+
+/// Footnote and endnote references in the text, in order, including
+/// those in table cells and in text frames anchored in the text
+fn collect_notes(
+    idml_package: &IDMLPackage,
+    paragraph_style_ranges: &Option<Vec<ParagraphStyleRange>>,
+    notes: &mut Vec<Note>,
+) {
+    let contents = paragraph_style_ranges
+        .iter()
+        .flatten()
+        .filter_map(|p_style| p_style.character_style_ranges().as_ref())
+        .flatten()
+        .filter_map(|c_style| c_style.contents().as_ref())
+        .flatten();
+    for content in contents {
+        match content {
+            StoryContent::Footnote(footnote) => {
+                if let Some(id) = footnote.id() {
+                    notes.push(Note::Footnote(id.clone()));
+                }
+            }
+            StoryContent::Endnote(endnote) => {
+                if let Some(id) = endnote.id() {
+                    notes.push(Note::Endnote(id.clone()));
+                }
+            }
+            StoryContent::Table(table) => {
+                for cell in table.cells().iter().flatten() {
+                    collect_notes(idml_package, cell.paragraph_style_ranges(), notes);
+                }
+            }
+            StoryContent::Polygon(polygon) => {
+                let story = polygon
+                    .parent_story()
+                    .as_ref()
+                    .and_then(|story_id| idml_package.stories().get(story_id));
+                if let Some(story) = story {
+                    collect_notes(idml_package, story.paragraph_style_ranges(), notes);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::idml_parser::spread_parser::Page;
+    use quick_xml::de::from_str;
+
+    fn numbering(restart: &str, style: &str) -> NoteNumbering {
+        NoteNumbering {
+            start_at: 1,
+            restart: restart.to_string(),
+            style: style.to_string(),
+        }
+    }
+
+    /// Two spreads of two pages, the second spread in a section of its own
+    fn pages() -> Vec<NotePage> {
+        [
+            ("p1", 0, "s1"),
+            ("p2", 0, "s1"),
+            ("p3", 1, "s2"),
+            ("p4", 1, "s2"),
+        ]
+        .iter()
+        .map(|(id, spread, section)| NotePage {
+            id: id.to_string(),
+            spread: *spread,
+            section: Some(section.to_string()),
+        })
+        .collect()
+    }
+
+    /// Two footnotes on each page, numbered "<page>a" and "<page>b"
+    fn footnotes_by_page() -> HashMap<Option<String>, Vec<Note>> {
+        pages()
+            .into_iter()
+            .map(|page| {
+                let notes = vec![
+                    Note::Footnote(format!("{}a", page.id)),
+                    Note::Footnote(format!("{}b", page.id)),
+                ];
+                (Some(page.id), notes)
+            })
+            .collect()
+    }
+
+    fn numbers_of(numbers: &HashMap<String, String>, ids: &[&str]) -> Vec<String> {
+        ids.iter().map(|id| numbers[*id].clone()).collect()
+    }
+
+    const IDS: [&str; 8] = ["p1a", "p1b", "p2a", "p2b", "p3a", "p3b", "p4a", "p4b"];
+
+    #[test]
+    fn footnotes_are_numbered_through_the_document() {
+        let numbers = numbering("Continuous", "Arabic").number_notes(&pages(), footnotes_by_page());
+        assert_eq!(
+            numbers_of(&numbers, &IDS),
+            vec!["1", "2", "3", "4", "5", "6", "7", "8"]
+        );
+    }
+
+    #[test]
+    fn footnotes_restart_on_each_page() {
+        let numbers =
+            numbering("PageRestart", "Arabic").number_notes(&pages(), footnotes_by_page());
+        assert_eq!(
+            numbers_of(&numbers, &IDS),
+            vec!["1", "2", "1", "2", "1", "2", "1", "2"]
+        );
+    }
+
+    #[test]
+    fn footnotes_restart_on_each_spread() {
+        let numbers =
+            numbering("SpreadRestart", "Arabic").number_notes(&pages(), footnotes_by_page());
+        assert_eq!(
+            numbers_of(&numbers, &IDS),
+            vec!["1", "2", "3", "4", "1", "2", "3", "4"]
+        );
+    }
+
+    #[test]
+    fn footnotes_restart_on_each_section() {
+        let mut pages = pages();
+        // The section starts in the middle of the first spread instead
+        pages[1].section = Some("s2".to_string());
+        let numbers =
+            numbering("SectionRestart", "Arabic").number_notes(&pages, footnotes_by_page());
+        assert_eq!(
+            numbers_of(&numbers, &IDS),
+            vec!["1", "2", "1", "2", "3", "4", "5", "6"]
+        );
+    }
+
+    #[test]
+    fn footnotes_are_numbered_in_their_style() {
+        let mut numbering = numbering("PageRestart", "Asterisks");
+        let numbers = numbering.number_notes(&pages(), footnotes_by_page());
+        assert_eq!(numbers_of(&numbers, &["p1a", "p1b"]), vec!["*", "**"]);
+
+        numbering.style = "LowerRoman".to_string();
+        numbering.start_at = 3;
+        let numbers = numbering.number_notes(&pages(), footnotes_by_page());
+        assert_eq!(numbers_of(&numbers, &["p1a", "p1b"]), vec!["iii", "iv"]);
+    }
+
+    #[test]
+    fn endnotes_are_numbered_through_the_document() {
+        let mut notes_by_page = footnotes_by_page();
+        notes_by_page
+            .get_mut(&Some("p1".to_string()))
+            .unwrap()
+            .push(Note::Endnote("e1".to_string()));
+        notes_by_page
+            .get_mut(&Some("p3".to_string()))
+            .unwrap()
+            .push(Note::Endnote("e2".to_string()));
+        let numbers = numbering("PageRestart", "Arabic").number_notes(&pages(), notes_by_page);
+        assert_eq!(numbers_of(&numbers, &["e1", "e2"]), vec!["1", "2"]);
+    }
+
+    #[test]
+    fn notes_of_threaded_stories_are_on_the_page_their_reference_is_set_on() {
+        let page: Page =
+            from_str(r#"<Page Self="p2" UseMasterGrid="true"><Properties/></Page>"#).unwrap();
+        let mut layout = StoryLayout::measuring();
+        layout.start_page(&page);
+        layout.add_story("u1", &[3], vec!["f2".to_string()]);
+
+        // The first footnote was set before the page, the last one was not set
+        let notes = vec![
+            Note::Footnote("f1".to_string()),
+            Note::Footnote("f2".to_string()),
+            Note::Footnote("f3".to_string()),
+        ];
+        let mut notes_by_page = HashMap::new();
+        place_notes(notes, Some(&"p1".to_string()), &layout, &mut notes_by_page);
+
+        assert_eq!(
+            notes_by_page[&Some("p1".to_string())],
+            vec![
+                Note::Footnote("f1".to_string()),
+                Note::Footnote("f3".to_string())
+            ]
+        );
+        assert_eq!(
+            notes_by_page[&Some("p2".to_string())],
+            vec![Note::Footnote("f2".to_string())]
+        );
+
+        let numbers = numbering("PageRestart", "Arabic").number_notes(&pages(), notes_by_page);
+        assert_eq!(
+            numbers_of(&numbers, &["f1", "f3", "f2"]),
+            vec!["1", "2", "1"]
+        );
+    }
+}
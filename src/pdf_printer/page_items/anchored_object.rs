@@ -0,0 +1,521 @@
+use super::textframe::{RenderProperties, StoryState};
+use crate::idml_parser::{spread_parser::*, story_parser::StoryContent, IDMLPackage};
+use crate::pdf_printer::{
+    font_manager::FontLibrary,
+    image_manager::ImageLibrary,
+    navigation::Navigation,
+    page_numbering::PageNumber,
+    pdf_utils::*,
+    story_layout::StoryLayout,
+    text_layout::PositionedGlyph,
+    transforms::{self, Transform},
+};
+use libharu_sys::*;
+use std::collections::HashMap;
+
+/// A page item anchored in a story, drawn once the text around it has been set
+#[derive(Debug)]
+pub struct AnchoredObject {
+    item: StoryContent,
+    /// Where the left edge of the item goes on the page
+    left: f64,
+    /// Where the bottom edge of the item goes on the page
+    bottom: f64,
+}
+
+impl AnchoredObject {
+    /// Moves an item set inline along its line, once the line is placed
+    pub(super) fn set_left(&mut self, left: f64) {
+        self.left = left;
+    }
+
+    /// Moves an item set inline down its column, once the column is placed
+    pub(super) fn set_bottom(&mut self, bottom: f64) {
+        self.bottom = bottom;
+    }
+
+    /// Renders the item, moved from where its own transform puts it to its place in the story
+    pub fn render(
+        &self,
+        parent_transform: &Transform,
+        idml_package: &IDMLPackage,
+        font_lib: &FontLibrary,
+        image_lib: &ImageLibrary,
+        current_page: HPDF_Page,
+        page_number: &PageNumber,
+        text_variables: &HashMap<String, String>,
+        note_numbers: &HashMap<String, String>,
+        navigation: &mut Navigation,
+        layout: &mut StoryLayout,
+    ) -> Result<(), String> {
+        let bb = match item_boundingbox(&self.item, parent_transform) {
+            Some(bb) => bb,
+            None => return Ok(()),
+        };
+        let offset = transforms::from_values(
+            1_f64,
+            0_f64,
+            0_f64,
+            1_f64,
+            self.left - bb.left,
+            self.bottom - bb.bottom,
+        );
+        let mut transform = parent_transform.combine_with(&offset);
+
+        match &self.item {
+            StoryContent::Polygon(polygon) => {
+                polygon.render(
+                    &transform,
+                    idml_package.resources(),
+                    font_lib,
+                    image_lib,
+                    current_page,
+                )?;
+                polygon.render_story(
+                    idml_package,
+                    &mut transform,
+                    font_lib,
+                    image_lib,
+                    current_page,
+                    page_number,
+                    text_variables,
+                    note_numbers,
+                    navigation,
+                    layout,
+                )?;
+            }
+            StoryContent::Group(group) => group.render(
+                &transform,
+                idml_package,
+                font_lib,
+                image_lib,
+                current_page,
+                page_number,
+                text_variables,
+                note_numbers,
+                navigation,
+                layout,
+            )?,
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+fn item_boundingbox(item: &StoryContent, parent_transform: &Transform) -> Option<BoundingBox> {
+    match item {
+        StoryContent::Polygon(polygon) => try_boundingbox(polygon, parent_transform),
+        StoryContent::Group(group) => group.boundingbox(parent_transform),
+        _ => None,
+    }
+}
+
+/// Horizontal and vertical position of an anchor point on an item,
+/// from its left and bottom edge, as a fraction of its size
+pub(super) fn anchor_point_fraction(anchor_point: AnchorPoint) -> (f64, f64) {
+    match anchor_point {
+        AnchorPoint::TopLeftAnchor => (0_f64, 1_f64),
+        AnchorPoint::TopCenterAnchor => (0.5_f64, 1_f64),
+        AnchorPoint::TopRightAnchor => (1_f64, 1_f64),
+        AnchorPoint::LeftCenterAnchor => (0_f64, 0.5_f64),
+        AnchorPoint::CenterAnchor => (0.5_f64, 0.5_f64),
+        AnchorPoint::RightCenterAnchor => (1_f64, 0.5_f64),
+        AnchorPoint::BottomLeftAnchor => (0_f64, 0_f64),
+        AnchorPoint::BottomCenterAnchor => (0.5_f64, 0_f64),
+        AnchorPoint::BottomRightAnchor => (1_f64, 0_f64),
+    }
+}
+
+impl Polygon {
+    /// Places an item anchored at the current position in the story. Inline items
+    /// take up room in the line like text, items above the line take up lines of their
+    /// own, and custom positioned items are placed relative to the line, frame or page.
+    /// In vertical stories lines are columns, so items above the line go right of it.
+    pub(super) fn render_anchored_object(
+        &self,
+        item: &StoryContent,
+        render_properties: &RenderProperties,
+        font_lib: &FontLibrary,
+        current_page: HPDF_Page,
+        state: &mut StoryState,
+    ) {
+        if state.is_past_frame() {
+            return;
+        }
+        // Only the size of the item is needed for now, which the page does not change
+        let bounds = match item_boundingbox(item, &transforms::identity()) {
+            Some(bounds) => bounds,
+            None => return,
+        };
+        let (width, height) = (bounds.right - bounds.left, bounds.top - bounds.bottom);
+        let setting = match item {
+            StoryContent::Polygon(polygon) => polygon.anchored_object_setting().clone(),
+            StoryContent::Group(group) => group.anchored_object_setting().cloned(),
+            _ => None,
+        }
+        .unwrap_or_default();
+        let x_offset = setting.anchor_x_offset().unwrap_or(0_f64);
+        let y_offset = setting.anchor_y_offset().unwrap_or(0_f64);
+        let alignment = setting
+            .horizontal_alignment()
+            .unwrap_or(HorizontalAlignment::LeftAlign);
+
+        if !state.has_offset {
+            state.has_offset = true;
+            state.leading = render_properties.leading();
+            match state.vertical {
+                true => state.column -= render_properties.font_size() / 2_f64,
+                false => state.y -= state.leading,
+            }
+        }
+        // Room the line takes up above its baseline, or right of the center of the column
+        let ascent = match state.vertical {
+            true => state.leading / 2_f64,
+            false => state.leading * 0.75_f64,
+        };
+        let frame = state.frame.clone();
+        // Where an item of `length` set at the current position starts along the line
+        let cursor = |state: &StoryState, length: f64| {
+            let level = render_properties.glyph_transforms().paragraph_level();
+            match (state.vertical, level % 2) {
+                (true, _) => frame.top - state.line_width,
+                (false, 0) => frame.left + state.line_width,
+                (false, _) => frame.right - state.line_width - length,
+            }
+        };
+        let align = |start: f64, end: f64, width: f64| match alignment {
+            HorizontalAlignment::CenterAlign => (start + end - width) / 2_f64,
+            HorizontalAlignment::RightAlign => end - width,
+            _ => start,
+        };
+
+        let (left, bottom) = match setting
+            .anchored_position()
+            .unwrap_or(AnchorPosition::InlinePosition)
+        {
+            AnchorPosition::InlinePosition => {
+                let (length, rise) = match state.vertical {
+                    true => (height, width / 2_f64),
+                    false => (width, height),
+                };
+                if !state.is_line_empty() && state.line_width + length > state.line_length() {
+                    state.new_line();
+                }
+                // Items taller than the line push its baseline down, and items wider
+                // than the column push the column to the left
+                let room = ascent.max(state.line_rise);
+                if rise > room {
+                    match state.vertical {
+                        true => state.column -= rise - room,
+                        false => state.y -= rise - room,
+                    }
+                    state.line_rise = rise;
+                }
+                if state.is_past_frame() {
+                    return;
+                }
+                let start = cursor(state, length);
+                // The item is moved along with its line once the line is placed
+                let level =
+                    state.content_level(render_properties.glyph_transforms().paragraph_level());
+                let mut room = render_properties.line_run(
+                    font_lib,
+                    vec![PositionedGlyph::spacer(length)],
+                    None,
+                    state,
+                );
+                room.glyphs[0].level = level;
+                room.decorations.clear();
+                room.anchored_object = Some(state.anchored_objects.len());
+                state.push_run(room);
+                match state.vertical {
+                    // Centered on the column, offset to the left like text shifted down
+                    true => (state.column - width / 2_f64 - y_offset, start - height),
+                    false => (start, state.y - y_offset),
+                }
+            }
+            AnchorPosition::AboveLine => {
+                if !state.is_line_empty() {
+                    state.new_line();
+                }
+                let space_above = setting.anchor_space_above().unwrap_or(0_f64);
+                match state.vertical {
+                    true => {
+                        let right = state.column + ascent - space_above;
+                        let length = state.line_length() - state.indent;
+                        let top = frame.top - state.indent - align(0_f64, length, height);
+                        state.column = right - width - ascent;
+                        if state.is_past_frame() {
+                            return;
+                        }
+                        (right - width, top - height)
+                    }
+                    false => {
+                        let top = state.y + ascent - space_above;
+                        let left = align(frame.left + state.indent, frame.right, width);
+                        state.y = top - height - ascent;
+                        if state.is_past_frame() {
+                            return;
+                        }
+                        (left, top - height)
+                    }
+                }
+            }
+            AnchorPosition::Anchored => {
+                let (page_width, page_height) = unsafe {
+                    (
+                        HPDF_Page_GetWidth(current_page) as f64,
+                        HPDF_Page_GetHeight(current_page) as f64,
+                    )
+                };
+                let margins = margin_box(&state.page_margins, page_width, page_height);
+                let x = match setting
+                    .horizontal_reference_point()
+                    .unwrap_or(AnchoredRelativeTo::TextFrame)
+                {
+                    AnchoredRelativeTo::AnchorLocation => match state.vertical {
+                        true => state.column,
+                        false => cursor(state, 0_f64),
+                    },
+                    AnchoredRelativeTo::ColumnEdge | AnchoredRelativeTo::TextFrame => {
+                        align(frame.left, frame.right, 0_f64)
+                    }
+                    AnchoredRelativeTo::PageMargins => align(margins.left, margins.right, 0_f64),
+                    AnchoredRelativeTo::PageEdge => align(0_f64, page_width, 0_f64),
+                };
+                let valign = |top: f64, bottom: f64| match setting.vertical_alignment() {
+                    Some(VerticalAlignment::CenterAlign) => (top + bottom) / 2_f64,
+                    Some(VerticalAlignment::BottomAlign) => bottom,
+                    _ => top,
+                };
+                let font_size = render_properties.font_size();
+                let (font_ascent, cap_height, x_height) = render_properties.font_heights(font_lib);
+                let y = match setting
+                    .vertical_reference_point()
+                    .unwrap_or(VerticallyRelativeTo::LineBaseline)
+                {
+                    VerticallyRelativeTo::ColumnEdge | VerticallyRelativeTo::TextFrame => {
+                        valign(frame.top, frame.bottom)
+                    }
+                    VerticallyRelativeTo::PageMargins => valign(margins.top, margins.bottom),
+                    VerticallyRelativeTo::PageEdge => valign(page_height, 0_f64),
+                    // Lines of vertical stories run down the column, so the points
+                    // of the line are all where the anchor is along the column
+                    _ if state.vertical => cursor(state, 0_f64),
+                    VerticallyRelativeTo::LineBaseline => state.y,
+                    VerticallyRelativeTo::LineXheight => state.y + x_height,
+                    VerticallyRelativeTo::LineAscent => state.y + font_ascent,
+                    VerticallyRelativeTo::CapHeight => state.y + cap_height,
+                    VerticallyRelativeTo::TopOfLeading => state.y + ascent,
+                    // The em box of Latin fonts reaches an eighth of an em below the baseline
+                    VerticallyRelativeTo::EmboxTop => state.y + font_size * 0.875_f64,
+                    VerticallyRelativeTo::EmboxMiddle => state.y + font_size * 0.375_f64,
+                    VerticallyRelativeTo::EmboxBottom => state.y - font_size * 0.125_f64,
+                };
+
+                // The anchor point of the item is put at the reference point,
+                // with offsets to the right and down
+                let (x_fraction, y_fraction) = anchor_point_fraction(
+                    setting
+                        .anchor_point()
+                        .unwrap_or(AnchorPoint::BottomLeftAnchor),
+                );
+                (
+                    x + x_offset - width * x_fraction,
+                    y - y_offset - height * y_fraction,
+                )
+            }
+        };
+
+        state.anchored_objects.push(AnchoredObject {
+            item: item.clone(),
+            left,
+            bottom,
+        });
+    }
+}
+
+/// Area within the margins of a page of `page_width` by `page_height`,
+/// which is the whole page when it has no margins
+fn margin_box(
+    margins: &Option<MarginPreference>,
+    page_width: f64,
+    page_height: f64,
+) -> BoundingBox {
+    let margins = margins.clone().unwrap_or_default();
+    BoundingBox {
+        left: margins.left().unwrap_or(0_f64),
+        right: page_width - margins.right().unwrap_or(0_f64),
+        top: page_height - margins.top().unwrap_or(0_f64),
+        bottom: margins.bottom().unwrap_or(0_f64),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::idml_parser::IDMLResources;
+    use quick_xml::de::from_str;
+
+    extern "C" fn ignore_errors(_: HPDF_STATUS, _: HPDF_STATUS, _: HPDF_HANDLE) {}
+
+    /// A 40 by 20pt rectangle anchored with the attributes of its anchored object settings
+    fn rectangle(setting: &str) -> StoryContent {
+        from_str(&format!(
+            r#"<Rectangle Self="r1" ItemTransform="1 0 0 1 0 0">
+                <Properties><PathGeometry><GeometryPathType PathOpen="false"><PathPointArray>
+                    <PathPointType Anchor="0 0"/>
+                    <PathPointType Anchor="0 20"/>
+                    <PathPointType Anchor="40 20"/>
+                    <PathPointType Anchor="40 0"/>
+                </PathPointArray></GeometryPathType></PathGeometry></Properties>
+                <AnchoredObjectSetting {}/>
+            </Rectangle>"#,
+            setting
+        ))
+        .unwrap()
+    }
+
+    /// State of a horizontal frame from (100, 300) to (300, 500) with 36pt page margins,
+    /// with the current line 30pt down the frame
+    fn state() -> StoryState {
+        StoryState {
+            frame: BoundingBox {
+                left: 100_f64,
+                right: 300_f64,
+                top: 500_f64,
+                bottom: 300_f64,
+            },
+            y: 470_f64,
+            leading: 12_f64,
+            has_offset: true,
+            page_margins: Some(
+                from_str(r#"<MarginPreference Top="36" Bottom="36" Left="36" Right="36"/>"#)
+                    .unwrap(),
+            ),
+            ..StoryState::default()
+        }
+    }
+
+    /// Places the rectangle on a page 600pt wide and 800pt high,
+    /// returning its left and bottom edge, or None if it is not placed
+    fn place(setting: &str, state: &mut StoryState) -> Option<(f64, f64)> {
+        let resources = IDMLResources::with_graphic(
+            from_str(
+                r#"<idPkg:Graphic>
+                    <Color Self="Color/Black" Model="Process" Space="CMYK" ColorValue="0 0 0 100" Name="Black"/>
+                    <Swatch Self="Swatch/None" Name="None"/>
+                </idPkg:Graphic>"#,
+            )
+            .unwrap(),
+        );
+        let frame: Polygon = from_str(r#"<TextFrame Self="f1"/>"#).unwrap();
+        unsafe {
+            let pdf_doc = HPDF_New(ignore_errors, std::ptr::null_mut());
+            let page = HPDF_AddPage(pdf_doc);
+            HPDF_Page_SetWidth(page, 600_f32);
+            HPDF_Page_SetHeight(page, 800_f32);
+            let font_lib = FontLibrary::new(&resources, pdf_doc, &None).unwrap();
+            frame.render_anchored_object(
+                &rectangle(setting),
+                &RenderProperties::new(&resources),
+                &font_lib,
+                page,
+                state,
+            );
+            HPDF_Free(pdf_doc);
+        }
+        let placed = state.anchored_objects.last()?;
+        Some((placed.left, placed.bottom))
+    }
+
+    #[test]
+    fn anchor_points_are_fractions_of_the_item_size() {
+        assert_eq!(
+            anchor_point_fraction(AnchorPoint::TopLeftAnchor),
+            (0_f64, 1_f64)
+        );
+        assert_eq!(
+            anchor_point_fraction(AnchorPoint::CenterAnchor),
+            (0.5_f64, 0.5_f64)
+        );
+        assert_eq!(
+            anchor_point_fraction(AnchorPoint::BottomRightAnchor),
+            (1_f64, 0_f64)
+        );
+    }
+
+    #[test]
+    fn pages_without_margins_are_margin_boxes_of_their_own() {
+        let margins = margin_box(&None, 600_f64, 800_f64);
+        assert_eq!(
+            (margins.left, margins.right, margins.top, margins.bottom),
+            (0_f64, 600_f64, 800_f64, 0_f64)
+        );
+        let margins = margin_box(&state().page_margins, 600_f64, 800_f64);
+        assert_eq!(
+            (margins.left, margins.right, margins.top, margins.bottom),
+            (36_f64, 564_f64, 764_f64, 36_f64)
+        );
+    }
+
+    #[test]
+    fn custom_positions_put_the_anchor_point_at_the_reference_point() {
+        // The top left of the item goes to the top left of the page, moved by the offsets
+        let placed = place(
+            r#"AnchoredPosition="Anchored" AnchorPoint="TopLeftAnchor"
+                HorizontalReferencePoint="PageEdge" VerticalReferencePoint="PageEdge"
+                AnchorXoffset="10" AnchorYoffset="5""#,
+            &mut state(),
+        );
+        assert_eq!(placed, Some((10_f64, 775_f64)));
+
+        // Centered in the text frame
+        let placed = place(
+            r#"AnchoredPosition="Anchored" AnchorPoint="CenterAnchor"
+                HorizontalAlignment="CenterAlign" VerticalAlignment="CenterAlign"
+                HorizontalReferencePoint="TextFrame" VerticalReferencePoint="TextFrame""#,
+            &mut state(),
+        );
+        assert_eq!(placed, Some((180_f64, 390_f64)));
+
+        // In the bottom right corner of the page margins
+        let placed = place(
+            r#"AnchoredPosition="Anchored" AnchorPoint="BottomRightAnchor"
+                HorizontalAlignment="RightAlign" VerticalAlignment="BottomAlign"
+                HorizontalReferencePoint="PageMargins" VerticalReferencePoint="PageMargins""#,
+            &mut state(),
+        );
+        assert_eq!(placed, Some((524_f64, 36_f64)));
+
+        // Sitting on the baseline of the line it is anchored in
+        let placed = place(
+            r#"AnchoredPosition="Anchored" HorizontalReferencePoint="TextFrame""#,
+            &mut state(),
+        );
+        assert_eq!(placed, Some((100_f64, 470_f64)));
+    }
+
+    #[test]
+    fn items_above_the_line_take_up_lines_of_their_own() {
+        let mut state = state();
+        let placed = place(
+            r#"AnchoredPosition="AboveLine" HorizontalAlignment="CenterAlign"
+                AnchorSpaceAbove="4""#,
+            &mut state,
+        );
+        // The top of the item is the top of the line, less the space above
+        assert_eq!(placed, Some((180_f64, 455_f64)));
+        // The text goes on below the item
+        assert_eq!(state.y, 446_f64);
+    }
+
+    #[test]
+    fn items_past_the_frame_are_not_placed() {
+        let mut state = StoryState {
+            y: 310_f64,
+            ..state()
+        };
+        assert_eq!(place(r#"AnchoredPosition="AboveLine""#, &mut state), None);
+    }
+}
@@ -0,0 +1,51 @@
+use super::frame_fitting::FrameFitting;
+use super::image::draw_placed_graphic;
+use crate::idml_parser::spread_parser::*;
+use crate::pdf_printer::image_manager::ImageLibrary;
+use crate::pdf_printer::transforms::{self, Transform};
+use libharu_sys::*;
+
+impl Eps {
+    /// Draws the preview of the graphic at its place in the frame, or fitted to the frame if
+    /// `fitting` is given. `parent_transform` is the transform of the frame, as the graphic
+    /// is placed relative to it.
+    pub fn render(
+        &self,
+        parent_transform: &Transform,
+        fitting: Option<&FrameFitting>,
+        image_lib: &ImageLibrary,
+        current_page: HPDF_Page,
+    ) -> Result<(), String> {
+        if *self.visible() == Some(false) {
+            return Ok(());
+        }
+        // The bounds are those of the bounding box of the PostScript, which the preview covers
+        let (item_transform, bounds) = match fitting {
+            Some(fitting) => {
+                let (width, height) = image_lib.eps_size(self)?;
+                let bounds = (0_f64, 0_f64, width, height);
+                (fitting.transform(bounds), bounds)
+            }
+            None => {
+                let bounds = self
+                    .properties()
+                    .as_ref()
+                    .and_then(|properties| properties.graphic_bounds().as_ref())
+                    .ok_or(format!("EPS '{}' has no bounds", self.id()))?;
+                (
+                    transforms::from_vec(self.item_transform()),
+                    (
+                        *bounds.left(),
+                        *bounds.top(),
+                        *bounds.right(),
+                        *bounds.bottom(),
+                    ),
+                )
+            }
+        };
+        let preview = image_lib.eps_preview(self)?;
+        let transform = item_transform.combine_with(parent_transform);
+        draw_placed_graphic(preview, &transform, bounds, current_page);
+        Ok(())
+    }
+}
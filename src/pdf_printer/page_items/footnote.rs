@@ -0,0 +1,701 @@
+use super::textframe::{RenderProperties, StoryState};
+use crate::idml_parser::{
+    preferences_parser::FootnoteOption,
+    spread_parser::Polygon,
+    story_parser::{Footnote, StoryContent},
+    styles::commom_text_properties::Position,
+    IDMLResources,
+};
+use crate::pdf_printer::{
+    font_manager::FontLibrary,
+    pdf_utils::*,
+    story_layout::{StoryLayout, StoryPosition},
+    text_decorations::{Decoration, DecorationStroke},
+};
+use libharu_sys::*;
+
+/// A footnote with room set aside for it at the bottom of the frame
+#[derive(Debug, Clone)]
+pub struct PlacedFootnote {
+    footnote: Footnote,
+    /// Number at the start of the footnote text, with its prefix and suffix
+    number: String,
+    /// Where the text of the footnote set in the frame starts, which is further
+    /// into it for footnotes that go on from the frame before
+    start: StoryPosition,
+    /// Room set aside for the footnote, including the space above it
+    height: f64,
+    space_above: f64,
+}
+
+impl PlacedFootnote {
+    fn new(footnote: Footnote, number: String) -> PlacedFootnote {
+        PlacedFootnote {
+            footnote,
+            number,
+            start: StoryPosition::default(),
+            height: 0_f64,
+            space_above: 0_f64,
+        }
+    }
+
+    fn is_continued(&self) -> bool {
+        self.start != StoryPosition::default()
+    }
+
+    fn name(&self) -> String {
+        self.footnote
+            .id()
+            .clone()
+            .unwrap_or_else(|| self.number.clone())
+    }
+}
+
+/// Rule above the footnotes of a frame, which is the continuing rule
+/// when the first of them goes on from the frame before
+struct FootnoteRule {
+    on: bool,
+    weight: f64,
+    offset: f64,
+    left_indent: f64,
+    width: f64,
+    stroke: DecorationStroke,
+    color_id: String,
+    gap_color_id: Option<String>,
+}
+
+impl FootnoteRule {
+    fn from_option(option: &FootnoteOption, continuing: bool) -> FootnoteRule {
+        let properties = option.properties().clone().unwrap_or_default();
+        let (on, weight, offset, left_indent, width) = match continuing {
+            true => (
+                *option.continuing_rule_on(),
+                *option.continuing_rule_line_weight(),
+                *option.continuing_rule_offset(),
+                *option.continuing_rule_left_indent(),
+                *option.continuing_rule_width(),
+            ),
+            false => (
+                *option.rule_on(),
+                *option.rule_line_weight(),
+                *option.rule_offset(),
+                *option.rule_left_indent(),
+                *option.rule_width(),
+            ),
+        };
+        let (stroke, color_id, gap_color_id) = match continuing {
+            true => (
+                properties.continuing_rule_type().clone(),
+                properties.continuing_rule_color().clone(),
+                properties.continuing_rule_gap_color().clone(),
+            ),
+            false => (
+                properties.rule_type().clone(),
+                properties.rule_color().clone(),
+                properties.rule_gap_color().clone(),
+            ),
+        };
+        FootnoteRule {
+            on: on != Some(false),
+            weight: weight.unwrap_or(1_f64),
+            offset: offset.unwrap_or(0_f64),
+            left_indent: left_indent.unwrap_or(0_f64),
+            width: width.unwrap_or(72_f64),
+            stroke: stroke
+                .as_deref()
+                .map(DecorationStroke::from_id)
+                .unwrap_or(DecorationStroke::Solid),
+            color_id: color_id.unwrap_or("Color/Black".to_string()),
+            gap_color_id,
+        }
+    }
+
+    /// Room the rule takes up above the first footnote
+    fn height(&self) -> f64 {
+        match self.on {
+            true => self.weight + self.offset,
+            false => 0_f64,
+        }
+    }
+}
+
+fn footnote_option(idml_resources: &IDMLResources) -> FootnoteOption {
+    idml_resources
+        .preferences()
+        .footnote_option()
+        .clone()
+        .unwrap_or_default()
+}
+
+/// Whether the prefix and suffix go around the number in the text
+/// (`in_reference`) or at the start of the footnote itself
+fn with_prefix_suffix(option: &FootnoteOption, number: &str, in_reference: bool) -> String {
+    let show_prefix_suffix = option
+        .properties()
+        .as_ref()
+        .and_then(|properties| properties.show_prefix_suffix().clone())
+        .unwrap_or_default();
+    match (show_prefix_suffix.as_str(), in_reference) {
+        ("PrefixSuffixBoth", _)
+        | ("PrefixSuffixReference", true)
+        | ("PrefixSuffixFootnoteText", false) => format!(
+            "{}{}{}",
+            option.prefix().clone().unwrap_or_default(),
+            number,
+            option.suffix().clone().unwrap_or_default()
+        ),
+        _ => number.to_string(),
+    }
+}
+
+impl Polygon {
+    /// Sets the number referencing a footnote or endnote in the text,
+    /// and sets aside room for footnotes at the bottom of the frame
+    pub(super) fn render_note_reference(
+        &self,
+        content: &StoryContent,
+        render_properties: &RenderProperties,
+        font_lib: &FontLibrary,
+        current_page: HPDF_Page,
+        state: &mut StoryState,
+    ) -> Result<(), String> {
+        let idml_resources = render_properties.idml_resources;
+        let option = footnote_option(idml_resources);
+        let id = match content {
+            StoryContent::Footnote(footnote) => footnote.id(),
+            StoryContent::Endnote(endnote) => endnote.id(),
+            _ => return Ok(()),
+        };
+        if let Some(id) = id {
+            state.note_references.push(id.clone());
+        }
+        let number = id
+            .as_ref()
+            .and_then(|id| state.note_numbers.get(id))
+            .cloned()
+            .unwrap_or_default();
+
+        let mut marker_properties = render_properties.clone();
+        let marker_positioning = option
+            .properties()
+            .as_ref()
+            .and_then(|properties| properties.marker_positioning().clone());
+        if marker_positioning.as_deref() != Some("NormalMarker") {
+            marker_properties.with_position(Position::Superscript);
+        }
+        let marker = match content {
+            StoryContent::Footnote(_) => {
+                if let Some(style_id) = option.footnote_marker_style() {
+                    marker_properties.with_character_style(style_id);
+                }
+                with_prefix_suffix(&option, &number, true)
+            }
+            _ => number.clone(),
+        };
+        if !marker.is_empty() {
+            self.render_story_content(
+                &StoryContent::Content(marker),
+                &marker_properties,
+                font_lib,
+                current_page,
+                state,
+            )?;
+        }
+
+        if let StoryContent::Footnote(footnote) = content {
+            let placed = PlacedFootnote::new(
+                footnote.clone(),
+                with_prefix_suffix(&option, &number, false),
+            );
+            match state.in_table {
+                true => state.footnotes.push(placed),
+                false => {
+                    self.place_footnote(placed, idml_resources, font_lib, current_page, state)?
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Sets aside room for a footnote at the bottom of the frame, or at its left in
+    /// vertical stories, which the text after the line referencing the footnote can no
+    /// longer use. What does not fit in the room left goes on in the next frame.
+    pub(super) fn place_footnote(
+        &self,
+        mut placed: PlacedFootnote,
+        idml_resources: &IDMLResources,
+        font_lib: &FontLibrary,
+        current_page: HPDF_Page,
+        state: &mut StoryState,
+    ) -> Result<(), String> {
+        if state.is_past_frame() {
+            return Ok(());
+        }
+        let option = footnote_option(idml_resources);
+        placed.space_above = match state.footnotes.is_empty() {
+            // The rule above the footnotes sits in the space before the first one
+            true => {
+                option.spacer().unwrap_or(0_f64)
+                    + FootnoteRule::from_option(&option, placed.is_continued()).height()
+            }
+            false => option.space_between().unwrap_or(0_f64),
+        };
+
+        let measured = self.render_footnote_text(
+            &placed,
+            note_state(
+                &placed,
+                note_box(state, 0_f64, f64::NEG_INFINITY),
+                true,
+                state,
+            ),
+            idml_resources,
+            font_lib,
+            current_page,
+        )?;
+        let room = (state.text_end() - state.frame_end()).max(0_f64);
+        placed.height = (placed.space_above + measured.text_extent()).min(room);
+        match state.vertical {
+            true => state.frame.left += placed.height,
+            false => state.frame.bottom += placed.height,
+        }
+        state.footnotes.push(placed);
+        Ok(())
+    }
+
+    /// Sets aside room for the footnotes referenced in the cells of a table, once it is set
+    pub(super) fn place_table_footnotes(
+        &self,
+        footnotes: Vec<PlacedFootnote>,
+        idml_resources: &IDMLResources,
+        font_lib: &FontLibrary,
+        current_page: HPDF_Page,
+        state: &mut StoryState,
+    ) -> Result<(), String> {
+        for placed in footnotes {
+            self.place_footnote(placed, idml_resources, font_lib, current_page, state)?;
+        }
+        Ok(())
+    }
+
+    /// Sets the footnotes of the frame in the room set aside for them at the bottom of
+    /// it, below a rule, and returns the footnotes whose text does not all fit, set from
+    /// where they go on in the next frame. Must be called within a text object.
+    pub(super) fn render_footnotes(
+        &self,
+        idml_resources: &IDMLResources,
+        font_lib: &FontLibrary,
+        current_page: HPDF_Page,
+        state: &mut StoryState,
+    ) -> Result<Vec<PlacedFootnote>, String> {
+        let footnotes = std::mem::take(&mut state.footnotes);
+        let option = footnote_option(idml_resources);
+        let rule = FootnoteRule::from_option(
+            &option,
+            footnotes.first().is_some_and(PlacedFootnote::is_continued),
+        );
+
+        // Footnotes are set from the end of the frame the text took up,
+        // downwards, or to the left in vertical stories
+        let mut continued = vec![];
+        let mut top = state.frame_end();
+        for (i, placed) in footnotes.into_iter().enumerate() {
+            let bottom = top - placed.height;
+            if i == 0 && rule.on && !state.measuring {
+                let gap_color = rule
+                    .gap_color_id
+                    .as_ref()
+                    .and_then(|color_id| idml_resources.color_from_id(color_id).ok());
+                let rule_top = (top - option.spacer().unwrap_or(0_f64)).max(bottom);
+                if let Ok(color) = idml_resources.color_from_id(&rule.color_id) {
+                    let decoration = match state.vertical {
+                        true => {
+                            let start = state.frame.top - rule.left_indent;
+                            Decoration::rule(
+                                rule.stroke,
+                                rule.weight,
+                                color,
+                                gap_color,
+                                start,
+                                (start - rule.width).max(state.frame.bottom),
+                                rule_top,
+                            )
+                            .vertical()
+                        }
+                        false => {
+                            let start = state.frame.left + rule.left_indent;
+                            Decoration::rule(
+                                rule.stroke,
+                                rule.weight,
+                                color,
+                                gap_color,
+                                start,
+                                (start + rule.width).min(state.frame.right),
+                                rule_top,
+                            )
+                        }
+                    };
+                    state.decorations.push(decoration);
+                }
+            }
+
+            let text_top = top - placed.space_above;
+            let end = match text_top > bottom {
+                true => {
+                    let mut note_state = self.render_footnote_text(
+                        &placed,
+                        note_state(
+                            &placed,
+                            note_box(state, text_top, bottom),
+                            state.measuring,
+                            state,
+                        ),
+                        idml_resources,
+                        font_lib,
+                        current_page,
+                    )?;
+                    state.decorations.append(&mut note_state.decorations);
+                    state.text_runs.append(&mut note_state.text_runs);
+                    state.gradient_runs.append(&mut note_state.gradient_runs);
+                    state
+                        .anchored_objects
+                        .append(&mut note_state.anchored_objects);
+                    state.link_areas.append(&mut note_state.link_areas);
+                    state.anchors.append(&mut note_state.anchors);
+                    state
+                        .condition_areas
+                        .append(&mut note_state.condition_areas);
+                    note_state.end
+                }
+                false => Some(placed.start),
+            };
+            if let Some(start) = end {
+                continued.push(PlacedFootnote {
+                    start,
+                    ..PlacedFootnote::new(placed.footnote, placed.number)
+                });
+            }
+            top = bottom;
+        }
+        Ok(continued)
+    }
+
+    /// Sets the footnotes that did not fit the frame threaded before this one
+    /// at the bottom of this frame, ahead of the footnotes of its own text
+    pub(super) fn place_continued_footnotes(
+        &self,
+        idml_resources: &IDMLResources,
+        font_lib: &FontLibrary,
+        current_page: HPDF_Page,
+        state: &mut StoryState,
+        layout: &StoryLayout,
+    ) -> Result<(), String> {
+        let previous_id = match self.previous_text_frame().as_deref() {
+            Some(id) if id != "n" => id,
+            _ => return Ok(()),
+        };
+        for placed in layout.continued_footnotes(previous_id) {
+            self.place_footnote(placed, idml_resources, font_lib, current_page, state)?;
+        }
+        Ok(())
+    }
+
+    /// Notes the footnotes whose text goes on in the next frame of the thread, and
+    /// warns about the ones that are cut off as no frame is threaded after this one
+    pub(super) fn continue_footnotes(
+        &self,
+        continued: Vec<PlacedFootnote>,
+        state: &StoryState,
+        layout: &mut StoryLayout,
+    ) {
+        let has_next_frame = matches!(self.next_text_frame().as_deref(), Some(id) if id != "n");
+        if !has_next_frame && !state.measuring {
+            for placed in &continued {
+                println!(
+                    "Footnote '{}' does not fit in frame '{}', which has no frame threaded after it. The rest of it is left out.",
+                    placed.name(),
+                    self.id()
+                );
+            }
+        }
+        layout.set_continued_footnotes(self.id(), continued);
+    }
+
+    /// Composes the text of a footnote in `note_state`, or only measures it,
+    /// returning the state the text ends in
+    fn render_footnote_text(
+        &self,
+        placed: &PlacedFootnote,
+        mut note_state: StoryState,
+        idml_resources: &IDMLResources,
+        font_lib: &FontLibrary,
+        current_page: HPDF_Page,
+    ) -> Result<StoryState, String> {
+        // Footnote text does not take on the formats of the paragraph referencing it
+        let render_properties = RenderProperties::new(idml_resources);
+        self.render_paragraphs(
+            placed
+                .footnote
+                .paragraph_style_ranges()
+                .as_deref()
+                .unwrap_or_default(),
+            &render_properties,
+            idml_resources,
+            font_lib,
+            current_page,
+            &mut note_state,
+        )?;
+        Ok(note_state)
+    }
+}
+
+/// Box the text of a footnote is set in, from `top` to `bottom` across the
+/// lines of the frame, which are the right and left of it in vertical stories
+fn note_box(state: &StoryState, top: f64, bottom: f64) -> BoundingBox {
+    match state.vertical {
+        true => BoundingBox {
+            right: top,
+            left: bottom,
+            ..state.frame.clone()
+        },
+        false => BoundingBox {
+            top,
+            bottom,
+            ..state.frame.clone()
+        },
+    }
+}
+
+/// State the text of a footnote is set from within `text_frame`, where it
+/// continues from where it was left off in the previous frame
+fn note_state(
+    placed: &PlacedFootnote,
+    text_frame: BoundingBox,
+    measuring: bool,
+    state: &StoryState,
+) -> StoryState {
+    StoryState {
+        y: text_frame.top,
+        column: text_frame.right,
+        frame: text_frame,
+        vertical: state.vertical,
+        measuring,
+        start: placed.start,
+        page_number: state.page_number.clone(),
+        page_margins: state.page_margins.clone(),
+        text_variables: state.text_variables.clone(),
+        note_number: Some(placed.number.clone()),
+        cross_references: state.cross_references.clone(),
+        ..StoryState::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use quick_xml::de::from_str;
+
+    extern "C" fn ignore_errors(_: HPDF_STATUS, _: HPDF_STATUS, _: HPDF_HANDLE) {}
+
+    fn option(xml: &str) -> FootnoteOption {
+        from_str(xml).unwrap()
+    }
+
+    /// State of a horizontal frame from (100, 300) to (300, 500),
+    /// with the current line 30pt down the frame
+    fn state() -> StoryState {
+        StoryState {
+            frame: BoundingBox {
+                left: 100_f64,
+                right: 300_f64,
+                top: 500_f64,
+                bottom: 300_f64,
+            },
+            y: 470_f64,
+            leading: 12_f64,
+            has_offset: true,
+            ..StoryState::default()
+        }
+    }
+
+    /// Sets aside room for each of the footnotes, which have no text, in turn
+    fn place(footnotes: Vec<PlacedFootnote>, preferences: &str, state: &mut StoryState) {
+        let resources = IDMLResources::with_graphic(
+            from_str(
+                r#"<idPkg:Graphic>
+                    <Color Self="Color/Black" Model="Process" Space="CMYK" ColorValue="0 0 0 100" Name="Black"/>
+                    <Swatch Self="Swatch/None" Name="None"/>
+                </idPkg:Graphic>"#,
+            )
+            .unwrap(),
+        )
+        .with_preferences(from_str(preferences).unwrap());
+        let frame: Polygon = from_str(r#"<TextFrame Self="f1"/>"#).unwrap();
+        unsafe {
+            let pdf_doc = HPDF_New(ignore_errors, std::ptr::null_mut());
+            let page = HPDF_AddPage(pdf_doc);
+            let font_lib = FontLibrary::new(&resources, pdf_doc, &None).unwrap();
+            for placed in footnotes {
+                frame
+                    .place_footnote(placed, &resources, &font_lib, page, state)
+                    .unwrap();
+            }
+            HPDF_Free(pdf_doc);
+        }
+    }
+
+    fn footnote(id: &str) -> PlacedFootnote {
+        PlacedFootnote::new(
+            from_str(&format!(r#"<Footnote Self="{}"/>"#, id)).unwrap(),
+            "1".to_string(),
+        )
+    }
+
+    #[test]
+    fn rules_default_to_a_one_point_black_line() {
+        let rule = FootnoteRule::from_option(&FootnoteOption::default(), false);
+        assert!(rule.on);
+        assert_eq!(
+            (rule.weight, rule.offset, rule.left_indent, rule.width),
+            (1_f64, 0_f64, 0_f64, 72_f64)
+        );
+        assert_eq!(rule.stroke, DecorationStroke::Solid);
+        assert_eq!(rule.color_id, "Color/Black");
+        assert_eq!(rule.gap_color_id, None);
+        assert_eq!(rule.height(), 1_f64);
+    }
+
+    #[test]
+    fn continued_footnotes_take_the_continuing_rule() {
+        let option = option(
+            r#"<FootnoteOption RuleLineWeight="2" RuleOffset="3" RuleWidth="100"
+                ContinuingRuleOn="false" ContinuingRuleLineWeight="4" ContinuingRuleWidth="200">
+                <Properties>
+                    <RuleType>StrokeStyle/$ID/Dashed</RuleType>
+                    <RuleColor>Color/Red</RuleColor>
+                    <ContinuingRuleColor>Color/Blue</ContinuingRuleColor>
+                </Properties>
+            </FootnoteOption>"#,
+        );
+        let rule = FootnoteRule::from_option(&option, false);
+        assert_eq!((rule.weight, rule.width), (2_f64, 100_f64));
+        assert_eq!(
+            rule.stroke,
+            DecorationStroke::from_id("StrokeStyle/$ID/Dashed")
+        );
+        assert_eq!(rule.color_id, "Color/Red");
+        assert_eq!(rule.height(), 5_f64);
+
+        let rule = FootnoteRule::from_option(&option, true);
+        assert!(!rule.on);
+        assert_eq!((rule.weight, rule.width), (4_f64, 200_f64));
+        assert_eq!(rule.stroke, DecorationStroke::Solid);
+        assert_eq!(rule.color_id, "Color/Blue");
+        assert_eq!(rule.height(), 0_f64);
+    }
+
+    #[test]
+    fn prefix_and_suffix_go_where_the_options_show_them() {
+        let shown = |show_prefix_suffix: &str| {
+            let option = option(&format!(
+                r#"<FootnoteOption Prefix="[" Suffix="]">
+                    <Properties><ShowPrefixSuffix>{}</ShowPrefixSuffix></Properties>
+                </FootnoteOption>"#,
+                show_prefix_suffix
+            ));
+            (
+                with_prefix_suffix(&option, "3", true),
+                with_prefix_suffix(&option, "3", false),
+            )
+        };
+        assert_eq!(
+            shown("PrefixSuffixBoth"),
+            ("[3]".to_string(), "[3]".to_string())
+        );
+        assert_eq!(
+            shown("PrefixSuffixReference"),
+            ("[3]".to_string(), "3".to_string())
+        );
+        assert_eq!(
+            shown("PrefixSuffixFootnoteText"),
+            ("3".to_string(), "[3]".to_string())
+        );
+        assert_eq!(shown("NoPrefixSuffix"), ("3".to_string(), "3".to_string()));
+    }
+
+    #[test]
+    fn footnotes_are_set_across_the_lines_of_the_frame() {
+        let mut state = state();
+        let text_frame = note_box(&state, 320_f64, 300_f64);
+        assert_eq!(
+            (
+                text_frame.left,
+                text_frame.right,
+                text_frame.top,
+                text_frame.bottom
+            ),
+            (100_f64, 300_f64, 320_f64, 300_f64)
+        );
+
+        state.vertical = true;
+        let text_frame = note_box(&state, 120_f64, 100_f64);
+        assert_eq!(
+            (
+                text_frame.left,
+                text_frame.right,
+                text_frame.top,
+                text_frame.bottom
+            ),
+            (100_f64, 120_f64, 500_f64, 300_f64)
+        );
+    }
+
+    #[test]
+    fn room_above_the_first_footnote_holds_the_spacer_and_rule() {
+        let mut state = state();
+        place(
+            vec![footnote("n1"), footnote("n2")],
+            r#"<idPkg:Preferences>
+                <FootnoteOption Spacer="6" SpaceBetween="4" RuleLineWeight="2" RuleOffset="3"/>
+            </idPkg:Preferences>"#,
+            &mut state,
+        );
+        let heights: Vec<(f64, f64)> = state
+            .footnotes
+            .iter()
+            .map(|placed| (placed.space_above, placed.height))
+            .collect();
+        assert_eq!(heights, vec![(11_f64, 11_f64), (4_f64, 4_f64)]);
+        assert_eq!(state.frame.bottom, 315_f64);
+    }
+
+    #[test]
+    fn footnotes_take_no_more_room_than_the_text_leaves() {
+        // The text ends 3pt above the bottom of the frame
+        let mut state = StoryState {
+            y: 306_f64,
+            ..state()
+        };
+        place(
+            vec![footnote("n1")],
+            r#"<idPkg:Preferences>
+                <FootnoteOption Spacer="6"/>
+            </idPkg:Preferences>"#,
+            &mut state,
+        );
+        assert_eq!(state.footnotes[0].height, 3_f64);
+        assert_eq!(state.frame.bottom, 303_f64);
+    }
+
+    #[test]
+    fn continued_footnotes_are_named_by_their_id() {
+        let mut placed = footnote("n1");
+        assert!(!placed.is_continued());
+        placed.start = StoryPosition {
+            content: 0,
+            offset: 5,
+        };
+        assert!(placed.is_continued());
+        assert_eq!(placed.name(), "n1");
+    }
+}
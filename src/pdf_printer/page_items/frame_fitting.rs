@@ -0,0 +1,209 @@
+use super::anchored_object::anchor_point_fraction;
+use crate::idml_parser::spread_parser::*;
+use crate::pdf_printer::transforms::{self, Transform};
+
+/// Places graphics in a frame as its frame fitting options say
+#[derive(Debug, Clone)]
+pub struct FrameFitting {
+    option: FrameFittingOption,
+    /// Left, top, right and bottom of the frame in its own coordinates
+    frame_bounds: (f64, f64, f64, f64),
+}
+
+impl Polygon {
+    /// How graphics are fitted to the frame, or None if they are placed by their own transform.
+    /// InDesign fits a graphic when it is placed, so only replaced graphics and graphics in frames
+    /// that fit them automatically are fitted again, as their size may have changed since.
+    pub fn frame_fitting(&self) -> Option<FrameFitting> {
+        let option = self.frame_fitting_option().clone().unwrap_or_default();
+        let auto_fit = *option.auto_fit() == Some(true)
+            && option
+                .fitting_on_empty_frame()
+                .map_or(false, |fitting| fitting != EmptyFrameFitting::None);
+        if !*self.content_replaced() && !auto_fit {
+            return None;
+        }
+        Some(FrameFitting {
+            option,
+            frame_bounds: self.frame_bounds()?,
+        })
+    }
+}
+
+impl FrameFitting {
+    /// Transform placing a graphic with the bounds in the frame. The graphic is fitted to the
+    /// frame grown by the crop amounts, and aligned to its reference point.
+    pub fn transform(&self, (left, top, right, bottom): (f64, f64, f64, f64)) -> Transform {
+        let option = &self.option;
+        let crop = |amount: &Option<f64>| amount.unwrap_or(0_f64);
+        let (frame_left, frame_top, frame_right, frame_bottom) = self.frame_bounds;
+        let area_left = frame_left - crop(option.left_crop());
+        let area_top = frame_top - crop(option.top_crop());
+        let area_width = frame_right + crop(option.right_crop()) - area_left;
+        let area_height = frame_bottom + crop(option.bottom_crop()) - area_top;
+        let (width, height) = (right - left, bottom - top);
+        if width <= 0_f64 || height <= 0_f64 {
+            return transforms::identity();
+        }
+
+        let fitting = option
+            .fitting_on_empty_frame()
+            .unwrap_or(EmptyFrameFitting::None);
+        let (x_scale, y_scale) = match fitting {
+            EmptyFrameFitting::None | EmptyFrameFitting::CenterContent => (1_f64, 1_f64),
+            EmptyFrameFitting::ContentToFrame => (area_width / width, area_height / height),
+            EmptyFrameFitting::Proportionally => {
+                let scale = f64::min(area_width / width, area_height / height);
+                (scale, scale)
+            }
+            EmptyFrameFitting::FillProportionally | EmptyFrameFitting::ContentAwareFit => {
+                let scale = f64::max(area_width / width, area_height / height);
+                (scale, scale)
+            }
+        };
+        // The fraction is measured from the bottom, while the frame runs down from the top
+        let (x_fraction, y_fraction) = match fitting {
+            EmptyFrameFitting::CenterContent => (0.5_f64, 0.5_f64),
+            _ => {
+                let anchor_point = option
+                    .fitting_alignment()
+                    .unwrap_or(AnchorPoint::TopLeftAnchor);
+                let (x_fraction, y_fraction) = anchor_point_fraction(anchor_point);
+                (x_fraction, 1_f64 - y_fraction)
+            }
+        };
+        transforms::from_values(
+            x_scale,
+            0_f64,
+            0_f64,
+            y_scale,
+            area_left + (area_width - width * x_scale) * x_fraction - left * x_scale,
+            area_top + (area_height - height * y_scale) * y_fraction - top * y_scale,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use quick_xml::de::from_str;
+
+    /// A frame 100pt wide and 50pt high with the frame fitting option attributes
+    fn frame(option: &str) -> Polygon {
+        from_str(&format!(
+            r#"<Rectangle Self="r1">
+                <Properties><PathGeometry><GeometryPathType PathOpen="false"><PathPointArray>
+                    <PathPointType Anchor="0 0"/>
+                    <PathPointType Anchor="0 50"/>
+                    <PathPointType Anchor="100 50"/>
+                    <PathPointType Anchor="100 0"/>
+                </PathPointArray></GeometryPathType></PathGeometry></Properties>
+                <FrameFittingOption {}/>
+            </Rectangle>"#,
+            option
+        ))
+        .unwrap()
+    }
+
+    /// Where the top left and bottom right corners of a graphic with the bounds go in the frame
+    fn corners(option: &str, bounds: (f64, f64, f64, f64)) -> (Vec<f64>, Vec<f64>) {
+        let mut frame = frame(option);
+        frame.replace_content("file:///graphic.png");
+        let transform = frame.frame_fitting().unwrap().transform(bounds);
+        let (left, top, right, bottom) = bounds;
+        (
+            transform.apply_to_point(&left, &top),
+            transform.apply_to_point(&right, &bottom),
+        )
+    }
+
+    #[test]
+    fn graphics_are_only_fitted_again_when_replaced_or_fitted_automatically() {
+        assert!(frame(r#"FittingOnEmptyFrame="ContentToFrame""#)
+            .frame_fitting()
+            .is_none());
+        assert!(frame(r#"AutoFit="true" FittingOnEmptyFrame="None""#)
+            .frame_fitting()
+            .is_none());
+        assert!(
+            frame(r#"AutoFit="true" FittingOnEmptyFrame="ContentToFrame""#)
+                .frame_fitting()
+                .is_some()
+        );
+
+        let mut replaced = frame("");
+        replaced.replace_content("file:///graphic.svg");
+        assert!(replaced.frame_fitting().is_some());
+    }
+
+    #[test]
+    fn content_to_frame_stretches_the_graphic_over_the_frame() {
+        assert_eq!(
+            corners(
+                r#"FittingOnEmptyFrame="ContentToFrame""#,
+                (0_f64, 0_f64, 20_f64, 10_f64)
+            ),
+            (vec![0_f64, 0_f64], vec![100_f64, 50_f64])
+        );
+    }
+
+    #[test]
+    fn proportional_fitting_keeps_the_shape_of_the_graphic() {
+        // Fitted inside the frame, centered across it
+        assert_eq!(
+            corners(
+                r#"FittingOnEmptyFrame="Proportionally" FittingAlignment="CenterAnchor""#,
+                (0_f64, 0_f64, 20_f64, 20_f64)
+            ),
+            (vec![25_f64, 0_f64], vec![75_f64, 50_f64])
+        );
+        // Filling the frame from its top left corner, reaching past its bottom
+        assert_eq!(
+            corners(
+                r#"FittingOnEmptyFrame="FillProportionally""#,
+                (0_f64, 0_f64, 20_f64, 20_f64)
+            ),
+            (vec![0_f64, 0_f64], vec![100_f64, 100_f64])
+        );
+    }
+
+    #[test]
+    fn graphics_kept_at_their_size_are_aligned_to_the_reference_point() {
+        assert_eq!(
+            corners(
+                r#"FittingOnEmptyFrame="None" FittingAlignment="BottomRightAnchor""#,
+                (5_f64, 5_f64, 25_f64, 15_f64)
+            ),
+            (vec![80_f64, 40_f64], vec![100_f64, 50_f64])
+        );
+        assert_eq!(
+            corners(
+                r#"FittingOnEmptyFrame="CenterContent" FittingAlignment="TopLeftAnchor""#,
+                (0_f64, 0_f64, 20_f64, 10_f64)
+            ),
+            (vec![40_f64, 20_f64], vec![60_f64, 30_f64])
+        );
+    }
+
+    #[test]
+    fn crops_grow_the_area_the_graphic_is_fitted_to() {
+        assert_eq!(
+            corners(
+                r#"FittingOnEmptyFrame="ContentToFrame" LeftCrop="10" TopCrop="5""#,
+                (0_f64, 0_f64, 20_f64, 10_f64)
+            ),
+            (vec![-10_f64, -5_f64], vec![100_f64, 50_f64])
+        );
+    }
+
+    #[test]
+    fn graphics_without_size_are_left_in_place() {
+        assert_eq!(
+            corners(
+                r#"FittingOnEmptyFrame="ContentToFrame""#,
+                (10_f64, 10_f64, 10_f64, 30_f64)
+            ),
+            (vec![10_f64, 10_f64], vec![10_f64, 30_f64])
+        );
+    }
+}
@@ -0,0 +1,89 @@
+use crate::idml_parser::spread_parser::*;
+use crate::idml_parser::IDMLPackage;
+use crate::pdf_printer::font_manager::FontLibrary;
+use crate::pdf_printer::image_manager::ImageLibrary;
+use crate::pdf_printer::navigation::Navigation;
+use crate::pdf_printer::page_numbering::PageNumber;
+use crate::pdf_printer::pdf_utils::*;
+use crate::pdf_printer::story_layout::StoryLayout;
+use crate::pdf_printer::transforms::{self, Transform};
+use libharu_sys::*;
+use std::collections::HashMap;
+
+impl Group {
+    /// Renders the items of the group, placed by the transform of the group
+    pub fn render(
+        &self,
+        parent_transform: &Transform,
+        idml_package: &IDMLPackage,
+        font_lib: &FontLibrary,
+        image_lib: &ImageLibrary,
+        current_page: HPDF_Page,
+        page_number: &PageNumber,
+        text_variables: &HashMap<String, String>,
+        note_numbers: &HashMap<String, String>,
+        navigation: &mut Navigation,
+        layout: &mut StoryLayout,
+    ) -> Result<(), String> {
+        let mut transform =
+            transforms::from_vec(self.item_transform()).combine_with(parent_transform);
+        for content in self.contents().iter().flatten() {
+            match content {
+                SpreadContent::Polygon(polygon) => {
+                    if !layout.is_measuring() {
+                        polygon.render(
+                            &transform,
+                            idml_package.resources(),
+                            font_lib,
+                            image_lib,
+                            current_page,
+                        )?;
+                    }
+                    polygon.render_story(
+                        idml_package,
+                        &mut transform,
+                        font_lib,
+                        image_lib,
+                        current_page,
+                        page_number,
+                        text_variables,
+                        note_numbers,
+                        navigation,
+                        layout,
+                    )?;
+                }
+                SpreadContent::Group(group) => group.render(
+                    &transform,
+                    idml_package,
+                    font_lib,
+                    image_lib,
+                    current_page,
+                    page_number,
+                    text_variables,
+                    note_numbers,
+                    navigation,
+                    layout,
+                )?,
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Smallest box containing all the items of the group
+    pub fn boundingbox(&self, parent_transform: &Transform) -> Option<BoundingBox> {
+        let transform = transforms::from_vec(self.item_transform()).combine_with(parent_transform);
+        self.contents()
+            .iter()
+            .flatten()
+            .filter_map(|content| match content {
+                SpreadContent::Polygon(polygon) => try_boundingbox(polygon, &transform),
+                SpreadContent::Group(group) => group.boundingbox(&transform),
+                _ => None,
+            })
+            .fold(None, |bounds: Option<BoundingBox>, bb| match bounds {
+                Some(bounds) => Some(bounds.union(&bb)),
+                None => Some(bb),
+            })
+    }
+}
@@ -0,0 +1,261 @@
+use super::frame_fitting::FrameFitting;
+use crate::idml_parser::spread_parser::*;
+use crate::pdf_printer::clipping::{self, PhotoshopPath};
+use crate::pdf_printer::image_manager::ImageLibrary;
+use crate::pdf_printer::transforms::{self, Transform};
+use libharu_sys::*;
+
+impl Image {
+    /// Draws the image at its place in the frame, or fitted to the frame if `fitting` is given.
+    /// `parent_transform` is the transform of the frame, as the image is placed relative to it.
+    pub fn render(
+        &self,
+        parent_transform: &Transform,
+        fitting: Option<&FrameFitting>,
+        image_lib: &ImageLibrary,
+        current_page: HPDF_Page,
+    ) -> Result<(), String> {
+        if *self.visible() == Some(false) {
+            return Ok(());
+        }
+        let (x_ppi, y_ppi) = match self.actual_ppi().as_deref() {
+            Some(&[x_ppi, y_ppi]) if x_ppi > 0_f64 && y_ppi > 0_f64 => (x_ppi, y_ppi),
+            _ => (72_f64, 72_f64),
+        };
+        let (item_transform, bounds) = match fitting {
+            // The image is fitted from its size at its resolution
+            Some(fitting) => {
+                let (width, height) = image_lib.image_pixel_size(self)?;
+                let bounds = (
+                    0_f64,
+                    0_f64,
+                    width as f64 * 72_f64 / x_ppi,
+                    height as f64 * 72_f64 / y_ppi,
+                );
+                (fitting.transform(bounds), Some(bounds))
+            }
+            None => (
+                transforms::from_vec(self.item_transform()),
+                self.properties()
+                    .as_ref()
+                    .and_then(|properties| properties.graphic_bounds().as_ref())
+                    .map(|bounds| {
+                        (
+                            *bounds.left(),
+                            *bounds.top(),
+                            *bounds.right(),
+                            *bounds.bottom(),
+                        )
+                    }),
+            ),
+        };
+        let transform = item_transform.combine_with(parent_transform);
+
+        // Size of the image on the page, which it may be downsampled for
+        let placed_size = bounds.map(|(left, top, right, bottom)| {
+            let origin = transform.apply_to_point(&left, &top);
+            let x_axis = transform.apply_to_point(&right, &top);
+            let y_axis = transform.apply_to_point(&left, &bottom);
+            (
+                (x_axis[0] - origin[0]).hypot(x_axis[1] - origin[1]),
+                (y_axis[0] - origin[0]).hypot(y_axis[1] - origin[1]),
+            )
+        });
+        let pdf_image = image_lib.pdf_image(self, placed_size)?;
+
+        // Without bounds the image is as large as its pixels at its resolution
+        let (left, top, right, bottom) = match bounds {
+            Some(bounds) => bounds,
+            None => {
+                let (width, height) = unsafe {
+                    (
+                        HPDF_Image_GetWidth(pdf_image) as f64,
+                        HPDF_Image_GetHeight(pdf_image) as f64,
+                    )
+                };
+                (
+                    0_f64,
+                    0_f64,
+                    width * 72_f64 / x_ppi,
+                    height * 72_f64 / y_ppi,
+                )
+            }
+        };
+
+        let settings = self.clipping_path_settings().clone().unwrap_or_default();
+        let clipping_path = match settings.clipping_type() {
+            Some(ClippingPathType::PhotoshopPath) => match image_lib.photoshop_path(self) {
+                Ok(Some(path)) => Some(photoshop_subpaths(
+                    &path,
+                    &transform,
+                    (left, top, right, bottom),
+                    settings.inset_frame().unwrap_or(0_f64),
+                )),
+                Ok(None) => {
+                    println!(
+                        "Warning: Image '{}' has no Photoshop path to clip to",
+                        self.id()
+                    );
+                    None
+                }
+                Err(err) => {
+                    println!(
+                        "Warning: Photoshop path of image '{}' not read: {}",
+                        self.id(),
+                        err
+                    );
+                    None
+                }
+            },
+            Some(ClippingPathType::UserModifiedPath) => match settings.clipping_path_geometry() {
+                Some(geometry) => Some(edited_subpaths(geometry, &transform)),
+                None => {
+                    println!(
+                        "Warning: Image '{}' has no edited clipping path to clip to",
+                        self.id()
+                    );
+                    None
+                }
+            },
+            _ => None,
+        };
+
+        unsafe {
+            HPDF_Page_GSave(current_page);
+            if let Some(subpaths) = &clipping_path {
+                add_clipping_path(
+                    current_page,
+                    subpaths,
+                    &transform,
+                    (left, top, right, bottom),
+                    settings.invert_path().unwrap_or(false),
+                );
+                HPDF_Page_Eoclip(current_page);
+                HPDF_Page_EndPath(current_page);
+            }
+            draw_placed_graphic(
+                pdf_image,
+                &transform,
+                (left, top, right, bottom),
+                current_page,
+            );
+            HPDF_Page_GRestore(current_page);
+        }
+        Ok(())
+    }
+}
+
+/// The subpaths of the Photoshop path on the page, stretched over the bounds of the image
+/// and moved inwards by `inset`
+fn photoshop_subpaths(
+    path: &PhotoshopPath,
+    transform: &Transform,
+    (left, top, right, bottom): (f64, f64, f64, f64),
+    inset: f64,
+) -> Vec<Vec<clipping::Knot>> {
+    let on_page = |(x, y): (f64, f64)| {
+        let point =
+            transform.apply_to_point(&(left + x * (right - left)), &(top + y * (bottom - top)));
+        (point[0], point[1])
+    };
+    path.subpaths
+        .iter()
+        .map(|subpath| {
+            let knots: Vec<clipping::Knot> = subpath
+                .iter()
+                .map(|knot| clipping::Knot {
+                    preceding: on_page(knot.preceding),
+                    anchor: on_page(knot.anchor),
+                    leaving: on_page(knot.leaving),
+                })
+                .collect();
+            clipping::inset_subpath(&knots, inset)
+        })
+        .collect()
+}
+
+/// The subpaths of an edited clipping path on the page. Its points are where the path
+/// ends up, so it is not moved by the inset of the clipping path settings.
+fn edited_subpaths(geometry: &PathGeometry, transform: &Transform) -> Vec<Vec<clipping::Knot>> {
+    let on_page = |point: &Option<Vec<f64>>, anchor: &[f64]| {
+        let point = match point.as_deref() {
+            Some(&[x, y]) => transform.apply_to_point(&x, &y),
+            _ => transform.apply_to_point(&anchor[0], &anchor[1]),
+        };
+        (point[0], point[1])
+    };
+    geometry
+        .geometry_path_type()
+        .path_point_arrays()
+        .iter()
+        .map(|path_point_array| {
+            path_point_array
+                .path_point_array()
+                .iter()
+                .filter_map(|point| match point.anchor().as_deref() {
+                    Some(anchor @ &[_, _]) => Some(clipping::Knot {
+                        preceding: on_page(point.left_direction(), anchor),
+                        anchor: on_page(point.anchor(), anchor),
+                        leaving: on_page(point.right_direction(), anchor),
+                    }),
+                    _ => None,
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// Adds the subpaths of a clipping path to the page. When the path is inverted the bounds
+/// of the image are added around it, so clipping by the even-odd rule shows what is outside
+/// the path.
+unsafe fn add_clipping_path(
+    current_page: HPDF_Page,
+    subpaths: &[Vec<clipping::Knot>],
+    transform: &Transform,
+    (left, top, right, bottom): (f64, f64, f64, f64),
+    invert: bool,
+) {
+    for subpath in subpaths {
+        clipping::add_subpath(current_page, subpath);
+    }
+
+    if invert {
+        let corners = [(left, top), (right, top), (right, bottom), (left, bottom)];
+        let point = transform.apply_to_point(&corners[0].0, &corners[0].1);
+        HPDF_Page_MoveTo(current_page, point[0] as f32, point[1] as f32);
+        for (x, y) in &corners[1..] {
+            let point = transform.apply_to_point(x, y);
+            HPDF_Page_LineTo(current_page, point[0] as f32, point[1] as f32);
+        }
+        HPDF_Page_ClosePath(current_page);
+    }
+}
+
+/// Draws an image or a form filling the unit square, such as placed graphics are
+/// in the PDF, over `bounds` in the coordinates of `transform`. As PDF images have
+/// their first row at the top, we map the corners of the unit square to the bottom
+/// left, bottom right and top left corner of the bounds.
+pub(super) fn draw_placed_graphic(
+    xobject: HPDF_Image,
+    transform: &Transform,
+    (left, top, right, bottom): (f64, f64, f64, f64),
+    current_page: HPDF_Page,
+) {
+    let origin = transform.apply_to_point(&left, &bottom);
+    let x_axis = transform.apply_to_point(&right, &bottom);
+    let y_axis = transform.apply_to_point(&left, &top);
+    unsafe {
+        HPDF_Page_GSave(current_page);
+        HPDF_Page_Concat(
+            current_page,
+            (x_axis[0] - origin[0]) as f32,
+            (x_axis[1] - origin[1]) as f32,
+            (y_axis[0] - origin[0]) as f32,
+            (y_axis[1] - origin[1]) as f32,
+            origin[0] as f32,
+            origin[1] as f32,
+        );
+        HPDF_Page_DrawImage(current_page, xobject, 0_f32, 0_f32, 1_f32, 1_f32);
+        HPDF_Page_GRestore(current_page);
+    }
+}
@@ -0,0 +1,450 @@
+use super::textframe::{GradientRun, StoryState};
+use crate::idml_parser::styles::commom_text_properties::Justification;
+use crate::pdf_printer::{
+    cjk_layout::{Adornments, Kenten, LineSpan, Ruby, Warichu},
+    color_manager::{Color, Gradient},
+    gradients::GradientPlacement,
+    pdf_utils::*,
+    text_decorations::Decoration,
+    text_layout::{self, FontEncoding, GlyphTransforms, PositionedGlyph, EM_BOX_ASCENT},
+};
+use libharu_sys::*;
+use std::rc::Rc;
+
+/// Font the glyphs of a run are drawn with
+#[derive(Debug, Clone)]
+pub(super) struct RunFont {
+    pub(super) font: HPDF_Font,
+    pub(super) encoding: Rc<FontEncoding>,
+    pub(super) font_data: Option<Rc<Vec<u8>>>,
+}
+
+/// Text drawn along with a run once the run is placed on its line
+#[derive(Debug, Clone)]
+pub(super) enum RunAdornment {
+    /// Ruby of a text of `run_length` glyphs, shared out over the lines it is set on
+    Ruby {
+        ruby: Ruby,
+        run_length: usize,
+    },
+    Kenten(Kenten),
+    /// Inline note set in the room the run takes up
+    Warichu {
+        warichu: Warichu,
+        lines: Vec<Vec<PositionedGlyph>>,
+    },
+    TateChuYoko {
+        text: String,
+        offset: (f64, f64),
+    },
+}
+
+/// Glyphs set on the current line, with what is needed to draw them. The runs of a line
+/// are placed once the line is complete, so the line is put in visual order and aligned
+/// as a whole.
+#[derive(Debug, Clone)]
+pub(super) struct LineRun {
+    /// Glyphs in logical order
+    pub(super) glyphs: Vec<PositionedGlyph>,
+    /// `None` for room that nothing is drawn in
+    pub(super) font: Option<RunFont>,
+    pub(super) fill_color: Option<Color>,
+    pub(super) stroke_color: Option<Color>,
+    pub(super) gradient: Option<(Gradient, GradientPlacement)>,
+    pub(super) transforms: GlyphTransforms,
+    pub(super) alignment: Justification,
+    /// Kashida that lengthens the Arabic words of the run when the line is justified
+    pub(super) kashida: Option<PositionedGlyph>,
+    /// Underlines and strikethroughs, spanned across the run once it is placed
+    pub(super) decorations: Vec<Decoration>,
+    pub(super) adornments: Vec<RunAdornment>,
+    /// Index of the first glyph of the run in the text it is part of
+    pub(super) first_glyph: usize,
+    pub(super) link_source: Option<String>,
+    pub(super) conditions: Option<String>,
+    /// Inline anchored object the run makes room for, by index in the anchored objects
+    pub(super) anchored_object: Option<usize>,
+}
+
+impl LineRun {
+    /// Room of `width` along the line that nothing is drawn in
+    pub(super) fn space(
+        width: f64,
+        level: u8,
+        transforms: GlyphTransforms,
+        alignment: Justification,
+    ) -> LineRun {
+        let mut space = PositionedGlyph::spacer(width);
+        space.level = level;
+        LineRun {
+            glyphs: vec![space],
+            font: None,
+            fill_color: None,
+            stroke_color: None,
+            gradient: None,
+            transforms,
+            alignment,
+            kashida: None,
+            decorations: vec![],
+            adornments: vec![],
+            first_glyph: 0,
+            link_source: None,
+            conditions: None,
+            anchored_object: None,
+        }
+    }
+
+    fn width(&self) -> f64 {
+        text_layout::glyphs_width(&self.glyphs)
+    }
+}
+
+/// Glyphs placed on their line, drawn once the text of the frame is set
+#[derive(Debug)]
+pub struct TextRun {
+    font: RunFont,
+    fill_color: Option<Color>,
+    stroke_color: Option<Color>,
+    glyphs: Vec<PositionedGlyph>,
+    /// The glyphs of runs filled with a gradient are drawn as a gradient run
+    shows_glyphs: bool,
+    transforms: GlyphTransforms,
+    adornments: Vec<RunAdornment>,
+    /// Index of the first glyph in the text it is part of
+    first_glyph: usize,
+    span: LineSpan,
+}
+
+impl TextRun {
+    /// Draws the run. Must be called inside a text object.
+    pub(super) unsafe fn render(&self, current_page: HPDF_Page) -> Result<(), String> {
+        HPDF_Page_SetFontAndSize(
+            current_page,
+            self.font.font,
+            self.transforms.font_size as f32,
+        );
+        if let Some(color) = &self.fill_color {
+            set_fill_color(current_page, color);
+        }
+        if let Some(color) = &self.stroke_color {
+            set_stroke_color(current_page, color);
+        }
+        let span = &self.span;
+        if self.shows_glyphs {
+            match span.vertical {
+                true => text_layout::show_glyphs_vertical(
+                    current_page,
+                    &self.glyphs,
+                    span.line,
+                    span.start,
+                    &self.transforms,
+                ),
+                false => text_layout::show_glyphs(
+                    current_page,
+                    &self.glyphs,
+                    span.start,
+                    span.line,
+                    &self.transforms,
+                ),
+            }?;
+        }
+
+        let adornments = Adornments {
+            page: current_page,
+            font: self.font.font,
+            encoding: &self.font.encoding,
+            font_data: self.font.font_data.as_ref().map(|data| data.as_slice()),
+            transforms: &self.transforms,
+        };
+        for adornment in &self.adornments {
+            match adornment {
+                RunAdornment::Ruby { ruby, run_length } => {
+                    adornments.show_ruby(ruby, &self.glyphs, span, self.first_glyph, *run_length)?
+                }
+                RunAdornment::Kenten(kenten) => {
+                    adornments.show_kenten(kenten, &self.glyphs, span)?
+                }
+                RunAdornment::Warichu { warichu, lines } => {
+                    adornments.show_warichu(warichu, lines, span.start, span.line)?
+                }
+                RunAdornment::TateChuYoko { text, offset } => {
+                    adornments.show_tate_chu_yoko(text, *offset, span.start, span.line)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl StoryState {
+    /// Sets `run` on the current line, after the runs already set on it
+    pub(super) fn push_run(&mut self, mut run: LineRun) {
+        let set_width: f64 = self.line_runs.iter().map(LineRun::width).sum();
+        if self.line_runs.is_empty() {
+            self.line_start = self.line_width;
+        } else if self.line_width > self.line_start + set_width + 0.01_f64 {
+            // Room skipped by a right indent tab
+            self.line_runs.push(LineRun::space(
+                self.line_width - self.line_start - set_width,
+                run.transforms.paragraph_level(),
+                run.transforms.clone(),
+                run.alignment.clone(),
+            ));
+        }
+        run.link_source = self.link_source.clone();
+        run.conditions = self.conditions.clone();
+        self.line_width += run.width();
+        self.line_runs.push(run);
+    }
+
+    /// Places the runs set on the current line and notes what is drawn for them. The line
+    /// is put in visual order, aligned and, in justified paragraphs, justified, unless it
+    /// is the `last_line` of its paragraph or otherwise ends before the end of the column.
+    pub(super) fn finish_line(&mut self, last_line: bool) {
+        let mut runs = std::mem::take(&mut self.line_runs);
+        let (alignment, paragraph_level) = match runs.first() {
+            Some(first) => (first.alignment.clone(), first.transforms.paragraph_level()),
+            None => return,
+        };
+
+        let slack = self.line_length() - self.line_start - content_width(&runs);
+        let slack = match alignment {
+            Justification::FullyJustified => justify(&mut runs, slack),
+            Justification::LeftJustified
+            | Justification::CenterJustified
+            | Justification::RightJustified
+                if !last_line =>
+            {
+                justify(&mut runs, slack)
+            }
+            _ => slack,
+        }
+        .max(0_f64);
+        // Alignment is relative to the direction of the paragraph, so left aligned
+        // right-to-left paragraphs start at the right edge of the frame
+        // TODO: Align to and from the binding side by the side of the page
+        let shift = match alignment {
+            Justification::CenterAlign | Justification::CenterJustified => slack / 2_f64,
+            Justification::RightAlign
+            | Justification::RightJustified
+            | Justification::FromBindingSide => slack,
+            _ => 0_f64,
+        };
+
+        let glyphs: Vec<PositionedGlyph> = runs
+            .iter()
+            .flat_map(|run| run.glyphs.iter().cloned())
+            .collect();
+        let run_of_glyph: Vec<(usize, usize)> = runs
+            .iter()
+            .enumerate()
+            .flat_map(|(r, run)| (0..run.glyphs.len()).map(move |g| (r, g)))
+            .collect();
+        let (order, mut pen, direction) = match (self.vertical, paragraph_level % 2) {
+            (true, _) => (
+                (0..glyphs.len()).collect(),
+                self.frame.top - self.line_start - shift,
+                -1_f64,
+            ),
+            (false, 0) => (
+                text_layout::reorder_line(&glyphs, paragraph_level),
+                self.frame.left + self.line_start + shift,
+                1_f64,
+            ),
+            (false, _) => (
+                text_layout::reorder_line(&glyphs, paragraph_level),
+                self.frame.right - self.line_start - shift - text_layout::glyphs_width(&glyphs),
+                1_f64,
+            ),
+        };
+
+        // Glyphs next to each other in visual order that belong to the same run are placed together
+        let mut start = 0;
+        while start < order.len() {
+            let run = run_of_glyph[order[start]].0;
+            let length = order[start..]
+                .iter()
+                .take_while(|&&i| run_of_glyph[i].0 == run)
+                .count();
+            let indices: Vec<usize> = order[start..start + length]
+                .iter()
+                .map(|&i| run_of_glyph[i].1)
+                .collect();
+            let piece: Vec<PositionedGlyph> = indices
+                .iter()
+                .map(|&g| runs[run].glyphs[g].clone())
+                .collect();
+            let width = text_layout::glyphs_width(&piece);
+            let first = indices.iter().copied().min().unwrap_or(0);
+            self.place_run(&runs[run], piece, first, pen, pen + direction * width);
+            pen += direction * width;
+            start += length;
+        }
+    }
+
+    /// Notes what is drawn for the glyphs of `run` placed from `start` to `end` along the
+    /// line, where `first` is the index in the run of the first of them in logical order
+    fn place_run(
+        &mut self,
+        run: &LineRun,
+        glyphs: Vec<PositionedGlyph>,
+        first: usize,
+        start: f64,
+        end: f64,
+    ) {
+        let span = LineSpan {
+            start,
+            end,
+            line: match self.vertical {
+                true => self.column,
+                false => self.y,
+            },
+            vertical: self.vertical,
+        };
+        if run.glyphs.iter().any(|glyph| glyph.advance != 0_f64) {
+            let area = self.line_area(start, end);
+            self.add_link_area(&run.link_source, area.clone());
+            self.add_condition_area(&run.conditions, area);
+        }
+        if let Some(index) = run.anchored_object {
+            if let Some(anchored_object) = self.anchored_objects.get_mut(index) {
+                match self.vertical {
+                    true => anchored_object.set_bottom(end),
+                    false => anchored_object.set_left(start),
+                }
+            }
+        }
+        if self.measuring {
+            return;
+        }
+
+        for decoration in &run.decorations {
+            let decoration = match self.vertical {
+                // Decorations run down the column from the baseline of the text turned
+                // along it, which is where rotated glyphs are set
+                true => {
+                    let ascent = run.transforms.font_size
+                        * (EM_BOX_ASCENT - 0.5_f64)
+                        * run.transforms.vertical_scale
+                        / 100_f64;
+                    let baseline = self.column - ascent + run.transforms.baseline_shift;
+                    decoration.clone().spanning(start, end, baseline).vertical()
+                }
+                false => {
+                    let baseline = self.y + run.transforms.baseline_shift;
+                    decoration.clone().spanning(start, end, baseline)
+                }
+            };
+            self.decorations.push(decoration);
+        }
+        let font = match &run.font {
+            Some(font) => font,
+            None => return,
+        };
+        if let Some((gradient, placement)) = &run.gradient {
+            let (x, y) = match self.vertical {
+                true => (span.line, span.start),
+                false => (span.start, span.line),
+            };
+            self.gradient_runs.push(GradientRun {
+                gradient: gradient.clone(),
+                placement: *placement,
+                font: font.font,
+                glyphs: glyphs.clone(),
+                transforms: run.transforms.clone(),
+                x,
+                y,
+            });
+        }
+        self.text_runs.push(TextRun {
+            font: font.clone(),
+            fill_color: run.fill_color.clone(),
+            stroke_color: run.stroke_color.clone(),
+            glyphs,
+            shows_glyphs: run.gradient.is_none(),
+            transforms: run.transforms.clone(),
+            adornments: run.adornments.clone(),
+            first_glyph: run.first_glyph + first,
+            span,
+        });
+    }
+}
+
+/// Width of the runs of a line, without the spaces at its end
+fn content_width(runs: &[LineRun]) -> f64 {
+    let glyphs: Vec<&PositionedGlyph> = runs.iter().flat_map(|run| &run.glyphs).collect();
+    let trailing = glyphs
+        .iter()
+        .rev()
+        .take_while(|glyph| glyph.is_space)
+        .count();
+    glyphs[..glyphs.len() - trailing]
+        .iter()
+        .map(|glyph| glyph.advance + glyph.kerning)
+        .sum()
+}
+
+/// Spreads `slack` over a line: over kashidas drawn between the joined Arabic letters
+/// of runs that have a kashida, and otherwise over the spaces between words. Returns
+/// the slack that is left, which is all of it when the line has neither.
+fn justify(runs: &mut [LineRun], slack: f64) -> f64 {
+    if slack <= 0_f64 {
+        return slack;
+    }
+    let points: Vec<(usize, usize)> = runs
+        .iter()
+        .enumerate()
+        .filter(|(_, run)| run.kashida.is_some())
+        .flat_map(|(r, run)| {
+            run.glyphs
+                .iter()
+                .enumerate()
+                .filter(|(_, glyph)| glyph.kashida)
+                .map(move |(g, _)| (r, g))
+        })
+        .collect();
+    if !points.is_empty() {
+        let share = slack / points.len() as f64;
+        // Kashidas are inserted from the end so the glyph indices stay valid
+        for &(r, g) in points.iter().rev() {
+            let kashida = match &runs[r].kashida {
+                Some(kashida) => kashida.clone(),
+                None => continue,
+            };
+            // Kashidas overlap to fill the share exactly
+            let count = (share / kashida.advance).ceil().max(1_f64);
+            let fill = PositionedGlyph {
+                kerning: (share - count * kashida.advance) / count,
+                level: runs[r].glyphs[g].level,
+                ..kashida
+            };
+            runs[r]
+                .glyphs
+                .splice(g + 1..g + 1, std::iter::repeat_n(fill, count as usize));
+        }
+        return 0_f64;
+    }
+
+    let positions: Vec<(usize, usize)> = runs
+        .iter()
+        .enumerate()
+        .flat_map(|(r, run)| (0..run.glyphs.len()).map(move |g| (r, g)))
+        .collect();
+    let last_word = positions
+        .iter()
+        .rposition(|&(r, g)| !runs[r].glyphs[g].is_space)
+        .unwrap_or(0);
+    let spaces: Vec<(usize, usize)> = positions[..last_word]
+        .iter()
+        .copied()
+        .filter(|&(r, g)| runs[r].glyphs[g].is_space)
+        .collect();
+    if spaces.is_empty() {
+        return slack;
+    }
+    let share = slack / spaces.len() as f64;
+    for (r, g) in spaces {
+        runs[r].glyphs[g].kerning += share;
+    }
+    0_f64
+}
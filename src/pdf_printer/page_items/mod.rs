@@ -1,2 +1,12 @@
+pub mod anchored_object;
+pub mod eps;
+pub mod footnote;
+pub mod frame_fitting;
+pub mod group;
+pub mod image;
+mod line_layout;
+pub mod pdf;
 pub mod polygon;
+pub mod svg;
+pub mod table;
 pub mod textframe;
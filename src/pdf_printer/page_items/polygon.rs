@@ -1,7 +1,12 @@
 use crate::idml_parser::spread_parser::*;
 use crate::idml_parser::IDMLResources;
 // use crate::pdf_printer::pdf_utils;
-use crate::pdf_printer::color_manager::Color;
+use crate::pdf_printer::clipping::{self, Knot};
+use crate::pdf_printer::color_manager::{Color, Gradient};
+use crate::pdf_printer::font_manager::FontLibrary;
+use crate::pdf_printer::gradients::{self, GradientPlacement};
+use crate::pdf_printer::image_manager::ImageLibrary;
+use crate::pdf_printer::pdf_utils::{set_fill_color, set_stroke_color};
 use crate::pdf_printer::transforms::{self, Transform};
 use libharu_sys::*;
 
@@ -10,12 +15,14 @@ impl Polygon {
         &self,
         parent_transform: &Transform,
         idml_resources: &IDMLResources,
+        font_lib: &FontLibrary,
+        image_lib: &ImageLibrary,
         current_page: HPDF_Page,
     ) -> Result<(), String> {
         let item_transform = transforms::from_vec(self.item_transform());
 
         // Parse the points and apply the relevant transformations
-        let mut points: Vec<Vec<HPDF_REAL>> = self
+        let points: Vec<Vec<HPDF_REAL>> = self
             .properties()
             .into_iter()
             .filter_map(|properties| properties.path_geometry().as_ref())
@@ -46,33 +53,46 @@ impl Polygon {
             .collect();
 
         // Initialize fill and stroke color to None
-        let mut fill_color = idml_resources.color_from_id(&"Swatch/None".to_string());
-        let mut stroke_color = idml_resources.color_from_id(&"Swatch/None".to_string());
+        let mut fill_color_id = "Swatch/None".to_string();
+        let mut stroke_color_id = "Swatch/None".to_string();
+        let mut fill_tint = None;
+        let mut stroke_tint = None;
         let mut stroke_weight = None;
 
         // If a graphic style is applied, then update fill and stroke color from that
         if let Some(style_id) = self.applied_object_style() {
             if let Some(style) = idml_resources.styles().object_style_from_id(style_id) {
                 if let Some(id) = style.fill_color() {
-                    fill_color = idml_resources.color_from_id(id);
+                    fill_color_id = id.clone();
                 }
                 if let Some(id) = style.stroke_color() {
-                    stroke_color = idml_resources.color_from_id(id);
+                    stroke_color_id = id.clone();
                 }
+                fill_tint = style.fill_tint().to_owned();
+                stroke_tint = style.stroke_tint().to_owned();
                 stroke_weight = style.stroke_weight().to_owned();
             }
         }
 
-        // Override fill color if one is available on the polygon
+        // Override fill color and tint if available on the polygon
         if let Some(id) = self.fill_color() {
-            fill_color = idml_resources.color_from_id(id)
+            fill_color_id = id.clone();
+        }
+        if let Some(tint) = self.fill_tint() {
+            fill_tint = Some(tint.to_owned());
         }
 
-        // Override stroke color if one is available on the polygon
+        // Override stroke color and tint if available on the polygon
         if let Some(id) = self.stroke_color() {
-            stroke_color = idml_resources.color_from_id(id)
+            stroke_color_id = id.clone();
+        }
+        if let Some(tint) = self.stroke_tint() {
+            stroke_tint = Some(tint.to_owned());
         }
 
+        let fill_color = idml_resources.tinted_color_from_id(&fill_color_id, fill_tint);
+        let stroke_color = idml_resources.tinted_color_from_id(&stroke_color_id, stroke_tint);
+
         // Override stroke weight if one is available on the polygon
         if let Some(weight) = self.stroke_weight() {
             stroke_weight = Some(weight.to_owned());
@@ -89,6 +109,20 @@ impl Polygon {
             _ => false,
         };
 
+        // Gradients are painted as shadings clipped to the fill or the stroke,
+        // so they are not filled or stroked with the path
+        let fill_gradient = match &fill_color {
+            Ok(Color::Gradient(gradient)) => Some(gradient),
+            _ => None,
+        };
+        let stroke_gradient = match &stroke_color {
+            Ok(Color::Gradient(gradient)) => Some(gradient),
+            _ => None,
+        };
+        let fills = fill_color.is_ok() && fill_gradient.is_none();
+        let strokes = stroke_color.is_ok() && stroke_gradient.is_none();
+        let frame_transform = item_transform.combine_with(parent_transform);
+
         unsafe {
             // Save the current graphic state
             HPDF_Page_GSave(current_page);
@@ -98,68 +132,320 @@ impl Polygon {
                 HPDF_Page_SetLineWidth(current_page, weight.to_owned() as f32);
             };
 
-            // Set fill color of shape
-            match fill_color {
-                Ok(Color::Cmyk(color)) => {
-                    HPDF_Page_SetCMYKFill(
-                        current_page,
-                        *color.c(),
-                        *color.m(),
-                        *color.y(),
-                        *color.k(),
-                    );
-                }
-                Ok(Color::Rgb(color)) => {
-                    HPDF_Page_SetRGBFill(current_page, *color.r(), *color.g(), *color.b());
-                }
-                _ => {}
+            // Set fill and stroke color of shape
+            if let Ok(color) = &fill_color {
+                set_fill_color(current_page, color);
+            }
+            if let Ok(color) = &stroke_color {
+                set_stroke_color(current_page, color);
             }
 
-            // Set stroke color of shape
-            match stroke_color {
-                Ok(Color::Cmyk(color)) => {
-                    HPDF_Page_SetCMYKStroke(
-                        current_page,
-                        *color.c(),
-                        *color.m(),
-                        *color.y(),
-                        *color.k(),
-                    );
-                }
-                Ok(Color::Rgb(color)) => {
-                    HPDF_Page_SetRGBStroke(current_page, *color.r(), *color.g(), *color.b());
+            let has_content = self.image().is_some()
+                || self.pdf().is_some()
+                || self.eps().is_some()
+                || self.svg().is_some();
+            match has_content {
+                false => {
+                    if let Some(gradient) = fill_gradient {
+                        self.fill_gradient(
+                            gradient,
+                            &points,
+                            &frame_transform,
+                            image_lib,
+                            current_page,
+                        );
+                    }
+                    add_path(current_page, &points, closed_path);
+                    match (closed_path, fills, strokes) {
+                        (_, true, false) => HPDF_Page_Fill(current_page),
+                        (false, false, true) => HPDF_Page_Stroke(current_page),
+                        (false, true, true) => HPDF_Page_FillStroke(current_page),
+                        (true, false, true) => HPDF_Page_ClosePathStroke(current_page),
+                        (true, true, true) => HPDF_Page_ClosePathFillStroke(current_page),
+                        _ => HPDF_Page_EndPath(current_page),
+                    };
+                    if let Some(gradient) = stroke_gradient {
+                        self.stroke_gradient(
+                            gradient,
+                            &points,
+                            closed_path,
+                            stroke_weight.unwrap_or(1_f64),
+                            &frame_transform,
+                            image_lib,
+                            current_page,
+                        );
+                    }
                 }
-                _ => {}
-            }
+                // Placed content goes between the fill and the stroke of the frame
+                true => {
+                    if let Some(gradient) = fill_gradient {
+                        self.fill_gradient(
+                            gradient,
+                            &points,
+                            &frame_transform,
+                            image_lib,
+                            current_page,
+                        );
+                    } else if fills {
+                        add_path(current_page, &points, closed_path);
+                        HPDF_Page_Fill(current_page);
+                    }
 
-            // Start from first anchorpoint
-            if let Some(p) = points.get(1) {
-                HPDF_Page_MoveTo(current_page, p[0], p[1]);
-            }
-            // The PDF library wants the points in a slightly different order
-            match closed_path {
-                true => points.rotate_left(2),
-                false => points = points[2..].to_vec(),
-            }
-            // Draw the rest of the bezier handles
-            for slice in points.chunks(3) {
-                if let [r, l, a] = slice {
-                    HPDF_Page_CurveTo(current_page, r[0], r[1], l[0], l[1], a[0], a[1]);
+                    // Only the part of the content inside the frame is shown
+                    HPDF_Page_GSave(current_page);
+                    if !points.is_empty() {
+                        add_path(current_page, &points, true);
+                        HPDF_Page_Clip(current_page);
+                        HPDF_Page_EndPath(current_page);
+                    }
+                    let fitting = self.frame_fitting();
+                    if let Some(image) = self.image() {
+                        if let Err(err) = image.render(
+                            &frame_transform,
+                            fitting.as_ref(),
+                            image_lib,
+                            current_page,
+                        ) {
+                            println!("Image '{}' not rendered: {}", image.id(), err);
+                        }
+                    }
+                    if let Some(pdf) = self.pdf() {
+                        if let Err(err) =
+                            pdf.render(&frame_transform, fitting.as_ref(), image_lib, current_page)
+                        {
+                            println!("PDF '{}' not rendered: {}", pdf.id(), err);
+                        }
+                    }
+                    if let Some(eps) = self.eps() {
+                        if let Err(err) =
+                            eps.render(&frame_transform, fitting.as_ref(), image_lib, current_page)
+                        {
+                            println!("EPS '{}' not rendered: {}", eps.id(), err);
+                        }
+                    }
+                    if let Some(svg) = self.svg() {
+                        if let Err(err) = svg.render(
+                            &frame_transform,
+                            fitting.as_ref(),
+                            image_lib,
+                            font_lib,
+                            current_page,
+                        ) {
+                            println!("SVG '{}' not rendered: {}", svg.id(), err);
+                        }
+                    }
+                    let links = [
+                        self.image().as_ref().map(|image| image.link()),
+                        self.pdf().as_ref().map(|pdf| pdf.link()),
+                        self.eps().as_ref().map(|eps| eps.link()),
+                        self.svg().as_ref().map(|svg| svg.link()),
+                    ];
+                    let missing_link = links
+                        .iter()
+                        .flatten()
+                        .any(|&link| image_lib.needs_placeholder(link));
+                    if missing_link {
+                        if let Some(bounds) = self.frame_bounds() {
+                            draw_missing_link_placeholder(&frame_transform, bounds, current_page);
+                        }
+                    }
+                    HPDF_Page_GRestore(current_page);
+
+                    if let Some(gradient) = stroke_gradient {
+                        self.stroke_gradient(
+                            gradient,
+                            &points,
+                            closed_path,
+                            stroke_weight.unwrap_or(1_f64),
+                            &frame_transform,
+                            image_lib,
+                            current_page,
+                        );
+                    } else if strokes {
+                        add_path(current_page, &points, closed_path);
+                        match closed_path {
+                            true => HPDF_Page_ClosePathStroke(current_page),
+                            false => HPDF_Page_Stroke(current_page),
+                        };
+                    }
                 }
             }
 
-            match (closed_path, fill_color.is_ok(), stroke_color.is_ok()) {
-                (_, true, false) => HPDF_Page_Fill(current_page),
-                (false, false, true) => HPDF_Page_Stroke(current_page),
-                (false, true, true) => HPDF_Page_FillStroke(current_page),
-                (true, false, true) => HPDF_Page_ClosePathStroke(current_page),
-                (true, true, true) => HPDF_Page_ClosePathFillStroke(current_page),
-                _ => HPDF_Page_EndPath(current_page),
-            };
-
             // Restore the previous graphic state
             HPDF_Page_GRestore(current_page);
         }
         Ok(())
     }
 }
+
+impl Polygon {
+    /// Paints the gradient inside the path, placed by the gradient fill attributes of the item
+    unsafe fn fill_gradient(
+        &self,
+        gradient: &Gradient,
+        points: &[Vec<HPDF_REAL>],
+        frame_transform: &Transform,
+        image_lib: &ImageLibrary,
+        current_page: HPDF_Page,
+    ) {
+        let bounds = match self.frame_bounds() {
+            Some(bounds) => bounds,
+            None => return,
+        };
+        let placement = GradientPlacement::from_attributes(
+            self.gradient_fill_start().as_deref(),
+            *self.gradient_fill_length(),
+            *self.gradient_fill_angle(),
+            *gradient.kind(),
+            bounds,
+        );
+        HPDF_Page_GSave(current_page);
+        add_path(current_page, points, true);
+        HPDF_Page_Clip(current_page);
+        HPDF_Page_EndPath(current_page);
+        if let Err(err) = gradients::paint_gradient(
+            current_page,
+            image_lib,
+            gradient,
+            &placement,
+            frame_transform,
+            bounds,
+        ) {
+            println!("Gradient fill of '{}' not rendered: {}", self.id(), err);
+        }
+        HPDF_Page_GRestore(current_page);
+    }
+
+    /// Paints the gradient inside the outline of the stroke along the path, placed by
+    /// the gradient stroke attributes of the item. PDF cannot clip to a stroke, so the
+    /// outline is made by moving the path half the stroke weight to either side.
+    unsafe fn stroke_gradient(
+        &self,
+        gradient: &Gradient,
+        points: &[Vec<HPDF_REAL>],
+        closed_path: bool,
+        stroke_weight: f64,
+        frame_transform: &Transform,
+        image_lib: &ImageLibrary,
+        current_page: HPDF_Page,
+    ) {
+        let bounds = match self.frame_bounds() {
+            Some(bounds) => bounds,
+            None => return,
+        };
+        let placement = GradientPlacement::from_attributes(
+            self.gradient_stroke_start().as_deref(),
+            *self.gradient_stroke_length(),
+            *self.gradient_stroke_angle(),
+            *gradient.kind(),
+            bounds,
+        );
+        let point = |p: &Vec<HPDF_REAL>| (p[0] as f64, p[1] as f64);
+        let knots: Vec<Knot> = points
+            .chunks_exact(3)
+            .map(|knot| Knot {
+                preceding: point(&knot[0]),
+                anchor: point(&knot[1]),
+                leaving: point(&knot[2]),
+            })
+            .collect();
+        // The stroke reaches half its weight outside the path
+        let half = stroke_weight / 2_f64;
+        let (left, top, right, bottom) = bounds;
+        let bounds = (left - half, top - half, right + half, bottom + half);
+
+        HPDF_Page_GSave(current_page);
+        for subpath in clipping::stroke_outline(&knots, stroke_weight, closed_path) {
+            clipping::add_subpath(current_page, &subpath);
+        }
+        HPDF_Page_Eoclip(current_page);
+        HPDF_Page_EndPath(current_page);
+        if let Err(err) = gradients::paint_gradient(
+            current_page,
+            image_lib,
+            gradient,
+            &placement,
+            frame_transform,
+            bounds,
+        ) {
+            println!("Gradient stroke of '{}' not rendered: {}", self.id(), err);
+        }
+        HPDF_Page_GRestore(current_page);
+    }
+
+    /// Left, top, right and bottom of the anchor points of the frame, in its own coordinates
+    pub(super) fn frame_bounds(&self) -> Option<(f64, f64, f64, f64)> {
+        let anchors: Vec<&Vec<f64>> = self
+            .properties()
+            .iter()
+            .filter_map(|properties| properties.path_geometry().as_ref())
+            .flat_map(|path_geom| path_geom.geometry_path_type().path_point_arrays())
+            .flat_map(|path_point_array| path_point_array.path_point_array())
+            .filter_map(|path_point_type| path_point_type.anchor().as_ref())
+            .filter(|point| point.len() >= 2)
+            .collect();
+        if anchors.is_empty() {
+            return None;
+        }
+        Some(anchors.iter().fold(
+            (f64::MAX, f64::MAX, f64::MIN, f64::MIN),
+            |(left, top, right, bottom), point| {
+                (
+                    left.min(point[0]),
+                    top.min(point[1]),
+                    right.max(point[0]),
+                    bottom.max(point[1]),
+                )
+            },
+        ))
+    }
+}
+
+/// Draws a grey box with a cross over `bounds` in the coordinates of `transform`,
+/// standing in for a graphic whose linked file is missing
+unsafe fn draw_missing_link_placeholder(
+    transform: &Transform,
+    (left, top, right, bottom): (f64, f64, f64, f64),
+    current_page: HPDF_Page,
+) {
+    let corners: Vec<Vec<f64>> = [(left, top), (right, top), (right, bottom), (left, bottom)]
+        .iter()
+        .map(|(x, y)| transform.apply_to_point(x, y))
+        .collect();
+    HPDF_Page_GSave(current_page);
+    HPDF_Page_SetGrayFill(current_page, 0.85);
+    HPDF_Page_SetGrayStroke(current_page, 0.5);
+    HPDF_Page_SetLineWidth(current_page, 1_f32);
+    HPDF_Page_MoveTo(current_page, corners[0][0] as f32, corners[0][1] as f32);
+    for corner in &corners[1..] {
+        HPDF_Page_LineTo(current_page, corner[0] as f32, corner[1] as f32);
+    }
+    HPDF_Page_ClosePathFillStroke(current_page);
+    HPDF_Page_MoveTo(current_page, corners[0][0] as f32, corners[0][1] as f32);
+    HPDF_Page_LineTo(current_page, corners[2][0] as f32, corners[2][1] as f32);
+    HPDF_Page_MoveTo(current_page, corners[1][0] as f32, corners[1][1] as f32);
+    HPDF_Page_LineTo(current_page, corners[3][0] as f32, corners[3][1] as f32);
+    HPDF_Page_Stroke(current_page);
+    HPDF_Page_GRestore(current_page);
+}
+
+/// Adds the path through the points to the page, where each anchor point
+/// is given with its left and right handle for the bezier curves
+unsafe fn add_path(current_page: HPDF_Page, points: &[Vec<HPDF_REAL>], closed_path: bool) {
+    let mut points = points.to_vec();
+    // Start from first anchorpoint
+    if let Some(p) = points.get(1) {
+        HPDF_Page_MoveTo(current_page, p[0], p[1]);
+    }
+    // The PDF library wants the points in a slightly different order
+    match closed_path {
+        true => points.rotate_left(2),
+        false => points = points.get(2..).unwrap_or_default().to_vec(),
+    }
+    // Draw the rest of the bezier handles
+    for slice in points.chunks(3) {
+        if let [r, l, a] = slice {
+            HPDF_Page_CurveTo(current_page, r[0], r[1], l[0], l[1], a[0], a[1]);
+        }
+    }
+}
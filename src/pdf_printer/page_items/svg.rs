@@ -0,0 +1,77 @@
+use super::frame_fitting::FrameFitting;
+use crate::idml_parser::spread_parser::*;
+use crate::pdf_printer::font_manager::FontLibrary;
+use crate::pdf_printer::image_manager::ImageLibrary;
+use crate::pdf_printer::svg::SvgPainter;
+use crate::pdf_printer::transforms::{self, Transform};
+use libharu_sys::*;
+
+impl Svg {
+    /// Draws the graphic as vector graphics at its place in the frame, or fitted to the frame
+    /// if `fitting` is given. `parent_transform` is the transform of the frame, as the graphic
+    /// is placed relative to it.
+    pub fn render(
+        &self,
+        parent_transform: &Transform,
+        fitting: Option<&FrameFitting>,
+        image_lib: &ImageLibrary,
+        font_lib: &FontLibrary,
+        current_page: HPDF_Page,
+    ) -> Result<(), String> {
+        if *self.visible() == Some(false) {
+            return Ok(());
+        }
+        let (tree, fonts) = image_lib.svg_tree(self, font_lib)?;
+        let size = tree.size();
+        let (width, height) = (size.width() as f64, size.height() as f64);
+        let (item_transform, (left, top, right, bottom)) = match fitting {
+            Some(fitting) => {
+                let bounds = (0_f64, 0_f64, width, height);
+                (fitting.transform(bounds), bounds)
+            }
+            None => {
+                let bounds = self
+                    .properties()
+                    .as_ref()
+                    .and_then(|properties| properties.graphic_bounds().as_ref())
+                    .ok_or(format!("SVG '{}' has no bounds", self.id()))?;
+                (
+                    transforms::from_vec(self.item_transform()),
+                    (
+                        *bounds.left(),
+                        *bounds.top(),
+                        *bounds.right(),
+                        *bounds.bottom(),
+                    ),
+                )
+            }
+        };
+        let transform = item_transform.combine_with(parent_transform);
+
+        // The SVG runs down from the top left corner of the bounds, as its y axis points down
+        let origin = transform.apply_to_point(&left, &top);
+        let x_axis = transform.apply_to_point(&right, &top);
+        let y_axis = transform.apply_to_point(&left, &bottom);
+        let painter = SvgPainter {
+            image_lib,
+            font_lib,
+            fonts: fonts.as_deref(),
+            current_page,
+        };
+        unsafe {
+            HPDF_Page_GSave(current_page);
+            HPDF_Page_Concat(
+                current_page,
+                ((x_axis[0] - origin[0]) / width) as f32,
+                ((x_axis[1] - origin[1]) / width) as f32,
+                ((y_axis[0] - origin[0]) / height) as f32,
+                ((y_axis[1] - origin[1]) / height) as f32,
+                origin[0] as f32,
+                origin[1] as f32,
+            );
+            painter.draw_group(tree.root(), 1_f32);
+            HPDF_Page_GRestore(current_page);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,929 @@
+use super::textframe::{RenderProperties, StoryState};
+use crate::idml_parser::{
+    spread_parser::Polygon,
+    story_parser::{Cell, Table},
+    styles::{cell_style::*, table_style::*},
+    IDMLResources,
+};
+use crate::pdf_printer::{
+    color_manager::{self, Color},
+    font_manager::FontLibrary,
+    pdf_utils::*,
+};
+use libharu_sys::*;
+use std::ops::Range;
+
+/// Stroke along one edge of a cell
+#[derive(Debug, Clone)]
+struct EdgeStroke {
+    weight: f64,
+    color: Option<Color>,
+}
+
+/// Weight and color id of a stroke of the table, either of them unset
+type TableStroke = (Option<f64>, Option<String>);
+
+/// Formats of a cell, resolved from the cell itself, its cell style,
+/// the cell style of its table region and the table style
+#[derive(Debug, Clone)]
+struct CellFormat {
+    /// Top, left, bottom and right insets
+    insets: [f64; 4],
+    fill_color: Option<Color>,
+    vertical_justification: VerticalJustification,
+    /// Top, left, bottom and right edges
+    edges: [EdgeStroke; 4],
+}
+
+impl CellFormat {
+    /// Formats of a cell spanning `rows` and `columns` of a table of `row_count` rows
+    /// and `column_count` columns. Edges the cell leaves unset take the stroke of the
+    /// table along them: its border along the outside and else its row or column strokes.
+    fn resolve(
+        idml_resources: &IDMLResources,
+        cell_layers: &[&dyn CellAttributes],
+        table_layers: &[&dyn TableAttributes],
+        rows: Range<usize>,
+        columns: Range<usize>,
+        (row_count, column_count): (usize, usize),
+    ) -> CellFormat {
+        let cell = |get: &dyn Fn(&dyn CellAttributes) -> Option<f64>| {
+            cell_layers.iter().find_map(|layer| get(*layer))
+        };
+        let table = |get: &dyn Fn(&dyn TableAttributes) -> Option<f64>| {
+            table_layers.iter().find_map(|layer| get(*layer))
+        };
+        let table_stroke = |weight: &dyn Fn(&dyn TableAttributes) -> Option<f64>,
+                            color: &dyn Fn(&dyn TableAttributes) -> Option<String>|
+         -> TableStroke {
+            (
+                table(weight),
+                table_layers.iter().find_map(|layer| color(*layer)),
+            )
+        };
+        let table_count = |get: &dyn Fn(&dyn TableAttributes) -> Option<usize>| {
+            table_layers.iter().find_map(|layer| get(*layer))
+        };
+        // The stroke between two rows or columns is the stroke of the one before it
+        let row_stroke = |row: usize| {
+            let start_count = table_count(&|t| t.start_row_stroke_count());
+            let end_count = table_count(&|t| t.end_row_stroke_count());
+            match takes_end_stroke(row, start_count, end_count) {
+                false => table_stroke(&|t| t.start_row_stroke_weight(), &|t| {
+                    t.start_row_stroke_color()
+                }),
+                true => table_stroke(&|t| t.end_row_stroke_weight(), &|t| {
+                    t.end_row_stroke_color()
+                }),
+            }
+        };
+        let column_stroke = |column: usize| {
+            let start_count = table_count(&|t| t.start_column_stroke_count());
+            let end_count = table_count(&|t| t.end_column_stroke_count());
+            match takes_end_stroke(column, start_count, end_count) {
+                false => table_stroke(&|t| t.start_column_stroke_weight(), &|t| {
+                    t.start_column_stroke_color()
+                }),
+                true => table_stroke(&|t| t.end_column_stroke_weight(), &|t| {
+                    t.end_column_stroke_color()
+                }),
+            }
+        };
+        let table_edges = [
+            match rows.start {
+                0 => table_stroke(&|t| t.top_border_stroke_weight(), &|t| {
+                    t.top_border_stroke_color()
+                }),
+                row => row_stroke(row - 1),
+            },
+            match columns.start {
+                0 => table_stroke(&|t| t.left_border_stroke_weight(), &|t| {
+                    t.left_border_stroke_color()
+                }),
+                column => column_stroke(column - 1),
+            },
+            match rows.end >= row_count {
+                true => table_stroke(&|t| t.bottom_border_stroke_weight(), &|t| {
+                    t.bottom_border_stroke_color()
+                }),
+                false => row_stroke(rows.end - 1),
+            },
+            match columns.end >= column_count {
+                true => table_stroke(&|t| t.right_border_stroke_weight(), &|t| {
+                    t.right_border_stroke_color()
+                }),
+                false => column_stroke(columns.end - 1),
+            },
+        ];
+        let edge = |weight: &dyn Fn(&dyn CellAttributes) -> Option<f64>,
+                    stroke_color: &dyn Fn(&dyn CellAttributes) -> Option<String>,
+                    (table_weight, table_color): &TableStroke| {
+            let color_id = cell_layers
+                .iter()
+                .find_map(|layer| stroke_color(*layer))
+                .or(table_color.clone())
+                .unwrap_or("Color/Black".to_string());
+            EdgeStroke {
+                weight: cell(weight).or(*table_weight).unwrap_or(1_f64),
+                color: color_manager::color_from_id(idml_resources, &color_id).ok(),
+            }
+        };
+
+        CellFormat {
+            insets: [
+                cell(&|c| c.top_inset())
+                    .or(table(&|t| t.text_top_inset()))
+                    .unwrap_or(4_f64),
+                cell(&|c| c.left_inset())
+                    .or(table(&|t| t.text_left_inset()))
+                    .unwrap_or(4_f64),
+                cell(&|c| c.bottom_inset())
+                    .or(table(&|t| t.text_bottom_inset()))
+                    .unwrap_or(4_f64),
+                cell(&|c| c.right_inset())
+                    .or(table(&|t| t.text_right_inset()))
+                    .unwrap_or(4_f64),
+            ],
+            fill_color: cell_layers
+                .iter()
+                .find_map(|layer| layer.fill_color())
+                .and_then(|id| color_manager::color_from_id(idml_resources, &id).ok()),
+            vertical_justification: cell_layers
+                .iter()
+                .find_map(|layer| layer.vertical_justification())
+                .unwrap_or(VerticalJustification::TopAlign),
+            edges: [
+                edge(
+                    &|c| c.top_edge_stroke_weight(),
+                    &|c| c.top_edge_stroke_color(),
+                    &table_edges[0],
+                ),
+                edge(
+                    &|c| c.left_edge_stroke_weight(),
+                    &|c| c.left_edge_stroke_color(),
+                    &table_edges[1],
+                ),
+                edge(
+                    &|c| c.bottom_edge_stroke_weight(),
+                    &|c| c.bottom_edge_stroke_color(),
+                    &table_edges[2],
+                ),
+                edge(
+                    &|c| c.right_edge_stroke_weight(),
+                    &|c| c.right_edge_stroke_color(),
+                    &table_edges[3],
+                ),
+            ],
+        }
+    }
+
+    /// Area inside the insets of a cell spanning `bb`. Tables in vertical
+    /// stories are turned, so their top inset is along the right of the cell.
+    fn text_frame(&self, bb: &BoundingBox, vertical: bool) -> BoundingBox {
+        let [top, left, bottom, right] = self.insets;
+        match vertical {
+            true => BoundingBox {
+                top: bb.top - left,
+                left: bb.left + bottom,
+                bottom: bb.bottom + right,
+                right: bb.right - top,
+            },
+            false => BoundingBox {
+                top: bb.top - top,
+                left: bb.left + left,
+                bottom: bb.bottom + bottom,
+                right: bb.right - right,
+            },
+        }
+    }
+}
+
+/// Whether the row or column at `index` takes the end stroke of the table, when the first
+/// `start_count` rows take the start stroke, the next `end_count` rows the end stroke, and so on
+fn takes_end_stroke(index: usize, start_count: Option<usize>, end_count: Option<usize>) -> bool {
+    let start_count = start_count.unwrap_or(1);
+    let end_count = end_count.unwrap_or(0);
+    end_count > 0 && index % (start_count + end_count) >= start_count
+}
+
+/// Which part of the table a row belongs to
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum TableRegion {
+    Header,
+    Body,
+    Footer,
+}
+
+/// The header, body and footer rows of a table
+struct TableRows {
+    count: usize,
+    header: usize,
+    footer: usize,
+    /// Leaves the header rows out of the frame the table starts in
+    skip_first_header: bool,
+    /// Leaves the footer rows out of the frame the table ends in
+    skip_last_footer: bool,
+}
+
+impl TableRows {
+    fn region(&self, row: usize) -> TableRegion {
+        match row {
+            row if row < self.header => TableRegion::Header,
+            row if row >= self.count - self.footer.min(self.count) => TableRegion::Footer,
+            _ => TableRegion::Body,
+        }
+    }
+
+    /// Places the rows of `heights` in a frame from `top` down to `frame_end`: the header
+    /// rows, as many body rows as fit above the footer rows after the `rows_set_before` body
+    /// rows that frames threaded before hold, and then the footer rows. Returns the top of
+    /// each row placed, where the rows end and the first body row that did not fit.
+    fn place(
+        &self,
+        heights: &[f64],
+        top: f64,
+        frame_end: f64,
+        rows_set_before: usize,
+        has_offset: bool,
+    ) -> (Vec<Option<f64>>, f64, Option<usize>) {
+        let skip_header = rows_set_before == 0 && self.skip_first_header;
+        let footer_height: f64 = (0..self.count)
+            .filter(|row| self.region(*row) == TableRegion::Footer)
+            .map(|row| heights[row])
+            .sum();
+        let mut row_tops: Vec<Option<f64>> = vec![None; self.count];
+        let mut y = top;
+        let mut body_rows_set = 0;
+        let mut overset_row = None;
+        for row in 0..self.count {
+            match self.region(row) {
+                TableRegion::Header if skip_header => continue,
+                TableRegion::Body if row < self.header + rows_set_before => continue,
+                // A table set at the top of its frame takes at least one body
+                // row, so that the rows that do not fit move on to the next frame
+                TableRegion::Body
+                    if overset_row.is_some()
+                        || (y - heights[row] - footer_height < frame_end
+                            && (body_rows_set > 0 || has_offset)) =>
+                {
+                    overset_row.get_or_insert(row);
+                    continue;
+                }
+                TableRegion::Body => body_rows_set += 1,
+                TableRegion::Footer if self.skip_last_footer && overset_row.is_none() => continue,
+                _ => {}
+            }
+            row_tops[row] = Some(y);
+            y -= heights[row];
+        }
+        // Without room for any of the body rows left, the table moves on to the next frame
+        if overset_row.is_some() && body_rows_set == 0 {
+            return (vec![None; self.count], top, overset_row);
+        }
+        (row_tops, y, overset_row)
+    }
+}
+
+/// A cell along with where it sits in the table
+struct TableCell<'a> {
+    cell: &'a Cell,
+    column: usize,
+    row: usize,
+    column_span: usize,
+    row_span: usize,
+    format: CellFormat,
+    /// Height of the text in the cell
+    content_height: f64,
+    /// Number of lines of the text after the first
+    lines: usize,
+}
+
+/// Directions a table is set in. Tables in horizontal stories have their rows go down
+/// the frame. Tables in vertical stories are turned, with their rows going from the
+/// right of the frame to the left and their columns going down it.
+struct TableAxes {
+    vertical: bool,
+    /// Edge of the frame the first column starts at
+    line_start: f64,
+}
+
+impl TableAxes {
+    /// Box of a cell from `start` to `end` along the rows, measured from the first
+    /// column, and from `top` to `bottom` across them. In vertical stories `top`
+    /// and `bottom` are the right and left of the box.
+    fn cell_box(&self, start: f64, end: f64, top: f64, bottom: f64) -> BoundingBox {
+        match self.vertical {
+            true => BoundingBox {
+                top: self.line_start - start,
+                left: bottom,
+                bottom: self.line_start - end,
+                right: top,
+            },
+            false => BoundingBox {
+                top,
+                left: self.line_start + start,
+                bottom,
+                right: self.line_start + end,
+            },
+        }
+    }
+
+    /// Side of a cell box that the top, left, bottom or right side of the cell is on,
+    /// by index in the order of `CellFormat::edges`
+    fn side(&self, side: usize) -> usize {
+        match self.vertical {
+            true => (side + 3) % 4,
+            false => side,
+        }
+    }
+}
+
+impl Polygon {
+    /// Sets a table in the text flow at the current line, with
+    /// the text in its cells composed like the text of the story
+    pub(super) fn render_table(
+        &self,
+        table: &Table,
+        render_properties: &RenderProperties,
+        font_lib: &FontLibrary,
+        current_page: HPDF_Page,
+        state: &mut StoryState,
+    ) -> Result<(), String> {
+        if state.overset {
+            return Ok(());
+        }
+        state.finish_line(true);
+        let idml_resources = render_properties.idml_resources;
+        let styles = idml_resources.styles();
+        let axes = TableAxes {
+            vertical: state.vertical,
+            line_start: match state.vertical {
+                true => state.frame.top,
+                false => state.frame.left,
+            },
+        };
+
+        let table_style = table
+            .applied_table_style()
+            .as_ref()
+            .and_then(|id| styles.table_style_from_id(id));
+        let mut table_layers: Vec<&dyn TableAttributes> = vec![table];
+        if let Some(style) = &table_style {
+            table_layers.push(style);
+        }
+        let table_attribute = |get: &dyn Fn(&dyn TableAttributes) -> Option<f64>| {
+            table_layers.iter().find_map(|layer| get(*layer))
+        };
+
+        let header_rows = table.header_row_count().unwrap_or(0);
+        let footer_rows = table.footer_row_count().unwrap_or(0);
+        let row_count = match (table.body_row_count(), table.rows()) {
+            (Some(body_rows), _) => header_rows + body_rows + footer_rows,
+            (None, Some(rows)) => rows.len(),
+            (None, None) => 0,
+        };
+        let table_rows = TableRows {
+            count: row_count,
+            header: header_rows,
+            footer: footer_rows,
+            skip_first_header: table.skip_first_header() == &Some(true),
+            skip_last_footer: table.skip_last_footer() == &Some(true),
+        };
+        let column_count = table
+            .column_count()
+            .or(table.columns().as_ref().map(|columns| columns.len()))
+            .unwrap_or(0);
+        if row_count == 0 || column_count == 0 {
+            return Ok(());
+        }
+
+        // Columns without a width share the frame equally
+        let column_widths: Vec<f64> = (0..column_count)
+            .map(|index| {
+                table
+                    .columns()
+                    .iter()
+                    .flatten()
+                    .find(|column| *column.name() == index)
+                    .and_then(|column| *column.single_column_width())
+                    .unwrap_or(state.line_length() / column_count as f64)
+            })
+            .collect();
+        let column_starts: Vec<f64> = column_widths
+            .iter()
+            .scan(0_f64, |start, width| {
+                let column_start = *start;
+                *start += width;
+                Some(column_start)
+            })
+            .collect();
+
+        // Resolve the formats of each cell and measure its text
+        let mut cells = vec![];
+        for cell in table.cells().iter().flatten() {
+            let (column, row) = match cell.position() {
+                Some((column, row)) if column < column_count && row < row_count => (column, row),
+                _ => continue,
+            };
+            let column_span = cell
+                .column_span()
+                .unwrap_or(1)
+                .max(1)
+                .min(column_count - column);
+            let row_span = cell.row_span().unwrap_or(1).max(1).min(row_count - row);
+
+            let cell_style = cell
+                .applied_cell_style()
+                .as_ref()
+                .and_then(|id| styles.cell_style_from_id(id));
+            let region_style = table_style
+                .as_ref()
+                .and_then(|style| match table_rows.region(row) {
+                    TableRegion::Header => style.header_region_cell_style().clone(),
+                    TableRegion::Body => style.body_region_cell_style().clone(),
+                    TableRegion::Footer => style.footer_region_cell_style().clone(),
+                })
+                .and_then(|id| styles.cell_style_from_id(&id));
+            let mut cell_layers: Vec<&dyn CellAttributes> = vec![cell];
+            cell_layers.extend(cell_style.iter().map(|style| style as &dyn CellAttributes));
+            cell_layers.extend(
+                region_style
+                    .iter()
+                    .map(|style| style as &dyn CellAttributes),
+            );
+            let format = CellFormat::resolve(
+                idml_resources,
+                &cell_layers,
+                &table_layers,
+                row..row + row_span,
+                column..column + column_span,
+                (row_count, column_count),
+            );
+
+            let width: f64 = column_widths[column..column + column_span].iter().sum();
+            let text_frame = format.text_frame(
+                &axes.cell_box(
+                    column_starts[column],
+                    column_starts[column] + width,
+                    0_f64,
+                    f64::NEG_INFINITY,
+                ),
+                axes.vertical,
+            );
+            let measured = self.render_cell_text(
+                cell,
+                cell_state(state, text_frame, true),
+                render_properties,
+                font_lib,
+                current_page,
+            )?;
+
+            cells.push(TableCell {
+                cell,
+                column,
+                row,
+                column_span,
+                row_span,
+                format,
+                content_height: measured.text_extent(),
+                lines: measured.lines,
+            });
+        }
+
+        // Rows grow to fit their cells, unless they have a fixed height.
+        // Cells spanning several rows grow the last row they span.
+        let rows: Vec<_> = (0..row_count)
+            .map(|index| {
+                table
+                    .rows()
+                    .iter()
+                    .flatten()
+                    .find(|row| *row.name() == index)
+            })
+            .collect();
+        let mut row_heights: Vec<f64> = rows
+            .iter()
+            .map(|row| match row {
+                Some(row) if row.auto_grow() == &Some(false) => {
+                    row.single_row_height().unwrap_or(0_f64)
+                }
+                Some(row) => row
+                    .minimum_height()
+                    .or(*row.single_row_height())
+                    .unwrap_or(0_f64),
+                None => 0_f64,
+            })
+            .collect();
+        cells.sort_by_key(|cell| cell.row_span);
+        for cell in &cells {
+            let last_row = cell.row + cell.row_span - 1;
+            if rows[last_row].map(|row| row.auto_grow() == &Some(false)) == Some(true) {
+                continue;
+            }
+            let needed = cell.content_height + cell.format.insets[0] + cell.format.insets[2];
+            let spanned: f64 = row_heights[cell.row..=last_row].iter().sum();
+            if needed > spanned {
+                row_heights[last_row] += needed - spanned;
+            }
+        }
+
+        // Place the header rows, as many body rows as fit the frame above the footer
+        // rows, and then the footer rows. In vertical stories the rows are placed from
+        // right to left, so `top` and `y` are along the x axis.
+        let (mut top, frame_end) = match (state.vertical, state.has_offset, state.is_line_empty()) {
+            (false, false, _) => (state.frame.top, state.frame.bottom),
+            // The table takes the place of the empty line after a paragraph break
+            (false, true, true) => (state.text_bottom() + state.leading, state.frame.bottom),
+            (false, true, false) => (state.text_bottom(), state.frame.bottom),
+            (true, false, _) => (state.frame.right, state.frame.left),
+            (true, true, true) => (state.column + state.leading / 2_f64, state.frame.left),
+            (true, true, false) => (state.column - state.leading / 2_f64, state.frame.left),
+        };
+        top -= table_attribute(&|t| t.space_before()).unwrap_or(0_f64);
+        // Body rows that frames threaded before this one already hold
+        let rows_set_before = std::mem::take(&mut state.skipped_glyphs);
+        let (row_tops, y, overset_row) = table_rows.place(
+            &row_heights,
+            top,
+            frame_end,
+            rows_set_before,
+            state.has_offset,
+        );
+        let first_row_set = row_tops.iter().position(Option::is_some);
+        let last_row_set = row_tops.iter().rposition(Option::is_some);
+
+        let placed: Vec<(&TableCell, BoundingBox, usize)> = cells
+            .iter()
+            .filter_map(|cell| {
+                let top = row_tops[cell.row]?;
+                let last_row = (cell.row..cell.row + cell.row_span)
+                    .take_while(|row| row_tops[*row].is_some())
+                    .last()?;
+                let width: f64 = column_widths[cell.column..cell.column + cell.column_span]
+                    .iter()
+                    .sum();
+                let bb = axes.cell_box(
+                    column_starts[cell.column],
+                    column_starts[cell.column] + width,
+                    top,
+                    row_tops[last_row]? - row_heights[last_row],
+                );
+                Some((cell, bb, last_row))
+            })
+            .collect();
+
+        let mut footnotes = vec![];
+        // Tables in cells that are being measured only take up room
+        if !state.measuring {
+            unsafe {
+                // Fills and strokes are paths, which cannot be drawn inside the text object
+                HPDF_Page_EndText(current_page);
+                HPDF_Page_GSave(current_page);
+                for (cell, bb, _) in &placed {
+                    if let Some(color) = &cell.format.fill_color {
+                        set_fill_color(current_page, color);
+                        HPDF_Page_Rectangle(
+                            current_page,
+                            bb.left as f32,
+                            bb.bottom as f32,
+                            (bb.right - bb.left) as f32,
+                            (bb.top - bb.bottom) as f32,
+                        );
+                        HPDF_Page_Fill(current_page);
+                    }
+                }
+                HPDF_Page_GRestore(current_page);
+                HPDF_Page_BeginText(current_page);
+
+                for (cell, bb, _) in &placed {
+                    let mut text_frame = cell.format.text_frame(bb, axes.vertical);
+                    let room = match axes.vertical {
+                        true => text_frame.right - text_frame.left,
+                        false => text_frame.top - text_frame.bottom,
+                    };
+                    let room = (room - cell.content_height).max(0_f64);
+                    let shift = match cell.format.vertical_justification {
+                        VerticalJustification::CenterAlign => room / 2_f64,
+                        VerticalJustification::BottomAlign => room,
+                        _ => 0_f64,
+                    };
+                    match axes.vertical {
+                        true => text_frame.right -= shift,
+                        false => text_frame.top -= shift,
+                    }
+                    let mut cell_state = cell_state(state, text_frame, false);
+                    // Justified cells spread the room left over between their lines
+                    if cell.format.vertical_justification == VerticalJustification::JustifyAlign
+                        && cell.lines > 0
+                    {
+                        cell_state.line_gap = room / cell.lines as f64;
+                    }
+                    let mut cell_state = self.render_cell_text(
+                        cell.cell,
+                        cell_state,
+                        render_properties,
+                        font_lib,
+                        current_page,
+                    )?;
+                    state.decorations.append(&mut cell_state.decorations);
+                    state.text_runs.append(&mut cell_state.text_runs);
+                    state.gradient_runs.append(&mut cell_state.gradient_runs);
+                    state
+                        .anchored_objects
+                        .append(&mut cell_state.anchored_objects);
+                    footnotes.append(&mut cell_state.footnotes);
+                    state.link_areas.append(&mut cell_state.link_areas);
+                    state.anchors.append(&mut cell_state.anchors);
+                    state
+                        .note_references
+                        .append(&mut cell_state.note_references);
+                    state
+                        .condition_areas
+                        .append(&mut cell_state.condition_areas);
+                }
+
+                HPDF_Page_EndText(current_page);
+                HPDF_Page_GSave(current_page);
+                let table_edge = |weight: &dyn Fn(&dyn TableAttributes) -> Option<f64>,
+                                  stroke_color: &dyn Fn(&dyn TableAttributes) -> Option<String>| {
+                    let weight = table_layers.iter().find_map(|layer| weight(*layer))?;
+                    let color = table_layers
+                        .iter()
+                        .find_map(|layer| stroke_color(*layer))
+                        .and_then(|id| color_manager::color_from_id(idml_resources, &id).ok());
+                    Some(EdgeStroke { weight, color })
+                };
+                let borders = [
+                    table_edge(&|t| t.top_border_stroke_weight(), &|t| {
+                        t.top_border_stroke_color()
+                    }),
+                    table_edge(&|t| t.left_border_stroke_weight(), &|t| {
+                        t.left_border_stroke_color()
+                    }),
+                    table_edge(&|t| t.bottom_border_stroke_weight(), &|t| {
+                        t.bottom_border_stroke_color()
+                    }),
+                    table_edge(&|t| t.right_border_stroke_weight(), &|t| {
+                        t.right_border_stroke_color()
+                    }),
+                ];
+                for (cell, bb, last_row) in &placed {
+                    // The table border replaces the edges of cells along the outside of the
+                    // part of the table in the frame
+                    let outer = [
+                        Some(cell.row) == first_row_set,
+                        cell.column == 0,
+                        Some(*last_row) == last_row_set,
+                        cell.column + cell.column_span == column_count,
+                    ];
+                    let lines = [
+                        ((bb.left, bb.top), (bb.right, bb.top)),
+                        ((bb.left, bb.top), (bb.left, bb.bottom)),
+                        ((bb.left, bb.bottom), (bb.right, bb.bottom)),
+                        ((bb.right, bb.top), (bb.right, bb.bottom)),
+                    ];
+                    for side in 0..4 {
+                        let edge = match (outer[side], &borders[side]) {
+                            (true, Some(border)) => border,
+                            _ => &cell.format.edges[side],
+                        };
+                        let color = match &edge.color {
+                            Some(color) if edge.weight > 0_f64 => color,
+                            _ => continue,
+                        };
+                        let ((x1, y1), (x2, y2)) = lines[axes.side(side)];
+                        set_stroke_color(current_page, color);
+                        HPDF_Page_SetLineWidth(current_page, edge.weight as f32);
+                        HPDF_Page_MoveTo(current_page, x1 as f32, y1 as f32);
+                        HPDF_Page_LineTo(current_page, x2 as f32, y2 as f32);
+                        HPDF_Page_Stroke(current_page);
+                    }
+                }
+                HPDF_Page_GRestore(current_page);
+                HPDF_Page_BeginText(current_page);
+            }
+        }
+
+        // Text after the table continues below it
+        let after = y - table_attribute(&|t| t.space_after()).unwrap_or(0_f64);
+        match state.vertical {
+            true => state.column = after,
+            false => state.y = after,
+        }
+        state.has_offset = true;
+        state.line_width = state.indent;
+        self.place_table_footnotes(
+            footnotes,
+            render_properties.idml_resources,
+            font_lib,
+            current_page,
+            state,
+        )?;
+        // The body rows that did not fit go on in the next frame of the thread,
+        // below its header rows
+        if let Some(row) = overset_row {
+            state.overflow_at(row - header_rows);
+        }
+        Ok(())
+    }
+
+    /// Composes the text of a cell in `cell_state`, returning the state the text ends in
+    fn render_cell_text(
+        &self,
+        cell: &Cell,
+        mut cell_state: StoryState,
+        render_properties: &RenderProperties,
+        font_lib: &FontLibrary,
+        current_page: HPDF_Page,
+    ) -> Result<StoryState, String> {
+        let idml_resources = render_properties.idml_resources;
+        // Cell text does not take on the formats of the paragraph the table is in
+        let render_properties = RenderProperties::new(idml_resources);
+        self.render_paragraphs(
+            cell.paragraph_style_ranges().as_deref().unwrap_or_default(),
+            &render_properties,
+            idml_resources,
+            font_lib,
+            current_page,
+            &mut cell_state,
+        )?;
+        Ok(cell_state)
+    }
+}
+
+/// State the text of a cell is composed in within `text_frame`, or only measured in
+fn cell_state(state: &StoryState, text_frame: BoundingBox, measuring: bool) -> StoryState {
+    StoryState {
+        y: text_frame.top,
+        column: text_frame.right,
+        frame: text_frame,
+        vertical: state.vertical,
+        measuring,
+        page_number: state.page_number.clone(),
+        page_margins: state.page_margins.clone(),
+        text_variables: state.text_variables.clone(),
+        note_numbers: state.note_numbers.clone(),
+        cross_references: state.cross_references.clone(),
+        in_table: true,
+        ..StoryState::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use quick_xml::de::from_str;
+
+    fn resources() -> IDMLResources {
+        let xml = r#"<idPkg:Graphic xmlns:idPkg="http://ns.adobe.com/AdobeInDesign/idml/1.0/packaging" DOMVersion="16.0">
+            <Color Self="Color/Black" Model="Process" Space="CMYK" ColorValue="0 0 0 100" Name="Black"/>
+            <Color Self="Color/Red" Model="Process" Space="CMYK" ColorValue="0 100 100 0" Name="Red"/>
+            <Color Self="Color/Blue" Model="Process" Space="CMYK" ColorValue="100 100 0 0" Name="Blue"/>
+            <Swatch Self="Swatch/None" Name="None"/>
+        </idPkg:Graphic>"#;
+        IDMLResources::with_graphic(from_str(xml).unwrap())
+    }
+
+    /// Weight and color of the top, left, bottom and right edges of a cell
+    fn edges(
+        resources: &IDMLResources,
+        cell: &Cell,
+        style: &TableStyle,
+        rows: Range<usize>,
+        columns: Range<usize>,
+    ) -> Vec<(f64, String)> {
+        let table = from_str::<Table>(r#"<Table Self="t1"/>"#).unwrap();
+        let format =
+            CellFormat::resolve(resources, &[cell], &[&table, style], rows, columns, (4, 3));
+        format
+            .edges
+            .iter()
+            .map(|edge| (edge.weight, format!("{:?}", edge.color)))
+            .collect()
+    }
+
+    #[test]
+    fn cell_edges_fall_back_to_the_strokes_of_the_table_style() {
+        let resources = resources();
+        let color = |id: &str| format!("{:?}", resources.color_from_id(&id.to_string()).ok());
+        let style = from_str::<TableStyle>(
+            r#"<TableStyle Self="TableStyle/Grid"
+                TopBorderStrokeWeight="3" TopBorderStrokeColor="Color/Red"
+                LeftBorderStrokeWeight="3" BottomBorderStrokeWeight="3" RightBorderStrokeWeight="3"
+                StartRowStrokeCount="2" StartRowStrokeWeight="0.5" StartRowStrokeColor="Color/Blue"
+                EndRowStrokeCount="1" EndRowStrokeWeight="2"
+                StartColumnStrokeWeight="0.25"/>"#,
+        )
+        .unwrap();
+
+        // A cell in the corner takes the table border along the outside
+        let cell = from_str::<Cell>(r#"<Cell Self="c1" Name="0:0"/>"#).unwrap();
+        assert_eq!(
+            edges(&resources, &cell, &style, 0..1, 0..1),
+            vec![
+                (3_f64, color("Color/Red")),
+                (3_f64, color("Color/Black")),
+                (0.5_f64, color("Color/Blue")),
+                (0.25_f64, color("Color/Black")),
+            ]
+        );
+
+        // Inside the table, the two start row strokes are followed by an end row stroke
+        let cell =
+            from_str::<Cell>(r#"<Cell Self="c2" Name="1:2" LeftEdgeStrokeWeight="4"/>"#).unwrap();
+        assert_eq!(
+            edges(&resources, &cell, &style, 2..3, 1..2),
+            vec![
+                (0.5_f64, color("Color/Blue")),
+                (4_f64, color("Color/Black")),
+                (2_f64, color("Color/Black")),
+                (0.25_f64, color("Color/Black")),
+            ]
+        );
+
+        // Without strokes in the table or its style, edges are 1pt black
+        let plain = from_str::<TableStyle>(r#"<TableStyle Self="TableStyle/Plain"/>"#).unwrap();
+        assert_eq!(
+            edges(&resources, &cell, &plain, 1..2, 1..2)[2],
+            (1_f64, color("Color/Black"))
+        );
+    }
+
+    #[test]
+    fn row_and_column_strokes_alternate_by_their_counts() {
+        let pattern: Vec<bool> = (0..6)
+            .map(|row| takes_end_stroke(row, Some(2), Some(1)))
+            .collect();
+        assert_eq!(pattern, vec![false, false, true, false, false, true]);
+        assert!(!takes_end_stroke(3, None, None));
+        assert!(!takes_end_stroke(3, Some(1), Some(0)));
+    }
+
+    /// One header row, three body rows and one footer row, each 10pt high
+    fn table_rows() -> TableRows {
+        TableRows {
+            count: 5,
+            header: 1,
+            footer: 1,
+            skip_first_header: false,
+            skip_last_footer: false,
+        }
+    }
+
+    #[test]
+    fn header_and_footer_rows_repeat_in_both_frames_of_a_split_table() {
+        let heights = [10_f64; 5];
+        // The first frame holds the header, two body rows and the footer
+        let (tops, end, overset_row) = table_rows().place(&heights, 100_f64, 60_f64, 0, false);
+        assert_eq!(
+            tops,
+            vec![
+                Some(100_f64),
+                Some(90_f64),
+                Some(80_f64),
+                None,
+                Some(70_f64)
+            ]
+        );
+        assert_eq!(end, 60_f64);
+        assert_eq!(overset_row, Some(3));
+
+        // The next frame repeats the header and footer around the last body row
+        let (tops, end, overset_row) = table_rows().place(&heights, 100_f64, 60_f64, 2, false);
+        assert_eq!(
+            tops,
+            vec![Some(100_f64), None, None, Some(90_f64), Some(80_f64)]
+        );
+        assert_eq!(end, 70_f64);
+        assert_eq!(overset_row, None);
+    }
+
+    #[test]
+    fn the_first_header_and_last_footer_may_be_skipped() {
+        let heights = [10_f64; 5];
+        let rows = TableRows {
+            skip_first_header: true,
+            skip_last_footer: true,
+            ..table_rows()
+        };
+        let (tops, _, overset_row) = rows.place(&heights, 100_f64, 70_f64, 0, false);
+        assert_eq!(
+            tops,
+            vec![None, Some(100_f64), Some(90_f64), None, Some(80_f64)]
+        );
+        assert_eq!(overset_row, Some(3));
+
+        let (tops, _, overset_row) = rows.place(&heights, 100_f64, 70_f64, 2, false);
+        assert_eq!(tops, vec![Some(100_f64), None, None, Some(90_f64), None]);
+        assert_eq!(overset_row, None);
+    }
+
+    #[test]
+    fn tables_after_text_move_on_without_room_for_a_body_row() {
+        let heights = [10_f64; 5];
+        let (tops, end, overset_row) = table_rows().place(&heights, 100_f64, 75_f64, 0, true);
+        assert_eq!(tops, vec![None; 5]);
+        assert_eq!(end, 100_f64);
+        assert_eq!(overset_row, Some(1));
+    }
+}
@@ -1,51 +1,634 @@
+use super::anchored_object::AnchoredObject;
+use super::footnote::PlacedFootnote;
+use super::line_layout::{LineRun, RunAdornment, RunFont, TextRun};
 use crate::idml_parser::{
     spread_parser::*,
-    story_parser::*,
+    story_parser::{self, *},
     styles::{
         character_style::{self},
+        commom_text_properties::{
+            Capitalization, CharacterDirection, CommonTextPropertiesAttributes, DigitsType,
+            Justification, Kashidas, ParagraphDirection, Position, StartParagraph,
+        },
         paragraph_style::{self},
     },
     IDMLPackage, IDMLResources,
 };
 use crate::pdf_printer::pdf_utils::*;
 use crate::pdf_printer::{
-    color_manager::{self, Color, *},
+    bidi::ParagraphLevels,
+    cjk_layout::{Adornments, CjkStyle},
+    color_manager::{Color, Gradient, *},
+    condition_indicators::{self, ConditionArea},
     font_manager::FontLibrary,
-    transforms::*,
+    gradients::{self, GradientPlacement},
+    image_manager::ImageLibrary,
+    navigation::{Anchor, LinkArea, Navigation},
+    page_numbering::PageNumber,
+    story_layout::{StoryLayout, StoryPosition},
+    text_decorations::{Decoration, DecorationKind, DecorationStyle},
+    text_layout::{self, GlyphTransforms, KerningMethod, OpenTypeFeatures, PositionedGlyph},
+    transforms::{self, *},
 };
 use libharu_sys::*;
-use std::ffi::CString;
-use std::ptr;
+use std::collections::{HashMap, VecDeque};
 
 #[derive(Debug, Clone)]
 pub struct RenderProperties<'a> {
-    idml_resources: &'a IDMLResources,
+    pub(super) idml_resources: &'a IDMLResources,
     font_name: Option<String>,
     font_style: Option<String>,
     font_size: Option<f64>,
     auto_leading: Option<f64>,
     stroke_color: Option<Color>,
     fill_color: Option<Color>,
+    /// Swatches and tints the colors are resolved from, which may be set by different styles
+    stroke_color_id: Option<String>,
+    stroke_tint: Option<f64>,
+    fill_color_id: Option<String>,
+    fill_tint: Option<f64>,
+    /// Where a gradient fill starts, in the coordinates of the frame
+    gradient_fill_start: Option<Vec<f64>>,
+    gradient_fill_length: Option<f64>,
+    gradient_fill_angle: Option<f64>,
+    underline: DecorationStyle,
+    strike_through: DecorationStyle,
+    tracking: Option<f64>,
+    kerning_method: Option<String>,
+    kerning_value: Option<f64>,
+    horizontal_scale: Option<f64>,
+    vertical_scale: Option<f64>,
+    skew: Option<f64>,
+    baseline_shift: Option<f64>,
+    capitalization: Option<Capitalization>,
+    position: Option<Position>,
+    opentype: OpenTypeFeatures,
+    paragraph_direction: Option<ParagraphDirection>,
+    character_direction: Option<CharacterDirection>,
+    digits_type: Option<DigitsType>,
+    x_offset_diacritic: Option<f64>,
+    y_offset_diacritic: Option<f64>,
+    justification: Option<Justification>,
+    kashidas: Option<Kashidas>,
+    cjk: CjkStyle,
 }
 
-trait StyleProperties {
-    fn get_applied_font(self) -> Option<String>;
+/// State that carries over between the text runs of a story
+#[derive(Debug, Default)]
+pub struct StoryState {
+    /// Area the text is set in, which is the frame or a table cell
+    pub(super) frame: BoundingBox,
+    pub(super) has_offset: bool,
+    pub(super) decorations: Vec<Decoration>,
+    /// Runs set on the current line, placed once the line is complete
+    pub(super) line_runs: Vec<LineRun>,
+    /// Width the current line starts at
+    pub(super) line_start: f64,
+    /// Text placed on its line, drawn once the text is set
+    pub(super) text_runs: Vec<TextRun>,
+    /// Text filled with a gradient, drawn once the text is set
+    pub(super) gradient_runs: Vec<GradientRun>,
+    /// Page items anchored in the text, drawn once the text is set
+    pub(super) anchored_objects: Vec<AnchoredObject>,
+    /// Width taken up on the current line, measured from the left edge of
+    /// the frame, or from the right edge in right-to-left paragraphs
+    pub(super) line_width: f64,
+    pub(super) y: f64,
+    pub(super) leading: f64,
+    /// Vertical stories are set in columns from right to left
+    pub(super) vertical: bool,
+    /// Center of the current column in vertical stories
+    pub(super) column: f64,
+    /// Room added between lines, which spreads the lines of vertically justified cells
+    pub(super) line_gap: f64,
+    /// Number of lines set after the first
+    pub(super) lines: usize,
+    /// Room taken up above the baseline of the current line by tall inline items,
+    /// or right of the center of the column in vertical stories
+    pub(super) line_rise: f64,
+    /// Width lines of the current paragraph start at, set by indent to here characters
+    pub(super) indent: f64,
+    /// Set once a frame or page break, or a column break in the last column,
+    /// ends the text in the frame
+    pub(super) overset: bool,
+    /// Columns of the frame after the one being set, which the text goes on in
+    pub(super) next_columns: VecDeque<BoundingBox>,
+    /// Break that ended the text in the frame, when the text after it goes on a later page
+    pub(super) page_break: Option<StartParagraph>,
+    /// Position in the story of the content being set
+    pub(super) position: StoryPosition,
+    /// Where the text of the frame starts, as frames continue the text of the frame
+    /// threaded before them. Content before it is skipped.
+    pub(super) start: StoryPosition,
+    /// Glyphs of the text run, or body rows of the table, being set that the
+    /// previous frame already holds
+    pub(super) skipped_glyphs: usize,
+    /// Where the text that does not fit the frame starts
+    pub(super) end: Option<StoryPosition>,
+    /// Number of paragraphs before the one being set
+    pub(super) paragraph: usize,
+    /// Paragraphs with text set in the frame
+    pub(super) paragraphs_set: Vec<usize>,
+    /// Index markers in the text set in the frame, by id
+    pub(super) page_references: Vec<String>,
+    /// Footnotes and endnotes referenced in the text set in the frame, by id
+    pub(super) note_references: Vec<String>,
+    /// Lays out the text without drawing it, to measure how much room it takes up
+    pub(super) measuring: bool,
+    pub(super) page_number: PageNumber,
+    /// Margins of the page the frame is on, which anchored objects can be placed against
+    pub(super) page_margins: Option<MarginPreference>,
+    /// Text variables resolved for the current page, keyed by variable id
+    pub(super) text_variables: HashMap<String, String>,
+    /// Numbers of the footnotes and endnotes, keyed by note id
+    pub(super) note_numbers: HashMap<String, String>,
+    /// Number shown by footnote number characters in the text of a footnote
+    pub(super) note_number: Option<String>,
+    /// Endnotes numbered so far, when setting the endnote story
+    pub(super) endnote_count: Option<u32>,
+    /// Footnotes referenced in the text, set at the bottom of the frame
+    pub(super) footnotes: Vec<PlacedFootnote>,
+    /// Text in table cells leaves its footnotes to the frame the table is in
+    pub(super) in_table: bool,
+    /// Text of cross-references, keyed by source id
+    pub(super) cross_references: HashMap<String, String>,
+    /// Id of the hyperlink source the text being set is in
+    pub(super) link_source: Option<String>,
+    pub(super) link_areas: Vec<LinkArea>,
+    /// Text destinations in the text, where hyperlinks and bookmarks can go
+    pub(super) anchors: Vec<Anchor>,
+    /// Applied conditions of the text being set
+    pub(super) conditions: Option<String>,
+    pub(super) condition_areas: Vec<ConditionArea>,
+    /// Bidi levels of the text, resolved over whole paragraphs before the text is set
+    pub(super) paragraph_levels: ParagraphLevels,
+    /// Gathers the text of the paragraphs into `paragraph_levels` instead of setting it
+    pub(super) collecting_levels: bool,
+}
+
+/// Glyphs filled with a gradient. The gradient is painted through the glyphs as a clipping path,
+/// which only applies once the text object is closed, so they are drawn after the rest of the text.
+#[derive(Debug)]
+pub struct GradientRun {
+    pub(super) gradient: Gradient,
+    pub(super) placement: GradientPlacement,
+    pub(super) font: HPDF_Font,
+    pub(super) glyphs: Vec<PositionedGlyph>,
+    pub(super) transforms: GlyphTransforms,
+    /// Start of the baseline, or top of the column in vertical stories
+    pub(super) x: f64,
+    pub(super) y: f64,
 }
 
-impl StyleProperties for paragraph_style::ParagraphProperties {
-    fn get_applied_font(self) -> Option<String> {
-        self.applied_font().clone()
+impl GradientRun {
+    unsafe fn render(
+        &self,
+        frame_transform: &Transform,
+        frame_bounds: (f64, f64, f64, f64),
+        image_lib: &ImageLibrary,
+        current_page: HPDF_Page,
+    ) -> Result<(), String> {
+        HPDF_Page_GSave(current_page);
+        HPDF_Page_BeginText(current_page);
+        HPDF_Page_SetFontAndSize(current_page, self.font, self.transforms.font_size as f32);
+        HPDF_Page_SetTextRenderingMode(current_page, HPDF_TextRenderingMode::HPDF_CLIPPING);
+        let shown = match self.transforms.vertical {
+            true => text_layout::show_glyphs_vertical(
+                current_page,
+                &self.glyphs,
+                self.x,
+                self.y,
+                &self.transforms,
+            ),
+            false => text_layout::show_glyphs(
+                current_page,
+                &self.glyphs,
+                self.x,
+                self.y,
+                &self.transforms,
+            ),
+        };
+        HPDF_Page_EndText(current_page);
+        let painted = shown.and_then(|_| {
+            gradients::paint_gradient(
+                current_page,
+                image_lib,
+                &self.gradient,
+                &self.placement,
+                frame_transform,
+                frame_bounds,
+            )
+        });
+        HPDF_Page_GRestore(current_page);
+        painted
     }
 }
 
-impl StyleProperties for character_style::CharacterProperties {
-    fn get_applied_font(self) -> Option<String> {
-        self.applied_font().clone()
+impl StoryState {
+    /// Moves to the start of the next line, or the next column in vertical stories
+    pub(super) fn new_line(&mut self) {
+        self.finish_line(false);
+        self.line_width = self.indent;
+        self.line_rise = 0_f64;
+        self.lines += 1;
+        match self.vertical {
+            true => self.column -= self.leading + self.line_gap,
+            false => self.y -= self.leading + self.line_gap,
+        }
+        if self.is_past_frame() && !self.overset {
+            self.next_column();
+        }
+    }
+
+    /// Splits the frame into columns of equal width, with `gutter` between them. The text
+    /// is set in the first column and goes on in the next ones, from left to right or,
+    /// in vertical stories, from top to bottom.
+    pub(super) fn split_into_columns(&mut self, count: usize, gutter: f64) {
+        if count < 2 {
+            return;
+        }
+        let frame = self.frame.clone();
+        let vertical = self.vertical;
+        let columns = (0..count).map(|i| {
+            let i = i as f64;
+            match vertical {
+                true => {
+                    let height =
+                        (frame.top - frame.bottom - gutter * (count - 1) as f64) / count as f64;
+                    BoundingBox {
+                        top: frame.top - i * (height + gutter),
+                        bottom: frame.top - i * (height + gutter) - height,
+                        ..frame.clone()
+                    }
+                }
+                false => {
+                    let width =
+                        (frame.right - frame.left - gutter * (count - 1) as f64) / count as f64;
+                    BoundingBox {
+                        left: frame.left + i * (width + gutter),
+                        right: frame.left + i * (width + gutter) + width,
+                        ..frame.clone()
+                    }
+                }
+            }
+        });
+        self.next_columns = columns.collect();
+        if let Some(first) = self.next_columns.pop_front() {
+            self.frame = first;
+        }
+    }
+
+    /// Moves the line being set to the top of the next column of the frame,
+    /// returning whether the frame has another column
+    fn next_column(&mut self) -> bool {
+        let column = match self.next_columns.pop_front() {
+            Some(column) => column,
+            None => return false,
+        };
+        match self.vertical {
+            true => self.column = column.right - self.leading / 2_f64,
+            false => self.y = column.top - self.leading,
+        }
+        self.frame = column;
+        true
+    }
+
+    /// Ends the text in the column, frame or page at a paragraph that starts in the next
+    /// one. Column breaks in the last column go on in the next frame, like frame breaks.
+    fn break_paragraph(&mut self, break_type: StartParagraph) {
+        match break_type {
+            StartParagraph::Anywhere => self.new_paragraph(),
+            StartParagraph::NextColumn => {
+                if !self.next_columns.is_empty() {
+                    self.finish_line(true);
+                    self.indent = 0_f64;
+                    self.line_width = 0_f64;
+                    self.line_rise = 0_f64;
+                    self.lines += 1;
+                    self.next_column();
+                } else {
+                    self.overset = true;
+                }
+            }
+            StartParagraph::NextFrame => self.overset = true,
+            StartParagraph::NextPage
+            | StartParagraph::NextOddPage
+            | StartParagraph::NextEvenPage => {
+                self.overset = true;
+                self.page_break = Some(break_type);
+            }
+        }
+    }
+
+    fn new_paragraph(&mut self) {
+        self.finish_line(true);
+        self.indent = 0_f64;
+        self.new_line();
+    }
+
+    /// Moves on to the next piece of content of the story, returning whether it is set in
+    /// this frame: content before the start of the frame is left to the frames before it,
+    /// and once the frame is full the rest is left to the next frame.
+    fn next_content(&mut self, content: &StoryContent) -> bool {
+        let position = self.position.content;
+        self.position.content += 1;
+        let is_set = if self.collecting_levels {
+            true
+        } else if position < self.start.content {
+            // Endnotes are numbered by the number characters before them
+            if let (StoryContent::FootnoteNumber, Some(count)) = (content, &mut self.endnote_count)
+            {
+                *count += 1;
+            }
+            false
+        } else if self.end.is_some() {
+            false
+        } else if self.is_past_frame() {
+            self.end = Some(StoryPosition {
+                content: position,
+                offset: 0,
+            });
+            false
+        } else {
+            self.skipped_glyphs = match position == self.start.content {
+                true => self.start.offset,
+                false => 0,
+            };
+            if self.paragraphs_set.last() != Some(&self.paragraph) {
+                self.paragraphs_set.push(self.paragraph);
+            }
+            true
+        };
+        if let StoryContent::Br = content {
+            self.paragraph += 1;
+        }
+        is_set
+    }
+
+    /// Ids of the text destinations, index markers and note references in the text set so far
+    fn markers(&self) -> Vec<String> {
+        self.anchors
+            .iter()
+            .map(|anchor| anchor.destination.clone())
+            .chain(self.page_references.iter().cloned())
+            .chain(self.note_references.iter().cloned())
+            .collect()
+    }
+
+    /// Leaves the text from `glyph` of the text run being set, or from
+    /// that body row of the table being set, to the next frame
+    pub(super) fn overflow_at(&mut self, glyph: usize) {
+        if self.end.is_none() {
+            self.end = Some(StoryPosition {
+                content: self.position.content - 1,
+                offset: glyph,
+            });
+        }
+    }
+
+    /// Adds the text of the content being set to the paragraph whose bidi levels are gathered
+    fn collect_text(&mut self, text: &str, transforms: &GlyphTransforms) {
+        if let Some(position) = self.position.content.checked_sub(1) {
+            self.paragraph_levels.push_text(position, text, transforms);
+        }
+    }
+
+    /// Bidi levels of the characters of the text of the content being set
+    pub(super) fn content_levels(&self) -> Option<&[u8]> {
+        let position = self.position.content.checked_sub(1)?;
+        self.paragraph_levels.levels(position)
+    }
+
+    /// Bidi level of room taken up by the content being set, like a space or an inline item
+    pub(super) fn content_level(&self, paragraph_level: u8) -> u8 {
+        self.content_levels()
+            .and_then(|levels| levels.first().copied())
+            .unwrap_or(paragraph_level)
+    }
+
+    /// Text that stands in for page numbers, section markers, text variables,
+    /// footnote numbers and non-breaking hyphens
+    fn substituted_text(&self, content: &StoryContent) -> Option<String> {
+        match content {
+            StoryContent::AutoPageNumber => Some(self.page_number.number.clone()),
+            StoryContent::SectionMarker => Some(self.page_number.section_marker.clone()),
+            StoryContent::NonBreakingHyphen => Some("-".to_string()),
+            StoryContent::FootnoteNumber => self.note_number.clone(),
+            StoryContent::TextVariableInstance(instance) => instance
+                .associated_text_variable()
+                .as_ref()
+                .and_then(|variable_id| self.text_variables.get(variable_id))
+                .or(instance.result_text().as_ref())
+                .cloned(),
+            _ => None,
+        }
+    }
+
+    /// Notes the area of a run of text set in a hyperlink source, extending
+    /// the area of the run before it when they are next to each other
+    pub(super) fn add_link_area(&mut self, source: &Option<String>, area: BoundingBox) {
+        let source = match source {
+            Some(source) => source.clone(),
+            None => return,
+        };
+        if let Some(last) = self.link_areas.last_mut() {
+            if last.source == source
+                && last.area.top == area.top
+                && last.area.bottom == area.bottom
+                && (last.area.right - area.left).abs() < 0.01_f64
+            {
+                last.area.right = area.right;
+                return;
+            }
+        }
+        self.link_areas.push(LinkArea { source, area });
+    }
+
+    /// Notes the area of a run of text with conditions applied to it, extending
+    /// the area of the run before it when they are next to each other
+    pub(super) fn add_condition_area(&mut self, conditions: &Option<String>, area: BoundingBox) {
+        let conditions = match conditions {
+            Some(conditions) => conditions.clone(),
+            None => return,
+        };
+        if let Some(last) = self.condition_areas.last_mut() {
+            if last.conditions == conditions
+                && last.area.top == area.top
+                && last.area.bottom == area.bottom
+                && (last.area.right - area.left).abs() < 0.01_f64
+            {
+                last.area.right = area.right;
+                return;
+            }
+        }
+        self.condition_areas
+            .push(ConditionArea { conditions, area });
+    }
+
+    /// Area a run of text takes up on the current line, from its start to its end
+    pub(super) fn line_area(&self, start: f64, end: f64) -> BoundingBox {
+        match self.vertical {
+            true => BoundingBox {
+                left: self.column - self.leading / 2_f64,
+                right: self.column + self.leading / 2_f64,
+                top: start,
+                bottom: end,
+            },
+            false => BoundingBox {
+                left: start,
+                right: end,
+                top: self.y + self.leading * 0.75_f64,
+                bottom: self.y - self.leading / 4_f64,
+            },
+        }
+    }
+
+    pub(super) fn is_line_empty(&self) -> bool {
+        self.line_width <= self.indent
+    }
+
+    pub(super) fn is_past_frame(&self) -> bool {
+        if self.overset {
+            return true;
+        }
+        match self.vertical {
+            true => self.column < self.frame.left,
+            false => self.y < self.frame.bottom,
+        }
+    }
+
+    pub(super) fn line_length(&self) -> f64 {
+        match self.vertical {
+            true => self.frame.top - self.frame.bottom,
+            false => self.frame.right - self.frame.left,
+        }
+    }
+
+    /// Lowest point of the text set so far, leaving room below
+    /// the last baseline for descenders
+    pub(super) fn text_bottom(&self) -> f64 {
+        match self.has_offset {
+            true => self.y - self.leading / 4_f64,
+            false => self.frame.top,
+        }
+    }
+
+    /// Where the text set so far ends: its lowest point, or the left
+    /// edge of its last column in vertical stories
+    pub(super) fn text_end(&self) -> f64 {
+        match (self.vertical, self.has_offset) {
+            (true, true) => self.column - self.leading / 2_f64,
+            (true, false) => self.frame.right,
+            (false, _) => self.text_bottom(),
+        }
+    }
+
+    /// Room the text set so far takes up from the top of the frame,
+    /// or from its right edge in vertical stories
+    pub(super) fn text_extent(&self) -> f64 {
+        match self.vertical {
+            true => self.frame.right - self.text_end(),
+            false => self.frame.top - self.text_end(),
+        }
+    }
+
+    /// Edge of the frame that lines are set towards: its bottom,
+    /// or its left edge in vertical stories
+    pub(super) fn frame_end(&self) -> f64 {
+        match self.vertical {
+            true => self.frame.left,
+            false => self.frame.bottom,
+        }
     }
 }
 
+/// Character that content other than text stands for when the
+/// bidi levels of the paragraph it is in are resolved
+fn stand_in_text(content: &StoryContent) -> Option<&'static str> {
+    match content {
+        StoryContent::ForcedLineBreak => Some("\u{2028}"),
+        StoryContent::RightIndentTab => Some("\t"),
+        StoryContent::EmSpace
+        | StoryContent::EnSpace
+        | StoryContent::ThirdSpace
+        | StoryContent::QuarterSpace
+        | StoryContent::SixthSpace
+        | StoryContent::ThinSpace
+        | StoryContent::HairSpace
+        | StoryContent::FigureSpace
+        | StoryContent::PunctuationSpace
+        | StoryContent::FlushSpace
+        | StoryContent::NonBreakingSpace
+        | StoryContent::FixedWidthNonBreakingSpace => Some(" "),
+        StoryContent::Table(_)
+        | StoryContent::Polygon(_)
+        | StoryContent::Group(_)
+        | StoryContent::Footnote(_)
+        | StoryContent::Endnote(_) => Some("\u{FFFC}"),
+        _ => None,
+    }
+}
+
+fn is_vertical(story: &Story) -> bool {
+    story
+        .story_preference()
+        .as_ref()
+        .and_then(|preference| *preference.story_orientation())
+        == Some(StoryOrientation::Vertical)
+}
+
+trait StyleProperties {
+    fn get_applied_font(self) -> Option<String>;
+    fn get_underline_style(
+        &self,
+        attributes: &impl CommonTextPropertiesAttributes,
+    ) -> DecorationStyle;
+    fn get_strike_through_style(
+        &self,
+        attributes: &impl CommonTextPropertiesAttributes,
+    ) -> DecorationStyle;
+}
+
+macro_rules! impl_style_properties {
+    ($PropertiesName:ty) => {
+        impl StyleProperties for $PropertiesName {
+            fn get_applied_font(self) -> Option<String> {
+                self.applied_font().clone()
+            }
+
+            fn get_underline_style(
+                &self,
+                attributes: &impl CommonTextPropertiesAttributes,
+            ) -> DecorationStyle {
+                DecorationStyle::underline_from(
+                    attributes,
+                    self.underline_color().clone(),
+                    self.underline_gap_color().clone(),
+                    self.underline_type().clone(),
+                )
+            }
+
+            fn get_strike_through_style(
+                &self,
+                attributes: &impl CommonTextPropertiesAttributes,
+            ) -> DecorationStyle {
+                DecorationStyle::strike_through_from(
+                    attributes,
+                    self.strike_through_color().clone(),
+                    self.strike_through_gap_color().clone(),
+                    self.strike_through_type().clone(),
+                )
+            }
+        }
+    };
+}
+
+impl_style_properties!(paragraph_style::ParagraphProperties);
+impl_style_properties!(character_style::CharacterProperties);
+impl_style_properties!(story_parser::Properties);
+
 impl<'a> RenderProperties<'a> {
-    fn new(idml_resources: &'a IDMLResources) -> Self {
+    pub(super) fn new(idml_resources: &'a IDMLResources) -> Self {
         RenderProperties {
             idml_resources,
             font_name: None,
@@ -54,6 +637,33 @@ impl<'a> RenderProperties<'a> {
             auto_leading: None,
             stroke_color: None,
             fill_color: None,
+            stroke_color_id: None,
+            stroke_tint: None,
+            fill_color_id: None,
+            fill_tint: None,
+            gradient_fill_start: None,
+            gradient_fill_length: None,
+            gradient_fill_angle: None,
+            underline: DecorationStyle::default(),
+            strike_through: DecorationStyle::default(),
+            tracking: None,
+            kerning_method: None,
+            kerning_value: None,
+            horizontal_scale: None,
+            vertical_scale: None,
+            skew: None,
+            baseline_shift: None,
+            capitalization: None,
+            position: None,
+            opentype: OpenTypeFeatures::default(),
+            paragraph_direction: None,
+            character_direction: None,
+            digits_type: None,
+            x_offset_diacritic: None,
+            y_offset_diacritic: None,
+            justification: None,
+            kashidas: None,
+            cjk: CjkStyle::default(),
         }
     }
 
@@ -88,9 +698,22 @@ impl<'a> RenderProperties<'a> {
         self
     }
 
-    fn with_stroke_color(&mut self, stroke_color: Option<String>) -> &mut Self {
-        if let Some(color_id) = stroke_color {
-            let color = match color_manager::color_from_id(self.idml_resources, &color_id) {
+    fn with_stroke_color(
+        &mut self,
+        stroke_color: Option<String>,
+        stroke_tint: Option<f64>,
+    ) -> &mut Self {
+        if stroke_color.is_some() {
+            self.stroke_color_id = stroke_color;
+        }
+        if stroke_tint.is_some() {
+            self.stroke_tint = stroke_tint;
+        }
+        if let Some(color_id) = &self.stroke_color_id {
+            let color = match self
+                .idml_resources
+                .tinted_color_from_id(color_id, self.stroke_tint)
+            {
                 Ok(c) => Some(c),
                 Err(ColorError::ColorNotImplemented) => None,
                 Err(_) => None,
@@ -100,9 +723,18 @@ impl<'a> RenderProperties<'a> {
         self
     }
 
-    fn with_fill_color(&mut self, fill_color: Option<String>) -> &mut Self {
-        if let Some(color_id) = fill_color {
-            let color = match color_manager::color_from_id(self.idml_resources, &color_id) {
+    fn with_fill_color(&mut self, fill_color: Option<String>, fill_tint: Option<f64>) -> &mut Self {
+        if fill_color.is_some() {
+            self.fill_color_id = fill_color;
+        }
+        if fill_tint.is_some() {
+            self.fill_tint = fill_tint;
+        }
+        if let Some(color_id) = &self.fill_color_id {
+            let color = match self
+                .idml_resources
+                .tinted_color_from_id(color_id, self.fill_tint)
+            {
                 Ok(c) => Some(c),
                 Err(ColorError::ColorNotImplemented) => None,
                 Err(_) => None,
@@ -111,6 +743,310 @@ impl<'a> RenderProperties<'a> {
         };
         self
     }
+
+    /// Applies the formats of a character style
+    pub(super) fn with_character_style(&mut self, style_id: &String) -> &mut Self {
+        if let Some(style) = self
+            .idml_resources
+            .styles()
+            .character_style_from_id(style_id)
+        {
+            self.with_fill_color(style.fill_color().clone(), style.fill_tint().clone())
+                .with_stroke_color(style.stroke_color().clone(), style.stroke_tint().clone())
+                .with_font_name(style.properties().clone())
+                .with_font_style(style.font_style().clone())
+                .with_font_size(style.point_size().clone())
+                .with_auto_leading(style.auto_leading().clone())
+                .with_decorations(&style, style.properties())
+                .with_gradient_fill(&style)
+                .with_glyph_transforms(&style);
+        }
+        self
+    }
+
+    pub(super) fn with_position(&mut self, position: Position) -> &mut Self {
+        self.position = Some(position);
+        self
+    }
+
+    fn with_decorations(
+        &mut self,
+        attributes: &impl CommonTextPropertiesAttributes,
+        style_properties: &Option<impl StyleProperties>,
+    ) -> &mut Self {
+        let (underline, strike_through) = match style_properties {
+            Some(p) => (
+                p.get_underline_style(attributes),
+                p.get_strike_through_style(attributes),
+            ),
+            None => (
+                DecorationStyle::underline_from(attributes, None, None, None),
+                DecorationStyle::strike_through_from(attributes, None, None, None),
+            ),
+        };
+        self.underline.merge(underline);
+        self.strike_through.merge(strike_through);
+        self
+    }
+
+    fn with_gradient_fill(
+        &mut self,
+        attributes: &impl CommonTextPropertiesAttributes,
+    ) -> &mut Self {
+        self.gradient_fill_start = attributes
+            .gradient_fill_start()
+            .or(self.gradient_fill_start.take());
+        self.gradient_fill_length = attributes
+            .gradient_fill_length()
+            .or(self.gradient_fill_length);
+        self.gradient_fill_angle = attributes
+            .gradient_fill_angle()
+            .or(self.gradient_fill_angle);
+        self
+    }
+
+    fn with_glyph_transforms(
+        &mut self,
+        attributes: &impl CommonTextPropertiesAttributes,
+    ) -> &mut Self {
+        self.tracking = attributes.tracking().or(self.tracking);
+        self.kerning_method = attributes.kerning_method().or(self.kerning_method.take());
+        self.kerning_value = attributes.kerning_value().or(self.kerning_value);
+        self.horizontal_scale = attributes.horizontal_scale().or(self.horizontal_scale);
+        self.vertical_scale = attributes.vertical_scale().or(self.vertical_scale);
+        self.skew = attributes.skew().or(self.skew);
+        self.baseline_shift = attributes.baseline_shift().or(self.baseline_shift);
+        self.capitalization = attributes.captilization().or(self.capitalization);
+        self.position = attributes.position().or(self.position);
+        self.opentype
+            .merge(OpenTypeFeatures::from_attributes(attributes));
+        self.paragraph_direction = attributes
+            .paragraph_direction()
+            .or(self.paragraph_direction);
+        self.character_direction = attributes
+            .character_direction()
+            .or(self.character_direction);
+        self.digits_type = attributes.digits_type().or(self.digits_type);
+        self.x_offset_diacritic = attributes.x_offset_diacritic().or(self.x_offset_diacritic);
+        self.y_offset_diacritic = attributes.y_offset_diacritic().or(self.y_offset_diacritic);
+        self.justification = attributes.justification().or(self.justification.take());
+        self.kashidas = attributes.kashidas().or(self.kashidas);
+        self.cjk.merge(CjkStyle::from_attributes(attributes));
+        self
+    }
+
+    pub(super) fn glyph_transforms(&self) -> GlyphTransforms {
+        let preferences = self.idml_resources.preferences();
+        let mut transforms = GlyphTransforms {
+            font_size: self.font_size.unwrap_or(12_f64),
+            tracking: self.tracking.unwrap_or(0_f64),
+            kerning_method: self
+                .kerning_method
+                .as_deref()
+                .map(KerningMethod::from_id)
+                .unwrap_or(KerningMethod::Metrics),
+            kerning_value: self.kerning_value.unwrap_or(0_f64),
+            horizontal_scale: self.horizontal_scale.unwrap_or(100_f64),
+            vertical_scale: self.vertical_scale.unwrap_or(100_f64),
+            skew: self.skew.unwrap_or(0_f64),
+            baseline_shift: self.baseline_shift.unwrap_or(0_f64),
+            capitalization: self.capitalization.unwrap_or(Capitalization::Normal),
+            position: self.position.unwrap_or(Position::Normal),
+            small_cap_size: preferences.small_cap(),
+            superscript: preferences.superscript(),
+            subscript: preferences.subscript(),
+            opentype: self.opentype,
+            paragraph_direction: self
+                .paragraph_direction
+                .unwrap_or(ParagraphDirection::LeftToRightDirection),
+            character_direction: self
+                .character_direction
+                .unwrap_or(CharacterDirection::DefaultDirection),
+            digits_type: self.digits_type.unwrap_or(DigitsType::DefaultDigits),
+            diacritic_offset: (
+                self.x_offset_diacritic.unwrap_or(0_f64),
+                self.y_offset_diacritic.unwrap_or(0_f64),
+            ),
+            vertical: false,
+            rotate_single_byte: false,
+            kinsoku_hang: false,
+            leading_aki: 0_f64,
+            trailing_aki: 0_f64,
+            tsume: 0_f64,
+            jidori: 0_f64,
+            shatai: (0_f64, 0_f64),
+        };
+        self.cjk.apply_to(&mut transforms);
+        transforms
+    }
+
+    pub(super) fn font_size(&self) -> f64 {
+        self.font_size.unwrap_or(12_f64)
+    }
+
+    fn alignment(&self) -> Justification {
+        self.justification
+            .clone()
+            .unwrap_or(Justification::LeftAlign)
+    }
+
+    /// A run of `glyphs` set with these properties, drawn with `font`
+    pub(super) fn line_run(
+        &self,
+        font_lib: &FontLibrary,
+        glyphs: Vec<PositionedGlyph>,
+        font: Option<RunFont>,
+        state: &StoryState,
+    ) -> LineRun {
+        let mut transforms = self.glyph_transforms();
+        transforms.vertical = state.vertical;
+        // Underlines and strikethroughs are spanned across the run once it is placed
+        let decorations = match state.measuring {
+            true => vec![],
+            false => self.decorations_for_run(font_lib, 0_f64, 0_f64, 0_f64),
+        };
+        LineRun {
+            glyphs,
+            font,
+            fill_color: self.fill_color.clone(),
+            stroke_color: self.stroke_color.clone(),
+            decorations,
+            ..LineRun::space(0_f64, 0, transforms, self.alignment())
+        }
+    }
+
+    /// Distance between the baselines of lines set with these properties
+    pub(super) fn leading(&self) -> f64 {
+        self.auto_leading.unwrap_or(120_f64) / 100_f64 * self.font_size()
+    }
+
+    /// Ascent, cap height and x-height of the font, in points
+    pub(super) fn font_heights(&self, font_lib: &FontLibrary) -> (f64, f64, f64) {
+        let font_size = self.font_size();
+        let font = match (&self.font_name, &self.font_style) {
+            (Some(f_name), Some(f_style)) => font_lib.get_font(f_name, f_style).ok(),
+            _ => None,
+        };
+        let heights = font.map(|font| unsafe {
+            (
+                HPDF_Font_GetAscent(font) as f64,
+                HPDF_Font_GetCapHeight(font) as f64,
+                HPDF_Font_GetXHeight(font) as f64,
+            )
+        });
+        match heights {
+            Some((ascent, cap_height, x_height)) if ascent > 0_f64 => (
+                ascent / 1000_f64 * font_size,
+                cap_height / 1000_f64 * font_size,
+                x_height / 1000_f64 * font_size,
+            ),
+            _ => (
+                font_size * 0.75_f64,
+                font_size * 0.7_f64,
+                font_size * 0.5_f64,
+            ),
+        }
+    }
+
+    /// Width of `text` when set with these properties
+    fn text_width(&self, font_lib: &FontLibrary, text: &str) -> f64 {
+        let (f_name, f_style) = match (&self.font_name, &self.font_style) {
+            (Some(f_name), Some(f_style)) => (f_name, f_style),
+            _ => return 0_f64,
+        };
+        let font = match font_lib.get_font(f_name, f_style) {
+            Ok(font) => font,
+            Err(_) => return 0_f64,
+        };
+        let font_data = font_lib.get_font_data(f_name, f_style);
+        let encoding = font_lib.get_encoding(font);
+        let glyphs = text_layout::layout_glyphs(
+            text,
+            font,
+            &encoding,
+            font_data.as_ref().map(|data| data.as_slice()),
+            &self.glyph_transforms(),
+            None,
+        );
+        text_layout::glyphs_width(&glyphs)
+    }
+
+    /// Width of a fixed width or non-breaking space character
+    fn space_width(&self, font_lib: &FontLibrary, content: &StoryContent) -> Option<f64> {
+        let em =
+            self.font_size.unwrap_or(12_f64) * self.horizontal_scale.unwrap_or(100_f64) / 100_f64;
+        match content {
+            StoryContent::EmSpace => Some(em),
+            StoryContent::EnSpace => Some(em / 2_f64),
+            StoryContent::ThirdSpace => Some(em / 3_f64),
+            StoryContent::QuarterSpace => Some(em / 4_f64),
+            StoryContent::SixthSpace => Some(em / 6_f64),
+            StoryContent::ThinSpace => Some(em / 8_f64),
+            StoryContent::HairSpace => Some(em / 24_f64),
+            StoryContent::FigureSpace => Some(self.text_width(font_lib, "0")),
+            StoryContent::PunctuationSpace => Some(self.text_width(font_lib, ".")),
+            // TODO: Flush spaces fill the last line of justified paragraphs
+            StoryContent::FlushSpace
+            | StoryContent::NonBreakingSpace
+            | StoryContent::FixedWidthNonBreakingSpace => Some(self.text_width(font_lib, " ")),
+            _ => None,
+        }
+    }
+
+    /// Width taken up on the line by `content`
+    fn content_width(
+        &self,
+        font_lib: &FontLibrary,
+        content: &StoryContent,
+        state: &StoryState,
+    ) -> f64 {
+        match (content, state.substituted_text(content)) {
+            (StoryContent::Content(text), _) => self.text_width(font_lib, text),
+            (_, Some(text)) => self.text_width(font_lib, &text),
+            _ => self.space_width(font_lib, content).unwrap_or(0_f64),
+        }
+    }
+
+    /// Resolves underline and strikethrough for a run of text drawn between `x_start` and `x_end`
+    fn decorations_for_run(
+        &self,
+        font_lib: &FontLibrary,
+        x_start: f64,
+        x_end: f64,
+        baseline: f64,
+    ) -> Vec<Decoration> {
+        let styles = [
+            (DecorationKind::Underline, &self.underline),
+            (DecorationKind::StrikeThrough, &self.strike_through),
+        ];
+        if !styles.iter().any(|(_, style)| style.is_enabled()) {
+            return vec![];
+        }
+
+        let metrics = match (&self.font_name, &self.font_style) {
+            (Some(f_name), Some(f_style)) => match font_lib.get_font_metrics(f_name, f_style) {
+                Ok(metrics) => metrics,
+                Err(_) => return vec![],
+            },
+            _ => return vec![],
+        };
+        let font_size = self.font_size.unwrap_or(12_f64);
+
+        styles
+            .iter()
+            .filter(|(_, style)| style.is_enabled())
+            .filter_map(|(kind, style)| {
+                style.resolve(
+                    *kind,
+                    self.idml_resources,
+                    self.fill_color.clone(),
+                    &metrics,
+                    font_size,
+                )
+            })
+            .map(|decoration| decoration.spanning(x_start, x_end, baseline))
+            .collect()
+    }
 }
 
 impl Polygon {
@@ -119,47 +1055,309 @@ impl Polygon {
         idml_package: &IDMLPackage,
         parent_transform: &mut Transform,
         font_lib: &FontLibrary,
+        image_lib: &ImageLibrary,
         current_page: HPDF_Page,
+        page_number: &PageNumber,
+        text_variables: &HashMap<String, String>,
+        note_numbers: &HashMap<String, String>,
+        navigation: &mut Navigation,
+        layout: &mut StoryLayout,
     ) -> Result<(), String> {
         if let Some(story_id) = self.parent_story() {
             if let Some(story) = idml_package.stories().get(story_id) {
-                let render_properties = RenderProperties::new(idml_package.resources());
                 let bb = boundingbox(&self, parent_transform);
                 unsafe {
+                    let mut state = StoryState {
+                        y: bb.top,
+                        column: bb.right,
+                        frame: bb,
+                        vertical: is_vertical(story),
+                        measuring: layout.is_measuring(),
+                        page_number: page_number.clone(),
+                        page_margins: layout.page_margins(),
+                        text_variables: text_variables.clone(),
+                        note_numbers: note_numbers.clone(),
+                        cross_references: navigation.cross_references.clone(),
+                        endnote_count: match story.is_endnote_story() {
+                            Some(true) => Some(0),
+                            _ => None,
+                        },
+                        ..StoryState::default()
+                    };
+                    if state.measuring {
+                        self.lay_out_story(
+                            story,
+                            idml_package,
+                            font_lib,
+                            current_page,
+                            &mut state,
+                            layout,
+                        )?;
+                        layout.add_story(story_id, &state.paragraphs_set, state.markers());
+                        return Ok(());
+                    }
+
                     HPDF_Page_GSave(current_page);
                     HPDF_Page_BeginText(current_page);
-                    HPDF_Page_MoveTextPos(current_page, bb.left as f32, bb.top as f32);
-                    let mut has_offset = false;
-                    if let Some(p_styles) = story.paragraph_style_ranges() {
-                        for p_style in p_styles {
-                            &self.render_paragraph_style(
-                                p_style,
-                                &render_properties,
-                                idml_package.resources(),
-                                parent_transform,
-                                font_lib,
-                                current_page,
-                                &mut has_offset,
-                            )?;
+                    self.lay_out_story(
+                        story,
+                        idml_package,
+                        font_lib,
+                        current_page,
+                        &mut state,
+                        layout,
+                    )?;
+                    for run in &state.text_runs {
+                        if let Err(err) = run.render(current_page) {
+                            println!("Text in '{}' not fully rendered: {}", self.id(), err);
                         }
                     }
                     HPDF_Page_EndText(current_page);
+                    layout.add_story(story_id, &state.paragraphs_set, state.markers());
+
+                    let frame_transform =
+                        transforms::from_vec(self.item_transform()).combine_with(parent_transform);
+                    let frame_bounds = self.frame_bounds().unwrap_or_default();
+                    for run in &state.gradient_runs {
+                        if let Err(err) =
+                            run.render(&frame_transform, frame_bounds, image_lib, current_page)
+                        {
+                            println!("Gradient text in '{}' not rendered: {}", self.id(), err);
+                        }
+                    }
+
+                    // Underlines, strikethroughs and the footnote rule are paths,
+                    // so they can only be drawn once the text object has been closed
+                    for decoration in &state.decorations {
+                        decoration.render(current_page);
+                    }
                     HPDF_Page_GRestore(current_page);
+
+                    navigation.add_story(
+                        current_page,
+                        std::mem::take(&mut state.link_areas),
+                        std::mem::take(&mut state.anchors),
+                    );
+                    condition_indicators::render_condition_indicators(
+                        idml_package,
+                        current_page,
+                        &state.condition_areas,
+                    );
+                    for anchored_object in &state.anchored_objects {
+                        anchored_object.render(
+                            parent_transform,
+                            idml_package,
+                            font_lib,
+                            image_lib,
+                            current_page,
+                            page_number,
+                            text_variables,
+                            note_numbers,
+                            navigation,
+                            layout,
+                        )?;
+                    }
                 }
             }
         }
         Ok(())
     }
 
+    /// Sets the text of the story and its footnotes in the frame, starting where the text
+    /// of the frame threaded before it ends, and notes where the text that does not fit starts
+    unsafe fn lay_out_story(
+        &self,
+        story: &Story,
+        idml_package: &IDMLPackage,
+        font_lib: &FontLibrary,
+        current_page: HPDF_Page,
+        state: &mut StoryState,
+        layout: &mut StoryLayout,
+    ) -> Result<(), String> {
+        state.start = self.thread_start(idml_package, font_lib, current_page, state, layout)?;
+        if self.waits_for_page_break(state, layout) {
+            return Ok(());
+        }
+        let (column_count, column_gutter) = self.text_columns();
+        state.split_into_columns(column_count, column_gutter);
+        let idml_resources = idml_package.resources();
+        self.place_continued_footnotes(idml_resources, font_lib, current_page, state, layout)?;
+        let render_properties = RenderProperties::new(idml_resources);
+        self.render_paragraphs(
+            story
+                .paragraph_style_ranges()
+                .as_deref()
+                .unwrap_or_default(),
+            &render_properties,
+            idml_resources,
+            font_lib,
+            current_page,
+            state,
+        )?;
+        let continued = self.render_footnotes(idml_resources, font_lib, current_page, state)?;
+        self.continue_footnotes(continued, state, layout);
+        layout.set_frame_end(self.id(), state.end.unwrap_or(StoryPosition::END));
+        if let (Some(break_type), Some(page_id)) = (&state.page_break, layout.current_page()) {
+            let page_id = page_id.clone();
+            layout.set_page_break(self.id(), break_type.clone(), page_id);
+        }
+        Ok(())
+    }
+
+    /// Number of columns of the text frame, and the room between them
+    fn text_columns(&self) -> (usize, f64) {
+        let preference = self.text_frame_preference().clone().unwrap_or_default();
+        (
+            preference.text_column_count().unwrap_or(1),
+            preference.text_column_gutter().unwrap_or(0_f64),
+        )
+    }
+
+    /// Whether the frame is passed over by a page break that ended the text of the frame
+    /// threaded before it: frames on the page the break was set on, and on even pages
+    /// after a break to the next odd page or the other way around, hold none of the text.
+    /// The text, and footnotes that go on from the frame before, go on in the next frame.
+    fn waits_for_page_break(&self, state: &StoryState, layout: &mut StoryLayout) -> bool {
+        let previous_id = match self.previous_text_frame().as_deref() {
+            Some(id) if id != "n" => id,
+            _ => return false,
+        };
+        let (break_type, page_id) = match layout.page_break(previous_id) {
+            Some(page_break) => page_break.clone(),
+            None => return false,
+        };
+        let page_value = state.page_number.label.as_ref().map(|label| label.value);
+        let waits = layout.current_page() == Some(&page_id)
+            || match (&break_type, page_value) {
+                (StartParagraph::NextOddPage, Some(value)) => value % 2 == 0,
+                (StartParagraph::NextEvenPage, Some(value)) => value % 2 == 1,
+                _ => false,
+            };
+        if waits {
+            let continued = layout.continued_footnotes(previous_id);
+            layout.set_continued_footnotes(self.id(), continued);
+            layout.set_frame_end(self.id(), state.start);
+            layout.set_page_break(self.id(), break_type, page_id);
+        }
+        waits
+    }
+
+    /// Where the text of the frame starts in its story, which is where the text of the
+    /// frame threaded before it ends. Frames before it that have not been set yet, such
+    /// as frames further down the page, are laid out without being drawn to find out.
+    unsafe fn thread_start(
+        &self,
+        idml_package: &IDMLPackage,
+        font_lib: &FontLibrary,
+        current_page: HPDF_Page,
+        state: &StoryState,
+        layout: &mut StoryLayout,
+    ) -> Result<StoryPosition, String> {
+        let previous_id = match self.previous_text_frame().as_deref() {
+            Some(id) if id != "n" => id,
+            _ => return Ok(StoryPosition::default()),
+        };
+        if let Some(end) = layout.frame_end(previous_id) {
+            return Ok(end);
+        }
+        let previous = match idml_package.frame_with_id(previous_id) {
+            Some(previous) => previous,
+            None => return Ok(StoryPosition::default()),
+        };
+        let story = match previous
+            .parent_story()
+            .as_ref()
+            .and_then(|story_id| idml_package.stories().get(story_id))
+        {
+            Some(story) => story,
+            None => return Ok(StoryPosition::default()),
+        };
+
+        // The frame is noted as holding the rest of the story while it is laid
+        // out, so a thread that loops back on itself comes to an end
+        layout.set_frame_end(previous_id, StoryPosition::END);
+        let bb = boundingbox(previous, &transforms::identity().with_scale(1_f64, -1_f64));
+        let mut previous_state = StoryState {
+            y: bb.top,
+            column: bb.right,
+            frame: bb,
+            vertical: is_vertical(story),
+            measuring: true,
+            page_number: state.page_number.clone(),
+            text_variables: state.text_variables.clone(),
+            note_numbers: state.note_numbers.clone(),
+            cross_references: state.cross_references.clone(),
+            endnote_count: state.endnote_count.map(|_| 0),
+            ..StoryState::default()
+        };
+        previous.lay_out_story(
+            story,
+            idml_package,
+            font_lib,
+            current_page,
+            &mut previous_state,
+            layout,
+        )?;
+        Ok(previous_state.end.unwrap_or(StoryPosition::END))
+    }
+
+    /// Sets the paragraphs of a story, table cell or footnote. Their text is gathered
+    /// first, so the bidi levels of the text are resolved over whole paragraphs.
+    pub(super) fn render_paragraphs(
+        &self,
+        p_styles: &[ParagraphStyleRange],
+        render_properties: &RenderProperties,
+        idml_resources: &IDMLResources,
+        font_lib: &FontLibrary,
+        current_page: HPDF_Page,
+        state: &mut StoryState,
+    ) -> Result<(), String> {
+        let mut gathering = StoryState {
+            measuring: true,
+            collecting_levels: true,
+            page_number: state.page_number.clone(),
+            text_variables: state.text_variables.clone(),
+            note_numbers: state.note_numbers.clone(),
+            note_number: state.note_number.clone(),
+            cross_references: state.cross_references.clone(),
+            endnote_count: state.endnote_count.map(|_| 0),
+            ..StoryState::default()
+        };
+        for p_style in p_styles {
+            self.render_paragraph_style(
+                p_style,
+                render_properties,
+                idml_resources,
+                font_lib,
+                current_page,
+                &mut gathering,
+            )?;
+        }
+        gathering.paragraph_levels.end_paragraph();
+        state.paragraph_levels = gathering.paragraph_levels;
+
+        for p_style in p_styles {
+            self.render_paragraph_style(
+                p_style,
+                render_properties,
+                idml_resources,
+                font_lib,
+                current_page,
+                state,
+            )?;
+        }
+        state.finish_line(true);
+        Ok(())
+    }
+
     pub fn render_paragraph_style(
         &self,
         p_style: &ParagraphStyleRange,
         parent_properties: &RenderProperties,
         idml_resources: &IDMLResources,
-        parent_transform: &Transform,
         font_lib: &FontLibrary,
         current_page: HPDF_Page,
-        has_offset: &mut bool,
+        state: &mut StoryState,
     ) -> Result<(), String> {
         let mut render_properties = parent_properties.clone();
 
@@ -167,12 +1365,15 @@ impl Polygon {
         if let Some(style_id) = p_style.applied_paragraph_style() {
             if let Some(style) = idml_resources.styles().paragraph_style_from_id(style_id) {
                 render_properties
-                    .with_fill_color(style.fill_color().clone())
-                    .with_stroke_color(style.stroke_color().clone())
+                    .with_fill_color(style.fill_color().clone(), style.fill_tint().clone())
+                    .with_stroke_color(style.stroke_color().clone(), style.stroke_tint().clone())
                     .with_font_name(style.properties().clone())
                     .with_font_style(style.font_style().clone())
                     .with_font_size(style.point_size().clone())
-                    .with_auto_leading(style.auto_leading().clone());
+                    .with_auto_leading(style.auto_leading().clone())
+                    .with_decorations(&style, style.properties())
+                    .with_gradient_fill(&style)
+                    .with_glyph_transforms(&style);
             }
         }
 
@@ -183,11 +1384,9 @@ impl Polygon {
                 &self.render_character_style(
                     c_style,
                     &render_properties,
-                    idml_resources,
-                    parent_transform,
                     font_lib,
                     current_page,
-                    has_offset,
+                    state,
                 )?;
             }
         }
@@ -199,47 +1398,82 @@ impl Polygon {
         &self,
         c_style: &CharacterStyleRange,
         parent_properties: &RenderProperties,
-        idml_resources: &IDMLResources,
-        parent_transform: &Transform,
         font_lib: &FontLibrary,
         current_page: HPDF_Page,
-        has_offset: &mut bool,
+        state: &mut StoryState,
     ) -> Result<(), String> {
         let mut render_properties = parent_properties.clone();
 
         // Apply character style formats
         if let Some(style_id) = c_style.applied_character_style() {
-            if let Some(style) = idml_resources.styles().character_style_from_id(style_id) {
-                render_properties
-                    .with_fill_color(style.fill_color().clone())
-                    .with_stroke_color(style.stroke_color().clone())
-                    .with_font_name(style.properties().clone())
-                    .with_font_style(style.font_style().clone())
-                    .with_font_size(style.point_size().clone())
-                    .with_auto_leading(style.auto_leading().clone());
-            }
+            render_properties.with_character_style(style_id);
         }
 
         // Apply local character formats
         render_properties
-            .with_fill_color(c_style.fill_color().clone())
-            .with_stroke_color(c_style.stroke_color().clone())
+            .with_fill_color(c_style.fill_color().clone(), c_style.fill_tint().clone())
+            .with_stroke_color(
+                c_style.stroke_color().clone(),
+                c_style.stroke_tint().clone(),
+            )
             // .with_font_name(c_style.properties().clone())
             .with_font_style(c_style.font_style().clone())
-            .with_font_size(c_style.point_size().clone());
+            .with_font_size(c_style.point_size().clone())
+            .with_decorations(c_style, &c_style.local_properties().cloned())
+            .with_gradient_fill(c_style)
+            .with_glyph_transforms(c_style);
 
+        let outer_conditions = std::mem::replace(
+            &mut state.conditions,
+            c_style
+                .applied_conditions()
+                .clone()
+                .filter(|conditions| !conditions.is_empty()),
+        );
         if let Some(contents) = c_style.contents() {
-            for content in contents {
+            for (i, content) in contents.iter().enumerate() {
+                if !state.next_content(content) {
+                    continue;
+                }
+                match (content, c_style.paragraph_break_type()) {
+                    _ if state.collecting_levels => {}
+                    // Column breaks go on in the next column of the frame, frame breaks in
+                    // the next frame of the thread, and page breaks in the first frame of
+                    // the thread on a later page
+                    (StoryContent::Br, Some(break_type))
+                        if *break_type != StartParagraph::Anywhere =>
+                    {
+                        state.break_paragraph(break_type.clone());
+                        continue;
+                    }
+                    // Right indent tabs push the rest of the line against the
+                    // right edge of the frame
+                    (StoryContent::RightIndentTab, _) => {
+                        let rest_of_line: f64 = contents[i + 1..]
+                            .iter()
+                            .take_while(|content| match content {
+                                StoryContent::Br | StoryContent::ForcedLineBreak => false,
+                                _ => true,
+                            })
+                            .map(|content| {
+                                render_properties.content_width(font_lib, content, state)
+                            })
+                            .sum();
+                        state.line_width = state.line_width.max(state.line_length() - rest_of_line);
+                        continue;
+                    }
+                    _ => {}
+                }
                 &self.render_story_content(
                     content,
                     &render_properties,
-                    parent_transform,
                     font_lib,
                     current_page,
-                    has_offset,
+                    state,
                 );
             }
         }
+        state.conditions = outer_conditions;
         Ok(())
     }
 
@@ -247,82 +1481,525 @@ impl Polygon {
         &self,
         content: &StoryContent,
         render_properties: &RenderProperties,
-        parent_transform: &Transform,
         font_lib: &FontLibrary,
         current_page: HPDF_Page,
-        has_offset: &mut bool,
+        state: &mut StoryState,
     ) -> Result<(), String> {
-        unsafe {
-            match content {
-                StoryContent::Content(text) => {
-                    // Font and size
-                    let font = match (&render_properties.font_name, &render_properties.font_style) {
-                        (Some(f_name), Some(f_style)) => {
-                            font_lib.get_font(&f_name, &f_style).unwrap()
-                        }
-                        _ => {
-                            return Err(format!(
-                                "Cannot print text. Please set font name and style"
-                            ))
-                        }
-                    };
-                    HPDF_Page_SetFontAndSize(
-                        current_page,
-                        font,
-                        render_properties.font_size.unwrap() as f32,
-                    );
+        // While the text of the paragraphs is gathered, content other than text is
+        // gathered as the character it stands for. Content that stands for text,
+        // like page numbers and cross-references, is set as usual to gather its text.
+        if state.collecting_levels {
+            let text = match (content, stand_in_text(content)) {
+                (StoryContent::Br, _) => {
+                    state.paragraph_levels.end_paragraph();
+                    return Ok(());
+                }
+                (StoryContent::Content(text), _) => text.as_str(),
+                (_, Some(text)) => text,
+                _ => "",
+            };
+            if !text.is_empty() {
+                state.collect_text(text, &render_properties.glyph_transforms());
+                return Ok(());
+            }
+        }
+        match content {
+            StoryContent::Content(text) => {
+                // Font and size
+                let font = match (&render_properties.font_name, &render_properties.font_style) {
+                    (Some(f_name), Some(f_style)) => font_lib.get_font(&f_name, &f_style).unwrap(),
+                    _ => return Err(format!("Cannot print text. Please set font name and style")),
+                };
 
-                    // Color
-                    if let Some(color) = render_properties.fill_color {
-                        set_fill_color(current_page, color);
-                    }
-                    if let Some(color) = render_properties.stroke_color {
-                        set_stroke_color(current_page, color);
+                // Leading
+                state.leading = render_properties.auto_leading.unwrap() / 100_f64
+                    * render_properties.font_size.unwrap();
+
+                if state.has_offset == false {
+                    state.has_offset = true;
+                    match state.vertical {
+                        true => state.column -= render_properties.font_size.unwrap() / 2_f64,
+                        false => state.y -= state.leading,
                     }
+                }
 
-                    // Leading
-                    let leading = render_properties.auto_leading.unwrap() / 100_f64
-                        * render_properties.font_size.unwrap();
-                    HPDF_Page_SetTextLeading(current_page, leading as f32);
+                let mut transforms = render_properties.glyph_transforms();
+                transforms.vertical = state.vertical;
+                let font_data = match (&render_properties.font_name, &render_properties.font_style)
+                {
+                    (Some(f_name), Some(f_style)) => font_lib.get_font_data(f_name, f_style),
+                    _ => None,
+                };
+                let encoding = font_lib.get_encoding(font);
+                let run_font = RunFont {
+                    font,
+                    encoding: encoding.clone(),
+                    font_data: font_data.clone(),
+                };
+                let font_data = font_data.as_ref().map(|data| data.as_slice());
+                let adornments = Adornments {
+                    page: current_page,
+                    font,
+                    encoding: &encoding,
+                    font_data,
+                    transforms: &transforms,
+                };
+                let cjk = &render_properties.cjk;
+                let line_length = state.line_length();
 
-                    if *has_offset == false {
-                        *has_offset = true;
-                        HPDF_Page_MoveToNextLine(current_page);
+                // Warichu and tate-chu-yoko are set as a single unit in the line
+                let tate_chu_yoko = state.vertical && cjk.is_tate_chu_yoko();
+                if cjk.is_warichu() || tate_chu_yoko {
+                    state.skipped_glyphs = 0;
+                    let warichu = cjk.warichu();
+                    let (lines, length) = match tate_chu_yoko {
+                        true => (vec![], transforms.font_size),
+                        false => adornments.layout_warichu(&warichu, text),
+                    };
+                    if !state.is_line_empty() && state.line_width + length > line_length {
+                        state.new_line();
                     }
+                    if state.is_past_frame() {
+                        state.overflow_at(0);
+                        return Ok(());
+                    }
+                    let adornment = match tate_chu_yoko {
+                        true => RunAdornment::TateChuYoko {
+                            text: text.clone(),
+                            offset: cjk.tate_chu_yoko_offset(),
+                        },
+                        false => RunAdornment::Warichu { warichu, lines },
+                    };
+                    let mut unit = PositionedGlyph::spacer(length);
+                    unit.level = state.content_level(transforms.paragraph_level());
+                    let run = LineRun {
+                        adornments: vec![adornment],
+                        decorations: vec![],
+                        ..render_properties.line_run(font_lib, vec![unit], Some(run_font), state)
+                    };
+                    state.push_run(run);
+                    return Ok(());
+                }
 
-                    let bb = boundingbox(&self, parent_transform);
-                    let mut text_remaining: &str = text;
-                    while text_remaining.len() > 0 {
-                        let pos = HPDF_Page_GetCurrentTextPos(current_page);
-                        if (pos.y as f64) < bb.bottom {
-                            break;
-                        }
-                        let available_chars = HPDF_Page_MeasureText(
+                let levels = state.content_levels().map(<[u8]>::to_vec);
+                let glyphs = text_layout::layout_glyphs(
+                    text,
+                    font,
+                    &encoding,
+                    font_data,
+                    &transforms,
+                    levels.as_deref(),
+                );
+
+                // Justified lines lengthen Arabic words with kashidas
+                let justified = matches!(
+                    render_properties.alignment(),
+                    Justification::LeftJustified
+                        | Justification::CenterJustified
+                        | Justification::RightJustified
+                        | Justification::FullyJustified
+                );
+                let kashida = match justified
+                    && render_properties.kashidas != Some(Kashidas::KashidasOff)
+                    && glyphs.iter().any(|glyph| glyph.kashida)
+                {
+                    true => text_layout::kashida_glyph(font, &encoding, font_data, &transforms),
+                    false => None,
+                };
+                // Text filled with a gradient is drawn once the text object is closed
+                let gradient = match &render_properties.fill_color {
+                    Some(Color::Gradient(gradient)) if !state.measuring => Some((
+                        gradient.clone(),
+                        GradientPlacement::from_attributes(
+                            render_properties.gradient_fill_start.as_deref(),
+                            render_properties.gradient_fill_length,
+                            render_properties.gradient_fill_angle,
+                            *gradient.kind(),
+                            self.frame_bounds().unwrap_or_default(),
+                        ),
+                    )),
+                    _ => None,
+                };
+                let mut run_adornments = vec![];
+                if let Some(ruby) = cjk.ruby() {
+                    run_adornments.push(RunAdornment::Ruby {
+                        ruby,
+                        run_length: glyphs.len(),
+                    });
+                }
+                if let Some(kenten) = cjk.kenten() {
+                    run_adornments.push(RunAdornment::Kenten(kenten));
+                }
+
+                let skipped_glyphs = std::mem::take(&mut state.skipped_glyphs);
+                let mut glyphs_remaining = &glyphs[skipped_glyphs.min(glyphs.len())..];
+                while glyphs_remaining.len() > 0 {
+                    if state.is_past_frame() {
+                        state.overflow_at(glyphs.len() - glyphs_remaining.len());
+                        break;
+                    }
+                    let available_glyphs = text_layout::fit_glyphs(
+                        glyphs_remaining,
+                        line_length - state.line_width,
+                        state.is_line_empty(),
+                    );
+                    if available_glyphs > 0 {
+                        let first_glyph = glyphs.len() - glyphs_remaining.len();
+                        let (glyphs_to_print, remaining) =
+                            glyphs_remaining.split_at(available_glyphs);
+                        glyphs_remaining = remaining;
+                        let run = LineRun {
+                            gradient: gradient.clone(),
+                            kashida: kashida.clone(),
+                            adornments: run_adornments.clone(),
+                            first_glyph,
+                            ..render_properties.line_run(
+                                font_lib,
+                                glyphs_to_print.to_vec(),
+                                Some(run_font.clone()),
+                                state,
+                            )
+                        };
+                        state.push_run(run);
+                    } else {
+                        state.new_line();
+                    }
+                }
+            }
+            StoryContent::Br => state.new_paragraph(),
+            StoryContent::ForcedLineBreak => state.new_line(),
+            StoryContent::IndentToHere => state.indent = state.line_width,
+            StoryContent::Table(table) => {
+                self.render_table(table, render_properties, font_lib, current_page, state)?
+            }
+            StoryContent::Polygon(_) | StoryContent::Group(_) => self.render_anchored_object(
+                content,
+                render_properties,
+                font_lib,
+                current_page,
+                state,
+            ),
+            StoryContent::HyperlinkTextSource(source) => self.render_link_source(
+                source.id(),
+                source.applied_character_style(),
+                source.contents().iter().flatten(),
+                render_properties,
+                font_lib,
+                current_page,
+                state,
+            )?,
+            // Cross-references are set with their text regenerated from their format
+            StoryContent::CrossReferenceSource(source) => {
+                let regenerated = state
+                    .cross_references
+                    .get(source.id())
+                    .map(|text| StoryContent::Content(text.clone()));
+                let contents: Vec<&StoryContent> = match &regenerated {
+                    Some(content) => vec![content],
+                    None => source.contents().iter().flatten().collect(),
+                };
+                self.render_link_source(
+                    source.id(),
+                    source.applied_character_style(),
+                    contents.into_iter(),
+                    render_properties,
+                    font_lib,
+                    current_page,
+                    state,
+                )?
+            }
+            StoryContent::HyperlinkTextDestination(destination) => {
+                let top = match state.vertical {
+                    true => state.frame.top,
+                    false => state.y + state.leading * 0.75_f64,
+                };
+                state.anchors.push(Anchor {
+                    destination: destination.id().clone(),
+                    top,
+                });
+            }
+            StoryContent::PageReference(page_reference) => {
+                state.page_references.push(page_reference.id().clone())
+            }
+            StoryContent::Footnote(_) | StoryContent::Endnote(_) => self.render_note_reference(
+                content,
+                render_properties,
+                font_lib,
+                current_page,
+                state,
+            )?,
+            // Endnotes are numbered in the order they are in the endnote story
+            StoryContent::FootnoteNumber if state.note_number.is_none() => {
+                if let Some(count) = state.endnote_count.as_mut() {
+                    *count += 1;
+                    let text = count.to_string();
+                    return self.render_story_content(
+                        &StoryContent::Content(text),
+                        render_properties,
+                        font_lib,
+                        current_page,
+                        state,
+                    );
+                }
+            }
+            // TODO: Keep the words around non-breaking hyphens together
+            // once lines are fitted across runs
+            StoryContent::AutoPageNumber
+            | StoryContent::FootnoteNumber
+            | StoryContent::SectionMarker
+            | StoryContent::NonBreakingHyphen
+            | StoryContent::TextVariableInstance(_) => {
+                if let Some(text) = state.substituted_text(content) {
+                    if !text.is_empty() {
+                        return self.render_story_content(
+                            &StoryContent::Content(text),
+                            render_properties,
+                            font_lib,
                             current_page,
-                            CString::new(text_remaining.clone()).unwrap().as_ptr(),
-                            (bb.right as f32) - pos.x,
-                            HPDF_TRUE,
-                            ptr::null_mut(),
+                            state,
                         );
-                        if available_chars > 0 {
-                            let (text_to_print, remaning) =
-                                text_remaining.split_at(available_chars as usize);
-                            text_remaining = remaning;
-                            HPDF_Page_ShowText(
-                                current_page,
-                                CString::new(text_to_print).unwrap().as_ptr(),
-                            );
-                        } else {
-                            HPDF_Page_MoveToNextLine(current_page);
-                        }
                     }
                 }
-                StoryContent::Br => {
-                    HPDF_Page_MoveToNextLine(current_page);
+            }
+            _ => {
+                if let Some(width) = render_properties.space_width(font_lib, content) {
+                    self.render_space(width, content, render_properties, font_lib, state);
                 }
-                _ => {}
             }
         }
         Ok(())
     }
+
+    /// Sets the text of a hyperlink source, noting the area it takes up
+    fn render_link_source<'c>(
+        &self,
+        source_id: &String,
+        applied_character_style: &Option<String>,
+        contents: impl Iterator<Item = &'c StoryContent>,
+        render_properties: &RenderProperties,
+        font_lib: &FontLibrary,
+        current_page: HPDF_Page,
+        state: &mut StoryState,
+    ) -> Result<(), String> {
+        let mut source_properties = render_properties.clone();
+        if let Some(style_id) = applied_character_style {
+            source_properties.with_character_style(style_id);
+        }
+        let outer_source = state.link_source.replace(source_id.clone());
+        for content in contents {
+            if state.next_content(content) {
+                self.render_story_content(
+                    content,
+                    &source_properties,
+                    font_lib,
+                    current_page,
+                    state,
+                )?;
+            }
+        }
+        state.link_source = outer_source;
+        Ok(())
+    }
+
+    /// Advances the line by a fixed width space. Breaking spaces that do
+    /// not fit the line are dropped at the line break.
+    fn render_space(
+        &self,
+        width: f64,
+        content: &StoryContent,
+        render_properties: &RenderProperties,
+        font_lib: &FontLibrary,
+        state: &mut StoryState,
+    ) {
+        if state.is_past_frame() {
+            return;
+        }
+        if !state.is_line_empty() && state.line_width + width > state.line_length() {
+            state.new_line();
+            match content {
+                StoryContent::NonBreakingSpace | StoryContent::FixedWidthNonBreakingSpace => {}
+                _ => return,
+            }
+        }
+
+        // Underlines and strikethroughs continue across the space
+        let mut space = PositionedGlyph::spacer(width);
+        space.level = state.content_level(render_properties.glyph_transforms().paragraph_level());
+        let run = render_properties.line_run(font_lib, vec![space], None, state);
+        state.push_run(run);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::idml_parser::designmap_parser::PageNumberStyle;
+    use crate::pdf_printer::page_numbering::PageLabel;
+    use quick_xml::de::from_str;
+
+    /// State of a frame 200pt wide and 100pt high, split into two columns 20pt apart
+    fn two_columns(vertical: bool) -> StoryState {
+        let frame = BoundingBox {
+            left: 0_f64,
+            right: 200_f64,
+            top: 100_f64,
+            bottom: 0_f64,
+        };
+        let mut state = StoryState {
+            y: frame.top,
+            column: frame.right,
+            frame,
+            vertical,
+            leading: 12_f64,
+            ..StoryState::default()
+        };
+        state.split_into_columns(2, 20_f64);
+        state
+    }
+
+    fn page(id: &str) -> Page {
+        from_str(&format!(
+            r#"<Page Self="{}" UseMasterGrid="true"><Properties/></Page>"#,
+            id
+        ))
+        .unwrap()
+    }
+
+    fn page_number(value: u32) -> PageNumber {
+        PageNumber {
+            number: value.to_string(),
+            section_marker: String::new(),
+            label: Some(PageLabel {
+                style: PageNumberStyle::Arabic,
+                prefix: String::new(),
+                value,
+            }),
+            section: None,
+        }
+    }
+
+    /// Whether the frame threaded after a frame whose text ended with `break_type`
+    /// on page "p1" holds text on a page numbered `value`
+    fn waits_on_page(break_type: StartParagraph, page_id: &str, value: u32) -> bool {
+        let frame: Polygon =
+            from_str(r#"<TextFrame Self="f2" PreviousTextFrame="f1" NextTextFrame="n"/>"#).unwrap();
+        let mut layout = StoryLayout::measuring();
+        layout.start_page(&page("p1"));
+        layout.set_page_break("f1", break_type, "p1".to_string());
+        layout.start_page(&page(page_id));
+        let state = StoryState {
+            page_number: page_number(value),
+            ..StoryState::default()
+        };
+        frame.waits_for_page_break(&state, &mut layout)
+    }
+
+    #[test]
+    fn frames_are_split_into_columns_from_left_to_right() {
+        let mut state = two_columns(false);
+        assert_eq!((state.frame.left, state.frame.right), (0_f64, 90_f64));
+        let next = state.next_columns.pop_front().unwrap();
+        assert_eq!((next.left, next.right), (110_f64, 200_f64));
+        assert_eq!((next.top, next.bottom), (100_f64, 0_f64));
+        assert!(state.next_columns.is_empty());
+    }
+
+    #[test]
+    fn vertical_frames_are_split_into_columns_from_top_to_bottom() {
+        let mut state = two_columns(true);
+        assert_eq!((state.frame.top, state.frame.bottom), (100_f64, 60_f64));
+        let next = state.next_columns.pop_front().unwrap();
+        assert_eq!((next.top, next.bottom), (40_f64, 0_f64));
+        assert_eq!((next.left, next.right), (0_f64, 200_f64));
+    }
+
+    #[test]
+    fn text_past_the_bottom_of_a_column_goes_on_in_the_next_one() {
+        let mut state = two_columns(false);
+        state.y = 5_f64;
+        state.new_line();
+        assert_eq!(state.frame.left, 110_f64);
+        assert_eq!(state.y, 88_f64);
+        assert!(!state.is_past_frame());
+
+        // The last column fills up the frame
+        state.y = 5_f64;
+        state.new_line();
+        assert!(state.is_past_frame());
+    }
+
+    #[test]
+    fn column_breaks_go_on_at_the_top_of_the_next_column() {
+        let mut state = two_columns(false);
+        state.y = 50_f64;
+        state.line_width = 30_f64;
+        state.break_paragraph(StartParagraph::NextColumn);
+        assert_eq!(state.frame.left, 110_f64);
+        assert_eq!(state.y, 88_f64);
+        assert_eq!(state.line_width, 0_f64);
+        assert!(!state.overset);
+    }
+
+    #[test]
+    fn column_breaks_in_the_last_column_go_on_in_the_next_frame() {
+        let mut state = two_columns(false);
+        state.break_paragraph(StartParagraph::NextColumn);
+        state.break_paragraph(StartParagraph::NextColumn);
+        assert!(state.overset);
+        assert_eq!(state.page_break, None);
+    }
+
+    #[test]
+    fn frame_breaks_go_on_in_the_next_frame() {
+        let mut state = two_columns(false);
+        state.break_paragraph(StartParagraph::NextFrame);
+        assert!(state.overset);
+        assert_eq!(state.frame.left, 0_f64);
+        assert_eq!(state.page_break, None);
+    }
+
+    #[test]
+    fn page_breaks_go_on_on_a_later_page() {
+        let mut state = two_columns(false);
+        state.break_paragraph(StartParagraph::NextPage);
+        assert!(state.overset);
+        assert_eq!(state.page_break, Some(StartParagraph::NextPage));
+    }
+
+    #[test]
+    fn frames_on_the_page_of_a_page_break_hold_no_text() {
+        assert!(waits_on_page(StartParagraph::NextPage, "p1", 1));
+        assert!(!waits_on_page(StartParagraph::NextPage, "p2", 2));
+    }
+
+    #[test]
+    fn odd_and_even_page_breaks_pass_over_pages_of_the_other_side() {
+        assert!(waits_on_page(StartParagraph::NextOddPage, "p2", 2));
+        assert!(!waits_on_page(StartParagraph::NextOddPage, "p3", 3));
+        assert!(waits_on_page(StartParagraph::NextEvenPage, "p3", 3));
+        assert!(!waits_on_page(StartParagraph::NextEvenPage, "p2", 2));
+    }
+
+    #[test]
+    fn frames_passed_over_pass_the_page_break_on() {
+        let frame: Polygon =
+            from_str(r#"<TextFrame Self="f2" PreviousTextFrame="f1" NextTextFrame="f3"/>"#)
+                .unwrap();
+        let mut layout = StoryLayout::measuring();
+        layout.start_page(&page("p1"));
+        layout.set_page_break("f1", StartParagraph::NextPage, "p1".to_string());
+        let state = StoryState {
+            start: StoryPosition {
+                content: 7,
+                offset: 0,
+            },
+            ..StoryState::default()
+        };
+        assert!(frame.waits_for_page_break(&state, &mut layout));
+        assert_eq!(layout.frame_end("f2"), Some(state.start));
+        assert_eq!(
+            layout.page_break("f2"),
+            Some(&(StartParagraph::NextPage, "p1".to_string()))
+        );
+    }
 }
@@ -0,0 +1,189 @@
+use crate::idml_parser::designmap_parser::{PageNumberStyle, Section};
+use crate::idml_parser::spread_parser::SpreadContent;
+use crate::idml_parser::IDMLPackage;
+use std::collections::HashMap;
+
+/// Page number of the page being rendered, as shown by
+/// auto page number and section marker characters
+#[derive(Debug, Default, Clone)]
+pub struct PageNumber {
+    pub number: String,
+    pub section_marker: String,
+    pub label: Option<PageLabel>,
+    /// Id of the section the page is in
+    pub section: Option<String>,
+}
+
+/// Page label of a page in the PDF, which is what viewers show as its number
+#[derive(Debug, Clone, PartialEq)]
+pub struct PageLabel {
+    pub style: PageNumberStyle,
+    pub prefix: String,
+    pub value: u32,
+}
+
+impl PageLabel {
+    /// The label of the following page, if it continues the same numbering
+    pub fn next(&self) -> PageLabel {
+        PageLabel {
+            value: self.value + 1,
+            ..self.clone()
+        }
+    }
+}
+
+/// Numbers each page of the document by the section it is in, keyed by page id.
+/// Sections either continue the numbering of the previous section or restart it.
+pub fn page_numbers(idml_package: &IDMLPackage) -> HashMap<String, PageNumber> {
+    let sections = idml_package.designmap().sections();
+    let mut page_numbers = HashMap::new();
+    let mut section: Option<&Section> = None;
+    let mut value = 0;
+
+    let spreads = idml_package
+        .designmap()
+        .spread_ids()
+        .iter()
+        .filter_map(|spread_id| idml_package.spreads().get(spread_id));
+    for spread in spreads {
+        for content in spread.contents() {
+            let page = match content {
+                SpreadContent::Page(page) => page,
+                _ => continue,
+            };
+
+            let section_start = sections
+                .iter()
+                .find(|section| section.page_start().as_deref() == Some(page.id().as_str()));
+            value = match section_start {
+                Some(start) if *start.continue_numbering() == Some(false) => {
+                    start.page_number_start().unwrap_or(1)
+                }
+                _ => value + 1,
+            };
+            if section_start.is_some() {
+                section = section_start;
+            }
+
+            let style = section
+                .map(|section| section.page_number_style())
+                .unwrap_or(PageNumberStyle::Arabic);
+            let prefix = match section {
+                Some(section) if *section.include_section_prefix() == Some(true) => {
+                    section.section_prefix().clone().unwrap_or_default()
+                }
+                _ => String::new(),
+            };
+            let section_marker = section
+                .and_then(|section| section.marker().clone())
+                .unwrap_or_default();
+
+            page_numbers.insert(
+                page.id().clone(),
+                PageNumber {
+                    number: format!("{}{}", prefix, format_page_number(value, style)),
+                    section_marker,
+                    label: Some(PageLabel {
+                        style,
+                        prefix,
+                        value,
+                    }),
+                    section: section.map(|section| section.id().clone()),
+                },
+            );
+        }
+    }
+
+    page_numbers
+}
+
+/// Formats `value` in the numbering style of a section
+pub fn format_page_number(value: u32, style: PageNumberStyle) -> String {
+    match style {
+        PageNumberStyle::Arabic => value.to_string(),
+        PageNumberStyle::FullWidthArabic => value
+            .to_string()
+            .chars()
+            .filter_map(|c| c.to_digit(10))
+            .filter_map(|digit| std::char::from_u32('０' as u32 + digit))
+            .collect(),
+        PageNumberStyle::UpperRoman => roman_numeral(value),
+        PageNumberStyle::LowerRoman => roman_numeral(value).to_lowercase(),
+        PageNumberStyle::UpperLetters => letters(value),
+        PageNumberStyle::LowerLetters => letters(value).to_lowercase(),
+    }
+}
+
+fn roman_numeral(mut value: u32) -> String {
+    let numerals = [
+        (1000, "M"),
+        (900, "CM"),
+        (500, "D"),
+        (400, "CD"),
+        (100, "C"),
+        (90, "XC"),
+        (50, "L"),
+        (40, "XL"),
+        (10, "X"),
+        (9, "IX"),
+        (5, "V"),
+        (4, "IV"),
+        (1, "I"),
+    ];
+    let mut roman = String::new();
+    for (numeral_value, numeral) in numerals.iter() {
+        while value >= *numeral_value {
+            roman.push_str(numeral);
+            value -= numeral_value;
+        }
+    }
+    roman
+}
+
+/// Letters as PDF viewers number pages with them: A to Z, then AA to ZZ and so on
+fn letters(value: u32) -> String {
+    if value == 0 {
+        return String::new();
+    }
+    let letter = (b'A' + ((value - 1) % 26) as u8) as char;
+    letter.to_string().repeat(((value - 1) / 26 + 1) as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn roman_numerals_use_subtractive_forms() {
+        assert_eq!(roman_numeral(4), "IV");
+        assert_eq!(roman_numeral(9), "IX");
+        assert_eq!(roman_numeral(14), "XIV");
+        assert_eq!(roman_numeral(1994), "MCMXCIV");
+        assert_eq!(roman_numeral(2024), "MMXXIV");
+    }
+
+    #[test]
+    fn zero_has_no_roman_numeral() {
+        assert_eq!(roman_numeral(0), "");
+    }
+
+    #[test]
+    fn page_numbers_are_formatted_in_the_section_style() {
+        assert_eq!(format_page_number(12, PageNumberStyle::Arabic), "12");
+        assert_eq!(
+            format_page_number(12, PageNumberStyle::FullWidthArabic),
+            "１２"
+        );
+        assert_eq!(format_page_number(12, PageNumberStyle::UpperRoman), "XII");
+        assert_eq!(format_page_number(12, PageNumberStyle::LowerRoman), "xii");
+        assert_eq!(format_page_number(3, PageNumberStyle::UpperLetters), "C");
+        assert_eq!(format_page_number(3, PageNumberStyle::LowerLetters), "c");
+    }
+
+    #[test]
+    fn letters_repeat_after_z() {
+        assert_eq!(format_page_number(26, PageNumberStyle::UpperLetters), "Z");
+        assert_eq!(format_page_number(27, PageNumberStyle::UpperLetters), "AA");
+        assert_eq!(format_page_number(53, PageNumberStyle::LowerLetters), "aaa");
+    }
+}
@@ -0,0 +1,282 @@
+use crate::idml_parser::spread_parser::PdfCrop;
+use crate::pdf_printer::shading::Shading;
+use lopdf::{Dictionary, Document, Object, ObjectId, Stream};
+use std::collections::HashMap;
+use std::rc::Rc;
+
+/// Page of a placed PDF. libharu cannot read PDF files, so each placed page is
+/// drawn as a placeholder form, which is replaced by the page once the PDF is saved.
+#[derive(Debug, Clone)]
+pub struct PlacedPage {
+    pub data: Rc<Vec<u8>>,
+    pub page_number: u32,
+    pub crop: PdfCrop,
+    pub transparent_background: bool,
+}
+
+/// Content libharu cannot write, which is drawn as a placeholder form until the PDF is saved
+#[derive(Debug, Clone)]
+pub enum Placeholder {
+    Page(PlacedPage),
+    Shading(Shading),
+}
+
+/// Bounding box of the `index`th placeholder form. Placeholders are the only forms libharu
+/// makes for us, and as their bounding boxes are replaced, we use them to tell them apart.
+pub fn placeholder_bbox(index: usize) -> [f32; 4] {
+    [0_f32, 0_f32, 1_f32, index as f32 + 1_f32]
+}
+
+/// Replaces the placeholder forms in the saved PDF at `path` with what they stand for. Placed
+/// pages are scaled to fill the unit square, as placed images do. The contents and resources
+/// of the pages are copied into the PDF, so they stay vector content.
+pub fn replace_placeholders(path: &str, placeholders: &[Placeholder]) -> Result<(), String> {
+    let mut doc = Document::load(path).map_err(|err| err.to_string())?;
+
+    let placeholder_ids: Vec<(ObjectId, usize)> = doc
+        .objects
+        .iter()
+        .filter_map(|(&id, object)| {
+            let dict = &object.as_stream().ok()?.dict;
+            if dict.get(b"Subtype").and_then(Object::as_name).ok()? != b"Form" {
+                return None;
+            }
+            let bbox = dict.get(b"BBox").and_then(Object::as_array).ok()?;
+            let bbox: Vec<f32> = bbox.iter().filter_map(|n| n.as_float().ok()).collect();
+            (0..placeholders.len())
+                .find(|&index| bbox == placeholder_bbox(index))
+                .map(|index| (id, index))
+        })
+        .collect();
+
+    // Each placed file is only read once, and objects shared by its pages are only copied once
+    let mut sources: HashMap<*const Vec<u8>, (Document, HashMap<ObjectId, ObjectId>)> =
+        HashMap::new();
+    for (placeholder_id, index) in placeholder_ids {
+        let placed_page = match &placeholders[index] {
+            Placeholder::Page(placed_page) => placed_page,
+            Placeholder::Shading(shading) => {
+                let form = shading.form().unwrap_or_else(|err| {
+                    println!("Shading not written: {}", err);
+                    empty_form()
+                });
+                doc.objects.insert(placeholder_id, Object::Stream(form));
+                continue;
+            }
+        };
+        let key = Rc::as_ptr(&placed_page.data);
+        if !sources.contains_key(&key) {
+            match Document::load_mem(&placed_page.data) {
+                Ok(source) => {
+                    sources.insert(key, (source, HashMap::new()));
+                }
+                Err(err) => println!("Failed to read placed PDF: {}", err),
+            }
+        }
+        let form = match sources.get_mut(&key) {
+            Some((source, imported)) => page_form(source, placed_page, &mut doc, imported),
+            None => Err("No PDF".to_string()),
+        };
+        // A placeholder that could not be replaced is left empty
+        let form = form.unwrap_or_else(|err| {
+            println!("Placed PDF page not imported: {}", err);
+            empty_form()
+        });
+        doc.objects.insert(placeholder_id, Object::Stream(form));
+    }
+
+    doc.save(path).map_err(|err| err.to_string())?;
+    Ok(())
+}
+
+fn empty_form() -> Stream {
+    let mut dict = Dictionary::new();
+    dict.set("Type", Object::Name(b"XObject".to_vec()));
+    dict.set("Subtype", Object::Name(b"Form".to_vec()));
+    dict.set("BBox", bbox_object(&[0_f32, 0_f32, 1_f32, 1_f32]));
+    Stream::new(dict, vec![])
+}
+
+/// The placed page as a form, with its contents and resources copied into `doc`
+fn page_form(
+    source: &Document,
+    placed_page: &PlacedPage,
+    doc: &mut Document,
+    imported: &mut HashMap<ObjectId, ObjectId>,
+) -> Result<Stream, String> {
+    let page_id = page_id(source, placed_page.page_number)?;
+    let [left, bottom, right, top] = page_box(source, page_id, placed_page.crop)?;
+    let (width, height) = (right - left, top - bottom);
+    if width <= 0_f32 || height <= 0_f32 {
+        return Err(format!("Page {} is empty", placed_page.page_number));
+    }
+
+    let mut content = vec![];
+    if !placed_page.transparent_background {
+        content.extend(
+            format!("q 1 g {} {} {} {} re f Q\n", left, bottom, width, height).into_bytes(),
+        );
+    }
+    content.extend(
+        source
+            .get_page_content(page_id)
+            .map_err(|err| err.to_string())?,
+    );
+
+    let resources = inherited(source, page_id, b"Resources")
+        .map(|resources| import_object(source, doc, resources, imported))
+        .unwrap_or(Object::Dictionary(Dictionary::new()));
+
+    let mut dict = Dictionary::new();
+    dict.set("Type", Object::Name(b"XObject".to_vec()));
+    dict.set("Subtype", Object::Name(b"Form".to_vec()));
+    dict.set("BBox", bbox_object(&[left, bottom, right, top]));
+    dict.set(
+        "Matrix",
+        Object::Array(
+            vec![
+                1_f32 / width,
+                0_f32,
+                0_f32,
+                1_f32 / height,
+                -left / width,
+                -bottom / height,
+            ]
+            .into_iter()
+            .map(Object::Real)
+            .collect(),
+        ),
+    );
+    dict.set("Resources", resources);
+    let mut form = Stream::new(dict, content);
+    // The form is left uncompressed if compressing it fails
+    let _ = form.compress();
+    Ok(form)
+}
+
+/// Width and height of the placed page in points, once cropped
+pub fn page_size(data: &[u8], page_number: u32, crop: PdfCrop) -> Result<(f32, f32), String> {
+    let source = Document::load_mem(data).map_err(|err| err.to_string())?;
+    let [left, bottom, right, top] = page_box(&source, page_id(&source, page_number)?, crop)?;
+    Ok((right - left, top - bottom))
+}
+
+/// The page with the number, or the first page if there is no such page
+fn page_id(source: &Document, page_number: u32) -> Result<ObjectId, String> {
+    let pages = source.get_pages();
+    pages
+        .get(&page_number)
+        .or(pages.get(&1))
+        .copied()
+        .ok_or("PDF has no pages".to_string())
+}
+
+/// The box of the page shown for the crop. Boxes that are not set default to
+/// the crop box, and the crop box defaults to the media box. We do not know
+/// the bounds of the content itself, so it is cropped to the art box.
+fn page_box(source: &Document, page_id: ObjectId, crop: PdfCrop) -> Result<[f32; 4], String> {
+    let page_box = |key: &[u8]| -> Option<[f32; 4]> {
+        let bounds = match inherited(source, page_id, key)? {
+            Object::Reference(id) => source.get_object(*id).ok()?,
+            bounds => bounds,
+        };
+        match bounds
+            .as_array()
+            .ok()?
+            .iter()
+            .map(|n| n.as_float().ok())
+            .collect::<Option<Vec<f32>>>()?
+            .as_slice()
+        {
+            &[x1, y1, x2, y2] => Some([x1.min(x2), y1.min(y2), x1.max(x2), y1.max(y2)]),
+            _ => None,
+        }
+    };
+    let key: &[u8] = match crop {
+        PdfCrop::CropContent
+        | PdfCrop::CropContentAllLayers
+        | PdfCrop::CropContentVisibleLayers
+        | PdfCrop::CropArt => b"ArtBox",
+        PdfCrop::CropPdf => b"CropBox",
+        PdfCrop::CropTrim => b"TrimBox",
+        PdfCrop::CropBleed => b"BleedBox",
+        PdfCrop::CropMedia => b"MediaBox",
+    };
+    page_box(key)
+        .or(page_box(b"CropBox"))
+        .or(page_box(b"MediaBox"))
+        .ok_or("Page has no media box".to_string())
+}
+
+/// Value of an entry of the page, which may be inherited from the page tree
+fn inherited<'d>(source: &'d Document, page_id: ObjectId, key: &[u8]) -> Option<&'d Object> {
+    let mut node = source.get_dictionary(page_id).ok()?;
+    // Page trees are not that deep, unless they go in circles
+    for _ in 0..32 {
+        if let Ok(value) = node.get(key) {
+            return Some(value);
+        }
+        let parent_id = node.get(b"Parent").and_then(Object::as_reference).ok()?;
+        node = source.get_dictionary(parent_id).ok()?;
+    }
+    None
+}
+
+/// Copies the object into `doc` with the objects it refers to, which are given new ids
+fn import_object(
+    source: &Document,
+    doc: &mut Document,
+    object: &Object,
+    imported: &mut HashMap<ObjectId, ObjectId>,
+) -> Object {
+    match object {
+        Object::Reference(id) => {
+            if let Some(new_id) = imported.get(id) {
+                return Object::Reference(*new_id);
+            }
+            let new_id = doc.new_object_id();
+            imported.insert(*id, new_id);
+            let copy = match source.get_object(*id) {
+                Ok(object) => import_object(source, doc, object, imported),
+                Err(_) => Object::Null,
+            };
+            doc.objects.insert(new_id, copy);
+            Object::Reference(new_id)
+        }
+        Object::Array(items) => Object::Array(
+            items
+                .iter()
+                .map(|item| import_object(source, doc, item, imported))
+                .collect(),
+        ),
+        Object::Dictionary(dict) => {
+            Object::Dictionary(import_dictionary(source, doc, dict, imported))
+        }
+        Object::Stream(stream) => {
+            let dict = import_dictionary(source, doc, &stream.dict, imported);
+            Object::Stream(Stream::new(dict, stream.content.clone()).with_compression(false))
+        }
+        object => object.clone(),
+    }
+}
+
+fn import_dictionary(
+    source: &Document,
+    doc: &mut Document,
+    dict: &Dictionary,
+    imported: &mut HashMap<ObjectId, ObjectId>,
+) -> Dictionary {
+    let mut copy = Dictionary::new();
+    for (key, value) in dict.iter() {
+        // Links back to the page tree would copy every page of the file
+        if key.as_slice() == b"Parent" {
+            continue;
+        }
+        copy.set(key.clone(), import_object(source, doc, value, imported));
+    }
+    copy
+}
+
+fn bbox_object(bbox: &[f32; 4]) -> Object {
+    Object::Array(bbox.iter().map(|&n| Object::Real(n)).collect())
+}
@@ -1,9 +1,12 @@
 use crate::idml_parser::spread_parser::Polygon;
 use crate::pdf_printer::color_manager::Color;
+use crate::pdf_printer::separations;
 use crate::pdf_printer::transforms::{self, *};
 use libharu_sys::*;
 
-pub fn set_fill_color(page: HPDF_Page, fill_color: Color) {
+/// Sets the fill color, or the color of the first stop of a gradient,
+/// which can only be painted as a shading
+pub fn set_fill_color(page: HPDF_Page, fill_color: &Color) {
     unsafe {
         match fill_color {
             Color::Cmyk(color) => {
@@ -12,12 +15,18 @@ pub fn set_fill_color(page: HPDF_Page, fill_color: Color) {
             Color::Rgb(color) => {
                 HPDF_Page_SetRGBFill(page, *color.r(), *color.g(), *color.b());
             }
+            Color::Spot(spot) => separations::set_spot_fill(page, spot),
+            Color::Gradient(gradient) => {
+                if let Some(stop) = gradient.stops().first() {
+                    set_fill_color(page, stop.color());
+                }
+            }
             _ => {}
         }
     }
 }
 
-pub fn set_stroke_color(page: HPDF_Page, stroke_color: Color) {
+pub fn set_stroke_color(page: HPDF_Page, stroke_color: &Color) {
     unsafe {
         match stroke_color {
             Color::Cmyk(color) => {
@@ -26,12 +35,18 @@ pub fn set_stroke_color(page: HPDF_Page, stroke_color: Color) {
             Color::Rgb(color) => {
                 HPDF_Page_SetRGBStroke(page, *color.r(), *color.g(), *color.b());
             }
+            Color::Spot(spot) => separations::set_spot_stroke(page, spot),
+            Color::Gradient(gradient) => {
+                if let Some(stop) = gradient.stops().first() {
+                    set_stroke_color(page, stop.color());
+                }
+            }
             _ => {}
         }
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct BoundingBox {
     pub left: f64,
     pub right: f64,
@@ -39,6 +54,32 @@ pub struct BoundingBox {
     pub bottom: f64,
 }
 
+impl BoundingBox {
+    /// Smallest box containing both boxes
+    pub fn union(&self, other: &BoundingBox) -> BoundingBox {
+        BoundingBox {
+            left: self.left.min(other.left),
+            right: self.right.max(other.right),
+            top: self.top.max(other.top),
+            bottom: self.bottom.min(other.bottom),
+        }
+    }
+}
+
+/// Like `boundingbox`, but returns `None` for items without any path points
+pub fn try_boundingbox(polygon: &Polygon, parent_transform: &Transform) -> Option<BoundingBox> {
+    let has_points = polygon
+        .properties()
+        .iter()
+        .filter_map(|properties| properties.path_geometry().as_ref())
+        .flat_map(|path_geom| path_geom.geometry_path_type().path_point_arrays())
+        .any(|path_point_array| !path_point_array.path_point_array().is_empty());
+    match has_points {
+        true => Some(boundingbox(polygon, parent_transform)),
+        false => None,
+    }
+}
+
 pub fn boundingbox(textframe: &Polygon, parent_transform: &Transform) -> BoundingBox {
     let item_transform = transforms::from_vec(textframe.item_transform());
 
@@ -0,0 +1,258 @@
+/// Color channels of the composite image of a Photoshop document
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PsdColorMode {
+    Gray,
+    Rgb,
+    Cmyk,
+}
+
+/// The composite image of a Photoshop document, which is
+/// saved after the layers as all the layers flattened into one
+#[derive(Debug)]
+pub struct PsdImage {
+    pub width: u32,
+    pub height: u32,
+    pub color_mode: PsdColorMode,
+    /// Interleaved 8 bit samples of the color channels, with CMYK samples as ink
+    /// percentages like in PDF. Photoshop saves them the other way around.
+    pub pixels: Vec<u8>,
+    /// 8 bit samples of the channels after the color channels, which are the alpha channels
+    pub extra_channels: Vec<Vec<u8>>,
+}
+
+/// Reads the composite image of a PSD or PSB file.
+/// See https://www.adobe.com/devnet-apps/photoshop/fileformatashtml/
+pub fn read_psd(data: &[u8]) -> Result<PsdImage, String> {
+    let mut reader = Reader { data, pos: 0 };
+    let Header {
+        large,
+        channel_count,
+        height,
+        width,
+        depth,
+        mode,
+    } = read_header(&mut reader)?;
+
+    // Indexed images keep their palette in the color mode data
+    let color_mode_data = reader.section(false)?;
+    // Image resources and layers
+    reader.section(false)?;
+    reader.section(large)?;
+
+    let compression = reader.u16()?;
+    let row_length = (width as usize * depth + 7) / 8;
+    let rows = height as usize * channel_count;
+    let channel_data = match compression {
+        0 => reader.bytes(row_length * rows)?.to_vec(),
+        1 => {
+            let mut row_lengths = vec![];
+            for _ in 0..rows {
+                row_lengths.push(match large {
+                    true => reader.u32()? as usize,
+                    false => reader.u16()? as usize,
+                });
+            }
+            let mut channel_data = Vec::with_capacity(row_length * rows);
+            for length in row_lengths {
+                let mut row = unpack_bits(reader.bytes(length)?);
+                row.resize(row_length, 0);
+                channel_data.extend(row);
+            }
+            channel_data
+        }
+        _ => return Err(format!("Unknown compression {} of image data", compression)),
+    };
+
+    // Samples of each channel, scaled to 8 bits
+    let pixel_count = width as usize * height as usize;
+    let channel = |index: usize| -> Vec<u8> {
+        let data = &channel_data[index * row_length * height as usize..];
+        match depth {
+            1 => (0..pixel_count)
+                .map(|i| {
+                    let (row, column) = (i / width as usize, i % width as usize);
+                    let byte = data[row * row_length + column / 8];
+                    // Set bits are black
+                    match byte & (0x80 >> (column % 8)) {
+                        0 => 255,
+                        _ => 0,
+                    }
+                })
+                .collect(),
+            16 => data.chunks(2).take(pixel_count).map(|s| s[0]).collect(),
+            32 => data
+                .chunks(4)
+                .take(pixel_count)
+                .map(|s| {
+                    let sample = f32::from_be_bytes([s[0], s[1], s[2], s[3]]);
+                    (sample.max(0_f32).min(1_f32) * 255_f32) as u8
+                })
+                .collect(),
+            _ => data[..pixel_count].to_vec(),
+        }
+    };
+    let interleave = |channels: Vec<Vec<u8>>| -> Vec<u8> {
+        (0..pixel_count)
+            .flat_map(|i| channels.iter().map(move |channel| channel[i]))
+            .collect()
+    };
+
+    let color_channel_count = match mode {
+        3 => 3,
+        4 => 4,
+        _ => 1,
+    };
+    let extra_channels = (color_channel_count..channel_count)
+        .map(|i| channel(i))
+        .collect();
+
+    let (color_mode, pixels) = match (mode, channel_count) {
+        // Bitmap, grayscale and duotone images
+        (0, _) | (1, _) | (8, _) => (PsdColorMode::Gray, channel(0)),
+        (2, _) => {
+            let palette = color_mode_data;
+            let pixels = channel(0)
+                .iter()
+                .flat_map(|&i| {
+                    let i = i as usize;
+                    vec![
+                        *palette.get(i).unwrap_or(&0),
+                        *palette.get(256 + i).unwrap_or(&0),
+                        *palette.get(512 + i).unwrap_or(&0),
+                    ]
+                })
+                .collect();
+            (PsdColorMode::Rgb, pixels)
+        }
+        (3, count) if count >= 3 => (
+            PsdColorMode::Rgb,
+            interleave((0..3).map(|i| channel(i)).collect()),
+        ),
+        (4, count) if count >= 4 => {
+            let inverted = interleave((0..4).map(|i| channel(i)).collect());
+            (
+                PsdColorMode::Cmyk,
+                inverted.iter().map(|sample| 255 - sample).collect(),
+            )
+        }
+        _ => {
+            return Err(format!(
+                "Unsupported color mode {} with {} channels",
+                mode, channel_count
+            ))
+        }
+    };
+
+    Ok(PsdImage {
+        width,
+        height,
+        color_mode,
+        pixels,
+        extra_channels,
+    })
+}
+
+/// The image resources of a PSD or PSB file, which hold its paths and the names of its alpha channels
+pub fn read_image_resources(data: &[u8]) -> Result<&[u8], String> {
+    let mut reader = Reader { data, pos: 0 };
+    read_header(&mut reader)?;
+    reader.section(false)?;
+    reader.section(false)
+}
+
+struct Header {
+    large: bool,
+    channel_count: usize,
+    height: u32,
+    width: u32,
+    depth: usize,
+    mode: u16,
+}
+
+fn read_header(reader: &mut Reader) -> Result<Header, String> {
+    if reader.bytes(4)? != b"8BPS" {
+        return Err("Not a Photoshop document".to_string());
+    }
+    // Large documents (PSB) have version 2 and use longer lengths
+    let large = match reader.u16()? {
+        1 => false,
+        2 => true,
+        version => return Err(format!("Unknown Photoshop document version {}", version)),
+    };
+    reader.bytes(6)?;
+    Ok(Header {
+        large,
+        channel_count: reader.u16()? as usize,
+        height: reader.u32()?,
+        width: reader.u32()?,
+        depth: reader.u16()? as usize,
+        mode: reader.u16()?,
+    })
+}
+
+/// Decompresses a row compressed with PackBits run length encoding
+fn unpack_bits(data: &[u8]) -> Vec<u8> {
+    let mut row = vec![];
+    let mut i = 0;
+    while i < data.len() {
+        let header = data[i] as i8;
+        i += 1;
+        match header {
+            // Copy the next bytes as they are
+            0..=127 => {
+                let end = (i + header as usize + 1).min(data.len());
+                row.extend_from_slice(&data[i..end]);
+                i = end;
+            }
+            // No operation
+            -128 => {}
+            // Repeat the next byte
+            _ => {
+                if let Some(&byte) = data.get(i) {
+                    row.extend(std::iter::repeat(byte).take((1 - header as isize) as usize));
+                }
+                i += 1;
+            }
+        }
+    }
+    row
+}
+
+struct Reader<'d> {
+    data: &'d [u8],
+    pos: usize,
+}
+
+impl<'d> Reader<'d> {
+    fn bytes(&mut self, count: usize) -> Result<&'d [u8], String> {
+        let bytes = self
+            .data
+            .get(self.pos..self.pos + count)
+            .ok_or("Unexpected end of Photoshop document")?;
+        self.pos += count;
+        Ok(bytes)
+    }
+
+    fn u16(&mut self) -> Result<u16, String> {
+        let b = self.bytes(2)?;
+        Ok(u16::from_be_bytes([b[0], b[1]]))
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        let b = self.bytes(4)?;
+        Ok(u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    /// A section of the document preceded by its length
+    fn section(&mut self, long_length: bool) -> Result<&'d [u8], String> {
+        let length = match long_length {
+            true => {
+                let high = self.u32()? as u64;
+                let low = self.u32()? as u64;
+                (high << 32 | low) as usize
+            }
+            false => self.u32()? as usize,
+        };
+        self.bytes(length)
+    }
+}
@@ -0,0 +1,106 @@
+use crate::pdf_printer::image_settings::Resampling;
+
+/// Resizes interleaved 8 bit samples with `channels` samples per pixel from `size` to `new_size`,
+/// first along the rows and then along the columns
+pub fn resample(
+    samples: &[u8],
+    channels: usize,
+    (width, height): (u32, u32),
+    (new_width, new_height): (u32, u32),
+    resampling: Resampling,
+) -> Vec<u8> {
+    let (width, height) = (width as usize, height as usize);
+    let (new_width, new_height) = (new_width as usize, new_height as usize);
+    let columns = weights(width, new_width, resampling);
+    let rows = weights(height, new_height, resampling);
+
+    let mut resized_rows = vec![0_f32; height * new_width * channels];
+    for y in 0..height {
+        for (x, (start, weights)) in columns.iter().enumerate() {
+            for channel in 0..channels {
+                resized_rows[(y * new_width + x) * channels + channel] = weights
+                    .iter()
+                    .enumerate()
+                    .map(|(i, weight)| {
+                        weight * samples[(y * width + start + i) * channels + channel] as f32
+                    })
+                    .sum();
+            }
+        }
+    }
+
+    let mut resized = vec![0_u8; new_height * new_width * channels];
+    for (y, (start, weights)) in rows.iter().enumerate() {
+        for x in 0..new_width {
+            for channel in 0..channels {
+                let value: f32 = weights
+                    .iter()
+                    .enumerate()
+                    .map(|(i, weight)| {
+                        weight * resized_rows[((start + i) * new_width + x) * channels + channel]
+                    })
+                    .sum();
+                resized[(y * new_width + x) * channels + channel] =
+                    value.round().clamp(0_f32, 255_f32) as u8;
+            }
+        }
+    }
+    resized
+}
+
+/// For each new pixel along a row or column, the first old pixel it is made from
+/// and the weights of the old pixels from there on, which add up to 1
+fn weights(size: usize, new_size: usize, resampling: Resampling) -> Vec<(usize, Vec<f32>)> {
+    let scale = size as f32 / new_size as f32;
+    // When downsampling, the filter is stretched to cover all the pixels that make up a new pixel
+    let filter_scale = scale.max(1_f32);
+    let support = match resampling {
+        Resampling::Average => 0.5_f32,
+        Resampling::Bicubic => 2_f32,
+    } * filter_scale;
+
+    (0..new_size)
+        .map(|i| {
+            let center = (i as f32 + 0.5) * scale;
+            let start = (center - support).floor().max(0_f32) as usize;
+            let end = ((center + support).ceil() as usize)
+                .min(size)
+                .max(start + 1);
+            let mut weights: Vec<f32> = (start..end)
+                .map(|j| match resampling {
+                    // Part of the old pixel covered by the new pixel
+                    Resampling::Average => {
+                        let from = center - support;
+                        let to = center + support;
+                        (to.min(j as f32 + 1_f32) - from.max(j as f32)).max(0_f32)
+                    }
+                    Resampling::Bicubic => bicubic((j as f32 + 0.5 - center) / filter_scale),
+                })
+                .collect();
+            let sum: f32 = weights.iter().sum();
+            match sum.abs() > f32::EPSILON {
+                true => weights.iter_mut().for_each(|weight| *weight /= sum),
+                // The new pixel is as the old pixel nearest to it
+                false => {
+                    let nearest = (center as usize).clamp(start, end - 1) - start;
+                    weights = (start..end).map(|_| 0_f32).collect();
+                    weights[nearest] = 1_f32;
+                }
+            }
+            (start, weights)
+        })
+        .collect()
+}
+
+/// Cubic convolution kernel with a = -0.5, as in Keys, "Cubic convolution interpolation for digital image processing"
+fn bicubic(x: f32) -> f32 {
+    let a = -0.5_f32;
+    let x = x.abs();
+    if x < 1_f32 {
+        (a + 2_f32) * x.powi(3) - (a + 3_f32) * x.powi(2) + 1_f32
+    } else if x < 2_f32 {
+        a * x.powi(3) - 5_f32 * a * x.powi(2) + 8_f32 * a * x - 4_f32 * a
+    } else {
+        0_f32
+    }
+}
@@ -0,0 +1,423 @@
+use crate::pdf_printer::color_manager::{Spot, SpotAlternate};
+use libharu_sys::*;
+use lopdf::content::{Content, Operation};
+use lopdf::{Dictionary, Document, Object, ObjectId};
+use std::collections::{HashMap, HashSet};
+
+/// Spot colors are marked by gray levels of multiples of this, which have no other use.
+/// libharu writes numbers with five decimals (`HPDF_FToA` rounds to the nearest 0.00001),
+/// so each marker is written exactly and read back as the spot color it marks,
+/// as long as markers stay below a gray level of 1.
+const MARKER_STEP: f32 = 0.00001;
+
+/// Sets the fill color to the spot color. libharu has no Separation color spaces, so the fill
+/// is set to two gray levels in a row, marking the spot color and its tint, which
+/// `write_separations` replaces with the Separation color space of the ink once the PDF is saved.
+pub unsafe fn set_spot_fill(page: HPDF_Page, spot: &Spot) {
+    HPDF_Page_SetGrayFill(page, marker(*spot.index()));
+    HPDF_Page_SetGrayFill(page, *spot.tint());
+}
+
+/// Sets the stroke color to the spot color, like `set_spot_fill`
+pub unsafe fn set_spot_stroke(page: HPDF_Page, spot: &Spot) {
+    HPDF_Page_SetGrayStroke(page, marker(*spot.index()));
+    HPDF_Page_SetGrayStroke(page, *spot.tint());
+}
+
+fn marker(index: usize) -> f32 {
+    let marker = (index + 1) as f32 * MARKER_STEP;
+    assert!(
+        marker < 1_f32,
+        "Spot color {} is beyond the gray levels that can mark it",
+        index
+    );
+    marker
+}
+
+/// The index of the spot color a gray level marks, if it is a marker
+fn marked_index(level: f32) -> Option<usize> {
+    let code = (level / MARKER_STEP).round();
+    match code >= 1_f32 && (code * MARKER_STEP - level).abs() < MARKER_STEP / 10_f32 {
+        true => Some(code as usize - 1),
+        false => None,
+    }
+}
+
+/// Replaces the spot colors marked in the pages of the saved PDF at `path`, and in the forms
+/// drawn on them, with tints of Separation color spaces, one for each ink, shown as their
+/// alternate color where the ink is not printed
+pub fn write_separations(path: &str, spots: &[Spot]) -> Result<(), String> {
+    if spots.is_empty() {
+        return Ok(());
+    }
+    let mut doc = Document::load(path).map_err(|err| err.to_string())?;
+
+    let spots: HashMap<usize, &Spot> = spots.iter().map(|spot| (*spot.index(), spot)).collect();
+    let mut color_spaces: HashMap<String, ObjectId> = HashMap::new();
+    let mut form_ids: Vec<ObjectId> = vec![];
+    let page_ids: Vec<ObjectId> = doc.get_pages().values().cloned().collect();
+    for page_id in page_ids {
+        let content = doc
+            .get_page_content(page_id)
+            .and_then(|content| Content::decode(&content))
+            .map_err(|err| err.to_string())?;
+        if let Some(resources) = doc
+            .get_page_resources(page_id)
+            .ok()
+            .and_then(|(dict, _)| dict)
+        {
+            form_ids.extend(form_ids_in(&doc, resources));
+        }
+
+        let (operations, used) = replace_markers(content.operations, &spots);
+        if used.is_empty() {
+            continue;
+        }
+        let encoded = Content { operations }
+            .encode()
+            .map_err(|err| err.to_string())?;
+        doc.change_page_content(page_id, encoded)
+            .map_err(|err| err.to_string())?;
+        for spot in used {
+            let id = color_space_id(&mut doc, &mut color_spaces, spot);
+            let resources = doc
+                .get_or_create_resources(page_id)
+                .and_then(Object::as_dict_mut)
+                .map_err(|err| err.to_string())?;
+            add_color_space(resources, &color_space_name(spot), id)?;
+        }
+    }
+
+    // Forms drawn on the pages, like placed PDF pages and SVG graphics, and the forms they draw
+    let mut visited: HashSet<ObjectId> = HashSet::new();
+    while let Some(form_id) = form_ids.pop() {
+        if !visited.insert(form_id) {
+            continue;
+        }
+        let form = match doc.get_object(form_id).and_then(Object::as_stream) {
+            Ok(form) => form,
+            Err(_) => continue,
+        };
+        if let Ok((_, resources)) =
+            doc.dereference(form.dict.get(b"Resources").unwrap_or(&Object::Null))
+        {
+            if let Ok(resources) = resources.as_dict() {
+                form_ids.extend(form_ids_in(&doc, resources));
+            }
+        }
+        let content = form
+            .get_plain_content()
+            .and_then(|content| Content::decode(&content))
+            .map_err(|err| err.to_string())?;
+
+        let (operations, used) = replace_markers(content.operations, &spots);
+        if used.is_empty() {
+            continue;
+        }
+        let encoded = Content { operations }
+            .encode()
+            .map_err(|err| err.to_string())?;
+        let ids: Vec<(String, ObjectId)> = used
+            .into_iter()
+            .map(|spot| {
+                let id = color_space_id(&mut doc, &mut color_spaces, spot);
+                (color_space_name(spot), id)
+            })
+            .collect();
+        doc.get_object_mut(form_id)
+            .and_then(Object::as_stream_mut)
+            .map_err(|err| err.to_string())?
+            .set_plain_content(encoded);
+        let resources = form_resources(&mut doc, form_id)?;
+        for (name, id) in ids {
+            add_color_space(resources, &name, id)?;
+        }
+    }
+
+    doc.save(path).map_err(|err| err.to_string())?;
+    Ok(())
+}
+
+/// Replaces each pair of gray levels marking a spot color and its tint with the Separation
+/// color space of the spot color and the tint, returning the spot colors that were used
+fn replace_markers<'a>(
+    operations: Vec<Operation>,
+    spots: &HashMap<usize, &'a Spot>,
+) -> (Vec<Operation>, Vec<&'a Spot>) {
+    let mut replaced: Vec<Operation> = vec![];
+    let mut used: Vec<&Spot> = vec![];
+    let mut ops = operations.into_iter().peekable();
+    while let Some(op) = ops.next() {
+        let (set_space, set_color) = match op.operator.as_str() {
+            "g" => ("cs", "scn"),
+            "G" => ("CS", "SCN"),
+            _ => {
+                replaced.push(op);
+                continue;
+            }
+        };
+        let tint = match ops.peek() {
+            Some(next) if next.operator == op.operator => next.operands.first().cloned(),
+            _ => None,
+        };
+        let spot = op
+            .operands
+            .first()
+            .and_then(|level| level.as_float().ok())
+            .and_then(marked_index)
+            .and_then(|index| spots.get(&index));
+        match (spot, tint) {
+            (Some(&spot), Some(tint)) => {
+                ops.next();
+                replaced.push(Operation::new(
+                    set_space,
+                    vec![Object::Name(color_space_name(spot).into_bytes())],
+                ));
+                replaced.push(Operation::new(set_color, vec![tint]));
+                used.push(spot);
+            }
+            _ => replaced.push(op),
+        }
+    }
+    (replaced, used)
+}
+
+/// The forms among the XObjects of the resources
+fn form_ids_in(doc: &Document, resources: &Dictionary) -> Vec<ObjectId> {
+    let xobjects = match resources
+        .get(b"XObject")
+        .and_then(|xobjects| doc.dereference(xobjects))
+        .and_then(|(_, xobjects)| xobjects.as_dict())
+    {
+        Ok(xobjects) => xobjects,
+        Err(_) => return vec![],
+    };
+    xobjects
+        .iter()
+        .filter_map(|(_, xobject)| xobject.as_reference().ok())
+        .filter(|&id| {
+            doc.get_object(id)
+                .and_then(Object::as_stream)
+                .and_then(|stream| stream.dict.get(b"Subtype"))
+                .and_then(Object::as_name)
+                .map(|subtype| subtype == b"Form")
+                .unwrap_or(false)
+        })
+        .collect()
+}
+
+/// The resources of the form, which may be shared with other forms
+fn form_resources(doc: &mut Document, form_id: ObjectId) -> Result<&mut Dictionary, String> {
+    let shared = match doc.get_object(form_id).and_then(Object::as_stream) {
+        Ok(form) => form
+            .dict
+            .get(b"Resources")
+            .and_then(Object::as_reference)
+            .ok(),
+        Err(err) => return Err(err.to_string()),
+    };
+    if let Some(id) = shared {
+        return doc
+            .get_object_mut(id)
+            .and_then(Object::as_dict_mut)
+            .map_err(|err| err.to_string());
+    }
+    let form = doc
+        .get_object_mut(form_id)
+        .and_then(Object::as_stream_mut)
+        .map_err(|err| err.to_string())?;
+    if !form.dict.has(b"Resources") {
+        form.dict.set("Resources", Dictionary::new());
+    }
+    form.dict
+        .get_mut(b"Resources")
+        .and_then(Object::as_dict_mut)
+        .map_err(|err| err.to_string())
+}
+
+fn color_space_name(spot: &Spot) -> String {
+    format!("CS{}", spot.index())
+}
+
+/// The object of the Separation color space of the ink, added the first time it is used
+fn color_space_id(
+    doc: &mut Document,
+    color_spaces: &mut HashMap<String, ObjectId>,
+    spot: &Spot,
+) -> ObjectId {
+    match color_spaces.get(spot.ink()) {
+        Some(&id) => id,
+        None => {
+            let id = doc.add_object(separation(spot));
+            color_spaces.insert(spot.ink().clone(), id);
+            id
+        }
+    }
+}
+
+/// Separation color space of the ink of the spot color, going from no ink to its solid alternate
+pub fn separation(spot: &Spot) -> Object {
+    let (alternate, no_ink, solid) = match spot.alternate() {
+        SpotAlternate::Cmyk(color) => (
+            Object::Name(b"DeviceCMYK".to_vec()),
+            vec![0_f32; 4],
+            vec![*color.c(), *color.m(), *color.y(), *color.k()],
+        ),
+        SpotAlternate::Rgb(color) => (
+            Object::Name(b"DeviceRGB".to_vec()),
+            vec![1_f32; 3],
+            vec![*color.r(), *color.g(), *color.b()],
+        ),
+        SpotAlternate::Lab(color) => {
+            let mut lab = Dictionary::new();
+            lab.set("WhitePoint", numbers(&[0.9642_f32, 1_f32, 0.8249_f32]));
+            lab.set("Range", numbers(&[-128_f32, 127_f32, -128_f32, 127_f32]));
+            (
+                Object::Array(vec![Object::Name(b"Lab".to_vec()), Object::Dictionary(lab)]),
+                vec![100_f32, 0_f32, 0_f32],
+                vec![*color.l(), *color.a(), *color.b()],
+            )
+        }
+    };
+
+    let mut tint_transform = Dictionary::new();
+    tint_transform.set("FunctionType", Object::Integer(2));
+    tint_transform.set("Domain", numbers(&[0_f32, 1_f32]));
+    tint_transform.set("C0", numbers(&no_ink));
+    tint_transform.set("C1", numbers(&solid));
+    tint_transform.set("N", Object::Integer(1));
+
+    Object::Array(vec![
+        Object::Name(b"Separation".to_vec()),
+        Object::Name(spot.ink().clone().into_bytes()),
+        alternate,
+        Object::Dictionary(tint_transform),
+    ])
+}
+
+fn add_color_space(resources: &mut Dictionary, name: &str, id: ObjectId) -> Result<(), String> {
+    if !resources.has(b"ColorSpace") {
+        resources.set("ColorSpace", Dictionary::new());
+    }
+    let color_spaces = resources
+        .get_mut(b"ColorSpace")
+        .and_then(Object::as_dict_mut)
+        .map_err(|err| err.to_string())?;
+    color_spaces.set(name, Object::Reference(id));
+    Ok(())
+}
+
+fn numbers(values: &[f32]) -> Object {
+    Object::Array(values.iter().map(|&n| Object::Real(n)).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::CStr;
+    use std::os::raw::c_char;
+
+    extern "C" {
+        fn HPDF_FToA(s: *mut c_char, val: HPDF_REAL, eptr: *mut c_char) -> *mut c_char;
+    }
+
+    /// The number as libharu writes it into content streams
+    fn written(value: f32) -> String {
+        let mut buf = [0 as c_char; 12];
+        unsafe {
+            let end = buf.as_mut_ptr().add(11);
+            HPDF_FToA(buf.as_mut_ptr(), value, end);
+            CStr::from_ptr(buf.as_ptr()).to_str().unwrap().to_string()
+        }
+    }
+
+    #[test]
+    fn markers_are_read_back_as_written_by_libharu() {
+        for index in 0..99_998 {
+            let level: f32 = written(marker(index)).parse().unwrap();
+            assert_eq!(marked_index(level), Some(index), "marker {}", level);
+        }
+    }
+
+    #[test]
+    fn only_marked_pairs_of_gray_levels_are_replaced() {
+        let spot = Spot::new(3, "PANTONE 185 C", (0_f32, 0.8, 0.9, 0_f32));
+        let spots: HashMap<usize, &Spot> = vec![(3, &spot)].into_iter().collect();
+        let gray = |operator: &str, level: f32| Operation::new(operator, vec![Object::Real(level)]);
+        let operations = vec![
+            gray("g", 0.85),
+            gray("G", 0.5),
+            gray("g", marker(3)),
+            gray("g", 0.4),
+            gray("G", marker(3)),
+            gray("g", 1_f32),
+            gray("g", marker(4)),
+            gray("g", 0.4),
+        ];
+
+        let (replaced, used) = replace_markers(operations, &spots);
+        let written: Vec<String> = replaced
+            .iter()
+            .map(|op| format!("{:?} {}", op.operands, op.operator))
+            .collect();
+        assert_eq!(
+            written,
+            vec![
+                "[0.85] g",
+                "[0.5] G",
+                "[/CS3] cs",
+                "[0.4] scn",
+                "[0.00004] G",
+                "[1] g",
+                "[0.00005] g",
+                "[0.4] g",
+            ]
+        );
+        assert_eq!(used.len(), 1);
+    }
+
+    #[test]
+    fn separations_go_from_no_ink_to_the_solid_alternate() {
+        let spot = Spot::new(0, "PANTONE 185 C", (0_f32, 0.8, 0.9, 0_f32));
+        let separation = separation(&spot);
+        let separation = separation.as_array().unwrap();
+        assert_eq!(separation[0].as_name().unwrap(), b"Separation");
+        assert_eq!(separation[1].as_name().unwrap(), b"PANTONE 185 C");
+        assert_eq!(separation[2].as_name().unwrap(), b"DeviceCMYK");
+
+        let tint_transform = separation[3].as_dict().unwrap();
+        let values = |key: &[u8]| -> Vec<f32> {
+            tint_transform
+                .get(key)
+                .and_then(Object::as_array)
+                .unwrap()
+                .iter()
+                .map(|n| n.as_float().unwrap())
+                .collect()
+        };
+        assert_eq!(values(b"Domain"), vec![0_f32, 1_f32]);
+        assert_eq!(values(b"C0"), vec![0_f32; 4]);
+        assert_eq!(values(b"C1"), vec![0_f32, 0.8, 0.9, 0_f32]);
+        assert_eq!(tint_transform.get(b"N").unwrap().as_i64().unwrap(), 1);
+    }
+
+    #[test]
+    fn spot_colors_of_one_ink_share_a_color_space() {
+        let mut doc = Document::with_version("1.4");
+        let mut color_spaces = HashMap::new();
+        let solid = Spot::new(2, "Gold", (0_f32, 0.2, 0.6, 0.2));
+        let aliased = Spot::new(2, "Gold", (0_f32, 0.2, 0.6, 0.2));
+        let other = Spot::new(5, "Silver", (0_f32, 0_f32, 0_f32, 0.3));
+
+        let gold = color_space_id(&mut doc, &mut color_spaces, &solid);
+        assert_eq!(color_space_id(&mut doc, &mut color_spaces, &aliased), gold);
+        assert_ne!(color_space_id(&mut doc, &mut color_spaces, &other), gold);
+        assert_eq!(doc.objects.len(), 2);
+    }
+
+    #[test]
+    #[should_panic]
+    fn markers_stay_below_one() {
+        marker(99_999);
+    }
+}
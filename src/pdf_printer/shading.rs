@@ -0,0 +1,299 @@
+use crate::pdf_printer::color_manager::Spot;
+use crate::pdf_printer::separations;
+use lopdf::{Dictionary, Object, Stream};
+
+/// Color space the colors of a shading are given in. Shadings in the Separation color space
+/// of a spot ink go between tints of the ink.
+#[derive(Debug, Clone)]
+pub enum ShadingColorSpace {
+    Gray,
+    Rgb,
+    Cmyk,
+    Separation(Spot),
+}
+
+/// Where the colors of a shading go. Linear shadings run from the start to the end point,
+/// while radial shadings run from the start circle to the end circle.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ShadingGeometry {
+    Linear {
+        start: (f32, f32),
+        end: (f32, f32),
+    },
+    Radial {
+        start: (f32, f32),
+        start_radius: f32,
+        end: (f32, f32),
+        end_radius: f32,
+    },
+}
+
+/// Color of a shading at `offset`, from 0 at the start to 1 at the end
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColorStop {
+    pub offset: f32,
+    pub color: Vec<f32>,
+}
+
+/// A smooth shading between color stops, painted over `bbox`. libharu cannot make shadings,
+/// so they are drawn as placeholder forms and written once the PDF is saved, like placed PDF pages.
+#[derive(Debug, Clone)]
+pub struct Shading {
+    pub geometry: ShadingGeometry,
+    pub color_space: ShadingColorSpace,
+    pub stops: Vec<ColorStop>,
+    /// Part of the shading that is painted, as left, bottom, right and top
+    pub bbox: [f32; 4],
+}
+
+impl Shading {
+    /// A form painting the shading, with the colors beyond the ends extended
+    pub fn form(&self) -> Result<Stream, String> {
+        if self.stops.is_empty() {
+            return Err("Shading has no color stops".to_string());
+        }
+        let (shading_type, coords) = match self.geometry {
+            ShadingGeometry::Linear { start, end } => (2, vec![start.0, start.1, end.0, end.1]),
+            ShadingGeometry::Radial {
+                start,
+                start_radius,
+                end,
+                end_radius,
+            } => (
+                3,
+                vec![start.0, start.1, start_radius, end.0, end.1, end_radius],
+            ),
+        };
+        let color_space = match &self.color_space {
+            ShadingColorSpace::Gray => Object::Name(b"DeviceGray".to_vec()),
+            ShadingColorSpace::Rgb => Object::Name(b"DeviceRGB".to_vec()),
+            ShadingColorSpace::Cmyk => Object::Name(b"DeviceCMYK".to_vec()),
+            ShadingColorSpace::Separation(spot) => separations::separation(spot),
+        };
+
+        let mut shading = Dictionary::new();
+        shading.set("ShadingType", Object::Integer(shading_type));
+        shading.set("ColorSpace", color_space);
+        shading.set("Coords", numbers(&coords));
+        shading.set("Function", self.function());
+        shading.set(
+            "Extend",
+            Object::Array(vec![Object::Boolean(true), Object::Boolean(true)]),
+        );
+
+        let mut shadings = Dictionary::new();
+        shadings.set("Sh0", Object::Dictionary(shading));
+        let mut resources = Dictionary::new();
+        resources.set("Shading", Object::Dictionary(shadings));
+
+        let mut dict = Dictionary::new();
+        dict.set("Type", Object::Name(b"XObject".to_vec()));
+        dict.set("Subtype", Object::Name(b"Form".to_vec()));
+        dict.set("BBox", numbers(&self.bbox));
+        dict.set("Resources", Object::Dictionary(resources));
+        Ok(Stream::new(dict, b"/Sh0 sh".to_vec()))
+    }
+
+    /// The colors between the stops are interpolated linearly, with a function for each
+    /// pair of stops stitched together. Stops at the same offset make a sharp edge.
+    fn function(&self) -> Object {
+        let mut stops = self.stops.clone();
+        stops.sort_by(|a, b| {
+            a.offset
+                .partial_cmp(&b.offset)
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
+        let (first, last) = (stops[0].clone(), stops[stops.len() - 1].clone());
+        stops.insert(
+            0,
+            ColorStop {
+                offset: 0_f32,
+                color: first.color.clone(),
+            },
+        );
+        stops.push(ColorStop {
+            offset: 1_f32,
+            color: last.color,
+        });
+
+        let ranges: Vec<(&ColorStop, &ColorStop)> = stops
+            .windows(2)
+            .map(|pair| (&pair[0], &pair[1]))
+            .filter(|(from, to)| to.offset > from.offset)
+            .collect();
+        match ranges.as_slice() {
+            [] => interpolation(&first.color, &first.color),
+            [(from, to)] => interpolation(&from.color, &to.color),
+            ranges => {
+                let mut function = Dictionary::new();
+                function.set("FunctionType", Object::Integer(3));
+                function.set("Domain", numbers(&[0_f32, 1_f32]));
+                function.set(
+                    "Functions",
+                    Object::Array(
+                        ranges
+                            .iter()
+                            .map(|(from, to)| interpolation(&from.color, &to.color))
+                            .collect(),
+                    ),
+                );
+                let bounds: Vec<f32> = ranges[1..].iter().map(|(from, _)| from.offset).collect();
+                function.set("Bounds", numbers(&bounds));
+                let encode: Vec<f32> = ranges.iter().flat_map(|_| vec![0_f32, 1_f32]).collect();
+                function.set("Encode", numbers(&encode));
+                Object::Dictionary(function)
+            }
+        }
+    }
+}
+
+/// Function going linearly from one color to another
+fn interpolation(from: &[f32], to: &[f32]) -> Object {
+    let mut function = Dictionary::new();
+    function.set("FunctionType", Object::Integer(2));
+    function.set("Domain", numbers(&[0_f32, 1_f32]));
+    function.set("C0", numbers(from));
+    function.set("C1", numbers(to));
+    function.set("N", Object::Integer(1));
+    Object::Dictionary(function)
+}
+
+fn numbers(values: &[f32]) -> Object {
+    Object::Array(values.iter().map(|&n| Object::Real(n)).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stop(offset: f32, gray: f32) -> ColorStop {
+        ColorStop {
+            offset,
+            color: vec![gray],
+        }
+    }
+
+    fn shading(geometry: ShadingGeometry, stops: Vec<ColorStop>) -> Shading {
+        Shading {
+            geometry,
+            color_space: ShadingColorSpace::Gray,
+            stops,
+            bbox: [0_f32, 0_f32, 100_f32, 50_f32],
+        }
+    }
+
+    fn linear() -> ShadingGeometry {
+        ShadingGeometry::Linear {
+            start: (0_f32, 0_f32),
+            end: (100_f32, 0_f32),
+        }
+    }
+
+    /// The shading dictionary of the form painting the shading
+    fn shading_dict(form: &Stream) -> Dictionary {
+        form.dict
+            .get(b"Resources")
+            .and_then(Object::as_dict)
+            .and_then(|resources| resources.get(b"Shading"))
+            .and_then(Object::as_dict)
+            .and_then(|shadings| shadings.get(b"Sh0"))
+            .and_then(Object::as_dict)
+            .unwrap()
+            .clone()
+    }
+
+    #[test]
+    fn shadings_without_stops_are_not_made() {
+        assert!(shading(linear(), vec![]).form().is_err());
+    }
+
+    #[test]
+    fn forms_paint_the_shading_over_the_bounding_box() {
+        let form = shading(linear(), vec![stop(0_f32, 0_f32), stop(1_f32, 1_f32)])
+            .form()
+            .unwrap();
+        assert_eq!(form.content, b"/Sh0 sh".to_vec());
+        assert_eq!(
+            form.dict.get(b"BBox").unwrap(),
+            &numbers(&[0_f32, 0_f32, 100_f32, 50_f32])
+        );
+        let dict = shading_dict(&form);
+        assert_eq!(dict.get(b"ShadingType").unwrap(), &Object::Integer(2));
+        assert_eq!(
+            dict.get(b"Coords").unwrap(),
+            &numbers(&[0_f32, 0_f32, 100_f32, 0_f32])
+        );
+        assert_eq!(
+            dict.get(b"ColorSpace").unwrap(),
+            &Object::Name(b"DeviceGray".to_vec())
+        );
+        assert_eq!(
+            dict.get(b"Function").unwrap(),
+            &interpolation(&[0_f32], &[1_f32])
+        );
+    }
+
+    #[test]
+    fn radial_shadings_run_between_two_circles() {
+        let geometry = ShadingGeometry::Radial {
+            start: (50_f32, 25_f32),
+            start_radius: 0_f32,
+            end: (50_f32, 25_f32),
+            end_radius: 40_f32,
+        };
+        let form = shading(geometry, vec![stop(0_f32, 0_f32)]).form().unwrap();
+        let dict = shading_dict(&form);
+        assert_eq!(dict.get(b"ShadingType").unwrap(), &Object::Integer(3));
+        assert_eq!(
+            dict.get(b"Coords").unwrap(),
+            &numbers(&[50_f32, 25_f32, 0_f32, 50_f32, 25_f32, 40_f32])
+        );
+    }
+
+    #[test]
+    fn colors_before_the_first_and_after_the_last_stop_are_extended() {
+        let shading = shading(linear(), vec![stop(0.25_f32, 0_f32), stop(0.75_f32, 1_f32)]);
+        let function = shading.function();
+        let function = function.as_dict().unwrap();
+        assert_eq!(function.get(b"FunctionType").unwrap(), &Object::Integer(3));
+        assert_eq!(
+            function.get(b"Bounds").unwrap(),
+            &numbers(&[0.25_f32, 0.75_f32])
+        );
+        assert_eq!(
+            function.get(b"Functions").unwrap(),
+            &Object::Array(vec![
+                interpolation(&[0_f32], &[0_f32]),
+                interpolation(&[0_f32], &[1_f32]),
+                interpolation(&[1_f32], &[1_f32]),
+            ])
+        );
+        assert_eq!(
+            function.get(b"Encode").unwrap(),
+            &numbers(&[0_f32, 1_f32, 0_f32, 1_f32, 0_f32, 1_f32])
+        );
+    }
+
+    #[test]
+    fn stops_at_the_same_offset_make_a_sharp_edge() {
+        let shading = shading(
+            linear(),
+            vec![
+                stop(1_f32, 1_f32),
+                stop(0_f32, 0_f32),
+                stop(0.5_f32, 0_f32),
+                stop(0.5_f32, 1_f32),
+            ],
+        );
+        let function = shading.function();
+        let function = function.as_dict().unwrap();
+        assert_eq!(function.get(b"Bounds").unwrap(), &numbers(&[0.5_f32]));
+        assert_eq!(
+            function.get(b"Functions").unwrap(),
+            &Object::Array(vec![
+                interpolation(&[0_f32], &[0_f32]),
+                interpolation(&[1_f32], &[1_f32]),
+            ])
+        );
+    }
+}
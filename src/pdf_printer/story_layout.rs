@@ -0,0 +1,129 @@
+use crate::idml_parser::spread_parser::{MarginPreference, Page};
+use crate::idml_parser::styles::commom_text_properties::StartParagraph;
+use crate::pdf_printer::page_items::footnote::PlacedFootnote;
+use std::collections::HashMap;
+
+/// Place in the text of a story: the index of a piece of its content, counted
+/// in the order the story is set, and how far into that content the text goes on
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct StoryPosition {
+    pub content: usize,
+    /// Glyph of a text run, or body row of a table, the text continues from
+    pub offset: usize,
+}
+
+impl StoryPosition {
+    /// Past the end of any story, where frames start once the frames threaded
+    /// before them hold all of their story
+    pub const END: StoryPosition = StoryPosition {
+        content: usize::MAX,
+        offset: 0,
+    };
+}
+
+/// Where the text of the stories went as they were set in their frames: how far
+/// into its story each frame got, and the pages paragraphs, text destinations,
+/// index markers and note references were set on
+#[derive(Debug, Default)]
+pub struct StoryLayout {
+    /// Only lays out the stories, without drawing anything
+    measuring: bool,
+    /// Id of the page being set
+    current_page: Option<String>,
+    /// Margins of the page being set
+    page_margins: Option<MarginPreference>,
+    /// Where the text that did not fit each frame starts, keyed by frame id
+    frame_ends: HashMap<String, StoryPosition>,
+    /// Footnotes that did not fit each frame, which go on in the next frame, keyed by frame id
+    continued_footnotes: HashMap<String, Vec<PlacedFootnote>>,
+    /// Breaks to a later page that ended the text of frames, with the page the break
+    /// was set on, keyed by frame id
+    page_breaks: HashMap<String, (StartParagraph, String)>,
+    /// Page the first line of each paragraph is on, keyed by story id and the
+    /// number of paragraphs before it in the story
+    paragraph_pages: HashMap<(String, usize), String>,
+    /// Page each text destination, index marker and footnote or endnote
+    /// reference is on, keyed by its id
+    marker_pages: HashMap<String, String>,
+}
+
+impl StoryLayout {
+    /// A layout that stories are set into without being drawn
+    pub fn measuring() -> StoryLayout {
+        StoryLayout {
+            measuring: true,
+            ..StoryLayout::default()
+        }
+    }
+
+    pub fn is_measuring(&self) -> bool {
+        self.measuring
+    }
+
+    pub fn start_page(&mut self, page: &Page) {
+        self.current_page = Some(page.id().to_string());
+        self.page_margins = page.margin_preference().clone();
+    }
+
+    pub fn page_margins(&self) -> Option<MarginPreference> {
+        self.page_margins.clone()
+    }
+
+    pub fn frame_end(&self, frame_id: &str) -> Option<StoryPosition> {
+        self.frame_ends.get(frame_id).copied()
+    }
+
+    pub fn set_frame_end(&mut self, frame_id: &str, end: StoryPosition) {
+        self.frame_ends.insert(frame_id.to_string(), end);
+    }
+
+    pub fn continued_footnotes(&self, frame_id: &str) -> Vec<PlacedFootnote> {
+        self.continued_footnotes
+            .get(frame_id)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    pub fn set_continued_footnotes(&mut self, frame_id: &str, footnotes: Vec<PlacedFootnote>) {
+        self.continued_footnotes
+            .insert(frame_id.to_string(), footnotes);
+    }
+
+    pub fn current_page(&self) -> Option<&String> {
+        self.current_page.as_ref()
+    }
+
+    pub fn page_break(&self, frame_id: &str) -> Option<&(StartParagraph, String)> {
+        self.page_breaks.get(frame_id)
+    }
+
+    pub fn set_page_break(&mut self, frame_id: &str, break_type: StartParagraph, page_id: String) {
+        self.page_breaks
+            .insert(frame_id.to_string(), (break_type, page_id));
+    }
+
+    /// Notes the paragraphs of a story that started on the current page, and the
+    /// markers set on it. Paragraphs and markers stay on the page they were first set on.
+    pub fn add_story(&mut self, story_id: &str, paragraphs: &[usize], markers: Vec<String>) {
+        let page_id = match &self.current_page {
+            Some(page_id) => page_id,
+            None => return,
+        };
+        for paragraph in paragraphs {
+            self.paragraph_pages
+                .entry((story_id.to_string(), *paragraph))
+                .or_insert(page_id.clone());
+        }
+        for marker in markers {
+            self.marker_pages.entry(marker).or_insert(page_id.clone());
+        }
+    }
+
+    pub fn paragraph_page(&self, story_id: &str, paragraph: usize) -> Option<&String> {
+        self.paragraph_pages.get(&(story_id.to_string(), paragraph))
+    }
+
+    pub fn marker_page(&self, marker_id: &str) -> Option<&String> {
+        self.marker_pages.get(marker_id)
+    }
+}
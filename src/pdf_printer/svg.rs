@@ -0,0 +1,618 @@
+use crate::pdf_printer::font_manager::FontLibrary;
+use crate::pdf_printer::image_manager::ImageLibrary;
+use crate::pdf_printer::shading::{ColorStop, Shading, ShadingColorSpace, ShadingGeometry};
+use libharu_sys::*;
+use std::collections::HashMap;
+use std::ffi::CString;
+use std::fs;
+use std::sync::Arc;
+use usvg::fontdb;
+use usvg::tiny_skia_path::{Path as PathData, PathSegment, Point, Rect};
+use usvg::{FillRule, Group, LineCap, LineJoin, Node, Paint, PaintOrder, Transform};
+
+/// Font of the IDML resources that SVG text is laid out with
+#[derive(Debug, Clone)]
+struct SvgFace {
+    font_name: String,
+    font_style: String,
+    units_per_em: f32,
+}
+
+/// The fonts SVG text is laid out with. These are the fonts of the IDML resources,
+/// so the text can be drawn with the same fonts through the font library.
+#[derive(Debug)]
+pub struct SvgFonts {
+    database: Arc<fontdb::Database>,
+    faces: HashMap<fontdb::ID, SvgFace>,
+}
+
+impl SvgFonts {
+    pub fn new(font_lib: &FontLibrary) -> SvgFonts {
+        let mut database = fontdb::Database::new();
+        let mut faces = HashMap::new();
+        for (font_name, font_style, font_path) in font_lib.font_files() {
+            let data = match fs::read(&font_path) {
+                Ok(data) => Arc::new(data),
+                Err(_) => continue,
+            };
+            for id in database.load_font_source(fontdb::Source::Binary(data.clone())) {
+                let index = database.face(id).map_or(0, |face| face.index);
+                let units_per_em = match ttf_parser::Face::parse(&data, index) {
+                    Ok(face) => face.units_per_em() as f32,
+                    Err(_) => continue,
+                };
+                faces.insert(
+                    id,
+                    SvgFace {
+                        font_name: font_name.clone(),
+                        font_style: font_style.clone(),
+                        units_per_em,
+                    },
+                );
+            }
+        }
+        // Text in fonts we do not have is set in the first of ours
+        let fallback = database
+            .faces()
+            .next()
+            .and_then(|face| face.families.first())
+            .map(|(family, _)| family.clone());
+        if let Some(family) = fallback {
+            database.set_serif_family(family);
+        }
+        SvgFonts {
+            database: Arc::new(database),
+            faces,
+        }
+    }
+}
+
+/// Parses an SVG file. Shapes, styles and references are resolved by usvg, which
+/// leaves paths, images and text laid out in `fonts`, grouped with their transforms.
+pub fn parse_svg(data: &[u8], fonts: Option<&SvgFonts>) -> Result<usvg::Tree, String> {
+    let mut options = usvg::Options::default();
+    if let Some(fonts) = fonts {
+        options.fontdb = fonts.database.clone();
+    }
+    usvg::Tree::from_data(data, &options).map_err(|err| err.to_string())
+}
+
+/// Whether the SVG file has text in it, which needs fonts to be laid out
+pub fn has_text(data: &[u8]) -> bool {
+    data.windows(5)
+        .any(|window| window == b"<text" || window == b":text")
+}
+
+/// Draws SVG graphics as PDF vector graphics. Fills and strokes are drawn as paths and
+/// gradients as shadings, while text is drawn with the fonts of the font library. Masks,
+/// filters and patterns cannot be expressed with libharu and are left out, as is the
+/// opacity of gradient stops. The opacity of groups is applied to each item in them.
+pub struct SvgPainter<'p, 'a> {
+    pub image_lib: &'p ImageLibrary,
+    pub font_lib: &'p FontLibrary<'a>,
+    pub fonts: Option<&'p SvgFonts>,
+    pub current_page: HPDF_Page,
+}
+
+impl<'p, 'a> SvgPainter<'p, 'a> {
+    /// Draws the items of the group in the current coordinates, which have to be those of the SVG
+    pub fn draw_group(&self, group: &Group, opacity: f32) {
+        let opacity = opacity * group.opacity().get();
+        unsafe {
+            HPDF_Page_GSave(self.current_page);
+            concat(self.current_page, group.transform());
+            if let Some(clip_path) = group.clip_path() {
+                self.clip(clip_path);
+            }
+            for node in group.children() {
+                self.draw_node(node, opacity);
+            }
+            HPDF_Page_GRestore(self.current_page);
+        }
+    }
+
+    fn draw_node(&self, node: &Node, opacity: f32) {
+        match node {
+            Node::Group(group) => self.draw_group(group, opacity),
+            Node::Path(path) => self.draw_path(path, opacity),
+            Node::Image(image) => {
+                if !image.is_visible() {
+                    return;
+                }
+                match image.kind() {
+                    usvg::ImageKind::SVG(tree) => self.draw_group(tree.root(), opacity),
+                    usvg::ImageKind::JPEG(data)
+                    | usvg::ImageKind::PNG(data)
+                    | usvg::ImageKind::GIF(data)
+                    | usvg::ImageKind::WEBP(data) => match self.image_lib.svg_image(data) {
+                        Ok(pdf_image) => unsafe {
+                            let size = image.size();
+                            HPDF_Page_GSave(self.current_page);
+                            self.set_alpha(opacity, opacity);
+                            // Images have their first row at the top, as SVG has
+                            HPDF_Page_Concat(
+                                self.current_page,
+                                size.width(),
+                                0_f32,
+                                0_f32,
+                                -size.height(),
+                                0_f32,
+                                size.height(),
+                            );
+                            HPDF_Page_DrawImage(
+                                self.current_page,
+                                pdf_image,
+                                0_f32,
+                                0_f32,
+                                1_f32,
+                                1_f32,
+                            );
+                            HPDF_Page_GRestore(self.current_page);
+                        },
+                        Err(err) => println!("Image in SVG not rendered: {}", err),
+                    },
+                }
+            }
+            Node::Text(text) => self.draw_text(text, opacity),
+        }
+    }
+
+    fn draw_path(&self, path: &usvg::Path, opacity: f32) {
+        if !path.is_visible() {
+            return;
+        }
+        let fill = || {
+            if let Some(fill) = path.fill() {
+                self.fill(
+                    path.data(),
+                    fill.paint(),
+                    fill.rule(),
+                    opacity * fill.opacity().get(),
+                );
+            }
+        };
+        let stroke = || {
+            if let Some(stroke) = path.stroke() {
+                self.stroke(path.data(), stroke, opacity);
+            }
+        };
+        match path.paint_order() {
+            PaintOrder::FillAndStroke => {
+                fill();
+                stroke();
+            }
+            PaintOrder::StrokeAndFill => {
+                stroke();
+                fill();
+            }
+        }
+    }
+
+    fn fill(&self, data: &PathData, paint: &Paint, rule: FillRule, opacity: f32) {
+        unsafe {
+            HPDF_Page_GSave(self.current_page);
+            self.set_alpha(opacity, 1_f32);
+            if let Paint::Color(color) = paint {
+                let (r, g, b) = rgb(color);
+                HPDF_Page_SetRGBFill(self.current_page, r, g, b);
+            }
+            add_path(self.current_page, data);
+            match paint {
+                Paint::Color(_) => {
+                    match rule {
+                        FillRule::NonZero => HPDF_Page_Fill(self.current_page),
+                        FillRule::EvenOdd => HPDF_Page_Eofill(self.current_page),
+                    };
+                }
+                Paint::LinearGradient(_) | Paint::RadialGradient(_) => {
+                    match rule {
+                        FillRule::NonZero => HPDF_Page_Clip(self.current_page),
+                        FillRule::EvenOdd => HPDF_Page_Eoclip(self.current_page),
+                    };
+                    HPDF_Page_EndPath(self.current_page);
+                    self.shade(paint, data.bounds());
+                }
+                Paint::Pattern(_) => {
+                    HPDF_Page_EndPath(self.current_page);
+                }
+            }
+            HPDF_Page_GRestore(self.current_page);
+        }
+    }
+
+    fn stroke(&self, data: &PathData, stroke: &usvg::Stroke, opacity: f32) {
+        let opacity = opacity * stroke.opacity().get();
+        match stroke.paint() {
+            // Gradients are painted in the outline of the stroke
+            Paint::LinearGradient(_) | Paint::RadialGradient(_) => {
+                if let Some(outline) = data.stroke(&stroke.to_tiny_skia(), 1_f32) {
+                    self.fill(&outline, stroke.paint(), FillRule::NonZero, opacity);
+                }
+            }
+            Paint::Color(color) => {
+                // libharu only takes whole numbers as dashes, so the path is dashed here
+                let dashed = stroke
+                    .to_tiny_skia()
+                    .dash
+                    .and_then(|dash| data.dash(&dash, 1_f32));
+                let data = dashed.as_ref().unwrap_or(data);
+                let (r, g, b) = rgb(color);
+                unsafe {
+                    HPDF_Page_GSave(self.current_page);
+                    self.set_alpha(1_f32, opacity);
+                    HPDF_Page_SetRGBStroke(self.current_page, r, g, b);
+                    self.set_line_style(stroke);
+                    add_path(self.current_page, data);
+                    HPDF_Page_Stroke(self.current_page);
+                    HPDF_Page_GRestore(self.current_page);
+                }
+            }
+            Paint::Pattern(_) => {}
+        }
+    }
+
+    unsafe fn set_line_style(&self, stroke: &usvg::Stroke) {
+        HPDF_Page_SetLineWidth(self.current_page, stroke.width().get());
+        HPDF_Page_SetLineCap(
+            self.current_page,
+            match stroke.linecap() {
+                LineCap::Butt => HPDF_LineCap::HPDF_BUTT_END,
+                LineCap::Round => HPDF_LineCap::HPDF_ROUND_END,
+                LineCap::Square => HPDF_LineCap::HPDF_PROJECTING_SCUARE_END,
+            },
+        );
+        HPDF_Page_SetLineJoin(
+            self.current_page,
+            match stroke.linejoin() {
+                LineJoin::Miter | LineJoin::MiterClip => HPDF_LineJoin::HPDF_MITER_JOIN,
+                LineJoin::Round => HPDF_LineJoin::HPDF_ROUND_JOIN,
+                LineJoin::Bevel => HPDF_LineJoin::HPDF_BEVEL_JOIN,
+            },
+        );
+        HPDF_Page_SetMiterLimit(self.current_page, stroke.miterlimit().get().max(1_f32));
+    }
+
+    /// Paints the gradient over `bounds`, which must already be clipped to what it fills
+    unsafe fn shade(&self, paint: &Paint, bounds: Rect) {
+        let (geometry, transform, stops) = match paint {
+            Paint::LinearGradient(gradient) => (
+                ShadingGeometry::Linear {
+                    start: (gradient.x1(), gradient.y1()),
+                    end: (gradient.x2(), gradient.y2()),
+                },
+                gradient.transform(),
+                gradient.stops(),
+            ),
+            Paint::RadialGradient(gradient) => (
+                ShadingGeometry::Radial {
+                    start: (gradient.fx(), gradient.fy()),
+                    start_radius: 0_f32,
+                    end: (gradient.cx(), gradient.cy()),
+                    end_radius: gradient.r().get(),
+                },
+                gradient.transform(),
+                gradient.stops(),
+            ),
+            _ => return,
+        };
+        // The shading is painted in the coordinates of the gradient
+        let bbox = match transform.invert() {
+            Some(inverse) => {
+                let mut corners = [
+                    Point::from_xy(bounds.left(), bounds.top()),
+                    Point::from_xy(bounds.right(), bounds.top()),
+                    Point::from_xy(bounds.right(), bounds.bottom()),
+                    Point::from_xy(bounds.left(), bounds.bottom()),
+                ];
+                inverse.map_points(&mut corners);
+                let xs = corners.iter().map(|p| p.x);
+                let ys = corners.iter().map(|p| p.y);
+                [
+                    xs.clone().fold(f32::INFINITY, f32::min),
+                    ys.clone().fold(f32::INFINITY, f32::min),
+                    xs.fold(f32::NEG_INFINITY, f32::max),
+                    ys.fold(f32::NEG_INFINITY, f32::max),
+                ]
+            }
+            None => return,
+        };
+        let shading = Shading {
+            geometry,
+            color_space: ShadingColorSpace::Rgb,
+            stops: stops
+                .iter()
+                .map(|stop| {
+                    let (r, g, b) = rgb(&stop.color());
+                    ColorStop {
+                        offset: stop.offset().get(),
+                        color: vec![r, g, b],
+                    }
+                })
+                .collect(),
+            bbox,
+        };
+        match self.image_lib.shading(shading, self.current_page) {
+            Ok(form) => {
+                concat(self.current_page, transform);
+                HPDF_Page_DrawImage(self.current_page, form, 0_f32, 0_f32, 1_f32, 1_f32);
+            }
+            Err(err) => println!("Gradient in SVG not rendered: {}", err),
+        }
+    }
+
+    /// Clips to the union of the paths of the clip path, in the current coordinates
+    unsafe fn clip(&self, clip_path: &usvg::ClipPath) {
+        if let Some(clip_path) = clip_path.clip_path() {
+            self.clip(clip_path);
+        }
+        let mut paths = vec![];
+        collect_paths(clip_path.root(), clip_path.transform(), &mut paths);
+        let even_odd = matches!(paths.first(), Some((_, FillRule::EvenOdd)));
+        match paths.is_empty() {
+            // An empty clip path hides everything
+            true => {
+                HPDF_Page_Rectangle(self.current_page, 0_f32, 0_f32, 0_f32, 0_f32);
+            }
+            false => {
+                for (path, _) in &paths {
+                    add_path(self.current_page, path);
+                }
+            }
+        }
+        match even_odd {
+            true => HPDF_Page_Eoclip(self.current_page),
+            false => HPDF_Page_Clip(self.current_page),
+        };
+        HPDF_Page_EndPath(self.current_page);
+    }
+
+    /// Draws the laid out glyphs of the text with the fonts they were laid out in. The
+    /// decorations are drawn as paths, with the underline and overline below the glyphs.
+    fn draw_text(&self, text: &usvg::Text, opacity: f32) {
+        for span in text.layouted().iter().filter(|span| span.visible) {
+            for decoration in span.underline.iter().chain(span.overline.iter()) {
+                self.draw_path(decoration, opacity);
+            }
+            unsafe {
+                HPDF_Page_GSave(self.current_page);
+                if let Some(fill) = &span.fill {
+                    match fill.paint() {
+                        Paint::Color(color) => {
+                            let (r, g, b) = rgb(color);
+                            HPDF_Page_SetRGBFill(self.current_page, r, g, b);
+                            self.set_alpha(opacity * fill.opacity().get(), 1_f32);
+                            self.show_glyphs(span, HPDF_TextRenderingMode::HPDF_FILL);
+                        }
+                        // Gradients are painted through the glyphs
+                        Paint::LinearGradient(_) | Paint::RadialGradient(_) => {
+                            HPDF_Page_GSave(self.current_page);
+                            self.set_alpha(opacity * fill.opacity().get(), 1_f32);
+                            self.show_glyphs(span, HPDF_TextRenderingMode::HPDF_CLIPPING);
+                            self.shade(fill.paint(), text.bounding_box());
+                            HPDF_Page_GRestore(self.current_page);
+                        }
+                        Paint::Pattern(_) => {}
+                    }
+                }
+                if let Some(stroke) = &span.stroke {
+                    // Strokes of glyphs cannot be clipped to, so gradients are drawn in their first color
+                    let color = match stroke.paint() {
+                        Paint::Color(color) => Some(*color),
+                        Paint::LinearGradient(gradient) => {
+                            gradient.stops().first().map(|stop| stop.color())
+                        }
+                        Paint::RadialGradient(gradient) => {
+                            gradient.stops().first().map(|stop| stop.color())
+                        }
+                        Paint::Pattern(_) => None,
+                    };
+                    if let Some(color) = color {
+                        let (r, g, b) = rgb(&color);
+                        HPDF_Page_SetRGBStroke(self.current_page, r, g, b);
+                        self.set_alpha(1_f32, opacity * stroke.opacity().get());
+                        self.set_line_style(stroke);
+                        self.show_glyphs(span, HPDF_TextRenderingMode::HPDF_STROKE);
+                    }
+                }
+                HPDF_Page_GRestore(self.current_page);
+            }
+            if let Some(line_through) = &span.line_through {
+                self.draw_path(line_through, opacity);
+            }
+        }
+    }
+
+    /// Shows each glyph of the span on its own, placed where usvg laid it out
+    unsafe fn show_glyphs(&self, span: &usvg::layout::Span, mode: HPDF_TextRenderingMode) {
+        let fonts = match self.fonts {
+            Some(fonts) => fonts,
+            None => return,
+        };
+        HPDF_Page_BeginText(self.current_page);
+        HPDF_Page_SetTextRenderingMode(self.current_page, mode);
+        let mut current_font = None;
+        for glyph in &span.positioned_glyphs {
+            let face = match fonts.faces.get(&glyph.font) {
+                Some(face) => face,
+                None => continue,
+            };
+            let pdf_font = match self.font_lib.get_font(&face.font_name, &face.font_style) {
+                Ok(pdf_font) => pdf_font,
+                Err(_) => continue,
+            };
+            if current_font != Some(pdf_font) {
+                // The size is set through the text matrix
+                HPDF_Page_SetFontAndSize(self.current_page, pdf_font, 1_f32);
+                current_font = Some(pdf_font);
+            }
+            // Outlines are laid out in font units, while text space is in ems
+            let matrix = glyph
+                .outline_transform()
+                .pre_scale(face.units_per_em, face.units_per_em);
+            HPDF_Page_SetTextMatrix(
+                self.current_page,
+                matrix.sx,
+                matrix.ky,
+                matrix.kx,
+                matrix.sy,
+                matrix.tx,
+                matrix.ty,
+            );
+            let encoding = self.font_lib.get_encoding(pdf_font);
+            let text = match encoding.draws_glyph_ids() {
+                true => encoding
+                    .encode_glyph(glyph.id.0, &glyph.text)
+                    .unwrap_or_default(),
+                false => encoding.encode(&glyph.text),
+            };
+            HPDF_Page_ShowText(
+                self.current_page,
+                CString::new(text).unwrap_or_default().as_ptr(),
+            );
+        }
+        HPDF_Page_EndText(self.current_page);
+    }
+
+    unsafe fn set_alpha(&self, fill_alpha: f32, stroke_alpha: f32) {
+        if fill_alpha < 1_f32 || stroke_alpha < 1_f32 {
+            let state = self.image_lib.alpha_state(fill_alpha, stroke_alpha);
+            HPDF_Page_SetExtGState(self.current_page, state);
+        }
+    }
+}
+
+/// Paths of a clip path with their fill rules, transformed to the coordinates it is used in
+fn collect_paths(group: &Group, transform: Transform, paths: &mut Vec<(PathData, FillRule)>) {
+    let transform = transform.pre_concat(group.transform());
+    for node in group.children() {
+        match node {
+            Node::Group(group) => collect_paths(group, transform, paths),
+            Node::Path(path) => {
+                let rule = path.fill().map_or(FillRule::NonZero, |fill| fill.rule());
+                if let Some(data) = path.data().clone().transform(transform) {
+                    paths.push((data, rule));
+                }
+            }
+            Node::Text(text) => collect_paths(text.flattened(), transform, paths),
+            Node::Image(_) => {}
+        }
+    }
+}
+
+unsafe fn concat(current_page: HPDF_Page, transform: Transform) {
+    if !transform.is_identity() {
+        HPDF_Page_Concat(
+            current_page,
+            transform.sx,
+            transform.ky,
+            transform.kx,
+            transform.sy,
+            transform.tx,
+            transform.ty,
+        );
+    }
+}
+
+/// Adds the segments of the path to the page. Quadratic curves are drawn as cubic ones.
+unsafe fn add_path(current_page: HPDF_Page, data: &PathData) {
+    let mut start = Point::zero();
+    let mut last = Point::zero();
+    for segment in data.segments() {
+        match segment {
+            PathSegment::MoveTo(p) => {
+                HPDF_Page_MoveTo(current_page, p.x, p.y);
+                start = p;
+                last = p;
+            }
+            PathSegment::LineTo(p) => {
+                HPDF_Page_LineTo(current_page, p.x, p.y);
+                last = p;
+            }
+            PathSegment::QuadTo(c, p) => {
+                HPDF_Page_CurveTo(
+                    current_page,
+                    last.x + (c.x - last.x) * 2_f32 / 3_f32,
+                    last.y + (c.y - last.y) * 2_f32 / 3_f32,
+                    p.x + (c.x - p.x) * 2_f32 / 3_f32,
+                    p.y + (c.y - p.y) * 2_f32 / 3_f32,
+                    p.x,
+                    p.y,
+                );
+                last = p;
+            }
+            PathSegment::CubicTo(c1, c2, p) => {
+                HPDF_Page_CurveTo(current_page, c1.x, c1.y, c2.x, c2.y, p.x, p.y);
+                last = p;
+            }
+            PathSegment::Close => {
+                HPDF_Page_ClosePath(current_page);
+                last = start;
+            }
+        }
+    }
+}
+
+fn rgb(color: &usvg::Color) -> (f32, f32, f32) {
+    (
+        color.red as f32 / 255_f32,
+        color.green as f32 / 255_f32,
+        color.blue as f32 / 255_f32,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn text_is_found_with_or_without_a_namespace_prefix() {
+        assert!(has_text(br#"<svg><text x="0">A</text></svg>"#));
+        assert!(has_text(br#"<s:svg><s:text x="0">A</s:text></s:svg>"#));
+        assert!(!has_text(br#"<svg><rect width="10" height="10"/></svg>"#));
+    }
+
+    #[test]
+    fn files_that_are_not_svg_are_not_parsed() {
+        assert!(parse_svg(b"%!PS-Adobe-3.0 EPSF-3.0", None).is_err());
+    }
+
+    #[test]
+    fn clip_paths_are_collected_in_the_coordinates_they_are_used_in() {
+        let tree = parse_svg(
+            br#"<svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+                <g transform="translate(10 20)">
+                    <rect width="30" height="40"/>
+                    <path d="M 0 0 L 10 0 L 10 10 Z" fill-rule="evenodd"/>
+                </g>
+            </svg>"#,
+            None,
+        )
+        .unwrap();
+        let mut paths = Vec::new();
+        collect_paths(tree.root(), Transform::from_scale(2_f32, 2_f32), &mut paths);
+        let bounds: Vec<(f32, f32, f32, f32)> = paths
+            .iter()
+            .map(|(data, _)| {
+                let bounds = data.bounds();
+                (bounds.left(), bounds.top(), bounds.right(), bounds.bottom())
+            })
+            .collect();
+        assert_eq!(
+            bounds,
+            vec![
+                (20_f32, 40_f32, 80_f32, 120_f32),
+                (20_f32, 40_f32, 40_f32, 60_f32)
+            ]
+        );
+        let rules: Vec<FillRule> = paths.iter().map(|(_, rule)| *rule).collect();
+        assert_eq!(rules, vec![FillRule::NonZero, FillRule::EvenOdd]);
+    }
+
+    #[test]
+    fn colors_are_scaled_to_fractions() {
+        assert_eq!(
+            rgb(&usvg::Color::new_rgb(255, 0, 51)),
+            (1_f32, 0_f32, 0.2_f32)
+        );
+    }
+}
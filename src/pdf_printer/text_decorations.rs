@@ -0,0 +1,550 @@
+use crate::idml_parser::styles::commom_text_properties::CommonTextPropertiesAttributes;
+use crate::idml_parser::IDMLResources;
+use crate::pdf_printer::color_manager::Color;
+use crate::pdf_printer::font_manager::FontMetrics;
+use crate::pdf_printer::pdf_utils::*;
+use libharu_sys::*;
+
+/// IDML uses this value for weights and offsets that should be derived from the font
+const AUTO_VALUE: f64 = -9999_f64;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DecorationKind {
+    Underline,
+    StrikeThrough,
+}
+
+/// The stroke types InDesign ships with. Custom stroke styles fall back to `Solid`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DecorationStroke {
+    Solid,
+    Dashed,
+    Dotted,
+    ThinThin,
+    ThickThin,
+    ThinThick,
+    ThickThick,
+    Triple,
+    Wavy,
+}
+
+impl DecorationStroke {
+    /// Maps a stroke style id such as `StrokeStyle/$ID/Dashed` to a stroke type
+    pub fn from_id(id: &str) -> DecorationStroke {
+        match id
+            .trim_start_matches("StrokeStyle/")
+            .trim_start_matches("$ID/")
+        {
+            "Dashed" | "Canned Dashed 3x2" | "Canned Dashed 4x4" => DecorationStroke::Dashed,
+            "Dotted" | "Canned Dotted" | "Japanese Dots" => DecorationStroke::Dotted,
+            "ThinThin" => DecorationStroke::ThinThin,
+            "ThickThin" => DecorationStroke::ThickThin,
+            "ThinThick" => DecorationStroke::ThinThick,
+            "ThickThick" => DecorationStroke::ThickThick,
+            "Triple_Stroke" | "ThinThickThin" | "ThickThinThick" => DecorationStroke::Triple,
+            "Wavy" => DecorationStroke::Wavy,
+            _ => DecorationStroke::Solid,
+        }
+    }
+
+    /// Bands of the stroke as (start, end) fractions of the total weight, from the top
+    fn bands(&self) -> Vec<(f64, f64)> {
+        match self {
+            DecorationStroke::ThinThin => vec![(0_f64, 1_f64 / 3_f64), (2_f64 / 3_f64, 1_f64)],
+            DecorationStroke::ThickThin => vec![(0_f64, 0.5_f64), (0.75_f64, 1_f64)],
+            DecorationStroke::ThinThick => vec![(0_f64, 0.25_f64), (0.5_f64, 1_f64)],
+            DecorationStroke::ThickThick => vec![(0_f64, 0.4_f64), (0.6_f64, 1_f64)],
+            DecorationStroke::Triple => {
+                vec![(0_f64, 0.2_f64), (0.4_f64, 0.6_f64), (0.8_f64, 1_f64)]
+            }
+            _ => vec![(0_f64, 1_f64)],
+        }
+    }
+}
+
+/// Underline or strikethrough settings as they cascade through paragraph,
+/// character and local formatting. Unset values are `None`.
+#[derive(Debug, Clone, Default)]
+pub struct DecorationStyle {
+    enabled: Option<bool>,
+    weight: Option<f64>,
+    offset: Option<f64>,
+    tint: Option<f64>,
+    color: Option<String>,
+    gap_color: Option<String>,
+    gap_tint: Option<f64>,
+    stroke_type: Option<String>,
+}
+
+impl DecorationStyle {
+    pub fn underline_from(
+        attributes: &impl CommonTextPropertiesAttributes,
+        color: Option<String>,
+        gap_color: Option<String>,
+        stroke_type: Option<String>,
+    ) -> DecorationStyle {
+        DecorationStyle {
+            enabled: attributes.underline(),
+            weight: attributes.underline_weight(),
+            offset: attributes.underline_offset(),
+            tint: attributes.underline_tint(),
+            color,
+            gap_color,
+            gap_tint: attributes.underline_gap_tint(),
+            stroke_type,
+        }
+    }
+
+    pub fn strike_through_from(
+        attributes: &impl CommonTextPropertiesAttributes,
+        color: Option<String>,
+        gap_color: Option<String>,
+        stroke_type: Option<String>,
+    ) -> DecorationStyle {
+        DecorationStyle {
+            enabled: attributes.strike_thru(),
+            weight: attributes.strike_through_weight(),
+            offset: attributes.strike_through_offset(),
+            tint: attributes.strike_through_tint(),
+            color,
+            gap_color,
+            gap_tint: attributes.strike_through_gap_tint(),
+            stroke_type,
+        }
+    }
+
+    /// Overrides the current values with the ones set in `other`
+    pub fn merge(&mut self, other: DecorationStyle) {
+        self.enabled = other.enabled.or(self.enabled);
+        self.weight = other.weight.or(self.weight);
+        self.offset = other.offset.or(self.offset);
+        self.tint = other.tint.or(self.tint);
+        self.color = other.color.or(self.color.take());
+        self.gap_color = other.gap_color.or(self.gap_color.take());
+        self.gap_tint = other.gap_tint.or(self.gap_tint);
+        self.stroke_type = other.stroke_type.or(self.stroke_type.take());
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.unwrap_or(false)
+    }
+
+    /// Resolves the style into a decoration that can be placed under or through a run of text
+    pub fn resolve(
+        &self,
+        kind: DecorationKind,
+        idml_resources: &IDMLResources,
+        text_color: Option<Color>,
+        metrics: &FontMetrics,
+        font_size: f64,
+    ) -> Option<Decoration> {
+        let em = font_size / 1000_f64;
+        let (auto_weight, auto_offset) = match kind {
+            DecorationKind::Underline => (
+                metrics.underline_thickness * em,
+                -metrics.underline_position * em,
+            ),
+            DecorationKind::StrikeThrough => (
+                metrics.strikeout_thickness * em,
+                metrics.strikeout_position * em,
+            ),
+        };
+        let weight = match self.weight {
+            Some(w) if w != AUTO_VALUE && w >= 0_f64 => w,
+            _ => auto_weight,
+        };
+        let offset = match self.offset {
+            Some(o) if o != AUTO_VALUE => o,
+            _ => auto_offset,
+        };
+
+        let color = match self.color.as_deref() {
+            None | Some("Text Color") => text_color?,
+            Some(id) => idml_resources.color_from_id(&id.to_string()).ok()?,
+        };
+        let gap_color = match self.gap_color.as_deref() {
+            Some(id) => idml_resources.color_from_id(&id.to_string()).ok(),
+            None => None,
+        };
+
+        Some(Decoration {
+            kind,
+            weight,
+            offset,
+            color: color.with_tint(self.tint.unwrap_or(-1_f64)),
+            gap_color: gap_color.map(|c| c.with_tint(self.gap_tint.unwrap_or(-1_f64))),
+            stroke: self
+                .stroke_type
+                .as_deref()
+                .map(DecorationStroke::from_id)
+                .unwrap_or(DecorationStroke::Solid),
+            x_start: 0_f64,
+            x_end: 0_f64,
+            baseline: 0_f64,
+            vertical: false,
+        })
+    }
+}
+
+/// A single underline or strikethrough segment, in page coordinates
+#[derive(Debug, Clone)]
+pub struct Decoration {
+    kind: DecorationKind,
+    weight: f64,
+    offset: f64,
+    color: Color,
+    gap_color: Option<Color>,
+    stroke: DecorationStroke,
+    /// Start and end along the line, which are y coordinates in vertical text
+    x_start: f64,
+    x_end: f64,
+    /// Baseline, or the x coordinate of the center of the column in vertical text
+    baseline: f64,
+    vertical: bool,
+}
+
+impl Decoration {
+    /// Places the decoration along a run of text
+    pub fn spanning(mut self, x_start: f64, x_end: f64, baseline: f64) -> Decoration {
+        self.x_start = x_start;
+        self.x_end = x_end;
+        self.baseline = baseline;
+        self
+    }
+
+    /// A rule across the column whose top edge is at `top`, like the one above footnotes
+    pub fn rule(
+        stroke: DecorationStroke,
+        weight: f64,
+        color: Color,
+        gap_color: Option<Color>,
+        x_start: f64,
+        x_end: f64,
+        top: f64,
+    ) -> Decoration {
+        Decoration {
+            kind: DecorationKind::Underline,
+            weight,
+            offset: 0_f64,
+            color,
+            gap_color,
+            stroke,
+            x_start,
+            x_end,
+            baseline: top,
+            vertical: false,
+        }
+    }
+
+    /// Turns the decoration to run down a column of vertical text. It is drawn as if
+    /// the column were a line of horizontal text turned a quarter clockwise, so
+    /// underlines run along the left of the column and strikethroughs through it.
+    pub fn vertical(mut self) -> Decoration {
+        self.vertical = true;
+        self
+    }
+
+    /// The offset is measured from the baseline to the top of the line,
+    /// downwards for underlines and upwards for strikethroughs
+    fn top(&self) -> f64 {
+        match self.kind {
+            DecorationKind::Underline => self.baseline - self.offset,
+            DecorationKind::StrikeThrough => self.baseline + self.offset,
+        }
+    }
+
+    /// Draws the decoration. Must be called outside of a text object.
+    pub fn render(&self, current_page: HPDF_Page) {
+        let (x1, x2) = match self.vertical {
+            true => (-self.x_start as f32, -self.x_end as f32),
+            false => (self.x_start as f32, self.x_end as f32),
+        };
+        let top = self.top() as f32;
+        let weight = self.weight as f32;
+        let middle = top - weight / 2_f32;
+
+        unsafe {
+            HPDF_Page_GSave(current_page);
+            if self.vertical {
+                HPDF_Page_Concat(current_page, 0_f32, -1_f32, 1_f32, 0_f32, 0_f32, 0_f32);
+            }
+
+            // The gap color fills the space the stroke itself leaves open
+            if let Some(gap_color) = &self.gap_color {
+                if self.stroke != DecorationStroke::Solid {
+                    set_fill_color(current_page, gap_color);
+                    HPDF_Page_Rectangle(current_page, x1, top - weight, x2 - x1, weight);
+                    HPDF_Page_Fill(current_page);
+                }
+            }
+
+            set_fill_color(current_page, &self.color);
+            set_stroke_color(current_page, &self.color);
+
+            match self.stroke {
+                DecorationStroke::Dashed | DecorationStroke::Dotted => {
+                    let dash: [HPDF_UINT16; 2] = match self.stroke {
+                        DecorationStroke::Dashed => [
+                            (weight * 4_f32).round().max(1_f32) as HPDF_UINT16,
+                            (weight * 2_f32).round().max(1_f32) as HPDF_UINT16,
+                        ],
+                        _ => {
+                            HPDF_Page_SetLineCap(current_page, HPDF_LineCap::HPDF_ROUND_END);
+                            [1, (weight * 2_f32).round().max(2_f32) as HPDF_UINT16]
+                        }
+                    };
+                    HPDF_Page_SetLineWidth(current_page, weight);
+                    HPDF_Page_SetDash(current_page, dash.as_ptr(), 2, 0);
+                    HPDF_Page_MoveTo(current_page, x1, middle);
+                    HPDF_Page_LineTo(current_page, x2, middle);
+                    HPDF_Page_Stroke(current_page);
+                }
+                DecorationStroke::Wavy => {
+                    // Half a wave per two stroke weights, with an amplitude of one weight
+                    let amplitude = weight;
+                    let half_wave = (weight * 2_f32).max(1_f32);
+                    HPDF_Page_SetLineWidth(current_page, weight / 2_f32);
+                    HPDF_Page_MoveTo(current_page, x1, middle);
+                    let mut x = x1;
+                    let mut direction = 1_f32;
+                    while x < x2 {
+                        let next = (x + half_wave).min(x2);
+                        let peak = middle + direction * amplitude;
+                        HPDF_Page_CurveTo(
+                            current_page,
+                            x + (next - x) / 3_f32,
+                            peak,
+                            x + (next - x) * 2_f32 / 3_f32,
+                            peak,
+                            next,
+                            middle,
+                        );
+                        x = next;
+                        direction = -direction;
+                    }
+                    HPDF_Page_Stroke(current_page);
+                }
+                _ => {
+                    for (start, end) in self.stroke.bands() {
+                        let band_top = top - weight * start as f32;
+                        let band_height = weight * (end - start) as f32;
+                        HPDF_Page_Rectangle(
+                            current_page,
+                            x1,
+                            band_top - band_height,
+                            x2 - x1,
+                            band_height,
+                        );
+                    }
+                    HPDF_Page_Fill(current_page);
+                }
+            }
+
+            HPDF_Page_GRestore(current_page);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::idml_parser::story_parser::CharacterStyleRange;
+    use quick_xml::de::from_str;
+
+    fn resources() -> IDMLResources {
+        let xml = r#"<idPkg:Graphic xmlns:idPkg="http://ns.adobe.com/AdobeInDesign/idml/1.0/packaging" DOMVersion="16.0">
+            <Color Self="Color/Black" Model="Process" Space="CMYK" ColorValue="0 0 0 100" Name="Black"/>
+            <Color Self="Color/Red" Model="Process" Space="CMYK" ColorValue="0 100 100 0" Name="Red"/>
+            <Swatch Self="Swatch/None" Name="None"/>
+        </idPkg:Graphic>"#;
+        IDMLResources::with_graphic(from_str(xml).unwrap())
+    }
+
+    fn metrics() -> FontMetrics {
+        FontMetrics {
+            underline_position: -100_f64,
+            underline_thickness: 50_f64,
+            strikeout_position: 300_f64,
+            strikeout_thickness: 40_f64,
+        }
+    }
+
+    fn underline(attributes: &str) -> DecorationStyle {
+        let range =
+            from_str::<CharacterStyleRange>(&format!("<CharacterStyleRange {}/>", attributes))
+                .unwrap();
+        DecorationStyle::underline_from(&range, None, None, None)
+    }
+
+    fn color(resources: &IDMLResources, id: &str) -> String {
+        format!("{:?}", resources.color_from_id(&id.to_string()).unwrap())
+    }
+
+    #[test]
+    fn stroke_styles_are_found_by_their_id() {
+        assert_eq!(
+            DecorationStroke::from_id("StrokeStyle/$ID/Dashed"),
+            DecorationStroke::Dashed
+        );
+        assert_eq!(
+            DecorationStroke::from_id("StrokeStyle/$ID/Canned Dotted"),
+            DecorationStroke::Dotted
+        );
+        assert_eq!(
+            DecorationStroke::from_id("StrokeStyle/$ID/ThickThin"),
+            DecorationStroke::ThickThin
+        );
+        assert_eq!(
+            DecorationStroke::from_id("StrokeStyle/$ID/ThinThickThin"),
+            DecorationStroke::Triple
+        );
+        assert_eq!(
+            DecorationStroke::from_id("StrokeStyle/$ID/Wavy"),
+            DecorationStroke::Wavy
+        );
+        // Custom stroke styles are drawn solid
+        assert_eq!(
+            DecorationStroke::from_id("StrokeStyle/uabc"),
+            DecorationStroke::Solid
+        );
+    }
+
+    #[test]
+    fn stroke_bands_go_down_the_weight_with_gaps_between_them() {
+        assert_eq!(DecorationStroke::Solid.bands(), vec![(0_f64, 1_f64)]);
+        assert_eq!(
+            DecorationStroke::ThinThick.bands(),
+            vec![(0_f64, 0.25_f64), (0.5_f64, 1_f64)]
+        );
+        for stroke in &[
+            DecorationStroke::ThinThin,
+            DecorationStroke::ThickThin,
+            DecorationStroke::ThinThick,
+            DecorationStroke::ThickThick,
+            DecorationStroke::Triple,
+        ] {
+            let bands = stroke.bands();
+            assert!(bands.len() > 1);
+            assert_eq!(bands.first().unwrap().0, 0_f64);
+            assert_eq!(bands.last().unwrap().1, 1_f64);
+            for (band, next) in bands.iter().zip(bands.iter().skip(1)) {
+                assert!(band.0 < band.1 && band.1 < next.0);
+            }
+        }
+    }
+
+    #[test]
+    fn merged_formatting_overrides_only_what_it_sets() {
+        let mut style = underline(r#"Underline="true" UnderlineWeight="2" UnderlineTint="50""#);
+        style.merge(underline(r#"UnderlineOffset="3""#));
+        assert!(style.is_enabled());
+        assert_eq!(style.weight, Some(2_f64));
+        assert_eq!(style.offset, Some(3_f64));
+        assert_eq!(style.tint, Some(50_f64));
+
+        style.merge(DecorationStyle {
+            color: Some("Color/Red".to_string()),
+            ..DecorationStyle::default()
+        });
+        assert_eq!(style.color.as_deref(), Some("Color/Red"));
+        assert_eq!(style.weight, Some(2_f64));
+
+        style.merge(underline(r#"Underline="false""#));
+        assert!(!style.is_enabled());
+        assert!(!DecorationStyle::default().is_enabled());
+    }
+
+    #[test]
+    fn auto_weights_and_offsets_come_from_the_font() {
+        let resources = resources();
+        let black = resources.color_from_id(&"Color/Black".to_string()).ok();
+        let style =
+            underline(r#"Underline="true" UnderlineWeight="-9999" UnderlineOffset="-9999""#);
+        let decoration = style
+            .resolve(
+                DecorationKind::Underline,
+                &resources,
+                black.clone(),
+                &metrics(),
+                10_f64,
+            )
+            .unwrap();
+        assert_eq!((decoration.weight, decoration.offset), (0.5_f64, 1_f64));
+
+        let decoration = DecorationStyle::default()
+            .resolve(
+                DecorationKind::StrikeThrough,
+                &resources,
+                black.clone(),
+                &metrics(),
+                10_f64,
+            )
+            .unwrap();
+        assert_eq!((decoration.weight, decoration.offset), (0.4_f64, 3_f64));
+
+        // Set values are used as they are, measured from the baseline
+        let decoration = underline(r#"UnderlineWeight="2" UnderlineOffset="4""#)
+            .resolve(
+                DecorationKind::Underline,
+                &resources,
+                black,
+                &metrics(),
+                10_f64,
+            )
+            .unwrap()
+            .spanning(0_f64, 100_f64, 50_f64);
+        assert_eq!(decoration.top(), 46_f64);
+    }
+
+    #[test]
+    fn decorations_take_the_text_color_unless_they_have_their_own() {
+        let resources = resources();
+        let black = resources.color_from_id(&"Color/Black".to_string()).ok();
+        let resolve = |style: &DecorationStyle, text_color: Option<Color>| {
+            style.resolve(
+                DecorationKind::Underline,
+                &resources,
+                text_color,
+                &metrics(),
+                10_f64,
+            )
+        };
+
+        let decoration = resolve(&DecorationStyle::default(), black.clone()).unwrap();
+        assert_eq!(
+            format!("{:?}", decoration.color),
+            color(&resources, "Color/Black")
+        );
+        let text_color = DecorationStyle {
+            color: Some("Text Color".to_string()),
+            ..DecorationStyle::default()
+        };
+        assert!(resolve(&text_color, black.clone()).is_some());
+        // Text without a color has no decoration in the text color
+        assert!(resolve(&text_color, None).is_none());
+
+        let red = DecorationStyle {
+            color: Some("Color/Red".to_string()),
+            tint: Some(50_f64),
+            gap_color: Some("Color/Black".to_string()),
+            stroke_type: Some("StrokeStyle/$ID/Dashed".to_string()),
+            ..DecorationStyle::default()
+        };
+        let decoration = resolve(&red, None).unwrap();
+        assert_eq!(
+            format!("{:?}", decoration.color),
+            format!(
+                "{:?}",
+                resources
+                    .color_from_id(&"Color/Red".to_string())
+                    .unwrap()
+                    .with_tint(50_f64)
+            )
+        );
+        assert_eq!(
+            format!("{:?}", decoration.gap_color.unwrap()),
+            color(&resources, "Color/Black")
+        );
+        assert_eq!(decoration.stroke, DecorationStroke::Dashed);
+    }
+}
@@ -0,0 +1,1476 @@
+use crate::idml_parser::styles::commom_text_properties::{
+    Capitalization, CharacterDirection, CommonTextPropertiesAttributes, DigitsType, OTFFigureStyle,
+    ParagraphDirection, Position,
+};
+use crate::pdf_printer::cid_fonts::CidFont;
+use crate::pdf_printer::cjk_layout;
+use libharu_sys::*;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::ffi::CString;
+use std::os::raw::c_char;
+use std::rc::Rc;
+use ttf_parser::gdef::GlyphClass;
+use ttf_parser::gsub::{SingleSubstitution, SubstitutionSubtable};
+use ttf_parser::{Face, GlyphId, Tag};
+use unicode_bidi::{BidiInfo, Level};
+
+/// Range of character spacing libharu accepts
+const MIN_CHAR_SPACE: f64 = -30_f64;
+const MAX_CHAR_SPACE: f64 = 300_f64;
+
+/// Top of the ideographic em box above the baseline, as a fraction of the
+/// font size. Vertical text is centered on the em box.
+pub const EM_BOX_ASCENT: f64 = 0.88_f64;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum KerningMethod {
+    None,
+    Metrics,
+    Optical,
+}
+
+impl KerningMethod {
+    /// Maps a kerning method such as `$ID/Metrics` to a kerning method
+    pub fn from_id(id: &str) -> KerningMethod {
+        match id.trim_start_matches("$ID/") {
+            "Metrics" | "Metrics - Roman Only" => KerningMethod::Metrics,
+            "Optical" => KerningMethod::Optical,
+            _ => KerningMethod::None,
+        }
+    }
+}
+
+/// OpenType features set on a text run. Unset features keep the font's defaults.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct OpenTypeFeatures {
+    ligatures: Option<bool>,
+    contextual_alternates: Option<bool>,
+    discretionary_ligatures: Option<bool>,
+    fractions: Option<bool>,
+    ordinals: Option<bool>,
+    swash: Option<bool>,
+    titling: Option<bool>,
+    stylistic_alternates: Option<bool>,
+    stylistic_sets: Option<i32>,
+    historical: Option<bool>,
+    slashed_zero: Option<bool>,
+    figure_style: Option<OTFFigureStyle>,
+    locale: Option<bool>,
+    marks: Option<bool>,
+    justification_alternates: Option<bool>,
+    roman_italics: Option<bool>,
+    proportional_metrics: Option<bool>,
+    kana: Option<bool>,
+}
+
+impl OpenTypeFeatures {
+    pub fn from_attributes(attributes: &impl CommonTextPropertiesAttributes) -> OpenTypeFeatures {
+        OpenTypeFeatures {
+            ligatures: attributes.ligatures(),
+            contextual_alternates: attributes.otf_contextual_alternate(),
+            discretionary_ligatures: attributes.otf_discretionary_ligature(),
+            fractions: attributes.otf_fraction(),
+            ordinals: attributes.otf_ordinal(),
+            swash: attributes.otf_swash(),
+            titling: attributes.otf_titling(),
+            stylistic_alternates: attributes.otf_stylistic_alternate(),
+            stylistic_sets: attributes.otf_stylistic_sets(),
+            historical: attributes.otf_historical(),
+            slashed_zero: attributes.otf_slashed_zero(),
+            figure_style: attributes.otf_figure_style(),
+            locale: attributes.otf_locale(),
+            marks: attributes.otf_mark(),
+            justification_alternates: attributes.otf_justification_alternate(),
+            roman_italics: attributes.otf_roman_italics(),
+            proportional_metrics: attributes.otf_proportional_metrics(),
+            kana: attributes.otf_hv_kana(),
+        }
+    }
+
+    /// Overrides the current values with the ones set in `other`
+    pub fn merge(&mut self, other: OpenTypeFeatures) {
+        self.ligatures = other.ligatures.or(self.ligatures);
+        self.contextual_alternates = other.contextual_alternates.or(self.contextual_alternates);
+        self.discretionary_ligatures = other
+            .discretionary_ligatures
+            .or(self.discretionary_ligatures);
+        self.fractions = other.fractions.or(self.fractions);
+        self.ordinals = other.ordinals.or(self.ordinals);
+        self.swash = other.swash.or(self.swash);
+        self.titling = other.titling.or(self.titling);
+        self.stylistic_alternates = other.stylistic_alternates.or(self.stylistic_alternates);
+        self.stylistic_sets = other.stylistic_sets.or(self.stylistic_sets);
+        self.historical = other.historical.or(self.historical);
+        self.slashed_zero = other.slashed_zero.or(self.slashed_zero);
+        self.figure_style = other.figure_style.or(self.figure_style);
+        self.locale = other.locale.or(self.locale);
+        self.marks = other.marks.or(self.marks);
+        self.justification_alternates = other
+            .justification_alternates
+            .or(self.justification_alternates);
+        self.roman_italics = other.roman_italics.or(self.roman_italics);
+        self.proportional_metrics = other.proportional_metrics.or(self.proportional_metrics);
+        self.kana = other.kana.or(self.kana);
+    }
+
+    /// The features to shape with. Kerning is only applied for the `Metrics` kerning method.
+    fn features(&self, kerning: bool) -> Vec<rustybuzz::Feature> {
+        let feature = |tag: &[u8], enabled: bool| {
+            rustybuzz::Feature::new(Tag::from_bytes_lossy(tag), enabled as u32, ..)
+        };
+        let toggles = [
+            (&b"liga"[..], self.ligatures),
+            (b"clig", self.ligatures),
+            (b"calt", self.contextual_alternates),
+            (b"dlig", self.discretionary_ligatures),
+            (b"frac", self.fractions),
+            (b"ordn", self.ordinals),
+            (b"swsh", self.swash),
+            (b"titl", self.titling),
+            (b"salt", self.stylistic_alternates),
+            (b"hist", self.historical),
+            (b"zero", self.slashed_zero),
+            (b"locl", self.locale),
+            (b"mark", self.marks),
+            (b"mkmk", self.marks),
+            (b"jalt", self.justification_alternates),
+            (b"ital", self.roman_italics),
+            (b"palt", self.proportional_metrics),
+            (b"hkna", self.kana),
+        ];
+
+        let mut features = vec![feature(b"kern", kerning)];
+        features.extend(
+            toggles
+                .iter()
+                .filter_map(|(tag, enabled)| enabled.map(|enabled| feature(tag, enabled))),
+        );
+
+        // Stylistic sets are stored as a bit field, bit 0 being `ss01`
+        if let Some(sets) = self.stylistic_sets {
+            for set in 1..=20 {
+                let tag = format!("ss{:02}", set);
+                features.push(feature(tag.as_bytes(), sets & (1 << (set - 1)) != 0));
+            }
+        }
+
+        let figures: &[&[u8]] = match self.figure_style {
+            Some(OTFFigureStyle::TabularOldstyle) => &[b"tnum", b"onum"],
+            Some(OTFFigureStyle::TabularLining) => &[b"tnum", b"lnum"],
+            Some(OTFFigureStyle::ProportionalOldstyle) => &[b"pnum", b"onum"],
+            Some(OTFFigureStyle::ProportionalLining) => &[b"pnum", b"lnum"],
+            _ => &[],
+        };
+        features.extend(figures.iter().map(|tag| feature(tag, true)));
+        features
+    }
+}
+
+/// Character level transforms applied to every glyph of a text run
+#[derive(Debug, Clone)]
+pub struct GlyphTransforms {
+    pub font_size: f64,
+    /// Extra space after every glyph, in 1/1000 em
+    pub tracking: f64,
+    pub kerning_method: KerningMethod,
+    /// Manual kerning after every glyph, in 1/1000 em
+    pub kerning_value: f64,
+    /// Percentage of the glyph width
+    pub horizontal_scale: f64,
+    /// Percentage of the glyph height
+    pub vertical_scale: f64,
+    /// Slant in degrees
+    pub skew: f64,
+    /// Vertical offset from the baseline in points
+    pub baseline_shift: f64,
+    pub capitalization: Capitalization,
+    pub position: Position,
+    /// Size of synthesized small caps as a percentage of the font size
+    pub small_cap_size: f64,
+    /// Size and offset of synthesized superscripts as percentages of the font size
+    pub superscript: (f64, f64),
+    /// Size and offset of synthesized subscripts as percentages of the font size
+    pub subscript: (f64, f64),
+    pub opentype: OpenTypeFeatures,
+    pub paragraph_direction: ParagraphDirection,
+    /// Overrides the direction resolved from the characters themselves
+    pub character_direction: CharacterDirection,
+    pub digits_type: DigitsType,
+    /// Extra offset of diacritics from where the font positions them, in points
+    pub diacritic_offset: (f64, f64),
+    /// Set in columns from top to bottom, with CJK characters upright
+    pub vertical: bool,
+    /// Draw single byte characters upright in vertical text
+    pub rotate_single_byte: bool,
+    /// Let punctuation hang past the end of the line
+    pub kinsoku_hang: bool,
+    /// Space before and after the run, in em
+    pub leading_aki: f64,
+    pub trailing_aki: f64,
+    /// Fraction of the side bearings removed from every glyph
+    pub tsume: f64,
+    /// Number of em the run is spread or squeezed into, 0 when unset
+    pub jidori: f64,
+    /// Magnification in percent and angle in degrees of the shatai slant
+    pub shatai: (f64, f64),
+}
+
+impl GlyphTransforms {
+    fn em(&self) -> f64 {
+        self.font_size / 1000_f64
+    }
+
+    fn x_scale(&self) -> f64 {
+        self.horizontal_scale / 100_f64
+    }
+
+    fn y_scale(&self) -> f64 {
+        self.vertical_scale / 100_f64
+    }
+
+    pub fn paragraph_level(&self) -> u8 {
+        match self.paragraph_direction {
+            ParagraphDirection::LeftToRightDirection => 0,
+            ParagraphDirection::RightToLeftDirection => 1,
+        }
+    }
+}
+
+/// Maps characters to the codes of a PDF font's encoding. Unicode encoded
+/// fonts take UTF-8, CJK fonts one or two bytes per character and other
+/// fonts a single byte per character. Shaped text of TrueType fonts is
+/// drawn by glyph id instead.
+#[derive(Debug, Clone, Default)]
+pub struct FontEncoding {
+    codes: HashMap<char, Vec<u8>>,
+    unicode: bool,
+    cid_font: Option<Rc<RefCell<CidFont>>>,
+}
+
+impl FontEncoding {
+    pub fn from_encoder(encoder: HPDF_Encoder) -> FontEncoding {
+        if !encoder.is_null() {
+            if let HPDF_EncoderType::HPDF_ENCODER_TYPE_DOUBLE_BYTE =
+                unsafe { HPDF_Encoder_GetType(encoder) }
+            {
+                return FontEncoding::from_cmap_encoder(encoder);
+            }
+        }
+        let codes = (1..=255_u8)
+            .filter_map(|code| {
+                let unicode = match encoder.is_null() {
+                    true if code.is_ascii() => code as u32,
+                    true => return None,
+                    false => unsafe {
+                        HPDF_Encoder_GetUnicode(encoder, code as HPDF_UINT16) as u32
+                    },
+                };
+                match std::char::from_u32(unicode) {
+                    Some(c) if unicode != 0 => Some((c, vec![code])),
+                    _ => None,
+                }
+            })
+            .collect();
+        FontEncoding {
+            codes,
+            ..FontEncoding::default()
+        }
+    }
+
+    /// Reads the codes of a CMap encoder, as used by the built-in CJK fonts
+    fn from_cmap_encoder(encoder: HPDF_Encoder) -> FontEncoding {
+        // libharu maps codes it does not know to a white square
+        const UNMAPPED: u16 = 0x25A1;
+        let mut codes = HashMap::new();
+        unsafe {
+            for lead in 0x20..=0xFE_u8 {
+                let text = [lead, 0x40, 0];
+                match HPDF_Encoder_GetByteType(encoder, text.as_ptr() as *const c_char, 0) {
+                    HPDF_ByteType::HPDF_BYTE_TYPE_SINGLE => {
+                        let unicode = HPDF_Encoder_GetUnicode(encoder, lead as HPDF_UINT16);
+                        if let Some(c) = std::char::from_u32(unicode as u32) {
+                            codes.entry(c).or_insert_with(|| vec![lead]);
+                        }
+                    }
+                    HPDF_ByteType::HPDF_BYTE_TYPE_LEAD => {
+                        for trail in 0x40..=0xFE_u8 {
+                            let code = (lead as u16) << 8 | trail as u16;
+                            let unicode = HPDF_Encoder_GetUnicode(encoder, code);
+                            if unicode == 0 || unicode == UNMAPPED {
+                                continue;
+                            }
+                            if let Some(c) = std::char::from_u32(unicode as u32) {
+                                codes.entry(c).or_insert_with(|| vec![lead, trail]);
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+        FontEncoding {
+            codes,
+            ..FontEncoding::default()
+        }
+    }
+
+    pub fn unicode() -> FontEncoding {
+        FontEncoding {
+            unicode: true,
+            ..FontEncoding::default()
+        }
+    }
+
+    /// Unicode encoding of a font whose shaped glyphs are drawn by glyph id
+    pub fn glyph_ids(cid_font: Rc<RefCell<CidFont>>) -> FontEncoding {
+        FontEncoding {
+            cid_font: Some(cid_font),
+            ..FontEncoding::unicode()
+        }
+    }
+
+    /// Text drawing the glyph with id `glyph`, shaped from `chars`, if the
+    /// font draws glyphs by glyph id
+    pub fn encode_glyph(&self, glyph: u16, chars: &str) -> Option<Vec<u8>> {
+        self.cid_font
+            .as_ref()?
+            .borrow_mut()
+            .encode_glyph(glyph, chars)
+    }
+
+    pub fn draws_glyph_ids(&self) -> bool {
+        self.cid_font.is_some()
+    }
+
+    /// libharu's UTF-8 encoder only covers the Basic Multilingual Plane
+    pub fn can_encode(&self, c: char) -> bool {
+        match self.unicode {
+            true => c != '\0' && (c as u32) <= 0xFFFF,
+            false => self.codes.contains_key(&c),
+        }
+    }
+
+    /// Encodes `text`, replacing characters the encoding lacks with `?`
+    pub fn encode(&self, text: &str) -> Vec<u8> {
+        let mut encoded = vec![];
+        for c in text.chars() {
+            match (self.unicode, self.can_encode(c)) {
+                (true, true) => encoded.extend(c.to_string().bytes()),
+                (false, true) => encoded.extend(&self.codes[&c]),
+                (_, false) => encoded.push(b'?'),
+            }
+        }
+        encoded
+    }
+}
+
+/// A character of a text run with its final spacing, in points
+#[derive(Debug, Clone)]
+pub struct PositionedGlyph {
+    /// Text in the encoding of the PDF font
+    pub text: Vec<u8>,
+    /// Advance including tracking and horizontal scale
+    pub advance: f64,
+    /// Kerning between this glyph and the next
+    pub kerning: f64,
+    pub is_space: bool,
+    /// Size relative to the font size, for synthesized small caps and positions
+    pub scale: f64,
+    /// Offset from the baseline in points
+    pub rise: f64,
+    /// Horizontal offset from the pen position in points, used for marks
+    pub x_offset: f64,
+    /// Bidi embedding level, odd levels are right-to-left
+    pub level: u8,
+    /// Characters the glyph was shaped from, empty for the later glyphs of a cluster
+    pub chars: String,
+    /// Whether the line may break after this glyph
+    pub break_after: bool,
+    /// Whether the glyph may extend past the end of the line
+    pub hangs: bool,
+    /// Drawn upright in vertical text, other glyphs are rotated
+    pub upright: bool,
+    /// Whether a kashida may lengthen the joint between this glyph and the next
+    pub kashida: bool,
+}
+
+impl PositionedGlyph {
+    /// Empty space of `width` along the line
+    pub fn spacer(width: f64) -> PositionedGlyph {
+        PositionedGlyph {
+            text: vec![],
+            advance: width,
+            kerning: 0_f64,
+            is_space: false,
+            scale: 1_f64,
+            rise: 0_f64,
+            x_offset: 0_f64,
+            level: 0,
+            chars: String::new(),
+            break_after: false,
+            hangs: false,
+            upright: false,
+            kashida: false,
+        }
+    }
+}
+
+/// A character after capitalization and position have been applied
+#[derive(Debug, Clone, Copy)]
+struct StyledChar {
+    c: char,
+    scale: f64,
+    rise: f64,
+    level: u8,
+    /// Alternate glyph drawn in place of the glyph of the character
+    glyph: Option<GlyphId>,
+}
+
+impl StyledChar {
+    fn new(c: char) -> StyledChar {
+        StyledChar {
+            c,
+            scale: 1_f64,
+            rise: 0_f64,
+            level: 0,
+            glyph: None,
+        }
+    }
+}
+
+/// Looks up alternate glyphs in the font's GSUB table. Fonts that draw glyphs by
+/// glyph id can draw any alternate, other fonts only the alternates the font maps
+/// to a character. Otherwise `None` is returned and the caller synthesizes the
+/// glyph instead.
+struct FeatureGlyphs<'a> {
+    face: Option<&'a Face<'a>>,
+    by_glyph_id: bool,
+    reverse_cmap: Option<HashMap<GlyphId, char>>,
+}
+
+impl<'a> FeatureGlyphs<'a> {
+    fn new(face: Option<&'a Face<'a>>, by_glyph_id: bool) -> FeatureGlyphs<'a> {
+        FeatureGlyphs {
+            face,
+            by_glyph_id,
+            reverse_cmap: None,
+        }
+    }
+
+    /// Returns `c` drawn with the first of `features` the font supports: the character
+    /// the font maps to the alternate, or `c` with the alternate glyph to draw it with
+    fn substitute(&mut self, c: char, features: &[&[u8; 4]]) -> Option<StyledChar> {
+        let face = self.face?;
+        let glyph = face.glyph_index(c)?;
+        let substitute = features
+            .iter()
+            .find_map(|feature| single_substitution(face, feature, glyph))?;
+
+        match self.char_for_glyph(substitute) {
+            Some(c) => Some(StyledChar::new(c)),
+            None if self.by_glyph_id => Some(StyledChar {
+                glyph: Some(substitute),
+                ..StyledChar::new(c)
+            }),
+            None => None,
+        }
+    }
+
+    /// Returns a character the font maps to `glyph`, if any
+    fn char_for_glyph(&mut self, glyph: GlyphId) -> Option<char> {
+        let face = self.face?;
+        let reverse_cmap = self.reverse_cmap.get_or_insert_with(|| reverse_cmap(face));
+        reverse_cmap.get(&glyph).cloned()
+    }
+}
+
+fn single_substitution(face: &Face, feature: &[u8; 4], glyph: GlyphId) -> Option<GlyphId> {
+    let gsub = face.tables().gsub?;
+    let feature = gsub.features.find(Tag::from_bytes(feature))?;
+    for lookup_index in feature.lookup_indices {
+        let lookup = match gsub.lookups.get(lookup_index) {
+            Some(lookup) => lookup,
+            None => continue,
+        };
+        for subtable in lookup.subtables.into_iter::<SubstitutionSubtable>() {
+            let substitute = match subtable {
+                SubstitutionSubtable::Single(SingleSubstitution::Format1 { coverage, delta }) => {
+                    coverage
+                        .get(glyph)
+                        .map(|_| GlyphId((glyph.0 as i32 + delta as i32) as u16))
+                }
+                SubstitutionSubtable::Single(SingleSubstitution::Format2 {
+                    coverage,
+                    substitutes,
+                }) => coverage.get(glyph).and_then(|index| substitutes.get(index)),
+                _ => None,
+            };
+            if substitute.is_some() {
+                return substitute;
+            }
+        }
+    }
+    None
+}
+
+fn reverse_cmap(face: &Face) -> HashMap<GlyphId, char> {
+    let mut reverse_cmap = HashMap::new();
+    if let Some(cmap) = face.tables().cmap {
+        for subtable in cmap.subtables.into_iter().filter(|s| s.is_unicode()) {
+            subtable.codepoints(|code_point| {
+                if let (Some(glyph), Some(c)) = (
+                    subtable.glyph_index(code_point),
+                    std::char::from_u32(code_point),
+                ) {
+                    reverse_cmap.entry(glyph).or_insert(c);
+                }
+            });
+        }
+    }
+    reverse_cmap
+}
+
+/// Applies capitalization and position. Real small caps and OpenType positions
+/// are used when the font has them, otherwise they are synthesized by scaling
+/// and shifting the regular glyphs.
+fn style_chars(
+    text: &str,
+    levels: &[u8],
+    face: Option<&Face>,
+    by_glyph_id: bool,
+    transforms: &GlyphTransforms,
+) -> Vec<StyledChar> {
+    let mut feature_glyphs = FeatureGlyphs::new(face, by_glyph_id);
+    let small_cap_scale = transforms.small_cap_size / 100_f64;
+    let mut small_cap = |c: char, feature: &[u8; 4]| -> Vec<StyledChar> {
+        match feature_glyphs.substitute(c, &[feature]) {
+            Some(small_cap) => vec![small_cap],
+            None => c
+                .to_uppercase()
+                .map(|upper| StyledChar {
+                    scale: small_cap_scale,
+                    ..StyledChar::new(upper)
+                })
+                .collect(),
+        }
+    };
+
+    // Characters added by capitalization take the level of the character they replace
+    let capitalized: Vec<StyledChar> = text
+        .chars()
+        .enumerate()
+        .flat_map(|(i, c)| {
+            let styled = match transforms.capitalization {
+                Capitalization::AllCaps => c.to_uppercase().map(StyledChar::new).collect(),
+                Capitalization::SmallCaps if c.is_lowercase() => small_cap(c, b"smcp"),
+                Capitalization::CapToSmallCap if c.is_lowercase() => small_cap(c, b"smcp"),
+                Capitalization::CapToSmallCap if c.is_uppercase() => small_cap(c, b"c2sc"),
+                _ => vec![StyledChar::new(c)],
+            };
+            let level = levels.get(i).copied().unwrap_or(0);
+            styled
+                .into_iter()
+                .map(move |styled| StyledChar { level, ..styled })
+        })
+        .collect();
+
+    let font_size = transforms.font_size;
+    let (superscript_size, superscript_position) = transforms.superscript;
+    let (subscript_size, subscript_position) = transforms.subscript;
+    let raised = |styled: StyledChar| StyledChar {
+        scale: styled.scale * superscript_size / 100_f64,
+        rise: styled.rise + font_size * superscript_position / 100_f64,
+        ..styled
+    };
+    let lowered = |styled: StyledChar| StyledChar {
+        scale: styled.scale * subscript_size / 100_f64,
+        rise: styled.rise - font_size * subscript_position / 100_f64,
+        ..styled
+    };
+    let shrunk = |styled: StyledChar| StyledChar {
+        scale: styled.scale * subscript_size / 100_f64,
+        ..styled
+    };
+
+    let mut feature_glyphs = FeatureGlyphs::new(face, by_glyph_id);
+    let mut opentype_position =
+        |styled: StyledChar, features: &[&[u8; 4]], fallback: &dyn Fn(StyledChar) -> StyledChar| {
+            match feature_glyphs.substitute(styled.c, features) {
+                Some(alternate) => StyledChar {
+                    c: alternate.c,
+                    glyph: alternate.glyph,
+                    ..styled
+                },
+                None => fallback(styled),
+            }
+        };
+
+    capitalized
+        .into_iter()
+        .map(|styled| match transforms.position {
+            Position::Superscript => raised(styled),
+            Position::Subscript => lowered(styled),
+            Position::OTSuperscript => opentype_position(styled, &[b"sups"], &raised),
+            Position::OTSubscript => opentype_position(styled, &[b"subs", b"sinf"], &lowered),
+            Position::OTNumerator => opentype_position(styled, &[b"numr"], &raised),
+            Position::OTDenominator => opentype_position(styled, &[b"dnom"], &shrunk),
+            Position::Normal => styled,
+        })
+        .collect()
+}
+
+/// Measures every character of `text` and applies tracking, kerning and scaling.
+/// When the font file is available the text is shaped first, so OpenType
+/// features, GPOS positioning and the contextual forms of complex scripts apply.
+/// `levels` are the bidi levels of the characters of `text` resolved over the
+/// whole paragraph, without them the levels are resolved over `text` alone.
+/// Glyphs are returned in logical order with their bidi level, lines are put in
+/// visual order by `reorder_line`. Shaped glyphs of TrueType fonts are drawn
+/// by glyph id. Other fonts draw them through characters: substituted glyphs
+/// are drawn when the font maps them to a character, otherwise the original
+/// characters are drawn at the shaped position.
+pub fn layout_glyphs(
+    text: &str,
+    pdf_font: HPDF_Font,
+    encoding: &FontEncoding,
+    font_data: Option<&[u8]>,
+    transforms: &GlyphTransforms,
+    levels: Option<&[u8]>,
+) -> Vec<PositionedGlyph> {
+    let face = font_data.and_then(|data| rustybuzz::Face::from_slice(data, 0));
+    let text = substitute_digits(text, transforms.digits_type);
+    let levels = levels.filter(|levels| levels.len() == text.chars().count());
+    let mut chars = style_chars(
+        &text,
+        levels.unwrap_or_default(),
+        face.as_deref(),
+        encoding.draws_glyph_ids(),
+        transforms,
+    );
+    if levels.is_none() {
+        resolve_levels(&mut chars, transforms);
+    }
+    if transforms.vertical {
+        for styled in chars.iter_mut() {
+            if let Some(form) = cjk_layout::vertical_form(styled.c) {
+                let has_form = match &face {
+                    Some(face) => face.glyph_index(form).is_some(),
+                    None => encoding.can_encode(form),
+                };
+                if has_form {
+                    styled.c = form;
+                }
+            }
+        }
+    }
+    let mut glyphs = match &face {
+        Some(face) => shape_chars(&chars, face, pdf_font, encoding, transforms),
+        None => chars
+            .iter()
+            .map(|styled| {
+                let text = encoding.encode(&styled.c.to_string());
+                let mut glyph = measure_text(text, *styled, pdf_font, transforms);
+                glyph.chars = styled.c.to_string();
+                glyph
+            })
+            .collect(),
+    };
+
+    set_line_breaks(&mut glyphs, transforms);
+    set_kashida_points(&mut glyphs);
+    if transforms.vertical {
+        set_vertical_advances(&mut glyphs, transforms);
+    }
+    set_mojikumi_spacing(&mut glyphs, transforms);
+
+    // Manual kerning is applied after every glyph
+    let manual_kerning = transforms.kerning_value * transforms.em();
+    for glyph in glyphs.iter_mut() {
+        glyph.kerning += manual_kerning;
+    }
+
+    // Jidori spreads or squeezes the run to a number of em
+    if transforms.jidori > 0_f64 && !glyphs.is_empty() {
+        let target = transforms.jidori * transforms.font_size;
+        let adjustment = (target - glyphs_width(&glyphs)) / glyphs.len() as f64;
+        for glyph in glyphs.iter_mut() {
+            glyph.kerning += adjustment;
+        }
+    }
+
+    if let Some(last) = glyphs.last_mut() {
+        last.kerning += transforms.trailing_aki * transforms.font_size;
+    }
+    if transforms.leading_aki > 0_f64 && !glyphs.is_empty() {
+        glyphs.insert(
+            0,
+            PositionedGlyph::spacer(transforms.leading_aki * transforms.font_size),
+        );
+    }
+    glyphs
+}
+
+/// Marks where lines may break: after spaces, and around CJK characters where
+/// kinsoku allows it. Kinsoku is applied as "push out": when a line cannot
+/// break at the margin, it breaks at the previous opportunity.
+fn set_line_breaks(glyphs: &mut [PositionedGlyph], transforms: &GlyphTransforms) {
+    for i in 0..glyphs.len() {
+        let next = match glyphs.get(i + 1) {
+            Some(next) => match next.chars.chars().next() {
+                Some(c) => Some(c),
+                // Never break inside a cluster
+                None => continue,
+            },
+            None => None,
+        };
+        let last = glyphs[i].chars.chars().last();
+        glyphs[i].break_after =
+            glyphs[i].is_space || last.is_some_and(|c| cjk_layout::can_break_between(c, next));
+        glyphs[i].hangs = glyphs[i].is_space
+            || (transforms.kinsoku_hang && last.is_some_and(cjk_layout::is_hanging_punctuation));
+    }
+}
+
+/// Marks the joints between Arabic letters where a kashida may be drawn
+/// to lengthen the word when its line is justified
+fn set_kashida_points(glyphs: &mut [PositionedGlyph]) {
+    let clusters: Vec<usize> = (0..glyphs.len())
+        .filter(|&i| !glyphs[i].chars.is_empty())
+        .collect();
+    for pair in clusters.windows(2) {
+        let (this, next) = (pair[0], pair[1]);
+        let joins_next = glyphs[this]
+            .chars
+            .chars()
+            .rev()
+            .find(|&c| !is_arabic_mark(c))
+            .is_some_and(joins_to_next);
+        let joins_previous = glyphs[next]
+            .chars
+            .chars()
+            .next()
+            .is_some_and(joins_to_previous);
+        // The kashida goes after the last glyph of the cluster
+        glyphs[next - 1].kashida = joins_next && joins_previous;
+    }
+}
+
+fn is_arabic_mark(c: char) -> bool {
+    matches!(c, '\u{064B}'..='\u{065F}' | '\u{0670}' | '\u{06D6}'..='\u{06ED}')
+}
+
+/// Whether the Arabic letter joins the letter after it (dual-joining letters and the kashida)
+fn joins_to_next(c: char) -> bool {
+    matches!(c,
+        '\u{0620}'..='\u{064A}'
+        | '\u{066E}'..='\u{066F}'
+        | '\u{0678}'..='\u{0687}'
+        | '\u{069A}'..='\u{06BF}'
+        | '\u{06C1}'..='\u{06C2}'
+        | '\u{06CC}'
+        | '\u{06CE}'
+        | '\u{06D0}'..='\u{06D1}'
+        | '\u{06FA}'..='\u{06FC}'
+        | '\u{06FF}'
+    ) && !joins_only_to_previous(c)
+        && c != '\u{0621}'
+}
+
+/// Whether the Arabic letter joins the letter before it
+fn joins_to_previous(c: char) -> bool {
+    joins_to_next(c) || joins_only_to_previous(c)
+}
+
+/// Right-joining letters, which join the letter before them but not the one after
+fn joins_only_to_previous(c: char) -> bool {
+    matches!(c,
+        '\u{0622}'..='\u{0625}'
+        | '\u{0627}'
+        | '\u{0629}'
+        | '\u{062F}'..='\u{0632}'
+        | '\u{0648}'
+        | '\u{0671}'..='\u{0673}'
+        | '\u{0675}'..='\u{0677}'
+        | '\u{0688}'..='\u{0699}'
+        | '\u{06C0}'
+        | '\u{06C3}'..='\u{06CB}'
+        | '\u{06CD}'
+        | '\u{06CF}'
+        | '\u{06D2}'..='\u{06D3}'
+        | '\u{06D5}'
+        | '\u{06EE}'..='\u{06EF}'
+    )
+}
+
+/// The kashida of the font, which justified lines stretch Arabic words with.
+/// `None` when the font has no kashida.
+pub fn kashida_glyph(
+    pdf_font: HPDF_Font,
+    encoding: &FontEncoding,
+    font_data: Option<&[u8]>,
+    transforms: &GlyphTransforms,
+) -> Option<PositionedGlyph> {
+    let has_kashida = match font_data.and_then(|data| Face::parse(data, 0).ok()) {
+        Some(face) => face.glyph_index('\u{0640}').is_some(),
+        None => encoding.can_encode('\u{0640}'),
+    };
+    if !has_kashida {
+        return None;
+    }
+    let mut transforms = transforms.clone();
+    transforms.leading_aki = 0_f64;
+    transforms.trailing_aki = 0_f64;
+    transforms.jidori = 0_f64;
+    layout_glyphs("\u{0640}", pdf_font, encoding, font_data, &transforms, None)
+        .into_iter()
+        .next()
+        .filter(|kashida| kashida.advance > 0_f64)
+}
+
+/// Turns horizontal advances into advances down a column. Upright glyphs take
+/// up an em and are centered on the column, rotated glyphs keep their width.
+fn set_vertical_advances(glyphs: &mut [PositionedGlyph], transforms: &GlyphTransforms) {
+    let mut upright = false;
+    for glyph in glyphs.iter_mut() {
+        if let Some(c) = glyph.chars.chars().next() {
+            upright = cjk_layout::is_upright(c, transforms.rotate_single_byte);
+        }
+        if !upright {
+            continue;
+        }
+        let tracking = match glyph.text.is_empty() {
+            true => 0_f64,
+            false => transforms.tracking * transforms.em(),
+        };
+        let width = glyph.advance + glyph.kerning - tracking;
+        glyph.upright = true;
+        glyph.x_offset -= width / 2_f64;
+        glyph.advance = match glyph.chars.is_empty() {
+            true => 0_f64,
+            false => transforms.font_size * transforms.y_scale() * glyph.scale + tracking,
+        };
+        glyph.kerning = 0_f64;
+    }
+}
+
+/// Closes up the blank halves of full width punctuation set next to each other.
+/// Punctuation narrower than three quarters of an em has no blank to take out.
+fn set_mojikumi_spacing(glyphs: &mut [PositionedGlyph], transforms: &GlyphTransforms) {
+    let full_width =
+        |glyph: &PositionedGlyph| glyph.advance >= 0.75_f64 * transforms.font_size * glyph.scale;
+    let starts: Vec<usize> = (0..glyphs.len())
+        .filter(|&i| !glyphs[i].chars.is_empty())
+        .collect();
+    for pair in starts.windows(2) {
+        let (before, after) = (&glyphs[pair[0]], &glyphs[pair[1]]);
+        if !full_width(before) || !full_width(after) {
+            continue;
+        }
+        if let (Some(c_before), Some(c_after)) =
+            (before.chars.chars().next(), after.chars.chars().next())
+        {
+            // The blank is taken out after the last glyph of the first character
+            glyphs[pair[1] - 1].kerning -=
+                cjk_layout::mojikumi_reduction(c_before, c_after) * transforms.font_size;
+        }
+    }
+}
+
+/// Width of `text` as drawn by libharu, without any shaping
+fn measure_text(
+    text: Vec<u8>,
+    styled: StyledChar,
+    pdf_font: HPDF_Font,
+    transforms: &GlyphTransforms,
+) -> PositionedGlyph {
+    let width = unsafe {
+        let c_text = CString::new(text.clone()).unwrap_or_default();
+        HPDF_Font_TextWidth(pdf_font, c_text.as_ptr(), text.len() as u32).width as f64
+    };
+    let tracking = match text.is_empty() {
+        true => 0_f64,
+        false => transforms.tracking * transforms.em(),
+    };
+    PositionedGlyph {
+        is_space: text.iter().all(|&code| code == b' ') && !text.is_empty(),
+        text,
+        advance: width * transforms.em() * transforms.x_scale() * styled.scale + tracking,
+        kerning: 0_f64,
+        scale: styled.scale,
+        rise: styled.rise,
+        x_offset: 0_f64,
+        level: styled.level,
+        chars: String::new(),
+        break_after: false,
+        hangs: false,
+        upright: false,
+        kashida: false,
+    }
+}
+
+/// Replaces decimal digits with the digits of the requested script
+fn substitute_digits(text: &str, digits_type: DigitsType) -> String {
+    let zero = match digits_type {
+        DigitsType::DefaultDigits => return text.to_string(),
+        DigitsType::ArabicDigits => '0',
+        DigitsType::HindiDigits => '\u{0660}',
+        DigitsType::FarsiDigits => '\u{06F0}',
+    };
+    text.chars()
+        .map(|c| {
+            let digit = match c {
+                '0'..='9' => c as u32 - '0' as u32,
+                '\u{0660}'..='\u{0669}' => c as u32 - 0x0660,
+                '\u{06F0}'..='\u{06F9}' => c as u32 - 0x06F0,
+                _ => return c,
+            };
+            std::char::from_u32(zero as u32 + digit).unwrap_or(c)
+        })
+        .collect()
+}
+
+/// Resolves the bidi embedding level of every character (UAX #9). An explicit
+/// character direction is applied as an embedding around the whole run.
+fn resolve_levels(chars: &mut [StyledChar], transforms: &GlyphTransforms) {
+    let (prefix, suffix) = match transforms.character_direction {
+        CharacterDirection::DefaultDirection => ("", ""),
+        CharacterDirection::LeftToRightDirection => ("\u{202A}", "\u{202C}"),
+        CharacterDirection::RightToLeftDirection => ("\u{202B}", "\u{202C}"),
+    };
+    let text: String = chars.iter().map(|styled| styled.c).collect();
+    let text = format!("{}{}{}", prefix, text, suffix);
+    let paragraph_level = Level::new(transforms.paragraph_level()).unwrap_or(Level::ltr());
+    let bidi_info = BidiInfo::new(&text, Some(paragraph_level));
+
+    let mut byte_index = prefix.len();
+    for styled in chars.iter_mut() {
+        styled.level = bidi_info
+            .levels
+            .get(byte_index)
+            .map(Level::number)
+            .unwrap_or(transforms.paragraph_level());
+        byte_index += styled.c.len_utf8();
+    }
+}
+
+/// Shapes runs of equally sized characters and turns the shaped glyphs into
+/// drawable text. Fonts drawn by glyph id are drawn with the shaped advances,
+/// for other fonts the difference between the shaped advance and the width
+/// libharu will draw is carried as kerning.
+fn shape_chars(
+    chars: &[StyledChar],
+    face: &rustybuzz::Face,
+    pdf_font: HPDF_Font,
+    encoding: &FontEncoding,
+    transforms: &GlyphTransforms,
+) -> Vec<PositionedGlyph> {
+    let mut glyph_chars = FeatureGlyphs::new(Some(face), false);
+    let features = transforms
+        .opentype
+        .features(transforms.kerning_method == KerningMethod::Metrics);
+    let units_per_em = face.units_per_em() as f64;
+
+    let mut glyphs: Vec<PositionedGlyph> = vec![];
+    let mut glyph_ids: Vec<GlyphId> = vec![];
+    let mut run_start = 0;
+    while run_start < chars.len() {
+        let first = chars[run_start];
+        let run_length = chars[run_start..]
+            .iter()
+            .take_while(|c| {
+                c.scale == first.scale && c.rise == first.rise && c.level == first.level
+            })
+            .count();
+        let run_chars = &chars[run_start..run_start + run_length];
+        let run: String = run_chars.iter().map(|c| c.c).collect();
+        run_start += run_length;
+
+        let is_rtl = first.level % 2 == 1;
+        let mut buffer = rustybuzz::UnicodeBuffer::new();
+        buffer.push_str(&run);
+        buffer.guess_segment_properties();
+        buffer.set_direction(match is_rtl {
+            true => rustybuzz::Direction::RightToLeft,
+            false => rustybuzz::Direction::LeftToRight,
+        });
+        let shaped = rustybuzz::shape(face, &features, buffer);
+        let run_glyphs_start = glyphs.len();
+
+        let infos = shaped.glyph_infos();
+        let positions = shaped.glyph_positions();
+        let to_points = transforms.font_size / units_per_em * transforms.x_scale() * first.scale;
+        for (i, (info, position)) in infos.iter().zip(positions).enumerate() {
+            let cluster_start = info.cluster as usize;
+            let cluster_end = infos
+                .iter()
+                .map(|other| other.cluster as usize)
+                .filter(|&other| other > cluster_start)
+                .min()
+                .unwrap_or(run.len());
+            let cluster = &run[cluster_start..cluster_end];
+            let is_first_in_cluster = i == 0 || infos[i - 1].cluster != info.cluster;
+
+            let shaped_id = GlyphId(info.glyph_id as u16);
+            let mut glyph_id = shaped_id;
+            let mut x_advance = position.x_advance;
+            let single_char = match cluster.chars().count() {
+                1 => cluster.chars().next(),
+                _ => None,
+            };
+            let is_char_glyph = |c: char| face.glyph_index(c) == Some(shaped_id);
+
+            // Alternates the font maps no character to are drawn in place of the character
+            let alternate = run
+                .char_indices()
+                .position(|(index, _)| index == cluster_start)
+                .and_then(|index| run_chars[index].glyph);
+            if let (Some(alternate), Some(c)) = (alternate, single_char) {
+                if is_char_glyph(c) {
+                    glyph_id = alternate;
+                    x_advance = face
+                        .glyph_hor_advance(alternate)
+                        .map_or(x_advance, i32::from);
+                }
+            }
+
+            let shaped_advance = |text: &[u8]| {
+                x_advance as f64 * to_points
+                    + match text.is_empty() {
+                        true => 0_f64,
+                        false => transforms.tracking * transforms.em(),
+                    }
+            };
+            let mut glyph = match encoding.draws_glyph_ids() {
+                true => {
+                    let chars = if is_first_in_cluster { cluster } else { "" };
+                    let text = encoding.encode_glyph(glyph_id.0, chars).unwrap_or_default();
+                    let mut glyph = measure_text(vec![], first, pdf_font, transforms);
+                    glyph.advance = shaped_advance(&text);
+                    glyph.text = text;
+                    glyph
+                }
+                false => {
+                    let mapped_char = match single_char {
+                        Some(c) if is_char_glyph(c) => Some(c),
+                        _ => glyph_chars.char_for_glyph(shaped_id),
+                    };
+                    let text = match mapped_char {
+                        Some(c) if encoding.can_encode(c) => encoding.encode(&c.to_string()),
+                        _ if is_first_in_cluster => encoding.encode(cluster),
+                        _ => vec![],
+                    };
+
+                    let mut glyph = measure_text(text.clone(), first, pdf_font, transforms);
+                    // libharu rounds widths to 1/1000 em, smaller differences are rounding
+                    // noise that would otherwise split the text into single glyphs
+                    let difference = shaped_advance(&text) - glyph.advance;
+                    if difference.abs() >= transforms.em() * transforms.x_scale() * first.scale {
+                        glyph.kerning = difference;
+                    }
+                    glyph
+                }
+            };
+            glyph.x_offset = position.x_offset as f64 * to_points;
+            glyph.rise +=
+                position.y_offset as f64 * to_points / transforms.x_scale() * transforms.y_scale();
+            let is_mark = match face.tables().gdef {
+                Some(gdef) => gdef.glyph_class(glyph_id) == Some(GlyphClass::Mark),
+                None => position.x_advance == 0 && !is_first_in_cluster,
+            };
+            if is_mark {
+                let (x_offset, y_offset) = transforms.diacritic_offset;
+                glyph.x_offset += x_offset;
+                glyph.rise += y_offset;
+            }
+            if transforms.tsume > 0_f64 && !glyph.text.is_empty() {
+                if let Some(bbox) = face.glyph_bounding_box(glyph_id) {
+                    let left_bearing = bbox.x_min as f64;
+                    let right_bearing = x_advance as f64 - bbox.x_max as f64;
+                    glyph.x_offset -= transforms.tsume * left_bearing * to_points;
+                    glyph.kerning -= transforms.tsume * (left_bearing + right_bearing) * to_points;
+                }
+            }
+            glyph.is_space = cluster.chars().all(char::is_whitespace);
+            glyph.level = first.level;
+            if is_first_in_cluster {
+                glyph.chars = cluster.to_string();
+            }
+            glyphs.push(glyph);
+            glyph_ids.push(glyph_id);
+        }
+
+        // Right-to-left runs are shaped in visual order, keep the glyphs
+        // in logical order until the line is reordered
+        if is_rtl {
+            glyphs[run_glyphs_start..].reverse();
+            glyph_ids[run_glyphs_start..].reverse();
+        }
+    }
+
+    if transforms.kerning_method == KerningMethod::Optical {
+        for i in 1..glyphs.len() {
+            if glyphs[i - 1].is_space || glyphs[i].is_space || glyphs[i].level % 2 == 1 {
+                continue;
+            }
+            if let Some(units) = optical_kerning(face, glyph_ids[i - 1], glyph_ids[i]) {
+                let scale = glyphs[i - 1].scale.min(glyphs[i].scale);
+                glyphs[i - 1].kerning +=
+                    units / units_per_em * transforms.font_size * transforms.x_scale() * scale;
+            }
+        }
+    }
+    glyphs
+}
+
+/// Approximates optical kerning by evening out the side bearings of a pair
+/// towards the spacing the font uses between two `n`s
+fn optical_kerning(face: &Face, left: GlyphId, right: GlyphId) -> Option<f64> {
+    let gap = |l: GlyphId, r: GlyphId| -> Option<f64> {
+        let right_bearing =
+            face.glyph_hor_advance(l)? as f64 - face.glyph_bounding_box(l)?.x_max as f64;
+        let left_bearing = face.glyph_bounding_box(r)?.x_min as f64;
+        Some(right_bearing + left_bearing)
+    };
+    let n = face.glyph_index('n')?;
+    let reference = gap(n, n)?;
+    let limit = face.units_per_em() as f64 * 0.1_f64;
+    let kerning = (reference - gap(left, right)?) / 2_f64;
+    Some(kerning.max(-limit).min(limit))
+}
+
+/// Total width of the glyphs, including the kerning between them
+pub fn glyphs_width(glyphs: &[PositionedGlyph]) -> f64 {
+    glyphs.iter().map(|g| g.advance + g.kerning).sum()
+}
+
+/// Returns how many glyphs fit within `available_width`, breaking at a break
+/// opportunity when possible. Spaces and hanging punctuation may hang past the edge. Words that do not even fit an
+/// empty line are broken between characters.
+pub fn fit_glyphs(glyphs: &[PositionedGlyph], available_width: f64, line_is_empty: bool) -> usize {
+    let mut width = 0_f64;
+    let mut last_break = None;
+    for (i, glyph) in glyphs.iter().enumerate() {
+        if !glyph.hangs && width + glyph.advance > available_width {
+            return match last_break {
+                Some(index) => index,
+                None if line_is_empty => i.max(1),
+                None => 0,
+            };
+        }
+        width += glyph.advance + glyph.kerning;
+        if glyph.break_after {
+            last_break = Some(i + 1);
+        }
+    }
+    glyphs.len()
+}
+
+/// Visual order of the glyphs of a line, as indices into `glyphs`. Every
+/// sequence of glyphs at or above each odd level is reversed, from the highest
+/// level down (UAX #9, L1 and L2). Trailing spaces take the paragraph level so
+/// they hang at the end.
+pub fn reorder_line(glyphs: &[PositionedGlyph], paragraph_level: u8) -> Vec<usize> {
+    let mut levels: Vec<u8> = glyphs.iter().map(|glyph| glyph.level).collect();
+    for (level, glyph) in levels.iter_mut().zip(glyphs).rev() {
+        if !glyph.is_space {
+            break;
+        }
+        *level = paragraph_level;
+    }
+
+    let mut order: Vec<usize> = (0..glyphs.len()).collect();
+    let highest_level = levels.iter().copied().max().unwrap_or(0);
+    let lowest_odd_level = levels.iter().map(|level| level | 1).min().unwrap_or(1);
+    for level in (lowest_odd_level..=highest_level).rev() {
+        let mut start = 0;
+        while start < order.len() {
+            if levels[order[start]] < level {
+                start += 1;
+                continue;
+            }
+            let length = order[start..]
+                .iter()
+                .take_while(|&&i| levels[i] >= level)
+                .count();
+            order[start..start + length].reverse();
+            start += length;
+        }
+    }
+    order
+}
+
+/// Draws the glyphs with their baseline starting at (x, y) and returns where
+/// the next glyph starts, or an error if their text holds a NUL byte, which cannot
+/// be drawn. Must be called inside a text object with the font set.
+pub fn show_glyphs(
+    current_page: HPDF_Page,
+    glyphs: &[PositionedGlyph],
+    x: f64,
+    y: f64,
+    transforms: &GlyphTransforms,
+) -> Result<f64, String> {
+    let mut x = x;
+    unsafe {
+        // Kerning cannot be expressed with ShowText, so a new segment is started
+        // after every kerned glyph and wherever the glyph size or offset changes
+        let mut segment_start = 0;
+        for (i, glyph) in glyphs.iter().enumerate() {
+            if let Some(next) = glyphs.get(i + 1) {
+                if glyph.kerning == 0_f64
+                    && glyph.scale == next.scale
+                    && glyph.rise == next.rise
+                    && next.x_offset == 0_f64
+                {
+                    continue;
+                }
+            }
+            let segment = &glyphs[segment_start..=i];
+            let text: Vec<u8> = segment.iter().flat_map(|g| g.text.clone()).collect();
+            if text.is_empty() {
+                x += glyphs_width(segment);
+                segment_start = i + 1;
+                continue;
+            }
+
+            // Synthesized small caps and positions are scaled through the text matrix
+            let x_scale = transforms.x_scale() * glyph.scale;
+            let y_scale = transforms.y_scale() * glyph.scale;
+            let shear = transforms.skew.to_radians().tan() * y_scale;
+
+            // Shatai slants the glyphs along its angle
+            let (magnification, angle) = transforms.shatai;
+            let slant = magnification / 100_f64;
+            let angle = angle.to_radians();
+
+            // Tracking is set as character spacing, which the text matrix scales horizontally
+            let char_space = (transforms.tracking * transforms.em() / x_scale)
+                .clamp(MIN_CHAR_SPACE, MAX_CHAR_SPACE);
+            HPDF_Page_SetCharSpace(current_page, char_space as f32);
+
+            HPDF_Page_SetTextMatrix(
+                current_page,
+                x_scale as f32,
+                (slant * angle.sin() * x_scale) as f32,
+                (shear + slant * angle.cos() * y_scale) as f32,
+                y_scale as f32,
+                (x + segment[0].x_offset) as f32,
+                (y + transforms.baseline_shift + glyph.rise) as f32,
+            );
+            let c_text = glyph_text(text)?;
+            HPDF_Page_ShowText(current_page, c_text.as_ptr());
+            x += glyphs_width(segment);
+            segment_start = i + 1;
+        }
+    }
+    Ok(x)
+}
+
+/// Draws the glyphs down a column centered on `x`, starting with the top of the
+/// first glyph at `y`, and returns where the next glyph starts. Upright glyphs
+/// are drawn one at a time in their em box, other glyphs are turned clockwise.
+/// Like `show_glyphs`, text holding a NUL byte is an error.
+/// Must be called inside a text object with the font set.
+pub fn show_glyphs_vertical(
+    current_page: HPDF_Page,
+    glyphs: &[PositionedGlyph],
+    x: f64,
+    y: f64,
+    transforms: &GlyphTransforms,
+) -> Result<f64, String> {
+    let mut y = y;
+    unsafe {
+        let mut segment_start = 0;
+        for (i, glyph) in glyphs.iter().enumerate() {
+            if let Some(next) = glyphs.get(i + 1) {
+                if !glyph.upright
+                    && !next.upright
+                    && glyph.kerning == 0_f64
+                    && glyph.scale == next.scale
+                    && glyph.rise == next.rise
+                    && next.x_offset == 0_f64
+                {
+                    continue;
+                }
+            }
+            let segment = &glyphs[segment_start..=i];
+            let text: Vec<u8> = segment.iter().flat_map(|g| g.text.clone()).collect();
+            let length = glyphs_width(segment);
+            if text.is_empty() {
+                y -= length;
+                segment_start = i + 1;
+                continue;
+            }
+
+            let x_scale = transforms.x_scale() * glyph.scale;
+            let y_scale = transforms.y_scale() * glyph.scale;
+            let char_space = (transforms.tracking * transforms.em() / x_scale)
+                .clamp(MIN_CHAR_SPACE, MAX_CHAR_SPACE);
+            HPDF_Page_SetCharSpace(current_page, char_space as f32);
+
+            // Baseline shift and positions move the glyphs across the column
+            let shift = transforms.baseline_shift + glyph.rise;
+            let size = transforms.font_size * glyph.scale;
+            match glyph.upright {
+                true => HPDF_Page_SetTextMatrix(
+                    current_page,
+                    x_scale as f32,
+                    0_f32,
+                    0_f32,
+                    y_scale as f32,
+                    (x + glyph.x_offset + shift) as f32,
+                    (y - size * EM_BOX_ASCENT * transforms.y_scale()) as f32,
+                ),
+                false => HPDF_Page_SetTextMatrix(
+                    current_page,
+                    0_f32,
+                    -x_scale as f32,
+                    y_scale as f32,
+                    0_f32,
+                    (x - size * (EM_BOX_ASCENT - 0.5_f64) * transforms.y_scale() + shift) as f32,
+                    (y - segment[0].x_offset) as f32,
+                ),
+            };
+            let c_text = glyph_text(text)?;
+            HPDF_Page_ShowText(current_page, c_text.as_ptr());
+            y -= length;
+            segment_start = i + 1;
+        }
+    }
+    Ok(y)
+}
+
+/// The encoded text of glyphs as the C string libharu draws
+fn glyph_text(text: Vec<u8>) -> Result<CString, String> {
+    CString::new(text).map_err(|err| {
+        format!(
+            "Glyphs {:?} not drawn, as their text holds a NUL byte",
+            String::from_utf8_lossy(&err.into_vec())
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn transforms(
+        paragraph_direction: ParagraphDirection,
+        character_direction: CharacterDirection,
+    ) -> GlyphTransforms {
+        GlyphTransforms {
+            font_size: 12_f64,
+            tracking: 0_f64,
+            kerning_method: KerningMethod::Metrics,
+            kerning_value: 0_f64,
+            horizontal_scale: 100_f64,
+            vertical_scale: 100_f64,
+            skew: 0_f64,
+            baseline_shift: 0_f64,
+            capitalization: Capitalization::Normal,
+            position: Position::Normal,
+            small_cap_size: 70_f64,
+            superscript: (58.3_f64, 33.3_f64),
+            subscript: (58.3_f64, 33.3_f64),
+            opentype: OpenTypeFeatures::default(),
+            paragraph_direction,
+            character_direction,
+            digits_type: DigitsType::DefaultDigits,
+            diacritic_offset: (0_f64, 0_f64),
+            vertical: false,
+            rotate_single_byte: false,
+            kinsoku_hang: false,
+            leading_aki: 0_f64,
+            trailing_aki: 0_f64,
+            tsume: 0_f64,
+            jidori: 0_f64,
+            shatai: (0_f64, 0_f64),
+        }
+    }
+
+    fn levels(text: &str, transforms: &GlyphTransforms) -> Vec<u8> {
+        let mut chars: Vec<StyledChar> = text.chars().map(StyledChar::new).collect();
+        resolve_levels(&mut chars, transforms);
+        chars.iter().map(|styled| styled.level).collect()
+    }
+
+    /// Glyphs of `text` at `levels`, with spaces marked
+    fn glyphs(text: &str, levels: &[u8]) -> Vec<PositionedGlyph> {
+        text.chars()
+            .zip(levels)
+            .map(|(c, &level)| PositionedGlyph {
+                is_space: c == ' ',
+                level,
+                chars: c.to_string(),
+                ..PositionedGlyph::spacer(1_f64)
+            })
+            .collect()
+    }
+
+    fn visual(text: &str, levels: &[u8], paragraph_level: u8) -> String {
+        let chars: Vec<char> = text.chars().collect();
+        reorder_line(&glyphs(text, levels), paragraph_level)
+            .into_iter()
+            .map(|i| chars[i])
+            .collect()
+    }
+
+    #[test]
+    fn hebrew_in_left_to_right_paragraphs_is_raised_a_level() {
+        let ltr = transforms(
+            ParagraphDirection::LeftToRightDirection,
+            CharacterDirection::DefaultDirection,
+        );
+        assert_eq!(levels("ab אב", &ltr), vec![0, 0, 0, 1, 1]);
+    }
+
+    #[test]
+    fn numbers_in_right_to_left_paragraphs_are_left_to_right() {
+        let rtl = transforms(
+            ParagraphDirection::RightToLeftDirection,
+            CharacterDirection::DefaultDirection,
+        );
+        assert_eq!(levels("אב 12", &rtl), vec![1, 1, 1, 2, 2]);
+    }
+
+    #[test]
+    fn character_direction_embeds_the_run() {
+        let forced = transforms(
+            ParagraphDirection::LeftToRightDirection,
+            CharacterDirection::RightToLeftDirection,
+        );
+        assert_eq!(levels("ab", &forced), vec![2, 2]);
+    }
+
+    #[test]
+    fn right_to_left_sequences_are_reversed() {
+        assert_eq!(visual("abCDE", &[0, 0, 1, 1, 1], 0), "abEDC");
+    }
+
+    #[test]
+    fn numbers_keep_their_order_in_right_to_left_text() {
+        assert_eq!(visual("AB 12", &[1, 1, 1, 2, 2], 1), "12 BA");
+    }
+
+    #[test]
+    fn trailing_spaces_take_the_paragraph_level() {
+        assert_eq!(visual("ab  ", &[1, 1, 1, 1], 0), "ba  ");
+    }
+
+    #[test]
+    fn empty_lines_have_no_order() {
+        assert!(reorder_line(&[], 0).is_empty());
+    }
+
+    #[test]
+    fn text_holding_a_nul_byte_is_not_drawn() {
+        assert_eq!(
+            glyph_text(b"AB".to_vec()).map(|text| text.into_bytes()),
+            Ok(b"AB".to_vec())
+        );
+        assert_eq!(
+            glyph_text(b"A\0B".to_vec()),
+            Err("Glyphs \"A\\0B\" not drawn, as their text holds a NUL byte".to_string())
+        );
+    }
+}
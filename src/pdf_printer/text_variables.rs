@@ -0,0 +1,740 @@
+use crate::idml_parser::designmap_parser::{
+    PageNumberStyle, TextVariable, TextVariablePreference, TextVariableType,
+};
+use crate::idml_parser::spread_parser::SpreadContent;
+use crate::idml_parser::story_parser::{Story, StoryContent};
+use crate::idml_parser::IDMLPackage;
+use crate::pdf_printer::note_numbering;
+use crate::pdf_printer::page_numbering::{self, PageNumber};
+use crate::pdf_printer::StoryLayout;
+use std::collections::{HashMap, HashSet};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Resolves the text variables of the document for each page, keyed by
+/// page id and then by variable id. Variables that cannot be resolved from
+/// the package, like the file name of the original document, are left out so
+/// the text saved with the variable instance is used instead. Running headers
+/// come from the pages their paragraphs are set on in `layout`.
+pub fn resolve_text_variables(
+    idml_package: &IDMLPackage,
+    page_numbers: &HashMap<String, PageNumber>,
+    layout: &StoryLayout,
+) -> HashMap<String, HashMap<String, String>> {
+    let (pages, stories) = document_stories(idml_package);
+    let mut resolved = HashMap::new();
+
+    // Text in the style of each running header on each page, keyed by variable id
+    let running_header_matches: HashMap<&str, HashMap<String, Vec<String>>> = idml_package
+        .designmap()
+        .text_variables()
+        .iter()
+        .filter(|variable| is_running_header(variable))
+        .map(|variable| {
+            let preference = variable.preference().cloned().unwrap_or_default();
+            let matches = matches_by_page(&stories, variable, &preference, layout);
+            (variable.id().as_str(), matches)
+        })
+        .collect();
+
+    // Running headers with no match on a page repeat the last match of the pages before it
+    let mut last_matches: HashMap<&str, String> = HashMap::new();
+
+    for page_id in &pages {
+        let mut values = HashMap::new();
+        for variable in idml_package.designmap().text_variables() {
+            let preference = variable.preference().cloned().unwrap_or_default();
+            let metadata = idml_package.meta_inf().metadata();
+            let modification_date = || {
+                metadata
+                    .modify_date()
+                    .as_deref()
+                    .and_then(Date::from_xmp)
+                    .unwrap_or_else(Date::now)
+            };
+            let value = match variable.variable_type() {
+                Some(TextVariableType::ChapterNumberType) => {
+                    Some(chapter_number(idml_package, &preference))
+                }
+                Some(TextVariableType::CreationDateType) => Some(
+                    metadata
+                        .create_date()
+                        .as_deref()
+                        .and_then(Date::from_xmp)
+                        .unwrap_or_else(modification_date)
+                        .format(preference.format().as_deref()),
+                ),
+                Some(TextVariableType::ModificationDateType) => {
+                    Some(modification_date().format(preference.format().as_deref()))
+                }
+                Some(TextVariableType::OutputDateType) => {
+                    Some(Date::now().format(preference.format().as_deref()))
+                }
+                Some(TextVariableType::LastPageNumberType) => {
+                    last_page_number(page_id, &pages, page_numbers, &preference)
+                }
+                Some(TextVariableType::MatchParagraphStyleType)
+                | Some(TextVariableType::MatchCharacterStyleType) => {
+                    let matches = running_header_matches
+                        .get(variable.id().as_str())
+                        .and_then(|matches| matches.get(page_id))
+                        .map(Vec::as_slice)
+                        .unwrap_or_default();
+                    let found = select_match(matches, &preference)
+                        .cloned()
+                        .or_else(|| last_matches.get(variable.id().as_str()).cloned());
+                    if let Some(last_match) = matches.last() {
+                        last_matches.insert(variable.id().as_str(), last_match.clone());
+                    }
+                    found.map(|text| running_header(text, &preference))
+                }
+                Some(TextVariableType::CustomTextType) => preference.contents().clone(),
+                _ => None,
+            };
+
+            if let Some(value) = value {
+                values.insert(
+                    variable.id().clone(),
+                    format!(
+                        "{}{}{}",
+                        preference.text_before().as_deref().unwrap_or(""),
+                        value,
+                        preference.text_after().as_deref().unwrap_or("")
+                    ),
+                );
+            }
+        }
+        resolved.insert(page_id.clone(), values);
+    }
+
+    resolved
+}
+
+fn is_running_header(variable: &TextVariable) -> bool {
+    matches!(
+        variable.variable_type(),
+        Some(TextVariableType::MatchParagraphStyleType)
+            | Some(TextVariableType::MatchCharacterStyleType)
+    )
+}
+
+/// Story id, story and the id of the page of its first frame
+type DocumentStory<'a> = (&'a String, &'a Story, Option<String>);
+
+/// Ids of the pages of the document in order, and the stories of the text frames
+/// on them in the order they first appear, each with the page of its first frame.
+/// Page items belong to the page before them in the spread, like when rendering.
+fn document_stories(idml_package: &IDMLPackage) -> (Vec<String>, Vec<DocumentStory<'_>>) {
+    let mut pages = vec![];
+    let mut stories = vec![];
+    let mut stories_seen = HashSet::new();
+    let spreads = idml_package
+        .designmap()
+        .spread_ids()
+        .iter()
+        .filter_map(|spread_id| idml_package.spreads().get(spread_id));
+    for spread in spreads {
+        for content in spread.contents() {
+            if let SpreadContent::Page(page) = content {
+                pages.push(page.id().clone());
+                continue;
+            }
+            for story_id in note_numbering::story_ids(content) {
+                if !stories_seen.insert(story_id) {
+                    continue;
+                }
+                if let Some(story) = idml_package.stories().get(story_id) {
+                    stories.push((story_id, story, pages.last().cloned()));
+                }
+            }
+        }
+    }
+    (pages, stories)
+}
+
+/// Text in the style of a running header on each page, keyed by page id. Each
+/// match is on the page its paragraph was set on, or on the page of the first
+/// frame of its story if the paragraph was not laid out.
+fn matches_by_page(
+    stories: &[DocumentStory],
+    variable: &TextVariable,
+    preference: &TextVariablePreference,
+    layout: &StoryLayout,
+) -> HashMap<String, Vec<String>> {
+    let mut pages: HashMap<String, Vec<String>> = HashMap::new();
+    for (story_id, story, first_page) in stories {
+        for (paragraph, text) in style_matches(story, variable, preference) {
+            let page_id = layout
+                .paragraph_page(story_id, paragraph)
+                .or(first_page.as_ref());
+            if let Some(page_id) = page_id {
+                pages.entry(page_id.clone()).or_default().push(text);
+            }
+        }
+    }
+    pages
+}
+
+/// The match on a page a running header shows, by its search strategy
+fn select_match<'a>(
+    matches: &'a [String],
+    preference: &TextVariablePreference,
+) -> Option<&'a String> {
+    match preference.search_strategy().as_deref() {
+        Some("LastOnPage") => matches.last(),
+        _ => matches.first(),
+    }
+}
+
+/// Chapter number of the document, in the format of the variable or of the document
+pub fn chapter_number(idml_package: &IDMLPackage, preference: &TextVariablePreference) -> String {
+    let (number, chapter_format) = idml_package.resources().preferences().chapter_number();
+    let style = match preference.format().as_deref() {
+        Some("Current") | None => chapter_format
+            .as_deref()
+            .map(PageNumberStyle::from_format)
+            .unwrap_or(PageNumberStyle::Arabic),
+        Some(format) => PageNumberStyle::from_name(format),
+    };
+    page_numbering::format_page_number(number, style)
+}
+
+/// Number of the last page in the section, or in the document
+fn last_page_number(
+    page_id: &str,
+    pages: &[String],
+    page_numbers: &HashMap<String, PageNumber>,
+    preference: &TextVariablePreference,
+) -> Option<String> {
+    let section = &page_numbers.get(page_id)?.section;
+    let last_page = pages
+        .iter()
+        .filter_map(|page_id| page_numbers.get(page_id))
+        .filter(|page_number| {
+            preference.scope().as_deref() == Some("DocumentScope")
+                || &page_number.section == section
+        })
+        .last()?;
+
+    match (preference.format().as_deref(), &last_page.label) {
+        (Some("Current"), _) | (None, _) => Some(last_page.number.clone()),
+        (Some(format), Some(label)) => Some(page_numbering::format_page_number(
+            label.value,
+            PageNumberStyle::from_name(format),
+        )),
+        (Some(_), None) => Some(last_page.number.clone()),
+    }
+}
+
+/// Text of the paragraphs, or runs of characters, in the style of a running header
+/// variable, each with the number of paragraphs before it in the story
+fn style_matches(
+    story: &Story,
+    variable: &TextVariable,
+    preference: &TextVariablePreference,
+) -> Vec<(usize, String)> {
+    let match_paragraphs =
+        *variable.variable_type() == Some(TextVariableType::MatchParagraphStyleType);
+    let style = match match_paragraphs {
+        true => preference.applied_paragraph_style(),
+        false => preference.applied_character_style(),
+    };
+    if style.is_none() {
+        return vec![];
+    }
+
+    let mut matches = StyleMatches::default();
+    for p_style in story.paragraph_style_ranges().iter().flatten() {
+        for c_style in p_style.character_style_ranges().iter().flatten() {
+            let is_match = match match_paragraphs {
+                true => p_style.applied_paragraph_style() == style,
+                false => c_style.applied_character_style() == style,
+            };
+            for content in c_style.contents().iter().flatten() {
+                matches.add(content, is_match);
+            }
+            if !is_match {
+                matches.end_match();
+            }
+        }
+        matches.end_match();
+    }
+    matches.matches
+}
+
+/// Matches of a running header in a story, collected as its contents are walked
+#[derive(Default)]
+struct StyleMatches {
+    matches: Vec<(usize, String)>,
+    /// Text of the match being collected
+    text: String,
+    /// Number of paragraphs before the current one, counted like the paragraphs
+    /// of the story are when it is laid out
+    paragraph: usize,
+}
+
+impl StyleMatches {
+    fn add(&mut self, content: &StoryContent, is_match: bool) {
+        match content {
+            StoryContent::Content(content) if is_match => self.text.push_str(content),
+            StoryContent::Br => {
+                self.end_match();
+                self.paragraph += 1;
+            }
+            StoryContent::HyperlinkTextSource(source) => {
+                for content in source.contents().iter().flatten() {
+                    self.add(content, is_match);
+                }
+            }
+            StoryContent::CrossReferenceSource(source) => {
+                for content in source.contents().iter().flatten() {
+                    self.add(content, is_match);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn end_match(&mut self) {
+        if !self.text.is_empty() {
+            self.matches.push((self.paragraph, self.text.clone()));
+            self.text.clear();
+        }
+    }
+}
+
+/// Applies the case and punctuation options of a running header
+fn running_header(text: String, preference: &TextVariablePreference) -> String {
+    let mut text = text.trim().to_string();
+    if *preference.delete_end_punctuation() == Some(true) {
+        text = text
+            .trim_end_matches(|c: char| c.is_ascii_punctuation() || "。、…！？".contains(c))
+            .to_string();
+    }
+
+    let capitalize = |word: &str| {
+        let mut chars = word.chars();
+        match chars.next() {
+            Some(first) => first.to_uppercase().chain(chars).collect::<String>(),
+            None => String::new(),
+        }
+    };
+    match preference.change_case().as_deref() {
+        Some("Uppercase") => text.to_uppercase(),
+        Some("Lowercase") => text.to_lowercase(),
+        Some("TitleCase") => text
+            .split(' ')
+            .map(|word| capitalize(word))
+            .collect::<Vec<String>>()
+            .join(" "),
+        Some("SentenceCase") => capitalize(&text),
+        _ => text,
+    }
+}
+
+/// Calendar date and time, without a time zone
+#[derive(Debug, Clone, Copy)]
+struct Date {
+    year: i64,
+    month: u32,
+    day: u32,
+    hour: u32,
+    minute: u32,
+    second: u32,
+}
+
+const MONTHS: [&str; 12] = [
+    "January",
+    "February",
+    "March",
+    "April",
+    "May",
+    "June",
+    "July",
+    "August",
+    "September",
+    "October",
+    "November",
+    "December",
+];
+
+const WEEKDAYS: [&str; 7] = [
+    "Sunday",
+    "Monday",
+    "Tuesday",
+    "Wednesday",
+    "Thursday",
+    "Friday",
+    "Saturday",
+];
+
+impl Date {
+    /// Parses XMP dates like "2020-12-22T17:07:03+01:00", keeping their local time.
+    /// Dates that are not in the calendar, like a 13th month, are rejected.
+    fn from_xmp(value: &str) -> Option<Date> {
+        let number = |range: std::ops::Range<usize>| value.get(range)?.parse::<u32>().ok();
+        let date = Date {
+            year: number(0..4)? as i64,
+            month: number(5..7)?,
+            day: number(8..10)?,
+            hour: number(11..13).unwrap_or(0),
+            minute: number(14..16).unwrap_or(0),
+            second: number(17..19).unwrap_or(0),
+        };
+        let valid = (1..=12).contains(&date.month)
+            && date.day >= 1
+            && date.day <= days_in_month(date.year, date.month)
+            && date.hour < 24
+            && date.minute < 60
+            && date.second < 61;
+        match valid {
+            true => Some(date),
+            false => None,
+        }
+    }
+
+    /// The current time in UTC, as the local time zone is not known
+    fn now() -> Date {
+        let seconds = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs() as i64)
+            .unwrap_or(0);
+        let (year, month, day) = civil_from_days(seconds.div_euclid(86400));
+        let time = seconds.rem_euclid(86400) as u32;
+        Date {
+            year,
+            month,
+            day,
+            hour: time / 3600,
+            minute: time / 60 % 60,
+            second: time % 60,
+        }
+    }
+
+    fn weekday(&self) -> usize {
+        // 1970-01-01 was a Thursday
+        (days_from_civil(self.year, self.month, self.day) + 4).rem_euclid(7) as usize
+    }
+
+    /// Formats the date with a pattern like "MMMM d, yyyy h:mm aa", where
+    /// each run of a pattern letter is a field and quoted text is literal
+    fn format(&self, pattern: Option<&str>) -> String {
+        let pattern: Vec<char> = pattern.unwrap_or("MM/dd/yy").chars().collect();
+        let mut formatted = String::new();
+        let mut i = 0;
+        while i < pattern.len() {
+            let c = pattern[i];
+            let mut count = 1;
+            while i + count < pattern.len() && pattern[i + count] == c {
+                count += 1;
+            }
+
+            if c == '\'' {
+                let literal: String = pattern[i + 1..]
+                    .iter()
+                    .take_while(|c| **c != '\'')
+                    .collect();
+                i += literal.chars().count() + 2;
+                formatted.push_str(&literal);
+                continue;
+            }
+
+            let hour_12 = match self.hour % 12 {
+                0 => 12,
+                hour => hour,
+            };
+            let field = match (c, count) {
+                ('y', 2) => format!("{:02}", self.year % 100),
+                ('y', _) => self.year.to_string(),
+                ('M', 1) => self.month.to_string(),
+                ('M', 2) => format!("{:02}", self.month),
+                ('M', 3) => MONTHS[self.month as usize - 1][..3].to_string(),
+                ('M', _) => MONTHS[self.month as usize - 1].to_string(),
+                ('d', 1) => self.day.to_string(),
+                ('d', _) => format!("{:02}", self.day),
+                ('E', 1..=3) => WEEKDAYS[self.weekday()][..3].to_string(),
+                ('E', _) => WEEKDAYS[self.weekday()].to_string(),
+                ('H', 1) => self.hour.to_string(),
+                ('H', _) => format!("{:02}", self.hour),
+                ('h', 1) => hour_12.to_string(),
+                ('h', _) => format!("{:02}", hour_12),
+                ('m', 1) => self.minute.to_string(),
+                ('m', _) => format!("{:02}", self.minute),
+                ('s', 1) => self.second.to_string(),
+                ('s', _) => format!("{:02}", self.second),
+                ('a', _) => match self.hour < 12 {
+                    true => "AM".to_string(),
+                    false => "PM".to_string(),
+                },
+                _ => pattern[i..i + count].iter().collect(),
+            };
+            formatted.push_str(&field);
+            i += count;
+        }
+        formatted
+    }
+}
+
+/// Days since 1970-01-01 of a date in the proleptic Gregorian calendar
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month = month as i64;
+    let day_of_year =
+        (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day as i64 - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+fn days_in_month(year: i64, month: u32) -> u32 {
+    (days_from_civil(year + month as i64 / 12, month % 12 + 1, 1) - days_from_civil(year, month, 1))
+        as u32
+}
+
+/// Date of a number of days since 1970-01-01
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days - era * 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * month_index + 2) / 5 + 1) as u32;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    } as u32;
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::idml_parser::spread_parser::Page;
+    use quick_xml::de::from_str;
+
+    fn date(year: i64, month: u32, day: u32, hour: u32, minute: u32) -> Date {
+        Date {
+            year,
+            month,
+            day,
+            hour,
+            minute,
+            second: 0,
+        }
+    }
+
+    fn variable(xml: &str) -> (TextVariable, TextVariablePreference) {
+        let variable: TextVariable = from_str(xml).unwrap();
+        let preference = variable.preference().cloned().unwrap_or_default();
+        (variable, preference)
+    }
+
+    fn heading_variable(search_strategy: &str) -> (TextVariable, TextVariablePreference) {
+        variable(&format!(
+            r#"<TextVariable Self="dTextVariablen<?AID 001b?>Heading" VariableType="MatchParagraphStyleType">
+                <MatchParagraphStylePreference AppliedParagraphStyle="ParagraphStyle/Heading" SearchStrategy="{}"/>
+            </TextVariable>"#,
+            search_strategy
+        ))
+    }
+
+    /// A story with a heading and a body paragraph after each of the headings
+    fn story(headings: &[&str]) -> Story {
+        let paragraphs: String = headings
+            .iter()
+            .map(|heading| {
+                format!(
+                    r#"<ParagraphStyleRange AppliedParagraphStyle="ParagraphStyle/Heading">
+                        <CharacterStyleRange><Content>{}</Content><Br/></CharacterStyleRange>
+                    </ParagraphStyleRange>
+                    <ParagraphStyleRange AppliedParagraphStyle="ParagraphStyle/Body">
+                        <CharacterStyleRange><Content>Body</Content><Br/></CharacterStyleRange>
+                    </ParagraphStyleRange>"#,
+                    heading
+                )
+            })
+            .collect();
+        from_str(&format!(r#"<Story Self="u1">{}</Story>"#, paragraphs)).unwrap()
+    }
+
+    fn page(id: &str) -> Page {
+        from_str(&format!(
+            r#"<Page Self="{}" UseMasterGrid="true"><Properties/></Page>"#,
+            id
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn xmp_dates_keep_their_local_time() {
+        let date = Date::from_xmp("2020-12-22T17:07:03+01:00").unwrap();
+        assert_eq!(
+            (
+                date.year,
+                date.month,
+                date.day,
+                date.hour,
+                date.minute,
+                date.second
+            ),
+            (2020, 12, 22, 17, 7, 3)
+        );
+    }
+
+    #[test]
+    fn xmp_dates_without_a_time_are_at_midnight() {
+        let date = Date::from_xmp("2021-02-03").unwrap();
+        assert_eq!((date.day, date.hour, date.minute), (3, 0, 0));
+    }
+
+    #[test]
+    fn dates_not_in_the_calendar_are_rejected() {
+        assert!(Date::from_xmp("2020-00-10T00:00:00").is_none());
+        assert!(Date::from_xmp("2020-13-10T00:00:00").is_none());
+        assert!(Date::from_xmp("2020-12-32T00:00:00").is_none());
+        assert!(Date::from_xmp("2021-02-29T00:00:00").is_none());
+        assert!(Date::from_xmp("2020-12-22T24:00:00").is_none());
+        assert!(Date::from_xmp("2020-12-22T10:60:00").is_none());
+        assert!(Date::from_xmp("not a date").is_none());
+        assert!(Date::from_xmp("2020-02-29T00:00:00").is_some());
+    }
+
+    #[test]
+    fn dates_default_to_the_short_format() {
+        assert_eq!(date(2020, 3, 7, 0, 0).format(None), "03/07/20");
+    }
+
+    #[test]
+    fn date_fields_follow_the_pattern() {
+        let date = date(2020, 12, 22, 17, 7);
+        assert_eq!(date.format(Some("MMMM d, yyyy")), "December 22, 2020");
+        assert_eq!(date.format(Some("EEE, MMM dd")), "Tue, Dec 22");
+        assert_eq!(date.format(Some("EEEE h:mm a")), "Tuesday 5:07 PM");
+        assert_eq!(date.format(Some("HH:mm:ss")), "17:07:00");
+        assert_eq!(date.format(Some("M/d/yy")), "12/22/20");
+    }
+
+    #[test]
+    fn quoted_text_in_date_patterns_is_literal() {
+        assert_eq!(
+            date(2020, 12, 22, 0, 0).format(Some("'Day' d 'of' MMMM")),
+            "Day 22 of December"
+        );
+    }
+
+    #[test]
+    fn midnight_is_twelve_am() {
+        assert_eq!(date(2020, 1, 1, 0, 30).format(Some("h:mm a")), "12:30 AM");
+        assert_eq!(date(2020, 1, 1, 12, 0).format(Some("hh a")), "12 PM");
+    }
+
+    #[test]
+    fn civil_dates_count_days_from_the_epoch() {
+        assert_eq!(days_from_civil(1970, 1, 1), 0);
+        assert_eq!(days_from_civil(2000, 3, 1), 11017);
+        assert_eq!(days_from_civil(1969, 12, 31), -1);
+        assert_eq!(civil_from_days(-1), (1969, 12, 31));
+        assert_eq!(civil_from_days(11016), (2000, 2, 29));
+    }
+
+    #[test]
+    fn civil_dates_round_trip() {
+        for days in (-800_000..800_000).step_by(997) {
+            let (year, month, day) = civil_from_days(days);
+            assert_eq!(days_from_civil(year, month, day), days);
+        }
+    }
+
+    #[test]
+    fn months_have_their_number_of_days() {
+        assert_eq!(days_in_month(2021, 1), 31);
+        assert_eq!(days_in_month(2021, 2), 28);
+        assert_eq!(days_in_month(2020, 2), 29);
+        assert_eq!(days_in_month(1900, 2), 28);
+        assert_eq!(days_in_month(2021, 4), 30);
+        assert_eq!(days_in_month(2021, 12), 31);
+    }
+
+    #[test]
+    fn weekdays_start_from_a_thursday() {
+        assert_eq!(WEEKDAYS[date(1970, 1, 1, 0, 0).weekday()], "Thursday");
+        assert_eq!(WEEKDAYS[date(2020, 12, 22, 0, 0).weekday()], "Tuesday");
+    }
+
+    #[test]
+    fn paragraph_style_matches_know_their_paragraph() {
+        let (variable, preference) = heading_variable("FirstOnPage");
+        assert_eq!(
+            style_matches(&story(&["One", "Two"]), &variable, &preference),
+            vec![(0, "One".to_string()), (2, "Two".to_string())]
+        );
+    }
+
+    #[test]
+    fn character_style_matches_end_with_the_run() {
+        let (variable, preference) = variable(
+            r#"<TextVariable Self="v" VariableType="MatchCharacterStyleType">
+                <MatchCharacterStylePreference AppliedCharacterStyle="CharacterStyle/Term"/>
+            </TextVariable>"#,
+        );
+        let story: Story = from_str(
+            r#"<Story Self="u1"><ParagraphStyleRange>
+                <CharacterStyleRange><Content>See </Content></CharacterStyleRange>
+                <CharacterStyleRange AppliedCharacterStyle="CharacterStyle/Term"><Content>kerning</Content></CharacterStyleRange>
+                <CharacterStyleRange><Content> and </Content><Br/><Content>then </Content></CharacterStyleRange>
+                <CharacterStyleRange AppliedCharacterStyle="CharacterStyle/Term"><Content>tracking</Content></CharacterStyleRange>
+            </ParagraphStyleRange></Story>"#,
+        )
+        .unwrap();
+        assert_eq!(
+            style_matches(&story, &variable, &preference),
+            vec![(0, "kerning".to_string()), (1, "tracking".to_string())]
+        );
+    }
+
+    #[test]
+    fn running_headers_are_on_the_page_their_paragraph_is_set_on() {
+        let (variable, preference) = heading_variable("FirstOnPage");
+        let story_id = "u1".to_string();
+        let story = story(&["One", "Two", "Three"]);
+        let stories = vec![(&story_id, &story, Some("p1".to_string()))];
+
+        // The story is threaded over two pages, and its last heading did not fit
+        let mut layout = StoryLayout::measuring();
+        layout.start_page(&page("p1"));
+        layout.add_story("u1", &[0, 1], vec![]);
+        layout.start_page(&page("p2"));
+        layout.add_story("u1", &[2, 3], vec![]);
+
+        let pages = matches_by_page(&stories, &variable, &preference, &layout);
+        assert_eq!(pages["p1"], vec!["One".to_string(), "Three".to_string()]);
+        assert_eq!(pages["p2"], vec!["Two".to_string()]);
+    }
+
+    #[test]
+    fn running_headers_take_the_first_or_last_match_on_the_page() {
+        let matches = vec!["One".to_string(), "Two".to_string()];
+        let (_, first) = heading_variable("FirstOnPage");
+        let (_, last) = heading_variable("LastOnPage");
+        assert_eq!(select_match(&matches, &first), Some(&matches[0]));
+        assert_eq!(select_match(&matches, &last), Some(&matches[1]));
+        assert_eq!(select_match(&[], &last), None);
+    }
+
+    #[test]
+    fn running_headers_change_case_and_drop_end_punctuation() {
+        let (_, preference) = variable(
+            r#"<TextVariable Self="v" VariableType="MatchParagraphStyleType">
+                <MatchParagraphStylePreference ChangeCase="TitleCase" DeleteEndPunctuation="true"/>
+            </TextVariable>"#,
+        );
+        assert_eq!(
+            running_header(" the end of it all. ".to_string(), &preference),
+            "The End Of It All"
+        );
+    }
+}